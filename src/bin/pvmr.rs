@@ -1,20 +1,206 @@
 //! Palindrome VM Runner - Execute PVM assembly programs
 
-use palindrome_vm::{VM, Parser};
+use palindrome_vm::compiler::disassemble_program;
+use palindrome_vm::vm::{StepOutcome, WatchHit};
+use palindrome_vm::{VM, Parser, Program};
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::fs;
-use std::io::{self, Write};
+use std::io::{self, BufRead, Write};
+use std::rc::Rc;
+
+/// Command-line options
+#[derive(Debug, PartialEq)]
+struct Options {
+    path: String,
+    /// Pause before every instruction at the debugger prompt
+    step: bool,
+    /// Labels or instruction indices that drop into the debugger when reached
+    breakpoints: Vec<String>,
+    /// Tape ranges whose writes drop into the debugger
+    watchpoints: Vec<(i64, i64)>,
+    /// Print executions per instruction after the run
+    trace: bool,
+    /// Stop after this many instructions, to bound runaway programs
+    budget: Option<usize>,
+    /// Print the parsed program back as assembly instead of running it
+    disasm: bool,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut path = None;
+        let mut step = false;
+        let mut trace = false;
+        let mut disasm = false;
+        let mut budget = None;
+        let mut breakpoints = Vec::new();
+        let mut watchpoints = Vec::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--step" => step = true,
+                "--trace" => trace = true,
+                "--disasm" => disasm = true,
+                "--budget" => {
+                    let count = args.next().ok_or("--budget requires an instruction count")?;
+                    budget = Some(count.parse::<usize>()
+                        .map_err(|_| format!("Invalid budget: {}", count))?);
+                }
+                "--break" => {
+                    let target = args.next().ok_or("--break requires a label or instruction index")?;
+                    breakpoints.push(target.clone());
+                }
+                "--watch" => {
+                    let range = args.next().ok_or("--watch requires a START:END range")?;
+                    watchpoints.push(parse_range(range)?);
+                }
+                flag if flag.starts_with("--") => return Err(format!("Unknown option: {}", flag)),
+                file if path.is_none() => path = Some(file.to_string()),
+                extra => return Err(format!("Unexpected argument: {}", extra)),
+            }
+        }
+        
+        let path = path.ok_or("Missing program file")?;
+        Ok(Options { path, step, breakpoints, watchpoints, trace, budget, disasm })
+    }
+}
+
+/// Parse a `START:END` tape range
+fn parse_range(range: &str) -> Result<(i64, i64), String> {
+    let invalid = || format!("Invalid watch range '{}' (expected START:END)", range);
+    let (start, end) = range.split_once(':').ok_or_else(invalid)?;
+    let start = start.parse::<i64>().map_err(|_| invalid())?;
+    let end = end.parse::<i64>().map_err(|_| invalid())?;
+    if start >= end {
+        return Err(invalid());
+    }
+    Ok((start, end))
+}
+
+/// A breakpoint resolved to the instruction index it stops at
+#[derive(Debug, PartialEq)]
+struct Breakpoint {
+    /// The label or index as given on the command line
+    name: String,
+    ip: i64,
+}
+
+/// Resolve `--break` targets through the parser's labels, or as raw indices
+fn resolve_breakpoints(targets: &[String], labels: &HashMap<String, i64>) -> Result<Vec<Breakpoint>, String> {
+    targets.iter()
+        .map(|target| {
+            let ip = match labels.get(target) {
+                Some(ip) => *ip,
+                None => target.parse::<i64>()
+                    .map_err(|_| format!("Unknown breakpoint label: {}", target))?,
+            };
+            Ok(Breakpoint { name: target.clone(), ip })
+        })
+        .collect()
+}
+
+/// The breakpoint at `ip`, if any
+fn breakpoint_at(breakpoints: &[Breakpoint], ip: i64) -> Option<&Breakpoint> {
+    breakpoints.iter().find(|breakpoint| breakpoint.ip == ip)
+}
+
+/// A command typed at the step prompt
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum StepCommand {
+    Next,
+    Reverse,
+    Continue,
+    Quit,
+}
+
+impl StepCommand {
+    /// Parse a prompt line; an empty line means `next`
+    fn parse(input: &str) -> Result<Self, String> {
+        match input.trim().to_lowercase().as_str() {
+            "" | "n" | "next" => Ok(StepCommand::Next),
+            "r" | "reverse" => Ok(StepCommand::Reverse),
+            "c" | "continue" => Ok(StepCommand::Continue),
+            "q" | "quit" => Ok(StepCommand::Quit),
+            other => Err(format!("Unknown command '{}' (expected n, r, c or q)", other)),
+        }
+    }
+}
+
+/// What the runner does after a step command
+#[derive(Debug, PartialEq)]
+enum StepAction {
+    /// Execute the next instruction
+    Resume,
+    /// Show the prompt again
+    Prompt,
+    Quit,
+}
+
+/// Single-step debugger state
+struct Stepper {
+    /// Whether to stop at the prompt before each instruction
+    stepping: bool,
+}
+
+impl Stepper {
+    /// Apply a command to the VM
+    fn dispatch(&mut self, vm: &mut VM, command: StepCommand) -> Result<StepAction, String> {
+        match command {
+            StepCommand::Next => Ok(StepAction::Resume),
+            StepCommand::Reverse => {
+                vm.reverse_last()?;
+                Ok(StepAction::Prompt)
+            }
+            StepCommand::Continue => {
+                self.stepping = false;
+                Ok(StepAction::Resume)
+            }
+            StepCommand::Quit => Ok(StepAction::Quit),
+        }
+    }
+    
+    /// Prompt until a command resumes execution; false means quit
+    fn prompt(&mut self, vm: &mut VM, input: &mut impl BufRead) -> bool {
+        loop {
+            match vm.fetch() {
+                Ok(Some(inst)) => println!("\nNext [{}]: {:?}", vm.ip, inst),
+                _ => println!("\nNext [{}]: end of program", vm.ip),
+            }
+            debug_vm(vm);
+            print!("(n)ext, (r)everse, (c)ontinue, (q)uit: ");
+            io::stdout().flush().unwrap();
+            
+            let mut line = String::new();
+            if input.read_line(&mut line).unwrap_or(0) == 0 {
+                return false;
+            }
+            
+            let action = StepCommand::parse(&line)
+                .and_then(|command| self.dispatch(vm, command));
+            match action {
+                Ok(StepAction::Resume) => return true,
+                Ok(StepAction::Prompt) => {}
+                Ok(StepAction::Quit) => return false,
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+    }
+}
 
 fn main() {
     let args: Vec<String> = std::env::args().collect();
-    if args.len() != 2 {
-        eprintln!("Usage: pvmr <file.pvm>");
-        std::process::exit(1);
-    }
+    let options = Options::parse(&args[1..])
+        .unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            eprintln!("Usage: pvmr [--step] [--trace] [--disasm] [--budget <count>] [--break <label|index>]... [--watch <start:end>]... <file.pvm>");
+            std::process::exit(1);
+        });
     
     // Read the assembly file
-    let code = fs::read_to_string(&args[1])
+    let code = fs::read_to_string(&options.path)
         .unwrap_or_else(|e| {
-            eprintln!("Failed to read file '{}': {}", args[1], e);
+            eprintln!("Failed to read file '{}': {}", options.path, e);
             std::process::exit(1);
         });
     
@@ -26,11 +212,19 @@ fn main() {
             std::process::exit(1);
         });
     
-    if instructions.is_empty() {
-        eprintln!("No instructions found in file");
-        std::process::exit(1);
+    if options.disasm {
+        let mut program = Program::new(instructions, parser.labels().clone());
+        program.data = parser.data().to_vec();
+        print!("{}", disassemble_program(&program));
+        return;
     }
     
+    let breakpoints = resolve_breakpoints(&options.breakpoints, parser.labels())
+        .unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        });
+    
     // Create VM and load program
     let mut vm = VM::new();
     
@@ -39,94 +233,223 @@ fn main() {
         vm.symbols.insert(label.clone(), *pos);
     }
     
-    vm.load_program(instructions.clone())
+    let program_len = instructions.len();
+    vm.load_program(instructions)
         .unwrap_or_else(|e| {
             eprintln!("Failed to load program: {}", e);
             std::process::exit(1);
         });
+    if !parser.data().is_empty() {
+        vm.load_data(parser.data())
+            .unwrap_or_else(|e| {
+                eprintln!("Failed to load data: {}", e);
+                std::process::exit(1);
+            });
+    }
     
     println!("Palindrome VM Runner");
     println!("===================");
-    println!("Loaded {} instructions", instructions.len());
+    println!("Loaded {} instructions", program_len);
     println!("Starting execution...\n");
     
+    let watch_hits: Rc<RefCell<Vec<WatchHit>>> = Rc::default();
+    if !options.watchpoints.is_empty() {
+        for &(start, end) in &options.watchpoints {
+            vm.add_watchpoint(start, end);
+        }
+        let sink = Rc::clone(&watch_hits);
+        vm.set_watch_handler(move |hit| sink.borrow_mut().push(hit));
+    }
+    
+    if options.trace {
+        vm.enable_trace();
+    }
+    
     // Execute instructions
-    let mut instruction_count = 0;
     let mut halted = false;
+    let mut finished = false;
+    let mut stepper = Stepper { stepping: options.step };
+    let mut executed = 0;
     
-    while (vm.ip as usize) < instructions.len() && !halted {
-        let inst = instructions[vm.ip as usize].clone();
+    loop {
+        if let Some(budget) = options.budget.filter(|&budget| executed >= budget) {
+            eprintln!("\nBudget of {} instructions exhausted at IP {}", budget, vm.ip);
+            break;
+        }
+        
+        if let Some(breakpoint) = breakpoint_at(&breakpoints, vm.ip) {
+            println!("\nBreakpoint '{}' hit at instruction {}", breakpoint.name, breakpoint.ip);
+            stepper.stepping = true;
+        }
+        if stepper.stepping && !stepper.prompt(&mut vm, &mut io::stdin().lock()) {
+            break;
+        }
         
-        match vm.execute(inst.clone()) {
-            Ok(()) => {
-                instruction_count += 1;
+        match vm.step() {
+            Ok(StepOutcome::Executed) => {
+                executed += 1;
+                for hit in watch_hits.take() {
+                    println!("\nWatchpoint: instruction {} wrote {} bytes at {}: {:?} -> {:?}",
+                             hit.ip, hit.new.len(), hit.pos, hit.old, hit.new);
+                    stepper.stepping = true;
+                }
+            }
+            Ok(StepOutcome::Halted) => {
+                halted = true;
+                break;
+            }
+            Ok(StepOutcome::Finished) => {
+                finished = true;
+                break;
             }
             Err(e) => {
-                if e == "HALT" {
-                    halted = true;
-                    println!("\nProgram halted normally.");
-                } else {
-                    eprintln!("\nExecution error at IP {}: {}", vm.ip, e);
+                eprintln!("\nExecution error at IP {}: {}", vm.ip, e);
+                if let Ok(Some(inst)) = vm.fetch() {
                     eprintln!("Instruction: {:?}", inst);
-                    
-                    // Offer to reverse or debug
-                    print!("\nOptions: (r)everse last, (d)ebug, (q)uit: ");
-                    io::stdout().flush().unwrap();
-                    
-                    let mut input = String::new();
-                    io::stdin().read_line(&mut input).unwrap();
-                    
-                    match input.trim() {
-                        "r" => {
-                            match vm.reverse_last() {
-                                Ok(()) => {
-                                    println!("Reversed last operation. IP now at {}", vm.ip);
-                                    continue;
-                                }
-                                Err(e) => {
-                                    eprintln!("Failed to reverse: {}", e);
-                                    break;
-                                }
+                }
+                
+                // Offer to reverse or debug
+                print!("\nOptions: (r)everse last, (d)ebug, (q)uit: ");
+                io::stdout().flush().unwrap();
+                
+                let mut input = String::new();
+                io::stdin().read_line(&mut input).unwrap();
+                
+                match input.trim() {
+                    "r" => {
+                        match vm.reverse_last() {
+                            Ok(()) => {
+                                println!("Reversed last operation. IP now at {}", vm.ip);
+                                continue;
+                            }
+                            Err(e) => {
+                                eprintln!("Failed to reverse: {}", e);
+                                break;
                             }
                         }
-                        "d" => {
-                            debug_vm(&vm);
-                            continue;
-                        }
-                        _ => break,
                     }
+                    "d" => {
+                        debug_vm(&vm);
+                        continue;
+                    }
+                    _ => break,
                 }
             }
         }
     }
     
-    if !halted && (vm.ip as usize) >= instructions.len() {
+    if halted {
+        println!("\nProgram halted normally.");
+    } else if finished {
         println!("\nProgram ended (reached end of instructions).");
     }
     
     println!("\nExecution statistics:");
-    println!("  Instructions executed: {}", instruction_count);
+    println!("  Instructions executed: {}", vm.ic);
     println!("  Final IP: {}", vm.ip);
     println!("  Final SP: {}", vm.sp);
     println!("  Tape position: {}", vm.tape.tape.position());
+    
+    if options.trace {
+        let mut summary: Vec<_> = vm.trace_summary().into_iter().collect();
+        summary.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        println!("\nInstruction counts:");
+        for (name, count) in summary {
+            println!("  {:<16} {}", name, count);
+        }
+    }
 }
 
 fn debug_vm(vm: &VM) {
-    println!("\n=== VM State ===");
-    println!("IP: {}, SP: {}, FP: {}", vm.ip, vm.sp, vm.fp);
-    println!("\nRegisters:");
-    for i in 0..8 {
-        print!("  R{}: {:8} ", i, vm.registers.general[i]);
-        if i % 4 == 3 { println!(); }
-    }
-    println!("\nFlags:");
-    println!("  Zero: {}, Negative: {}, Carry: {}, Overflow: {}",
-        vm.registers.flags.zero,
-        vm.registers.flags.negative,
-        vm.registers.flags.carry,
-        vm.registers.flags.overflow
-    );
-    println!("\nTape position: {}", vm.tape.tape.position());
-    println!("History depth: {}", vm.history.stack.len());
-    println!("================\n");
-}
\ No newline at end of file
+    println!();
+    vm.dump_state(&mut io::stdout()).unwrap();
+    println!();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use palindrome_vm::Instruction;
+
+    #[test]
+    fn test_parse_options() {
+        let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(Options::parse(&args(&["prog.pvm"])).unwrap(),
+                   Options { path: "prog.pvm".to_string(), step: false, breakpoints: vec![], watchpoints: vec![], trace: false, budget: None, disasm: false });
+        assert_eq!(Options::parse(&args(&["--step", "--trace", "prog.pvm"])).unwrap(),
+                   Options { path: "prog.pvm".to_string(), step: true, breakpoints: vec![], watchpoints: vec![], trace: true, budget: None, disasm: false });
+        assert_eq!(Options::parse(&args(&["--break", "loop", "prog.pvm", "--break", "3"])).unwrap().breakpoints,
+                   vec!["loop".to_string(), "3".to_string()]);
+        assert_eq!(Options::parse(&args(&["prog.pvm", "--break"])).unwrap_err(),
+                   "--break requires a label or instruction index");
+        assert_eq!(Options::parse(&args(&["--watch", "4000:4008", "prog.pvm"])).unwrap().watchpoints,
+                   vec![(4000, 4008)]);
+        assert_eq!(Options::parse(&args(&["--budget", "1000", "prog.pvm"])).unwrap().budget, Some(1000));
+        assert!(Options::parse(&args(&["--disasm", "prog.pvm"])).unwrap().disasm);
+        assert_eq!(Options::parse(&args(&["--budget", "lots", "prog.pvm"])).unwrap_err(), "Invalid budget: lots");
+        assert_eq!(Options::parse(&args(&["--watch", "8:8", "prog.pvm"])).unwrap_err(),
+                   "Invalid watch range '8:8' (expected START:END)");
+        assert_eq!(Options::parse(&args(&["--verbose", "prog.pvm"])).unwrap_err(), "Unknown option: --verbose");
+        assert_eq!(Options::parse(&args(&["--step"])).unwrap_err(), "Missing program file");
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let mut parser = Parser::new();
+        parser.parse("LI R0, 1\nloop:\nNOP\nJMP loop").unwrap();
+        
+        let targets = vec!["loop".to_string(), "0".to_string()];
+        let breakpoints = resolve_breakpoints(&targets, parser.labels()).unwrap();
+        assert_eq!(breakpoints, vec![
+            Breakpoint { name: "loop".to_string(), ip: 1 },
+            Breakpoint { name: "0".to_string(), ip: 0 },
+        ]);
+        assert_eq!(breakpoint_at(&breakpoints, 1).unwrap().name, "loop");
+        assert_eq!(breakpoint_at(&breakpoints, 0).unwrap().name, "0");
+        assert!(breakpoint_at(&breakpoints, 2).is_none());
+        
+        let err = resolve_breakpoints(&["nowhere".to_string()], parser.labels()).unwrap_err();
+        assert_eq!(err, "Unknown breakpoint label: nowhere");
+    }
+
+    #[test]
+    fn test_step_commands() {
+        assert_eq!(StepCommand::parse("n\n").unwrap(), StepCommand::Next);
+        assert_eq!(StepCommand::parse("").unwrap(), StepCommand::Next);
+        assert_eq!(StepCommand::parse(" R ").unwrap(), StepCommand::Reverse);
+        assert_eq!(StepCommand::parse("continue").unwrap(), StepCommand::Continue);
+        assert_eq!(StepCommand::parse("q").unwrap(), StepCommand::Quit);
+        assert_eq!(StepCommand::parse("x").unwrap_err(), "Unknown command 'x' (expected n, r, c or q)");
+    }
+
+    #[test]
+    fn test_step_dispatch() {
+        let mut vm = VM::new();
+        vm.load_program(vec![Instruction::LoadImm { reg: 0, value: 9 }, Instruction::Halt]).unwrap();
+        let mut stepper = Stepper { stepping: true };
+        
+        assert_eq!(stepper.dispatch(&mut vm, StepCommand::Next).unwrap(), StepAction::Resume);
+        vm.step().unwrap();
+        assert_eq!(vm.registers.read(0).unwrap(), 9);
+        
+        // Reversing stays at the prompt with the instruction undone
+        assert_eq!(stepper.dispatch(&mut vm, StepCommand::Reverse).unwrap(), StepAction::Prompt);
+        assert_eq!((vm.ip, vm.registers.read(0).unwrap()), (0, 0));
+        assert!(stepper.dispatch(&mut vm, StepCommand::Reverse).is_err());
+        
+        assert_eq!(stepper.dispatch(&mut vm, StepCommand::Continue).unwrap(), StepAction::Resume);
+        assert!(!stepper.stepping);
+        assert_eq!(stepper.dispatch(&mut vm, StepCommand::Quit).unwrap(), StepAction::Quit);
+    }
+
+    #[test]
+    fn test_prompt_reads_commands_until_resume() {
+        let mut vm = VM::new();
+        vm.load_program(vec![Instruction::Halt]).unwrap();
+        let mut stepper = Stepper { stepping: true };
+        
+        let mut input = io::Cursor::new("bogus\nn\n");
+        assert!(stepper.prompt(&mut vm, &mut input));
+        assert!(!stepper.prompt(&mut vm, &mut input));
+    }
+}
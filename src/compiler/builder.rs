@@ -0,0 +1,153 @@
+//! Fluent construction of programs from Rust
+//!
+//! `ProgramBuilder` is the Rust-side analog of the assembler: instructions are
+//! appended in order and labels are bound to the next instruction's position.
+
+use crate::compiler::Program;
+use crate::instruction::Instruction;
+use crate::vm::Register;
+use std::collections::HashMap;
+
+#[derive(Default)]
+pub struct ProgramBuilder {
+    instructions: Vec<Instruction>,
+    labels: HashMap<String, i64>,
+    /// First label defined more than once
+    duplicate: Option<String>,
+}
+
+impl ProgramBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Bind `name` to the position of the next instruction
+    pub fn label(mut self, name: &str) -> Self {
+        let position = self.instructions.len() as i64;
+        if self.labels.insert(name.to_string(), position).is_some() && self.duplicate.is_none() {
+            self.duplicate = Some(name.to_string());
+        }
+        self
+    }
+    
+    /// Append any instruction
+    pub fn inst(mut self, inst: Instruction) -> Self {
+        self.instructions.push(inst);
+        self
+    }
+    
+    pub fn li(self, reg: Register, value: i64) -> Self {
+        self.inst(Instruction::LoadImm { reg, value })
+    }
+    
+    /// dst += a + b
+    pub fn radd(self, dst: Register, a: Register, b: Register) -> Self {
+        self.inst(Instruction::RAdd { src1: a, src2: b, dst })
+    }
+    
+    /// dst -= a + b
+    pub fn rsub(self, dst: Register, a: Register, b: Register) -> Self {
+        self.inst(Instruction::RSub { src1: a, src2: b, dst })
+    }
+    
+    pub fn rxor(self, dst: Register, src: Register) -> Self {
+        self.inst(Instruction::RXor { src, dst })
+    }
+    
+    pub fn swap(self, reg1: Register, reg2: Register) -> Self {
+        self.inst(Instruction::Swap { reg1, reg2 })
+    }
+    
+    pub fn push(self, reg: Register) -> Self {
+        self.inst(Instruction::Push { reg })
+    }
+    
+    pub fn pop(self, reg: Register) -> Self {
+        self.inst(Instruction::Pop { reg })
+    }
+    
+    pub fn jump(self, label: &str) -> Self {
+        self.inst(Instruction::Jump { label: label.to_string() })
+    }
+    
+    pub fn branch_zero(self, reg: Register, label: &str) -> Self {
+        self.inst(Instruction::BranchZero { reg, label: label.to_string() })
+    }
+    
+    pub fn branch_not_zero(self, reg: Register, label: &str) -> Self {
+        self.inst(Instruction::BranchNotZero { reg, label: label.to_string() })
+    }
+    
+    pub fn call(self, label: &str) -> Self {
+        self.inst(Instruction::Call { label: label.to_string() })
+    }
+    
+    pub fn ret(self) -> Self {
+        self.inst(Instruction::Return)
+    }
+    
+    pub fn halt(self) -> Self {
+        self.inst(Instruction::Halt)
+    }
+    
+    /// Finish the program, checking that labels are unique and references resolve
+    pub fn build(self) -> Result<Program, String> {
+        if let Some(label) = self.duplicate {
+            return Err(format!("Duplicate label: {}", label));
+        }
+        
+        let program = Program::new(self.instructions, self.labels);
+        program.validate()?;
+        Ok(program)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::Parser;
+
+    #[test]
+    fn test_counting_loop_matches_assembly() {
+        let built = ProgramBuilder::new()
+            .label("main")
+            .li(0, 0)
+            .label("loop")
+            .li(1, 1)
+            .li(2, 0)
+            .radd(2, 0, 1)
+            .swap(0, 2)
+            .branch_not_zero(2, "loop")
+            .halt()
+            .build()
+            .unwrap();
+        
+        let parsed = Parser::new().parse_program(r#"
+        main:
+            LI R0, 0
+        loop:
+            LI R1, 1
+            LI R2, 0
+            RADD R0, R1, R2
+            SWAP R0, R2
+            BNZ R2, loop
+            HALT
+        "#).unwrap();
+        
+        assert_eq!(built.instructions, parsed.instructions);
+        assert_eq!(built.symbols, parsed.symbols);
+    }
+
+    #[test]
+    fn test_label_errors() {
+        let err = ProgramBuilder::new().jump("nowhere").build().unwrap_err();
+        assert!(err.contains("Undefined label 'nowhere'"));
+        
+        let err = ProgramBuilder::new()
+            .label("a").halt()
+            .label("a").halt()
+            .build()
+            .unwrap_err();
+        assert_eq!(err, "Duplicate label: a");
+    }
+}
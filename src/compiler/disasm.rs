@@ -0,0 +1,257 @@
+//! Disassembly of instructions back into assembly text
+//!
+//! The output uses the mnemonics and operand order `Parser` accepts, so parsing
+//! the text of a program gives back the same instructions.
+
+use crate::compiler::Program;
+use crate::instruction::{Instruction, MergeStrategy};
+use crate::vm::DATA_SEGMENT_BASE;
+use std::collections::BTreeMap;
+
+/// Bytes per `.byte` line in the data section
+const BYTES_PER_LINE: usize = 16;
+
+/// Render instructions as assembly, one per line
+///
+/// Jump operands are emitted by name; use `disassemble_program` to also emit
+/// the label definitions they refer to.
+pub fn disassemble(instructions: &[Instruction]) -> String {
+    let mut out = String::new();
+    for inst in instructions {
+        out.push_str("    ");
+        out.push_str(&render(inst));
+        out.push('\n');
+    }
+    out
+}
+
+/// Render a program with its label definitions and `.data` section
+///
+/// Code labels are placed before the instruction they point at and data labels
+/// before their byte. Labels at the same position are emitted in name order;
+/// symbols pointing outside both the code and the data are dropped.
+pub fn disassemble_program(program: &Program) -> String {
+    let data_end = DATA_SEGMENT_BASE + program.data.len() as i64;
+    let mut code_labels: BTreeMap<i64, Vec<&str>> = BTreeMap::new();
+    let mut data_labels: BTreeMap<i64, Vec<&str>> = BTreeMap::new();
+    for (name, &pos) in &program.symbols {
+        if (0..=program.instructions.len() as i64).contains(&pos) {
+            code_labels.entry(pos).or_default().push(name);
+        } else if (DATA_SEGMENT_BASE..=data_end).contains(&pos) {
+            data_labels.entry(pos - DATA_SEGMENT_BASE).or_default().push(name);
+        }
+    }
+    
+    let mut out = String::new();
+    if !program.data.is_empty() || !data_labels.is_empty() {
+        out.push_str(".data\n");
+        let mut start = 0;
+        while start <= program.data.len() {
+            push_labels(&mut out, data_labels.get(&(start as i64)));
+            // A line runs until the next label or the line width, whichever is first
+            let next_label = data_labels.range(start as i64 + 1..).next().map(|(&off, _)| off as usize);
+            let end = next_label.unwrap_or(usize::MAX).min(start + BYTES_PER_LINE).min(program.data.len());
+            if end == start {
+                break;
+            }
+            let bytes: Vec<String> = program.data[start..end].iter().map(u8::to_string).collect();
+            out.push_str(&format!("    .byte {}\n", bytes.join(", ")));
+            start = end;
+        }
+        out.push_str(".text\n");
+    }
+    
+    for (pos, inst) in program.instructions.iter().enumerate() {
+        push_labels(&mut out, code_labels.get(&(pos as i64)));
+        out.push_str("    ");
+        out.push_str(&render(inst));
+        out.push('\n');
+    }
+    push_labels(&mut out, code_labels.get(&(program.instructions.len() as i64)));
+    
+    out
+}
+
+fn push_labels(out: &mut String, names: Option<&Vec<&str>>) {
+    let mut names = names.cloned().unwrap_or_default();
+    names.sort_unstable();
+    for name in names {
+        out.push_str(name);
+        out.push_str(":\n");
+    }
+}
+
+/// The assembly text of a single instruction
+fn render(inst: &Instruction) -> String {
+    let r = |reg: &u8| format!("R{}", reg);
+    match inst {
+        Instruction::RAdd { src1, src2, dst } => format!("RADD {}, {}, {}", r(src1), r(src2), r(dst)),
+        Instruction::RSub { src1, src2, dst } => format!("RSUB {}, {}, {}", r(src1), r(src2), r(dst)),
+        Instruction::RXor { src, dst } => format!("RXOR {}, {}", r(src), r(dst)),
+        Instruction::ModRepeat { dst, src, modulus, quotient } => {
+            format!("MODREP {}, {}, {}, {}", r(dst), r(src), r(modulus), r(quotient))
+        }
+        Instruction::BitReverse { dst, src, width } => format!("BITREV {}, {}, {}", r(dst), r(src), width),
+        Instruction::INot { dst, src } => format!("NOT {}, {}", r(dst), r(src)),
+        Instruction::IMul { src1, src2, dst } => format!("IMUL {}, {}, {}", r(src1), r(src2), r(dst)),
+        Instruction::IDiv { src1, src2, dst } => format!("IDIV {}, {}, {}", r(src1), r(src2), r(dst)),
+        Instruction::IMod { src1, src2, dst } => format!("IMOD {}, {}, {}", r(src1), r(src2), r(dst)),
+        Instruction::IAnd { src1, src2, dst } => format!("AND {}, {}, {}", r(src1), r(src2), r(dst)),
+        Instruction::IOr { src1, src2, dst } => format!("OR {}, {}, {}", r(src1), r(src2), r(dst)),
+        Instruction::Shl { dst, src, amount } => format!("SHL {}, {}, {}", r(dst), r(src), r(amount)),
+        Instruction::Shr { dst, src, amount } => format!("SHR {}, {}, {}", r(dst), r(src), r(amount)),
+        Instruction::Sar { dst, src, amount } => format!("SAR {}, {}, {}", r(dst), r(src), r(amount)),
+        Instruction::RLoad { dst, addr, old } => format!("RLOAD {}, {}, {}", r(dst), r(addr), r(old)),
+        Instruction::RStore { addr, src, old } => format!("RSTORE {}, {}, {}", r(addr), r(src), r(old)),
+        Instruction::MSwap { addr, reg } => format!("MSWAP {}, {}", r(addr), r(reg)),
+        Instruction::Crc32 { dst, addr, len } => format!("CRC32 {}, {}, {}", r(dst), r(addr), r(len)),
+        Instruction::SortRange { addr, count, elem_size } => {
+            format!("SORT {}, {}, {}", r(addr), r(count), elem_size)
+        }
+        Instruction::Interleave { a, b, dst, count } => {
+            format!("ILV {}, {}, {}, {}", r(a), r(b), r(dst), r(count))
+        }
+        Instruction::Deinterleave { src, a, b, count } => {
+            format!("DILV {}, {}, {}, {}", r(src), r(a), r(b), r(count))
+        }
+        Instruction::HashUpdate { state, addr, len } => {
+            format!("HASHUPD {}, {}, {}", r(state), r(addr), r(len))
+        }
+        Instruction::IncSat { addr, max } => format!("INCSAT {}, {}", r(addr), r(max)),
+        Instruction::Swap { reg1, reg2 } => format!("SWAP {}, {}", r(reg1), r(reg2)),
+        Instruction::Push { reg } => format!("PUSH {}", r(reg)),
+        Instruction::Pop { reg } => format!("POP {}", r(reg)),
+        Instruction::Dup => "DUP".to_string(),
+        Instruction::Drop => "DROP".to_string(),
+        Instruction::Over => "OVER".to_string(),
+        Instruction::SwapTop => "SWAPTOP".to_string(),
+        Instruction::TapeRead { reg, len } => format!("TAPEREAD {}, {}", r(reg), len),
+        Instruction::TapeWrite { reg, len } => format!("TAPEWRITE {}, {}", r(reg), len),
+        Instruction::TapeSeek { position } => format!("TAPESEEK {}", position),
+        Instruction::TapeSeekReg { reg } => format!("TAPESEEKREG {}", r(reg)),
+        Instruction::TapeAdvance { delta } => format!("TAPEADVANCE {}", delta),
+        Instruction::TapeMark { label } => format!("TAPEMARK {}", label),
+        Instruction::TapeSeekMark { label } => format!("TAPESEEKMARK {}", label),
+        Instruction::SegmentCreate { name, size } => format!("SEGCREATE {}, {}", name, r(size)),
+        Instruction::SegmentSeek { name, offset } => format!("SEGSEEK {}, {}", name, r(offset)),
+        Instruction::SegmentRead { name, offset, len, dst } => {
+            format!("SEGREAD {}, {}, {}, {}", name, r(offset), r(len), r(dst))
+        }
+        Instruction::SegmentWrite { name, offset, len, src } => {
+            format!("SEGWRITE {}, {}, {}, {}", name, r(offset), r(len), r(src))
+        }
+        Instruction::Splice { dst, src, len } => format!("SPLICE {}, {}, {}", dst, src, r(len)),
+        Instruction::Compact { start, end } => format!("COMPACT {}, {}", start, end),
+        Instruction::Fork { label } => format!("FORK {}", label),
+        Instruction::Merge { strategy } => format!("MERGE {}", match strategy {
+            MergeStrategy::Latest => "latest",
+            MergeStrategy::Earliest => "earliest",
+            MergeStrategy::Combine => "combine",
+            MergeStrategy::Manual => "manual",
+        }),
+        Instruction::TimelineSwitch { label } => format!("TSWITCH {}", label),
+        Instruction::Call { label } => format!("CALL {}", label),
+        Instruction::Return => "RET".to_string(),
+        Instruction::Jump { label } => format!("JMP {}", label),
+        Instruction::Branch { condition, label } => format!("BR {}, {}", r(condition), label),
+        Instruction::BranchZero { reg, label } => format!("BZ {}, {}", r(reg), label),
+        Instruction::BranchNotZero { reg, label } => format!("BNZ {}, {}", r(reg), label),
+        Instruction::BranchLessThanU { src1, src2, label } => format!("BLTU {}, {}, {}", r(src1), r(src2), label),
+        Instruction::BranchGreaterEqualU { src1, src2, label } => format!("BGEU {}, {}, {}", r(src1), r(src2), label),
+        Instruction::Checkpoint { label } => format!("CHECKPOINT {}", label),
+        Instruction::Rewind { label } => format!("REWIND {}", label),
+        Instruction::RewindN { steps } => format!("REWINDN {}", r(steps)),
+        Instruction::Compare { dst, src1, src2 } => format!("CMP {}, {}, {}", r(dst), r(src1), r(src2)),
+        Instruction::Equal { dst, src1, src2 } => format!("EQ {}, {}, {}", r(dst), r(src1), r(src2)),
+        Instruction::LessThan { dst, src1, src2 } => format!("LT {}, {}, {}", r(dst), r(src1), r(src2)),
+        Instruction::CompareU { dst, src1, src2 } => format!("CMPU {}, {}, {}", r(dst), r(src1), r(src2)),
+        Instruction::LessThanU { dst, src1, src2 } => format!("LTU {}, {}, {}", r(dst), r(src1), r(src2)),
+        Instruction::LoadImm { reg, value } => format!("LI {}, {}", r(reg), value),
+        Instruction::Halt => "HALT".to_string(),
+        Instruction::Nop => "NOP".to_string(),
+        Instruction::Debug { message } => format!("DEBUG {}", quote(message)),
+        Instruction::Trap { code } => format!("TRAP {}", code),
+    }
+}
+
+/// A string literal the parser reads back as `text`
+fn quote(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c if c.is_ascii_control() => out.push_str(&format!("\\x{:02x}", c as u8)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::Parser;
+    use crate::instruction::every_instruction;
+
+    #[test]
+    fn test_every_instruction_round_trips() {
+        let instructions = every_instruction();
+        let text = disassemble(&instructions);
+        assert_eq!(Parser::new().parse(&text).unwrap(), instructions);
+    }
+
+    #[test]
+    fn test_debug_messages_round_trip() {
+        let messages = ["a; b", "two  spaces", " padded ", "say \"hi\"", "back\\slash", "tab\tbell\x07", "", "é"];
+        let instructions: Vec<Instruction> = messages.iter()
+            .map(|message| Instruction::Debug { message: message.to_string() })
+            .collect();
+        let text = disassemble(&instructions);
+        assert!(text.contains("DEBUG \"a; b\"\n"));
+        assert_eq!(Parser::new().parse(&text).unwrap(), instructions);
+        
+        // Unquoted messages are still accepted
+        assert_eq!(Parser::new().parse("DEBUG loop  done ; note").unwrap(),
+                   vec![Instruction::Debug { message: "loop done".to_string() }]);
+        assert_eq!(Parser::new().parse("DEBUG \"open").unwrap_err(),
+                   "Line 1, col 7: Unterminated string: \"open");
+    }
+
+    #[test]
+    fn test_program_round_trips_with_labels_and_data() {
+        let source = r#"
+        .data
+        greeting:
+            .ascii "hi"
+        table:
+            .word 258
+        .text
+        main:
+            LI R0, 10
+            LI R1, 1
+            LEA R4, table
+        loop:
+            RSUB R0, R1, R0
+            IMUL R0, R1, R2
+            TAPESEEKREG R4
+            TAPEREAD R3, 8
+            TAPEWRITE R2, 8
+            TAPEADVANCE -8
+            BNZ R0, loop
+            CALL finish
+        finish:
+            HALT
+        end:
+        "#;
+        let program = Parser::new().parse_program(source).unwrap();
+        let text = disassemble_program(&program);
+        
+        assert!(text.contains("loop:\n    RSUB R0, R1, R0"));
+        assert_eq!(Parser::new().parse_program(&text).unwrap(), program);
+    }
+}
@@ -2,6 +2,12 @@
 //! 
 //! Provides assembly parsing and future optimization passes.
 
+mod builder;
+mod disasm;
 mod parser;
+mod program;
 
-pub use parser::Parser;
\ No newline at end of file
+pub use builder::ProgramBuilder;
+pub use disasm::{disassemble, disassemble_program};
+pub use parser::Parser;
+pub use program::Program;
\ No newline at end of file
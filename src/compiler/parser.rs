@@ -1,12 +1,23 @@
 //! Assembly parser for Palindrome VM
 
-use crate::instruction::Instruction;
-use crate::vm::Register;
+use crate::compiler::Program;
+use crate::instruction::{Instruction, MergeStrategy};
+use crate::vm::{Register, DATA_SEGMENT_BASE, DEFAULT_REGISTER_COUNT};
 use std::collections::HashMap;
+use std::fmt;
+use std::ops::Deref;
 
 pub struct Parser {
     labels: HashMap<String, i64>,
     current_position: i64,
+    /// Initialized bytes of the `.data` section, loaded at `DATA_SEGMENT_BASE`
+    data: Vec<u8>,
+    /// Register names defined by `.alias`, keyed in lowercase
+    aliases: HashMap<String, Register>,
+    /// Treat a bare identifier at column 0 as a label definition
+    indented_labels: bool,
+    /// Registers `R0` up to this count (exclusive) are accepted
+    register_count: usize,
 }
 
 impl Parser {
@@ -14,6 +25,29 @@ impl Parser {
         Parser {
             labels: HashMap::new(),
             current_position: 0,
+            data: Vec::new(),
+            aliases: HashMap::new(),
+            indented_labels: false,
+            register_count: DEFAULT_REGISTER_COUNT,
+        }
+    }
+    
+    /// Create a parser for indentation-style sources
+    ///
+    /// A line starting at column 0 holding a single identifier defines a label,
+    /// so instructions must be indented. `label:` lines are still labels.
+    pub fn with_indented_labels() -> Self {
+        Parser {
+            indented_labels: true,
+            ..Self::new()
+        }
+    }
+    
+    /// Create a parser for a VM made with `VM::with_register_count`
+    pub fn with_register_count(count: usize) -> Self {
+        Parser {
+            register_count: count,
+            ..Self::new()
         }
     }
     
@@ -22,20 +56,44 @@ impl Parser {
         &self.labels
     }
     
+    /// Get the bytes of the `.data` section
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+    
+    /// Parse assembly source into instructions
+    ///
+    /// A source with no instructions (empty, blank or only comments and labels)
+    /// is not an error: it parses to an empty vector. Errors are reserved for
+    /// lines that cannot be parsed.
+    ///
+    /// Lines after `.data` are data directives and their labels are tape
+    /// addresses in the data segment; `.text` switches back to instructions.
     pub fn parse(&mut self, source: &str) -> Result<Vec<Instruction>, String> {
         let mut instructions = Vec::new();
         
-        // First pass: collect labels
+        // First pass: collect labels and assemble the data section
         self.current_position = 0;
-        for line in source.lines() {
-            let line = line.trim();
+        self.data.clear();
+        let mut in_data = false;
+        for (line_num, raw_line) in source.lines().enumerate() {
+            let line = raw_line.trim();
             if line.is_empty() || line.starts_with(';') {
                 continue;
             }
             
-            if line.ends_with(':') {
-                let label = line.trim_end_matches(':').to_string();
-                self.labels.insert(label, self.current_position);
+            if let Some(label) = self.label_definition(raw_line) {
+                let position = if in_data {
+                    DATA_SEGMENT_BASE + self.data.len() as i64
+                } else {
+                    self.current_position
+                };
+                self.labels.insert(label.to_string(), position);
+            } else if line.starts_with('.') {
+                self.parse_directive(Token::line(raw_line), &mut in_data)
+                    .map_err(|e| locate(raw_line, line_num, e))?;
+            } else if in_data {
+                return Err(format!("Line {}: Instruction in .data section: {}", line_num + 1, line));
             } else {
                 self.current_position += 1;
             }
@@ -43,8 +101,9 @@ impl Parser {
         
         // Second pass: parse instructions
         self.current_position = 0;
-        for (line_num, line) in source.lines().enumerate() {
-            let line = line.trim();
+        self.aliases.clear();
+        for (line_num, raw_line) in source.lines().enumerate() {
+            let line = raw_line.trim();
             
             // Skip empty lines and comments
             if line.is_empty() || line.starts_with(';') {
@@ -52,30 +111,123 @@ impl Parser {
             }
             
             // Skip labels
-            if line.ends_with(':') {
+            if self.label_definition(raw_line).is_some() {
+                continue;
+            }
+            
+            // Aliases take effect from their line on; other directives were
+            // applied by the first pass
+            if line.starts_with('.') {
+                if let Some(args) = alias_arguments(Token::line(raw_line)) {
+                    self.define_alias(args)
+                        .map_err(|e| locate(raw_line, line_num, e))?;
+                }
                 continue;
             }
             
-            match self.parse_instruction(line) {
+            match self.parse_instruction(Token::line(raw_line)) {
                 Ok(inst) => instructions.push(inst),
-                Err(e) => return Err(format!("Line {}: {}", line_num + 1, e)),
+                Err(e) => return Err(locate(raw_line, line_num, e)),
             }
         }
         
         Ok(instructions)
     }
     
-    fn parse_instruction(&self, line: &str) -> Result<Instruction, String> {
-        // Remove comments (everything after ';')
-        let line = if let Some(pos) = line.find(';') {
-            &line[..pos]
-        } else {
-            line
-        };
+    /// The label defined by this (untrimmed) line, if it is a label line
+    fn label_definition<'a>(&self, raw_line: &'a str) -> Option<&'a str> {
+        let line = raw_line.trim();
+        if let Some(label) = line.strip_suffix(':') {
+            return Some(label);
+        }
+        
+        if !self.indented_labels || raw_line.starts_with(char::is_whitespace) {
+            return None;
+        }
+        
+        let code = line.split(';').next().unwrap_or("").trim();
+        is_identifier(code).then_some(code)
+    }
+    
+    /// Parse source into a `Program` bundling the instructions with their labels
+    pub fn parse_program(&mut self, source: &str) -> Result<Program, String> {
+        let instructions = self.parse(source)?;
+        let mut program = Program::new(instructions, self.labels.clone());
+        program.data = self.data.clone();
+        Ok(program)
+    }
+    
+    /// Apply one directive line, appending any data it defines
+    fn parse_directive(&mut self, line: Token, in_data: &mut bool) -> Result<(), ParseError> {
+        let (name, args) = line.strip_comment().split_first_word();
+        let name = name.to_lowercase();
         
-        let parts: Vec<&str> = line.trim().split_whitespace().collect();
+        match name.as_str() {
+            ".data" => *in_data = true,
+            ".text" => *in_data = false,
+            ".alias" => {}
+            ".byte" | ".word" | ".ascii" | ".zero" if !*in_data => {
+                return Err(format!("{} outside .data section", name).into());
+            }
+            ".byte" => {
+                for token in split_operands(args, &name)? {
+                    let value = self.parse_immediate(token)?;
+                    if !(-128..=255).contains(&value) {
+                        return Err(ParseError::at(token, format!("Byte out of range: {}", value)));
+                    }
+                    self.data.push(value as u8);
+                }
+            }
+            ".word" => {
+                for value in split_operands(args, &name)? {
+                    let value = self.parse_immediate(value)?;
+                    self.data.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+            ".ascii" => {
+                let text = args.strip_prefix('"')
+                    .and_then(|rest| rest.strip_suffix('"'))
+                    .ok_or_else(|| format!(".ascii requires a quoted string: {}", args))?;
+                self.data.extend(unescape(text)?);
+            }
+            ".zero" => {
+                let count = self.parse_immediate(args)?;
+                if count < 0 {
+                    return Err(format!("Negative .zero count: {}", count).into());
+                }
+                self.data.resize(self.data.len() + count as usize, 0);
+            }
+            _ => return Err(format!("Unknown directive: {}", name).into()),
+        }
+        
+        Ok(())
+    }
+    
+    /// Define a register alias from the `name register` operands of `.alias`
+    fn define_alias(&mut self, args: Token) -> Result<(), ParseError> {
+        let operands = args.split_whitespace();
+        if operands.len() != 2 {
+            return Err(".alias requires 2 operands".to_string().into());
+        }
+        
+        let name = operands[0].trim_end_matches(',');
+        if !is_identifier(&name) {
+            return Err(format!("Invalid alias name: {}", name).into());
+        }
+        if is_register_syntax(&name) {
+            return Err(format!("Alias '{}' collides with register syntax", name).into());
+        }
+        
+        let reg = self.parse_register(operands[1])?;
+        self.aliases.insert(name.to_lowercase(), reg);
+        Ok(())
+    }
+    
+    fn parse_instruction(&self, line: Token) -> Result<Instruction, ParseError> {
+        let line = line.strip_comment();
+        let parts = line.split_whitespace();
         if parts.is_empty() {
-            return Err("Empty instruction".to_string());
+            return Err("Empty instruction".to_string().into());
         }
         
         let mnemonic = parts[0].to_uppercase();
@@ -83,7 +235,7 @@ impl Parser {
         match mnemonic.as_str() {
             "RADD" => {
                 if parts.len() != 4 {
-                    return Err("RADD requires 3 operands".to_string());
+                    return Err("RADD requires 3 operands".to_string().into());
                 }
                 Ok(Instruction::RAdd {
                     src1: self.parse_register(parts[1])?,
@@ -94,7 +246,7 @@ impl Parser {
             
             "RSUB" => {
                 if parts.len() != 4 {
-                    return Err("RSUB requires 3 operands".to_string());
+                    return Err("RSUB requires 3 operands".to_string().into());
                 }
                 Ok(Instruction::RSub {
                     src1: self.parse_register(parts[1])?,
@@ -105,7 +257,7 @@ impl Parser {
             
             "RXOR" => {
                 if parts.len() != 3 {
-                    return Err("RXOR requires 2 operands".to_string());
+                    return Err("RXOR requires 2 operands".to_string().into());
                 }
                 Ok(Instruction::RXor {
                     src: self.parse_register(parts[1])?,
@@ -113,9 +265,95 @@ impl Parser {
                 })
             }
             
+            "MODREP" => {
+                if parts.len() != 5 {
+                    return Err("MODREP requires 4 operands".to_string().into());
+                }
+                Ok(Instruction::ModRepeat {
+                    dst: self.parse_register(parts[1])?,
+                    src: self.parse_register(parts[2])?,
+                    modulus: self.parse_register(parts[3])?,
+                    quotient: self.parse_register(parts[4])?,
+                })
+            }
+            
+            "IMUL" => {
+                if parts.len() != 4 {
+                    return Err("IMUL requires 3 operands".to_string().into());
+                }
+                Ok(Instruction::IMul {
+                    src1: self.parse_register(parts[1])?,
+                    src2: self.parse_register(parts[2])?,
+                    dst: self.parse_register(parts[3])?,
+                })
+            }
+            
+            "IDIV" | "IMOD" => {
+                if parts.len() != 4 {
+                    return Err(format!("{} requires 3 operands", mnemonic).into());
+                }
+                let src1 = self.parse_register(parts[1])?;
+                let src2 = self.parse_register(parts[2])?;
+                let dst = self.parse_register(parts[3])?;
+                Ok(if mnemonic == "IDIV" {
+                    Instruction::IDiv { src1, src2, dst }
+                } else {
+                    Instruction::IMod { src1, src2, dst }
+                })
+            }
+            
+            "AND" | "OR" => {
+                if parts.len() != 4 {
+                    return Err(format!("{} requires 3 operands", mnemonic).into());
+                }
+                let src1 = self.parse_register(parts[1])?;
+                let src2 = self.parse_register(parts[2])?;
+                let dst = self.parse_register(parts[3])?;
+                Ok(if mnemonic == "AND" {
+                    Instruction::IAnd { src1, src2, dst }
+                } else {
+                    Instruction::IOr { src1, src2, dst }
+                })
+            }
+            
+            "NOT" => {
+                if parts.len() != 3 {
+                    return Err("NOT requires 2 operands".to_string().into());
+                }
+                Ok(Instruction::INot {
+                    dst: self.parse_register(parts[1])?,
+                    src: self.parse_register(parts[2])?,
+                })
+            }
+            
+            "SHL" | "SHR" | "SAR" => {
+                if parts.len() != 4 {
+                    return Err(format!("{} requires 3 operands", mnemonic).into());
+                }
+                let dst = self.parse_register(parts[1])?;
+                let src = self.parse_register(parts[2])?;
+                let amount = self.parse_register(parts[3])?;
+                Ok(match mnemonic.as_str() {
+                    "SHL" => Instruction::Shl { dst, src, amount },
+                    "SHR" => Instruction::Shr { dst, src, amount },
+                    _ => Instruction::Sar { dst, src, amount },
+                })
+            }
+            
+            "BITREV" => {
+                if parts.len() != 4 {
+                    return Err("BITREV requires 3 operands".to_string().into());
+                }
+                Ok(Instruction::BitReverse {
+                    dst: self.parse_register(parts[1])?,
+                    src: self.parse_register(parts[2])?,
+                    width: self.parse_byte(parts[3])?,
+                })
+            }
+            
             "RLOAD" => {
                 if parts.len() != 4 {
-                    return Err("RLOAD requires 3 operands".to_string());
+                    return Err("RLOAD requires 3 operands".to_string().into());
                 }
                 Ok(Instruction::RLoad {
                     dst: self.parse_register(parts[1])?,
@@ -126,7 +364,7 @@ impl Parser {
             
             "RSTORE" => {
                 if parts.len() != 4 {
-                    return Err("RSTORE requires 3 operands".to_string());
+                    return Err("RSTORE requires 3 operands".to_string().into());
                 }
                 Ok(Instruction::RStore {
                     addr: self.parse_register(parts[1])?,
@@ -137,7 +375,7 @@ impl Parser {
             
             "MSWAP" => {
                 if parts.len() != 3 {
-                    return Err("MSWAP requires 2 operands".to_string());
+                    return Err("MSWAP requires 2 operands".to_string().into());
                 }
                 Ok(Instruction::MSwap {
                     addr: self.parse_register(parts[1])?,
@@ -145,9 +383,76 @@ impl Parser {
                 })
             }
             
+            "CRC32" => {
+                if parts.len() != 4 {
+                    return Err("CRC32 requires 3 operands".to_string().into());
+                }
+                Ok(Instruction::Crc32 {
+                    dst: self.parse_register(parts[1])?,
+                    addr: self.parse_register(parts[2])?,
+                    len: self.parse_register(parts[3])?,
+                })
+            }
+            
+            "ILV" => {
+                if parts.len() != 5 {
+                    return Err("ILV requires 4 operands".to_string().into());
+                }
+                Ok(Instruction::Interleave {
+                    a: self.parse_register(parts[1])?,
+                    b: self.parse_register(parts[2])?,
+                    dst: self.parse_register(parts[3])?,
+                    count: self.parse_register(parts[4])?,
+                })
+            }
+            
+            "DILV" => {
+                if parts.len() != 5 {
+                    return Err("DILV requires 4 operands".to_string().into());
+                }
+                Ok(Instruction::Deinterleave {
+                    src: self.parse_register(parts[1])?,
+                    a: self.parse_register(parts[2])?,
+                    b: self.parse_register(parts[3])?,
+                    count: self.parse_register(parts[4])?,
+                })
+            }
+            
+            "HASHUPD" => {
+                if parts.len() != 4 {
+                    return Err("HASHUPD requires 3 operands".to_string().into());
+                }
+                Ok(Instruction::HashUpdate {
+                    state: self.parse_register(parts[1])?,
+                    addr: self.parse_register(parts[2])?,
+                    len: self.parse_register(parts[3])?,
+                })
+            }
+            
+            "INCSAT" => {
+                if parts.len() != 3 {
+                    return Err("INCSAT requires 2 operands".to_string().into());
+                }
+                Ok(Instruction::IncSat {
+                    addr: self.parse_register(parts[1])?,
+                    max: self.parse_register(parts[2])?,
+                })
+            }
+            
+            "SORT" => {
+                if parts.len() != 4 {
+                    return Err("SORT requires 3 operands".to_string().into());
+                }
+                Ok(Instruction::SortRange {
+                    addr: self.parse_register(parts[1])?,
+                    count: self.parse_register(parts[2])?,
+                    elem_size: self.parse_byte(parts[3])?,
+                })
+            }
+            
             "SWAP" => {
                 if parts.len() != 3 {
-                    return Err("SWAP requires 2 operands".to_string());
+                    return Err("SWAP requires 2 operands".to_string().into());
                 }
                 Ok(Instruction::Swap {
                     reg1: self.parse_register(parts[1])?,
@@ -157,7 +462,7 @@ impl Parser {
             
             "PUSH" => {
                 if parts.len() != 2 {
-                    return Err("PUSH requires 1 operand".to_string());
+                    return Err("PUSH requires 1 operand".to_string().into());
                 }
                 Ok(Instruction::Push {
                     reg: self.parse_register(parts[1])?,
@@ -166,26 +471,53 @@ impl Parser {
             
             "POP" => {
                 if parts.len() != 2 {
-                    return Err("POP requires 1 operand".to_string());
+                    return Err("POP requires 1 operand".to_string().into());
                 }
                 Ok(Instruction::Pop {
                     reg: self.parse_register(parts[1])?,
                 })
             }
             
+            "DUP" | "DROP" | "OVER" | "SWAPTOP" => {
+                if parts.len() != 1 {
+                    return Err(format!("{} takes no operands", mnemonic).into());
+                }
+                Ok(match mnemonic.as_str() {
+                    "DUP" => Instruction::Dup,
+                    "DROP" => Instruction::Drop,
+                    "OVER" => Instruction::Over,
+                    _ => Instruction::SwapTop,
+                })
+            }
+            
             "LOADIMM" | "LI" => {
                 if parts.len() != 3 {
-                    return Err("LOADIMM requires 2 operands".to_string());
+                    return Err("LOADIMM requires 2 operands".to_string().into());
                 }
+                let value = if is_identifier(&parts[2]) {
+                    self.label_address(parts[2])?
+                } else {
+                    self.parse_immediate(parts[2])?
+                };
                 Ok(Instruction::LoadImm {
                     reg: self.parse_register(parts[1])?,
-                    value: self.parse_immediate(parts[2])?,
+                    value,
+                })
+            }
+            
+            "LEA" => {
+                if parts.len() != 3 {
+                    return Err("LEA requires 2 operands".to_string().into());
+                }
+                Ok(Instruction::LoadImm {
+                    reg: self.parse_register(parts[1])?,
+                    value: self.label_address(parts[2])?,
                 })
             }
             
             "TAPEREAD" => {
                 if parts.len() != 3 {
-                    return Err("TAPEREAD requires 2 operands".to_string());
+                    return Err("TAPEREAD requires 2 operands".to_string().into());
                 }
                 Ok(Instruction::TapeRead {
                     reg: self.parse_register(parts[1])?,
@@ -195,7 +527,7 @@ impl Parser {
             
             "TAPEWRITE" => {
                 if parts.len() != 3 {
-                    return Err("TAPEWRITE requires 2 operands".to_string());
+                    return Err("TAPEWRITE requires 2 operands".to_string().into());
                 }
                 Ok(Instruction::TapeWrite {
                     reg: self.parse_register(parts[1])?,
@@ -205,16 +537,25 @@ impl Parser {
             
             "TAPESEEK" => {
                 if parts.len() != 2 {
-                    return Err("TAPESEEK requires 1 operand".to_string());
+                    return Err("TAPESEEK requires 1 operand".to_string().into());
                 }
                 Ok(Instruction::TapeSeek {
                     position: self.parse_immediate(parts[1])?,
                 })
             }
             
+            "TAPESEEKREG" => {
+                if parts.len() != 2 {
+                    return Err("TAPESEEKREG requires 1 operand".to_string().into());
+                }
+                Ok(Instruction::TapeSeekReg {
+                    reg: self.parse_register(parts[1])?,
+                })
+            }
+            
             "TAPEADVANCE" => {
                 if parts.len() != 2 {
-                    return Err("TAPEADVANCE requires 1 operand".to_string());
+                    return Err("TAPEADVANCE requires 1 operand".to_string().into());
                 }
                 Ok(Instruction::TapeAdvance {
                     delta: self.parse_immediate(parts[1])?,
@@ -223,7 +564,7 @@ impl Parser {
             
             "TAPEMARK" => {
                 if parts.len() != 2 {
-                    return Err("TAPEMARK requires 1 operand".to_string());
+                    return Err("TAPEMARK requires 1 operand".to_string().into());
                 }
                 Ok(Instruction::TapeMark {
                     label: parts[1].to_string(),
@@ -232,7 +573,7 @@ impl Parser {
             
             "TAPESEEKMARK" => {
                 if parts.len() != 2 {
-                    return Err("TAPESEEKMARK requires 1 operand".to_string());
+                    return Err("TAPESEEKMARK requires 1 operand".to_string().into());
                 }
                 Ok(Instruction::TapeSeekMark {
                     label: parts[1].to_string(),
@@ -241,16 +582,26 @@ impl Parser {
             
             "JMP" | "JUMP" => {
                 if parts.len() != 2 {
-                    return Err("JUMP requires 1 operand".to_string());
+                    return Err("JUMP requires 1 operand".to_string().into());
                 }
                 Ok(Instruction::Jump {
                     label: parts[1].to_string(),
                 })
             }
             
+            "BR" | "BRANCH" => {
+                if parts.len() != 3 {
+                    return Err("BRANCH requires 2 operands".to_string().into());
+                }
+                Ok(Instruction::Branch {
+                    condition: self.parse_register(parts[1])?,
+                    label: parts[2].to_string(),
+                })
+            }
+            
             "BZ" | "BRANCHZERO" => {
                 if parts.len() != 3 {
-                    return Err("BRANCHZERO requires 2 operands".to_string());
+                    return Err("BRANCHZERO requires 2 operands".to_string().into());
                 }
                 Ok(Instruction::BranchZero {
                     reg: self.parse_register(parts[1])?,
@@ -260,7 +611,7 @@ impl Parser {
             
             "BNZ" | "BRANCHNOTZERO" => {
                 if parts.len() != 3 {
-                    return Err("BRANCHNOTZERO requires 2 operands".to_string());
+                    return Err("BRANCHNOTZERO requires 2 operands".to_string().into());
                 }
                 Ok(Instruction::BranchNotZero {
                     reg: self.parse_register(parts[1])?,
@@ -268,9 +619,31 @@ impl Parser {
                 })
             }
             
+            "BLTU" => {
+                if parts.len() != 4 {
+                    return Err("BLTU requires 3 operands".to_string().into());
+                }
+                Ok(Instruction::BranchLessThanU {
+                    src1: self.parse_register(parts[1])?,
+                    src2: self.parse_register(parts[2])?,
+                    label: parts[3].to_string(),
+                })
+            }
+            
+            "BGEU" => {
+                if parts.len() != 4 {
+                    return Err("BGEU requires 3 operands".to_string().into());
+                }
+                Ok(Instruction::BranchGreaterEqualU {
+                    src1: self.parse_register(parts[1])?,
+                    src2: self.parse_register(parts[2])?,
+                    label: parts[3].to_string(),
+                })
+            }
+            
             "CALL" => {
                 if parts.len() != 2 {
-                    return Err("CALL requires 1 operand".to_string());
+                    return Err("CALL requires 1 operand".to_string().into());
                 }
                 Ok(Instruction::Call {
                     label: parts[1].to_string(),
@@ -283,7 +656,7 @@ impl Parser {
             
             "CHECKPOINT" | "CP" => {
                 if parts.len() != 2 {
-                    return Err("CHECKPOINT requires 1 operand".to_string());
+                    return Err("CHECKPOINT requires 1 operand".to_string().into());
                 }
                 Ok(Instruction::Checkpoint {
                     label: parts[1].to_string(),
@@ -292,16 +665,81 @@ impl Parser {
             
             "REWIND" | "RW" => {
                 if parts.len() != 2 {
-                    return Err("REWIND requires 1 operand".to_string());
+                    return Err("REWIND requires 1 operand".to_string().into());
                 }
                 Ok(Instruction::Rewind {
                     label: parts[1].to_string(),
                 })
             }
             
+            "REWINDN" => {
+                if parts.len() != 2 {
+                    return Err("REWINDN requires 1 operand".to_string().into());
+                }
+                Ok(Instruction::RewindN {
+                    steps: self.parse_register(parts[1])?,
+                })
+            }
+            
+            "FORK" => {
+                if parts.len() != 2 {
+                    return Err("FORK requires 1 operand".to_string().into());
+                }
+                Ok(Instruction::Fork {
+                    label: parts[1].to_string(),
+                })
+            }
+            
+            "TSWITCH" => {
+                if parts.len() != 2 {
+                    return Err("TSWITCH requires 1 operand".to_string().into());
+                }
+                Ok(Instruction::TimelineSwitch {
+                    label: parts[1].to_string(),
+                })
+            }
+            
+            "MERGE" => {
+                if parts.len() != 2 {
+                    return Err("MERGE requires 1 operand".to_string().into());
+                }
+                let strategy = match parts[1].to_lowercase().as_str() {
+                    "latest" => MergeStrategy::Latest,
+                    "earliest" => MergeStrategy::Earliest,
+                    "combine" => MergeStrategy::Combine,
+                    "manual" => MergeStrategy::Manual,
+                    _ => return Err(format!(
+                        "Invalid merge strategy: {} (expected latest, earliest, combine or manual)",
+                        parts[1]
+                    ).into()),
+                };
+                Ok(Instruction::Merge { strategy })
+            }
+            
+            "SPLICE" => {
+                if parts.len() != 4 {
+                    return Err("SPLICE requires 3 operands".to_string().into());
+                }
+                Ok(Instruction::Splice {
+                    dst: self.parse_immediate(parts[1].trim_end_matches(','))?,
+                    src: self.parse_immediate(parts[2].trim_end_matches(','))?,
+                    len: self.parse_register(parts[3])?,
+                })
+            }
+            
+            "COMPACT" => {
+                if parts.len() != 3 {
+                    return Err("COMPACT requires 2 operands".to_string().into());
+                }
+                Ok(Instruction::Compact {
+                    start: self.parse_immediate(parts[1].trim_end_matches(','))?,
+                    end: self.parse_immediate(parts[2])?,
+                })
+            }
+            
             "CMP" | "COMPARE" => {
                 if parts.len() != 4 {
-                    return Err("COMPARE requires 3 operands".to_string());
+                    return Err("COMPARE requires 3 operands".to_string().into());
                 }
                 Ok(Instruction::Compare {
                     dst: self.parse_register(parts[1])?,
@@ -310,9 +748,31 @@ impl Parser {
                 })
             }
             
+            "CMPU" => {
+                if parts.len() != 4 {
+                    return Err("CMPU requires 3 operands".to_string().into());
+                }
+                Ok(Instruction::CompareU {
+                    dst: self.parse_register(parts[1])?,
+                    src1: self.parse_register(parts[2])?,
+                    src2: self.parse_register(parts[3])?,
+                })
+            }
+            
+            "LTU" => {
+                if parts.len() != 4 {
+                    return Err("LTU requires 3 operands".to_string().into());
+                }
+                Ok(Instruction::LessThanU {
+                    dst: self.parse_register(parts[1])?,
+                    src1: self.parse_register(parts[2])?,
+                    src2: self.parse_register(parts[3])?,
+                })
+            }
+            
             "EQ" | "EQUAL" => {
                 if parts.len() != 4 {
-                    return Err("EQUAL requires 3 operands".to_string());
+                    return Err("EQUAL requires 3 operands".to_string().into());
                 }
                 Ok(Instruction::Equal {
                     dst: self.parse_register(parts[1])?,
@@ -323,7 +783,7 @@ impl Parser {
             
             "LT" | "LESSTHAN" => {
                 if parts.len() != 4 {
-                    return Err("LESSTHAN requires 3 operands".to_string());
+                    return Err("LESSTHAN requires 3 operands".to_string().into());
                 }
                 Ok(Instruction::LessThan {
                     dst: self.parse_register(parts[1])?,
@@ -332,51 +792,345 @@ impl Parser {
                 })
             }
             
+            "SEGCREATE" | "SEGSEEK" => {
+                if parts.len() != 3 {
+                    return Err(format!("{} requires 2 operands", mnemonic).into());
+                }
+                let name = parts[1].trim_end_matches(',').to_string();
+                let reg = self.parse_register(parts[2])?;
+                Ok(if mnemonic == "SEGCREATE" {
+                    Instruction::SegmentCreate { name, size: reg }
+                } else {
+                    Instruction::SegmentSeek { name, offset: reg }
+                })
+            }
+            
+            "SEGREAD" | "SEGWRITE" => {
+                if parts.len() != 5 {
+                    return Err(format!("{} requires 4 operands", mnemonic).into());
+                }
+                let name = parts[1].trim_end_matches(',').to_string();
+                let offset = self.parse_register(parts[2])?;
+                let len = self.parse_register(parts[3])?;
+                let reg = self.parse_register(parts[4])?;
+                Ok(if mnemonic == "SEGREAD" {
+                    Instruction::SegmentRead { name, offset, len, dst: reg }
+                } else {
+                    Instruction::SegmentWrite { name, offset, len, src: reg }
+                })
+            }
+            
             "HALT" => Ok(Instruction::Halt),
             "NOP" => Ok(Instruction::Nop),
             
             "DEBUG" => {
-                let message = parts[1..].join(" ");
+                // A quoted message is taken verbatim; bare words are joined by single spaces
+                let (_, rest) = line.split_first_word();
+                let message = if let Some(quoted) = rest.strip_prefix('"') {
+                    let text = quoted.strip_suffix('"')
+                        .ok_or_else(|| ParseError::at(rest, format!("Unterminated string: {}", rest)))?;
+                    String::from_utf8(unescape(text)?)
+                        .map_err(|_| ParseError::at(rest, format!("DEBUG message is not UTF-8: {}", rest)))?
+                } else {
+                    parts[1..].iter().map(|part| part.text).collect::<Vec<_>>().join(" ")
+                };
                 Ok(Instruction::Debug { message })
             }
             
-            _ => Err(format!("Unknown instruction: {}", mnemonic)),
+            "TRAP" => {
+                if parts.len() != 2 {
+                    return Err("TRAP requires 1 operand".to_string().into());
+                }
+                Ok(Instruction::Trap {
+                    code: self.parse_byte(parts[1])?,
+                })
+            }
+            
+            _ => Err(format!("Unknown instruction: {}", mnemonic).into()),
         }
     }
     
-    fn parse_register(&self, s: &str) -> Result<Register, String> {
-        let s = s.trim_end_matches(',');
+    fn parse_register(&self, token: Token) -> Result<Register, ParseError> {
+        let s = token.trim_end_matches(',');
+        if let Some(reg) = self.aliases.get(&s.to_lowercase()) {
+            return Ok(*reg);
+        }
         
-        if s.starts_with('R') || s.starts_with('r') {
-            let num_str = &s[1..];
-            let num = num_str.parse::<u8>()
-                .map_err(|_| format!("Invalid register: {}", s))?;
-            
-            if num < 16 {
-                Ok(num)
-            } else {
-                Err(format!("Register out of range: {}", s))
+        let result = if s.starts_with('R') || s.starts_with('r') {
+            match s[1..].parse::<u8>() {
+                Ok(num) if (num as usize) < self.register_count => Ok(num),
+                Ok(_) => Err(format!("Register out of range: {}", s)),
+                Err(_) => Err(format!("Invalid register: {}", s)),
             }
         } else {
             Err(format!("Invalid register format: {}", s))
+        };
+        result.map_err(|message| ParseError::at(s, message))
+    }
+    
+    /// The position the first pass recorded for a label
+    ///
+    /// Code labels resolve to instruction indices and `.data` labels to tape
+    /// addresses.
+    fn label_address(&self, label: Token) -> Result<i64, ParseError> {
+        self.labels.get(label.text).copied()
+            .ok_or_else(|| ParseError::at(label, format!("Undefined label: {}", label)))
+    }
+    
+    /// Parse a decimal, `0x` hex, `0b` binary, `0o` octal or `'c'` character immediate
+    ///
+    /// Numeric forms may be negated with a leading `-`.
+    fn parse_immediate(&self, token: Token) -> Result<i64, ParseError> {
+        immediate_value(&token).map_err(|message| ParseError::at(token, message))
+    }
+    
+    fn parse_byte(&self, token: Token) -> Result<u8, ParseError> {
+        token.parse::<u8>()
+            .map_err(|_| ParseError::at(token, format!("Invalid byte value: {}", token)))
+    }
+}
+
+/// A slice of a source line and its byte offset into that line
+///
+/// Operands carry their offset so an error about one can report its column.
+#[derive(Clone, Copy, Debug)]
+struct Token<'a> {
+    text: &'a str,
+    offset: usize,
+}
+
+impl<'a> Token<'a> {
+    /// A whole source line, trimmed
+    fn line(raw_line: &'a str) -> Self {
+        Token { text: raw_line, offset: 0 }.trim()
+    }
+    
+    /// The bytes `start..end` of this token
+    fn slice(self, start: usize, end: usize) -> Self {
+        Token { text: &self.text[start..end], offset: self.offset + start }
+    }
+    
+    fn trim(self) -> Self {
+        let start = self.text.len() - self.text.trim_start().len();
+        self.slice(start, start + self.text.trim().len())
+    }
+    
+    fn trim_end_matches(self, c: char) -> Self {
+        self.slice(0, self.text.trim_end_matches(c).len())
+    }
+    
+    /// Drop a trailing `;` comment, ignoring semicolons inside string and
+    /// character literals
+    fn strip_comment(self) -> Self {
+        self.slice(0, strip_comment(self.text).len())
+    }
+    
+    /// The whitespace-separated words of this token; a literal is never split
+    fn split_whitespace(self) -> Vec<Token<'a>> {
+        let mut words = Vec::new();
+        let mut start = None;
+        for (i, c) in unquoted_chars(self.text) {
+            match (c.is_whitespace(), start) {
+                (true, Some(word_start)) => {
+                    words.push(self.slice(word_start, i));
+                    start = None;
+                }
+                (false, None) => start = Some(i),
+                _ => {}
+            }
+        }
+        if let Some(word_start) = start {
+            words.push(self.slice(word_start, self.text.len()));
         }
+        words
     }
     
-    fn parse_immediate(&self, s: &str) -> Result<i64, String> {
-        let s = s.trim_start_matches('#');
-        
-        if s.starts_with("0x") || s.starts_with("0X") {
-            i64::from_str_radix(&s[2..], 16)
-                .map_err(|_| format!("Invalid hex immediate: {}", s))
-        } else {
-            s.parse::<i64>()
-                .map_err(|_| format!("Invalid immediate: {}", s))
+    /// The first word and the trimmed remainder
+    fn split_first_word(self) -> (Token<'a>, Token<'a>) {
+        let end = self.text.find(char::is_whitespace).unwrap_or(self.text.len());
+        (self.slice(0, end), self.slice(end, self.text.len()).trim())
+    }
+}
+
+impl Deref for Token<'_> {
+    type Target = str;
+    
+    fn deref(&self) -> &str {
+        self.text
+    }
+}
+
+impl fmt::Display for Token<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.text)
+    }
+}
+
+/// A parse error and the byte offset of the operand that caused it, if any
+#[derive(Debug)]
+struct ParseError {
+    message: String,
+    offset: Option<usize>,
+}
+
+impl ParseError {
+    /// An error caused by `token`
+    fn at(token: Token, message: String) -> Self {
+        ParseError { message, offset: Some(token.offset) }
+    }
+}
+
+impl From<String> for ParseError {
+    fn from(message: String) -> Self {
+        ParseError { message, offset: None }
+    }
+}
+
+/// Prefix an error with its line number and, if an operand caused it, column
+fn locate(raw_line: &str, line_num: usize, error: ParseError) -> String {
+    match error.offset {
+        Some(offset) => {
+            let column = raw_line[..offset].chars().count() + 1;
+            format!("Line {}, col {}: {}", line_num + 1, column, error.message)
         }
+        None => format!("Line {}: {}", line_num + 1, error.message),
     }
+}
+
+/// The value of an immediate operand, with any `#` prefix
+fn immediate_value(s: &str) -> Result<i64, String> {
+    let s = s.trim_start_matches('#');
     
-    fn parse_byte(&self, s: &str) -> Result<u8, String> {
-        s.parse::<u8>()
-            .map_err(|_| format!("Invalid byte value: {}", s))
+    if let Some(body) = s.strip_prefix('\'') {
+        let body = body.strip_suffix('\'')
+            .ok_or_else(|| format!("Unterminated character literal: {}", s))?;
+        return match unescape(body)?[..] {
+            [byte] => Ok(byte as i64),
+            _ => Err(format!("Character literal must hold exactly one character: {}", s)),
+        };
+    }
+    
+    let (sign, unsigned) = match s.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", s),
+    };
+    let (radix, digits, kind) = match unsigned.get(..2) {
+        Some("0x" | "0X") => (16, &unsigned[2..], "hex "),
+        Some("0b" | "0B") => (2, &unsigned[2..], "binary "),
+        Some("0o" | "0O") => (8, &unsigned[2..], "octal "),
+        _ => (10, unsigned, ""),
+    };
+    
+    // Digits must follow the prefix; `from_str_radix` would accept a second sign
+    if digits.is_empty() || digits.starts_with(['-', '+']) {
+        return Err(format!("Invalid {}immediate: {}", kind, s));
+    }
+    i64::from_str_radix(&format!("{}{}", sign, digits), radix)
+        .map_err(|_| format!("Invalid {}immediate: {}", kind, s))
+}
+
+/// Whether `s` has the shape of a label name
+fn is_identifier(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && s.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+}
+
+/// Whether `s` is an `R<n>` register name, in range or not
+fn is_register_syntax(s: &str) -> bool {
+    s.strip_prefix(['R', 'r'])
+        .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// The operands of an `.alias` directive line, or `None` for other directives
+fn alias_arguments(line: Token) -> Option<Token> {
+    let (name, args) = line.strip_comment().split_first_word();
+    name.eq_ignore_ascii_case(".alias").then_some(args)
+}
+
+/// The characters of `text` outside string and character literals, with
+/// their byte offsets
+///
+/// A literal's opening quote is included, so it still starts a word.
+fn unquoted_chars(text: &str) -> impl Iterator<Item = (usize, char)> + '_ {
+    let mut quote = None;
+    let mut escaped = false;
+    text.char_indices().filter(move |&(_, c)| {
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some(_) if c == '\\' => escaped = true,
+            Some(open) if c == open => quote = None,
+            Some(_) => {}
+            None => {
+                if c == '"' || c == '\'' {
+                    quote = Some(c);
+                }
+                return true;
+            }
+        }
+        false
+    })
+}
+
+/// Drop a trailing `;` comment, ignoring semicolons inside string and
+/// character literals
+fn strip_comment(line: &str) -> &str {
+    match unquoted_chars(line).find(|&(_, c)| c == ';') {
+        Some((i, _)) => line[..i].trim_end(),
+        None => line,
+    }
+}
+
+/// Split the comma-separated operands of a data directive
+fn split_operands<'a>(args: Token<'a>, directive: &str) -> Result<Vec<Token<'a>>, String> {
+    if args.is_empty() {
+        return Err(format!("{} requires at least 1 operand", directive));
+    }
+    
+    let mut operands = Vec::new();
+    let mut start = 0;
+    for (i, _) in unquoted_chars(args.text).filter(|&(_, c)| c == ',') {
+        operands.push(args.slice(start, i).trim());
+        start = i + 1;
+    }
+    operands.push(args.slice(start, args.text.len()).trim());
+    Ok(operands)
+}
+
+/// Resolve the backslash escapes of a string literal body
+fn unescape(text: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        bytes.push(match chars.next() {
+            Some('n') => b'\n',
+            Some('t') => b'\t',
+            Some('r') => b'\r',
+            Some('0') => 0,
+            Some('\\') => b'\\',
+            Some('"') => b'"',
+            Some('\'') => b'\'',
+            Some('x') => {
+                let digits: String = chars.by_ref().take(2).collect();
+                match u8::from_str_radix(&digits, 16) {
+                    Ok(byte) if digits.len() == 2 => byte,
+                    _ => return Err(format!("Invalid escape: \\x{}", digits)),
+                }
+            }
+            Some(other) => return Err(format!("Invalid escape: \\{}", other)),
+            None => return Err("Unterminated escape".to_string()),
+        });
+    }
+    Ok(bytes)
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -408,6 +1162,13 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_comment_only_source() {
+        let mut parser = Parser::new();
+        assert_eq!(parser.parse("").unwrap(), vec![]);
+        assert_eq!(parser.parse("  \n; just a comment\n\t\n  ; another\n").unwrap(), vec![]);
+    }
+
     #[test]
     fn test_parse_with_labels() {
         let mut parser = Parser::new();
@@ -431,6 +1192,379 @@ mod tests {
         assert_eq!(parser.labels.get("loop"), Some(&1));
     }
 
+    #[test]
+    fn test_parse_indented_labels() {
+        let mut parser = Parser::with_indented_labels();
+        let program = "main\n    LI R0, 3\nloop ; decrement\n    LI R1, 1\n    RSUB R1, R2, R0\n    BNZ R0, loop\ndone\n    HALT\n";
+        
+        let instructions = parser.parse(program).unwrap();
+        assert_eq!(instructions.len(), 5);
+        assert_eq!(parser.labels.get("main"), Some(&0));
+        assert_eq!(parser.labels.get("loop"), Some(&1));
+        assert_eq!(parser.labels.get("done"), Some(&4));
+        assert_eq!(instructions[4], Instruction::Halt);
+    }
+
+    #[test]
+    fn test_parse_indented_labels_with_colons() {
+        let mut parser = Parser::with_indented_labels();
+        let program = "start:\n    LI R0, 1\n  inner:\n    HALT\n";
+        
+        let instructions = parser.parse(program).unwrap();
+        assert_eq!(instructions.len(), 2);
+        assert_eq!(parser.labels.get("start"), Some(&0));
+        assert_eq!(parser.labels.get("inner"), Some(&1));
+    }
+
+    #[test]
+    fn test_parse_bitrev() {
+        let mut parser = Parser::new();
+        let instructions = parser.parse("BITREV R1, R0, 8").unwrap();
+        assert_eq!(instructions[0], Instruction::BitReverse { dst: 1, src: 0, width: 8 });
+    }
+
+    #[test]
+    fn test_parse_stack_word_ops() {
+        let mut parser = Parser::new();
+        let instructions = parser.parse("DUP\nDROP\nover\nSWAPTOP").unwrap();
+        assert_eq!(instructions, vec![
+            Instruction::Dup,
+            Instruction::Drop,
+            Instruction::Over,
+            Instruction::SwapTop,
+        ]);
+        assert!(parser.parse("DUP R0").is_err());
+    }
+
+    #[test]
+    fn test_parse_interleave() {
+        let mut parser = Parser::new();
+        let instructions = parser.parse("ILV R0, R1, R2, R3\nDILV R2, R0, R1, R3").unwrap();
+        assert_eq!(instructions[0], Instruction::Interleave { a: 0, b: 1, dst: 2, count: 3 });
+        assert_eq!(instructions[1], instructions[0].inverse().unwrap());
+    }
+
+    #[test]
+    fn test_parse_unsigned_comparisons() {
+        let mut parser = Parser::new();
+        let instructions = parser.parse("CMPU R0, R1, R2\nLTU R3, R4, R5").unwrap();
+        assert_eq!(instructions[0], Instruction::CompareU { dst: 0, src1: 1, src2: 2 });
+        assert_eq!(instructions[1], Instruction::LessThanU { dst: 3, src1: 4, src2: 5 });
+        
+        let instructions = parser.parse("BLTU R0, R1, low\nBGEU R2, R3, high").unwrap();
+        assert_eq!(instructions[0], Instruction::BranchLessThanU { src1: 0, src2: 1, label: "low".to_string() });
+        assert_eq!(instructions[1], Instruction::BranchGreaterEqualU { src1: 2, src2: 3, label: "high".to_string() });
+        assert!(parser.parse("BLTU R0, low").is_err());
+    }
+
+    #[test]
+    fn test_parse_sort() {
+        let mut parser = Parser::new();
+        let instructions = parser.parse("SORT R0, R1, 8").unwrap();
+        assert_eq!(instructions[0], Instruction::SortRange { addr: 0, count: 1, elem_size: 8 });
+    }
+
+    #[test]
+    fn test_parse_idiv_imod() {
+        let mut parser = Parser::new();
+        let instructions = parser.parse("IDIV R0, R1, R2\nIMOD R3, R4, R5").unwrap();
+        assert_eq!(instructions[0], Instruction::IDiv { src1: 0, src2: 1, dst: 2 });
+        assert_eq!(instructions[1], Instruction::IMod { src1: 3, src2: 4, dst: 5 });
+        assert!(parser.parse("IDIV R0, R1").is_err());
+    }
+
+    #[test]
+    fn test_parse_bitwise() {
+        let mut parser = Parser::new();
+        let instructions = parser.parse("AND R0, R1, R2\nOR R0, R1, R2\nNOT R3, R4").unwrap();
+        assert_eq!(instructions[0], Instruction::IAnd { src1: 0, src2: 1, dst: 2 });
+        assert_eq!(instructions[1], Instruction::IOr { src1: 0, src2: 1, dst: 2 });
+        assert_eq!(instructions[2], Instruction::INot { dst: 3, src: 4 });
+    }
+
+    #[test]
+    fn test_parse_rewind_n() {
+        let mut parser = Parser::new();
+        let instructions = parser.parse("REWIND start\nREWINDN R3").unwrap();
+        assert_eq!(instructions[0], Instruction::Rewind { label: "start".to_string() });
+        assert_eq!(instructions[1], Instruction::RewindN { steps: 3 });
+        
+        assert_eq!(parser.parse("NOP\nREWINDN 3").unwrap_err(), "Line 2, col 9: Invalid register format: 3");
+        assert_eq!(parser.parse("REWINDN").unwrap_err(), "Line 1: REWINDN requires 1 operand");
+    }
+
+    #[test]
+    fn test_parse_immediate_forms() {
+        assert_eq!(immediate_value("42").unwrap(), 42);
+        assert_eq!(immediate_value("#0x2A").unwrap(), 42);
+        assert_eq!(immediate_value("0b101010").unwrap(), 42);
+        assert_eq!(immediate_value("0o52").unwrap(), 42);
+        assert_eq!(immediate_value("-0x10").unwrap(), -16);
+        assert_eq!(immediate_value("-9223372036854775808").unwrap(), i64::MIN);
+        assert_eq!(immediate_value("'A'").unwrap(), 65);
+        assert_eq!(immediate_value("'\\n'").unwrap(), 10);
+        
+        assert_eq!(immediate_value("0b102").unwrap_err(), "Invalid binary immediate: 0b102");
+        assert_eq!(immediate_value("0o").unwrap_err(), "Invalid octal immediate: 0o");
+        assert_eq!(immediate_value("--5").unwrap_err(), "Invalid immediate: --5");
+        assert_eq!(immediate_value("'AB'").unwrap_err(),
+                   "Character literal must hold exactly one character: 'AB'");
+        assert_eq!(immediate_value("''").unwrap_err(),
+                   "Character literal must hold exactly one character: ''");
+        assert_eq!(immediate_value("'\\q'").unwrap_err(), "Invalid escape: \\q");
+        assert_eq!(immediate_value("'A").unwrap_err(), "Unterminated character literal: 'A");
+        assert_eq!(immediate_value("'\\x7F'").unwrap(), 0x7F);
+        assert_eq!(immediate_value("'\\xff'").unwrap(), 0xFF);
+        assert_eq!(immediate_value("'\\x4'").unwrap_err(), "Invalid escape: \\x4");
+        assert_eq!(immediate_value("'\\xg0'").unwrap_err(), "Invalid escape: \\xg0");
+        
+        let instructions = Parser::new().parse("LI R0, -7\nLI R1, 'z'").unwrap();
+        assert_eq!(instructions[0], Instruction::LoadImm { reg: 0, value: -7 });
+        assert_eq!(instructions[1], Instruction::LoadImm { reg: 1, value: 122 });
+    }
+
+    #[test]
+    fn test_char_literals_with_separators() {
+        let instructions = Parser::new().parse(r#"
+            LI R0, ' '
+            LI R1, ';'   ; a real comment
+            LI R2, ','
+            LI R3, '#'
+            LI R4, #'\''
+        "#).unwrap();
+        let values: Vec<i64> = instructions.iter().map(|inst| match inst {
+            Instruction::LoadImm { value, .. } => *value,
+            other => panic!("unexpected {:?}", other),
+        }).collect();
+        assert_eq!(values, vec![32, 59, 44, 35, 39]);
+        
+        let mut parser = Parser::new();
+        parser.parse(r#"
+            .data
+            .byte ' ', ';', ',', '#'   ; separators
+            .byte '\x00', '"'
+            .ascii "tab\x09; \x41"
+        "#).unwrap();
+        assert_eq!(parser.data(), b" ;,#\0\"tab\t; A");
+    }
+
+    #[test]
+    fn test_load_label_address() {
+        let mut parser = Parser::new();
+        let instructions = parser.parse(r#"
+            LI R0, target
+            LEA R1, message
+            JMP target
+            NOP
+        target:
+            HALT
+            .data
+        message:
+            .ascii "hi"
+        "#).unwrap();
+        
+        assert_eq!(parser.labels()["target"], 4);
+        assert_eq!(instructions[0], Instruction::LoadImm { reg: 0, value: parser.labels()["target"] });
+        assert_eq!(instructions[1], Instruction::LoadImm { reg: 1, value: parser.labels()["message"] });
+        assert_eq!(parser.labels()["message"], DATA_SEGMENT_BASE);
+        
+        assert_eq!(parser.parse("LI R0, nowhere").unwrap_err(), "Line 1, col 8: Undefined label: nowhere");
+        assert_eq!(parser.parse("LEA R0, 5").unwrap_err(), "Line 1, col 9: Undefined label: 5");
+    }
+
+    #[test]
+    fn test_register_alias() {
+        let mut parser = Parser::new();
+        let instructions = parser.parse(r#"
+            .alias acc R13
+            .ALIAS Step, r2
+            RADD ACC, step, acc
+            LI Acc, 1
+        "#).unwrap();
+        assert_eq!(instructions[0], Instruction::RAdd { src1: 13, src2: 2, dst: 13 });
+        assert_eq!(instructions[1], Instruction::LoadImm { reg: 13, value: 1 });
+        
+        // Aliases apply only to the lines after their definition
+        assert_eq!(parser.parse("PUSH acc\n.alias acc R1").unwrap_err(),
+                   "Line 1, col 6: Invalid register format: acc");
+        assert_eq!(parser.parse(".alias r3 R4").unwrap_err(),
+                   "Line 1: Alias 'r3' collides with register syntax");
+        assert_eq!(parser.parse(".alias R99 R4").unwrap_err(),
+                   "Line 1: Alias 'R99' collides with register syntax");
+        assert_eq!(parser.parse(".alias tmp R16").unwrap_err(), "Line 1, col 12: Register out of range: R16");
+        assert_eq!(parser.parse(".alias tmp").unwrap_err(), "Line 1: .alias requires 2 operands");
+    }
+
+    #[test]
+    fn test_error_reports_column_and_token() {
+        let mut parser = Parser::new();
+        let err = parser.parse("LI R0, 1\n\n    RADD R1, RX, R2 ; bad\n").unwrap_err();
+        assert_eq!(err, "Line 3, col 14: Invalid register: RX");
+        
+        let err = parser.parse("\tTAPESEEK #0xZZ").unwrap_err();
+        assert_eq!(err, "Line 1, col 11: Invalid hex immediate: 0xZZ");
+        
+        // Data operands keep their place after commas
+        let err = parser.parse(".data\n  .byte 'é', 1").unwrap_err();
+        assert_eq!(err, "Line 2, col 9: Character literal must hold exactly one character: 'é'");
+        let err = parser.parse(".data\n  .byte 1,  300 ; ok").unwrap_err();
+        assert_eq!(err, "Line 2, col 13: Byte out of range: 300");
+        
+        // Errors not tied to one operand keep the plain line prefix
+        assert_eq!(parser.parse("RADD R1").unwrap_err(), "Line 1: RADD requires 3 operands");
+    }
+
+    #[test]
+    fn test_extra_registers() {
+        assert_eq!(Parser::new().parse("LI R20, 1").unwrap_err(), "Line 1, col 4: Register out of range: R20");
+        
+        let program = Parser::with_register_count(32).parse_program(r#"
+            LI R20, 5
+            LI R31, 7
+            RADD R20, R31, R16
+            HALT
+        "#).unwrap();
+        assert!(Parser::with_register_count(32).parse("LI R32, 1").is_err());
+        
+        let mut vm = crate::vm::VM::with_register_count(32);
+        vm.load_program(program.instructions).unwrap();
+        vm.run(None).unwrap();
+        assert_eq!(vm.registers.read(16).unwrap(), 12);
+        assert_eq!(vm.registers.count(), 32);
+    }
+
+    #[test]
+    fn test_parse_data_section() {
+        let mut parser = Parser::new();
+        let instructions = parser.parse(r#"
+            .data
+        greeting:
+            .ascii "Hi; there\n"
+        table:
+            .byte 1, 0xFF, -1
+            .word 258
+        pad:
+            .zero 3
+        tail:
+            .text
+        start:
+            LI R0, 1
+            HALT
+        "#).unwrap();
+        
+        assert_eq!(instructions.len(), 2);
+        assert_eq!(parser.labels()["start"], 0);
+        assert_eq!(parser.labels()["greeting"], DATA_SEGMENT_BASE);
+        assert_eq!(parser.labels()["table"], DATA_SEGMENT_BASE + 10);
+        assert_eq!(parser.labels()["pad"], DATA_SEGMENT_BASE + 21);
+        assert_eq!(parser.labels()["tail"], DATA_SEGMENT_BASE + 24);
+        
+        let mut expected = b"Hi; there\n".to_vec();
+        expected.extend_from_slice(&[1, 0xFF, 0xFF]);
+        expected.extend_from_slice(&258i64.to_le_bytes());
+        expected.extend_from_slice(&[0; 3]);
+        assert_eq!(parser.data(), &expected[..]);
+        
+        assert_eq!(parser.parse(".byte 1").unwrap_err(), "Line 1: .byte outside .data section");
+        assert_eq!(parser.parse(".data\nNOP").unwrap_err(), "Line 2: Instruction in .data section: NOP");
+        assert_eq!(parser.parse(".data\n.byte 256").unwrap_err(), "Line 2, col 7: Byte out of range: 256");
+        assert_eq!(parser.parse(".data\n.ascii \"\\q\"").unwrap_err(), "Line 2: Invalid escape: \\q");
+        assert_eq!(parser.parse(".bss").unwrap_err(), "Line 1: Unknown directive: .bss");
+    }
+
+    #[test]
+    fn test_parse_timeline_ops() {
+        let mut parser = Parser::new();
+        let instructions = parser.parse(
+            "FORK alt\nMERGE latest\nMERGE Combine\nSPLICE 4096, 0x100, R3\nCOMPACT 0, 8192"
+        ).unwrap();
+        assert_eq!(instructions[0], Instruction::Fork { label: "alt".to_string() });
+        assert_eq!(instructions[1], Instruction::Merge { strategy: MergeStrategy::Latest });
+        assert_eq!(instructions[2], Instruction::Merge { strategy: MergeStrategy::Combine });
+        assert_eq!(instructions[3], Instruction::Splice { dst: 4096, src: 256, len: 3 });
+        assert_eq!(instructions[4], Instruction::Compact { start: 0, end: 8192 });
+        
+        let err = parser.parse("MERGE newest").unwrap_err();
+        assert!(err.contains("Invalid merge strategy: newest"), "{}", err);
+        assert!(parser.parse("MERGE").unwrap_err().contains("MERGE requires 1 operand"));
+        assert!(parser.parse("SPLICE 1, 2").unwrap_err().contains("SPLICE requires 3 operands"));
+        assert!(parser.parse("COMPACT 0").unwrap_err().contains("COMPACT requires 2 operands"));
+        assert!(parser.parse("FORK").unwrap_err().contains("FORK requires 1 operand"));
+    }
+
+    #[test]
+    fn test_parse_tape_seek_reg() {
+        let mut parser = Parser::new();
+        let instructions = parser.parse("TAPESEEKREG R7").unwrap();
+        assert_eq!(instructions[0], Instruction::TapeSeekReg { reg: 7 });
+        assert!(parser.parse("TAPESEEKREG 100").is_err());
+    }
+
+    #[test]
+    fn test_parse_segments() {
+        let mut parser = Parser::new();
+        let instructions = parser.parse(
+            "SEGCREATE buf, R0\nSEGSEEK buf, R1\nSEGREAD buf, R1, R2, R3\nSEGWRITE buf, R1, R2, R4"
+        ).unwrap();
+        let name = "buf".to_string();
+        assert_eq!(instructions[0], Instruction::SegmentCreate { name: name.clone(), size: 0 });
+        assert_eq!(instructions[1], Instruction::SegmentSeek { name: name.clone(), offset: 1 });
+        assert_eq!(instructions[2], Instruction::SegmentRead { name: name.clone(), offset: 1, len: 2, dst: 3 });
+        assert_eq!(instructions[3], Instruction::SegmentWrite { name, offset: 1, len: 2, src: 4 });
+        assert!(parser.parse("SEGREAD buf, R1").is_err());
+    }
+
+    #[test]
+    fn test_parse_shifts() {
+        let mut parser = Parser::new();
+        let instructions = parser.parse("SHL R0, R1, R2\nSHR R0, R1, R2\nSAR R0, R1, R2").unwrap();
+        assert_eq!(instructions[0], Instruction::Shl { dst: 0, src: 1, amount: 2 });
+        assert_eq!(instructions[1], Instruction::Shr { dst: 0, src: 1, amount: 2 });
+        assert_eq!(instructions[2], Instruction::Sar { dst: 0, src: 1, amount: 2 });
+    }
+
+    #[test]
+    fn test_parse_branch() {
+        let mut parser = Parser::new();
+        let instructions = parser.parse("BR R2, done\nBRANCH R3, done\ndone:\nHALT").unwrap();
+        assert_eq!(instructions[0], Instruction::Branch { condition: 2, label: "done".to_string() });
+        assert_eq!(instructions[1], Instruction::Branch { condition: 3, label: "done".to_string() });
+    }
+
+    #[test]
+    fn test_parse_hashupd() {
+        let mut parser = Parser::new();
+        let instructions = parser.parse("HASHUPD R0, R1, R2").unwrap();
+        assert_eq!(instructions[0], Instruction::HashUpdate { state: 0, addr: 1, len: 2 });
+    }
+
+    #[test]
+    fn test_parse_incsat() {
+        let mut parser = Parser::new();
+        let instructions = parser.parse("INCSAT R0, R1").unwrap();
+        assert_eq!(instructions[0], Instruction::IncSat { addr: 0, max: 1 });
+    }
+
+    #[test]
+    fn test_parse_crc32() {
+        let mut parser = Parser::new();
+        let instructions = parser.parse("CRC32 R0, R1, R2").unwrap();
+        assert_eq!(instructions[0], Instruction::Crc32 { dst: 0, addr: 1, len: 2 });
+        
+        assert!(parser.parse("CRC32 R0, R1").is_err());
+    }
+
+    #[test]
+    fn test_parse_modrep_tswitch_trap() {
+        let mut parser = Parser::new();
+        let instructions = parser.parse("MODREP R0, R1, R2, R3\nTSWITCH alt\nTRAP 7").unwrap();
+        assert_eq!(instructions[0], Instruction::ModRepeat { dst: 0, src: 1, modulus: 2, quotient: 3 });
+        assert_eq!(instructions[1], Instruction::TimelineSwitch { label: "alt".to_string() });
+        assert_eq!(instructions[2], Instruction::Trap { code: 7 });
+        
+        assert!(parser.parse("TRAP 300").is_err());
+    }
+
     #[test]
     fn test_parse_hex_immediates() {
         let mut parser = Parser::new();
@@ -0,0 +1,163 @@
+//! Compiled program representation
+
+use crate::instruction::Instruction;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+/// A parsed program: its instructions plus the resolved label table
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    pub instructions: Vec<Instruction>,
+    pub symbols: HashMap<String, i64>,
+    /// Initialized `.data` bytes, for `VM::load_data`
+    pub data: Vec<u8>,
+}
+
+impl Program {
+    pub fn new(instructions: Vec<Instruction>, symbols: HashMap<String, i64>) -> Self {
+        Program { instructions, symbols, data: Vec::new() }
+    }
+    
+    /// Check label and mark references before execution
+    ///
+    /// Every jump target must be a defined label, and every `TapeSeekMark` must
+    /// follow a `TapeMark` of the same name. Marks are tape positions rather
+    /// than code labels, so the two namespaces are checked separately.
+    pub fn validate(&self) -> Result<(), String> {
+        let mut marks = HashSet::new();
+        
+        for (index, inst) in self.instructions.iter().enumerate() {
+            match inst {
+                Instruction::Call { label } |
+                Instruction::Jump { label } |
+                Instruction::Branch { label, .. } |
+                Instruction::BranchZero { label, .. } |
+                Instruction::BranchNotZero { label, .. } |
+                Instruction::BranchLessThanU { label, .. } |
+                Instruction::BranchGreaterEqualU { label, .. } if !self.symbols.contains_key(label) => {
+                    return Err(format!("Undefined label '{}' at instruction {}", label, index));
+                }
+                Instruction::TapeMark { label } => {
+                    marks.insert(label.as_str());
+                }
+                Instruction::TapeSeekMark { label } if !marks.contains(label.as_str()) => {
+                    return Err(format!("Dangling mark '{}' at instruction {}", label, index));
+                }
+                _ => {}
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Stable hash of the program's identity, suitable as a cache key
+    ///
+    /// Covers every instruction and operand, the symbol table and the data
+    /// section. Symbols are hashed in sorted order so the result does not depend
+    /// on `HashMap` iteration order, and the hasher is unseeded so it is stable
+    /// across runs.
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = StableHasher::new();
+        
+        self.instructions.len().hash(&mut hasher);
+        for inst in &self.instructions {
+            inst.hash(&mut hasher);
+        }
+        
+        let mut symbols: Vec<_> = self.symbols.iter().collect();
+        symbols.sort();
+        symbols.len().hash(&mut hasher);
+        for (label, pos) in symbols {
+            label.hash(&mut hasher);
+            pos.hash(&mut hasher);
+        }
+        self.data.hash(&mut hasher);
+        
+        hasher.finish()
+    }
+}
+
+/// 64-bit FNV-1a hasher (deterministic, unlike `DefaultHasher`'s keyed SipHash)
+struct StableHasher(u64);
+
+impl StableHasher {
+    fn new() -> Self {
+        StableHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for StableHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+    
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::Parser;
+
+    #[test]
+    fn test_identical_programs_hash_equal() {
+        let source = r#"
+        start:
+            LI R0, 10
+            LI R1, 20
+            RADD R0, R1, R2
+            BNZ R2, start
+            HALT
+        "#;
+        
+        let a = Parser::new().parse_program(source).unwrap();
+        let b = Parser::new().parse_program(source).unwrap();
+        assert_eq!(a.content_hash(), b.content_hash());
+    }
+
+    #[test]
+    fn test_immediate_change_changes_hash() {
+        let a = Parser::new().parse_program("LI R0, 10\nHALT").unwrap();
+        let b = Parser::new().parse_program("LI R0, 11\nHALT").unwrap();
+        assert_ne!(a.content_hash(), b.content_hash());
+    }
+
+    #[test]
+    fn test_validate_marks() {
+        let dangling = Parser::new().parse_program("TAPESEEKMARK foo\nHALT").unwrap();
+        let err = dangling.validate().unwrap_err();
+        assert_eq!(err, "Dangling mark 'foo' at instruction 0");
+        
+        let marked = Parser::new().parse_program("TAPEMARK foo\nTAPESEEKMARK foo\nHALT").unwrap();
+        assert!(marked.validate().is_ok());
+        
+        // Jump labels do not establish marks
+        let label_only = Parser::new().parse_program("foo:\nTAPESEEKMARK foo\nHALT").unwrap();
+        assert!(label_only.validate().is_err());
+    }
+
+    #[test]
+    fn test_label_order_does_not_affect_hash() {
+        let a = Parser::new().parse_program(r#"
+        alpha:
+        beta:
+            LI R0, 1
+        gamma:
+            HALT
+        "#).unwrap();
+        let b = Parser::new().parse_program(r#"
+        beta:
+        alpha:
+            LI R0, 1
+        gamma:
+            HALT
+        "#).unwrap();
+        
+        assert_eq!(a.instructions, b.instructions);
+        assert_eq!(a.content_hash(), b.content_hash());
+    }
+}
@@ -0,0 +1,474 @@
+//! Binary encoding of instructions
+//!
+//! Each instruction is a 1-byte opcode followed by its operands: registers
+//! and small immediates as single bytes, `i64` immediates as 8 little-endian
+//! bytes, and strings as a little-endian `u16` length followed by UTF-8 bytes.
+
+use super::{Instruction, MergeStrategy};
+use crate::vm::Register;
+
+mod op {
+    // Reversible arithmetic
+    pub const RADD: u8 = 0x01;
+    pub const RSUB: u8 = 0x02;
+    pub const RXOR: u8 = 0x03;
+    pub const MOD_REPEAT: u8 = 0x04;
+    pub const BIT_REVERSE: u8 = 0x05;
+    pub const INOT: u8 = 0x06;
+    pub const IMUL: u8 = 0x08;
+    pub const IDIV: u8 = 0x09;
+    pub const IMOD: u8 = 0x0A;
+    pub const SHL: u8 = 0x0B;
+    pub const SHR: u8 = 0x0C;
+    pub const SAR: u8 = 0x0D;
+    pub const IAND: u8 = 0x0E;
+    pub const IOR: u8 = 0x0F;
+    
+    // Reversible memory
+    pub const RLOAD: u8 = 0x10;
+    pub const RSTORE: u8 = 0x11;
+    pub const MSWAP: u8 = 0x12;
+    pub const CRC32: u8 = 0x13;
+    pub const SORT_RANGE: u8 = 0x14;
+    pub const INTERLEAVE: u8 = 0x15;
+    pub const DEINTERLEAVE: u8 = 0x16;
+    pub const INC_SAT: u8 = 0x17;
+    pub const HASH_UPDATE: u8 = 0x18;
+    
+    // Register and stack
+    pub const SWAP: u8 = 0x20;
+    pub const PUSH: u8 = 0x28;
+    pub const POP: u8 = 0x29;
+    pub const DUP: u8 = 0x2A;
+    pub const DROP: u8 = 0x2B;
+    pub const OVER: u8 = 0x2C;
+    pub const SWAP_TOP: u8 = 0x2D;
+    
+    // Tape primitives
+    pub const TAPE_READ: u8 = 0x30;
+    pub const TAPE_WRITE: u8 = 0x31;
+    pub const TAPE_SEEK: u8 = 0x32;
+    pub const TAPE_SEEK_REG: u8 = 0x33;
+    pub const TAPE_ADVANCE: u8 = 0x34;
+    pub const TAPE_MARK: u8 = 0x35;
+    pub const TAPE_SEEK_MARK: u8 = 0x36;
+    
+    // Segments
+    pub const SEGMENT_CREATE: u8 = 0x40;
+    pub const SEGMENT_SEEK: u8 = 0x41;
+    pub const SEGMENT_READ: u8 = 0x42;
+    pub const SEGMENT_WRITE: u8 = 0x43;
+    
+    // Advanced tape operations
+    pub const SPLICE: u8 = 0x50;
+    pub const COMPACT: u8 = 0x51;
+    pub const FORK: u8 = 0x52;
+    pub const MERGE: u8 = 0x53;
+    pub const TIMELINE_SWITCH: u8 = 0x54;
+    
+    // Control flow
+    pub const CALL: u8 = 0x60;
+    pub const RETURN: u8 = 0x61;
+    pub const JUMP: u8 = 0x62;
+    pub const BRANCH: u8 = 0x63;
+    pub const BRANCH_ZERO: u8 = 0x64;
+    pub const BRANCH_NOT_ZERO: u8 = 0x65;
+    pub const BRANCH_LESS_THAN_U: u8 = 0x66;
+    pub const BRANCH_GREATER_EQUAL_U: u8 = 0x67;
+    
+    // Time
+    pub const CHECKPOINT: u8 = 0x70;
+    pub const REWIND: u8 = 0x71;
+    pub const REWIND_N: u8 = 0x72;
+    
+    // Comparison
+    pub const COMPARE: u8 = 0x80;
+    pub const EQUAL: u8 = 0x81;
+    pub const LESS_THAN: u8 = 0x82;
+    pub const COMPARE_U: u8 = 0x83;
+    pub const LESS_THAN_U: u8 = 0x84;
+    
+    // Constants
+    pub const LOAD_IMM: u8 = 0x90;
+    
+    // System
+    pub const HALT: u8 = 0xF0;
+    pub const NOP: u8 = 0xF1;
+    pub const DEBUG: u8 = 0xF2;
+    pub const TRAP: u8 = 0xF3;
+}
+
+impl Instruction {
+    /// Encode this instruction to its binary form
+    ///
+    /// Fails if a string operand is longer than `u16::MAX` bytes.
+    pub fn encode(&self) -> Result<Vec<u8>, String> {
+        let mut out = Encoder { bytes: Vec::with_capacity(8), error: None };
+        
+        match self {
+            Instruction::RAdd { src1, src2, dst } => out.op(op::RADD).regs(&[*src1, *src2, *dst]),
+            Instruction::RSub { src1, src2, dst } => out.op(op::RSUB).regs(&[*src1, *src2, *dst]),
+            Instruction::RXor { src, dst } => out.op(op::RXOR).regs(&[*src, *dst]),
+            Instruction::ModRepeat { dst, src, modulus, quotient } =>
+                out.op(op::MOD_REPEAT).regs(&[*dst, *src, *modulus, *quotient]),
+            Instruction::BitReverse { dst, src, width } =>
+                out.op(op::BIT_REVERSE).regs(&[*dst, *src, *width]),
+            Instruction::IMul { src1, src2, dst } => out.op(op::IMUL).regs(&[*src1, *src2, *dst]),
+            Instruction::IDiv { src1, src2, dst } => out.op(op::IDIV).regs(&[*src1, *src2, *dst]),
+            Instruction::IMod { src1, src2, dst } => out.op(op::IMOD).regs(&[*src1, *src2, *dst]),
+            Instruction::Shl { dst, src, amount } => out.op(op::SHL).regs(&[*dst, *src, *amount]),
+            Instruction::Shr { dst, src, amount } => out.op(op::SHR).regs(&[*dst, *src, *amount]),
+            Instruction::Sar { dst, src, amount } => out.op(op::SAR).regs(&[*dst, *src, *amount]),
+            Instruction::IAnd { src1, src2, dst } => out.op(op::IAND).regs(&[*src1, *src2, *dst]),
+            Instruction::IOr { src1, src2, dst } => out.op(op::IOR).regs(&[*src1, *src2, *dst]),
+            Instruction::INot { dst, src } => out.op(op::INOT).regs(&[*dst, *src]),
+            
+            Instruction::RLoad { dst, addr, old } => out.op(op::RLOAD).regs(&[*dst, *addr, *old]),
+            Instruction::RStore { addr, src, old } => out.op(op::RSTORE).regs(&[*addr, *src, *old]),
+            Instruction::MSwap { addr, reg } => out.op(op::MSWAP).regs(&[*addr, *reg]),
+            Instruction::Crc32 { dst, addr, len } => out.op(op::CRC32).regs(&[*dst, *addr, *len]),
+            Instruction::SortRange { addr, count, elem_size } =>
+                out.op(op::SORT_RANGE).regs(&[*addr, *count, *elem_size]),
+            Instruction::Interleave { a, b, dst, count } =>
+                out.op(op::INTERLEAVE).regs(&[*a, *b, *dst, *count]),
+            Instruction::Deinterleave { src, a, b, count } =>
+                out.op(op::DEINTERLEAVE).regs(&[*src, *a, *b, *count]),
+            Instruction::IncSat { addr, max } => out.op(op::INC_SAT).regs(&[*addr, *max]),
+            Instruction::HashUpdate { state, addr, len } =>
+                out.op(op::HASH_UPDATE).regs(&[*state, *addr, *len]),
+            
+            Instruction::Swap { reg1, reg2 } => out.op(op::SWAP).regs(&[*reg1, *reg2]),
+            Instruction::Push { reg } => out.op(op::PUSH).regs(&[*reg]),
+            Instruction::Pop { reg } => out.op(op::POP).regs(&[*reg]),
+            Instruction::Dup => out.op(op::DUP),
+            Instruction::Drop => out.op(op::DROP),
+            Instruction::Over => out.op(op::OVER),
+            Instruction::SwapTop => out.op(op::SWAP_TOP),
+            
+            Instruction::TapeRead { reg, len } => out.op(op::TAPE_READ).regs(&[*reg, *len]),
+            Instruction::TapeWrite { reg, len } => out.op(op::TAPE_WRITE).regs(&[*reg, *len]),
+            Instruction::TapeSeek { position } => out.op(op::TAPE_SEEK).imm(*position),
+            Instruction::TapeSeekReg { reg } => out.op(op::TAPE_SEEK_REG).regs(&[*reg]),
+            Instruction::TapeAdvance { delta } => out.op(op::TAPE_ADVANCE).imm(*delta),
+            Instruction::TapeMark { label } => out.op(op::TAPE_MARK).str(label),
+            Instruction::TapeSeekMark { label } => out.op(op::TAPE_SEEK_MARK).str(label),
+            
+            Instruction::SegmentCreate { name, size } =>
+                out.op(op::SEGMENT_CREATE).str(name).regs(&[*size]),
+            Instruction::SegmentSeek { name, offset } =>
+                out.op(op::SEGMENT_SEEK).str(name).regs(&[*offset]),
+            Instruction::SegmentRead { name, offset, len, dst } =>
+                out.op(op::SEGMENT_READ).str(name).regs(&[*offset, *len, *dst]),
+            Instruction::SegmentWrite { name, offset, len, src } =>
+                out.op(op::SEGMENT_WRITE).str(name).regs(&[*offset, *len, *src]),
+            
+            Instruction::Splice { dst, src, len } =>
+                out.op(op::SPLICE).imm(*dst).imm(*src).regs(&[*len]),
+            Instruction::Compact { start, end } => out.op(op::COMPACT).imm(*start).imm(*end),
+            Instruction::Fork { label } => out.op(op::FORK).str(label),
+            Instruction::Merge { strategy } => out.op(op::MERGE).regs(&[merge_strategy_byte(strategy)]),
+            Instruction::TimelineSwitch { label } => out.op(op::TIMELINE_SWITCH).str(label),
+            
+            Instruction::Call { label } => out.op(op::CALL).str(label),
+            Instruction::Return => out.op(op::RETURN),
+            Instruction::Jump { label } => out.op(op::JUMP).str(label),
+            Instruction::Branch { condition, label } =>
+                out.op(op::BRANCH).regs(&[*condition]).str(label),
+            Instruction::BranchZero { reg, label } =>
+                out.op(op::BRANCH_ZERO).regs(&[*reg]).str(label),
+            Instruction::BranchNotZero { reg, label } =>
+                out.op(op::BRANCH_NOT_ZERO).regs(&[*reg]).str(label),
+            Instruction::BranchLessThanU { src1, src2, label } =>
+                out.op(op::BRANCH_LESS_THAN_U).regs(&[*src1, *src2]).str(label),
+            Instruction::BranchGreaterEqualU { src1, src2, label } =>
+                out.op(op::BRANCH_GREATER_EQUAL_U).regs(&[*src1, *src2]).str(label),
+            
+            Instruction::Checkpoint { label } => out.op(op::CHECKPOINT).str(label),
+            Instruction::Rewind { label } => out.op(op::REWIND).str(label),
+            Instruction::RewindN { steps } => out.op(op::REWIND_N).regs(&[*steps]),
+            
+            Instruction::Compare { dst, src1, src2 } => out.op(op::COMPARE).regs(&[*dst, *src1, *src2]),
+            Instruction::Equal { dst, src1, src2 } => out.op(op::EQUAL).regs(&[*dst, *src1, *src2]),
+            Instruction::LessThan { dst, src1, src2 } => out.op(op::LESS_THAN).regs(&[*dst, *src1, *src2]),
+            Instruction::CompareU { dst, src1, src2 } => out.op(op::COMPARE_U).regs(&[*dst, *src1, *src2]),
+            Instruction::LessThanU { dst, src1, src2 } => out.op(op::LESS_THAN_U).regs(&[*dst, *src1, *src2]),
+            
+            Instruction::LoadImm { reg, value } => out.op(op::LOAD_IMM).regs(&[*reg]).imm(*value),
+            
+            Instruction::Halt => out.op(op::HALT),
+            Instruction::Nop => out.op(op::NOP),
+            Instruction::Debug { message } => out.op(op::DEBUG).str(message),
+            Instruction::Trap { code } => out.op(op::TRAP).regs(&[*code]),
+        };
+        
+        match out.error {
+            Some(error) => Err(error),
+            None => Ok(out.bytes),
+        }
+    }
+    
+    /// Decode one instruction from the front of `bytes`
+    ///
+    /// Returns the instruction and the number of bytes it occupied.
+    pub fn decode(bytes: &[u8]) -> Result<(Instruction, usize), String> {
+        let mut d = Decoder { bytes, pos: 0 };
+        let opcode = d.byte()?;
+        
+        let inst = match opcode {
+            op::RADD => Instruction::RAdd { src1: d.byte()?, src2: d.byte()?, dst: d.byte()? },
+            op::RSUB => Instruction::RSub { src1: d.byte()?, src2: d.byte()?, dst: d.byte()? },
+            op::RXOR => Instruction::RXor { src: d.byte()?, dst: d.byte()? },
+            op::MOD_REPEAT => Instruction::ModRepeat {
+                dst: d.byte()?, src: d.byte()?, modulus: d.byte()?, quotient: d.byte()?,
+            },
+            op::BIT_REVERSE => Instruction::BitReverse { dst: d.byte()?, src: d.byte()?, width: d.byte()? },
+            op::IMUL => Instruction::IMul { src1: d.byte()?, src2: d.byte()?, dst: d.byte()? },
+            op::IDIV => Instruction::IDiv { src1: d.byte()?, src2: d.byte()?, dst: d.byte()? },
+            op::IMOD => Instruction::IMod { src1: d.byte()?, src2: d.byte()?, dst: d.byte()? },
+            op::SHL => Instruction::Shl { dst: d.byte()?, src: d.byte()?, amount: d.byte()? },
+            op::SHR => Instruction::Shr { dst: d.byte()?, src: d.byte()?, amount: d.byte()? },
+            op::SAR => Instruction::Sar { dst: d.byte()?, src: d.byte()?, amount: d.byte()? },
+            op::IAND => Instruction::IAnd { src1: d.byte()?, src2: d.byte()?, dst: d.byte()? },
+            op::IOR => Instruction::IOr { src1: d.byte()?, src2: d.byte()?, dst: d.byte()? },
+            op::INOT => Instruction::INot { dst: d.byte()?, src: d.byte()? },
+            
+            op::RLOAD => Instruction::RLoad { dst: d.byte()?, addr: d.byte()?, old: d.byte()? },
+            op::RSTORE => Instruction::RStore { addr: d.byte()?, src: d.byte()?, old: d.byte()? },
+            op::MSWAP => Instruction::MSwap { addr: d.byte()?, reg: d.byte()? },
+            op::CRC32 => Instruction::Crc32 { dst: d.byte()?, addr: d.byte()?, len: d.byte()? },
+            op::SORT_RANGE => Instruction::SortRange { addr: d.byte()?, count: d.byte()?, elem_size: d.byte()? },
+            op::INTERLEAVE => Instruction::Interleave {
+                a: d.byte()?, b: d.byte()?, dst: d.byte()?, count: d.byte()?,
+            },
+            op::DEINTERLEAVE => Instruction::Deinterleave {
+                src: d.byte()?, a: d.byte()?, b: d.byte()?, count: d.byte()?,
+            },
+            op::INC_SAT => Instruction::IncSat { addr: d.byte()?, max: d.byte()? },
+            op::HASH_UPDATE => Instruction::HashUpdate { state: d.byte()?, addr: d.byte()?, len: d.byte()? },
+            
+            op::SWAP => Instruction::Swap { reg1: d.byte()?, reg2: d.byte()? },
+            op::PUSH => Instruction::Push { reg: d.byte()? },
+            op::POP => Instruction::Pop { reg: d.byte()? },
+            op::DUP => Instruction::Dup,
+            op::DROP => Instruction::Drop,
+            op::OVER => Instruction::Over,
+            op::SWAP_TOP => Instruction::SwapTop,
+            
+            op::TAPE_READ => Instruction::TapeRead { reg: d.byte()?, len: d.byte()? },
+            op::TAPE_WRITE => Instruction::TapeWrite { reg: d.byte()?, len: d.byte()? },
+            op::TAPE_SEEK => Instruction::TapeSeek { position: d.imm()? },
+            op::TAPE_SEEK_REG => Instruction::TapeSeekReg { reg: d.byte()? },
+            op::TAPE_ADVANCE => Instruction::TapeAdvance { delta: d.imm()? },
+            op::TAPE_MARK => Instruction::TapeMark { label: d.str()? },
+            op::TAPE_SEEK_MARK => Instruction::TapeSeekMark { label: d.str()? },
+            
+            op::SEGMENT_CREATE => Instruction::SegmentCreate { name: d.str()?, size: d.byte()? },
+            op::SEGMENT_SEEK => Instruction::SegmentSeek { name: d.str()?, offset: d.byte()? },
+            op::SEGMENT_READ => Instruction::SegmentRead {
+                name: d.str()?, offset: d.byte()?, len: d.byte()?, dst: d.byte()?,
+            },
+            op::SEGMENT_WRITE => Instruction::SegmentWrite {
+                name: d.str()?, offset: d.byte()?, len: d.byte()?, src: d.byte()?,
+            },
+            
+            op::SPLICE => Instruction::Splice { dst: d.imm()?, src: d.imm()?, len: d.byte()? },
+            op::COMPACT => Instruction::Compact { start: d.imm()?, end: d.imm()? },
+            op::FORK => Instruction::Fork { label: d.str()? },
+            op::MERGE => Instruction::Merge { strategy: merge_strategy_from_byte(d.byte()?)? },
+            op::TIMELINE_SWITCH => Instruction::TimelineSwitch { label: d.str()? },
+            
+            op::CALL => Instruction::Call { label: d.str()? },
+            op::RETURN => Instruction::Return,
+            op::JUMP => Instruction::Jump { label: d.str()? },
+            op::BRANCH => Instruction::Branch { condition: d.byte()?, label: d.str()? },
+            op::BRANCH_ZERO => Instruction::BranchZero { reg: d.byte()?, label: d.str()? },
+            op::BRANCH_NOT_ZERO => Instruction::BranchNotZero { reg: d.byte()?, label: d.str()? },
+            op::BRANCH_LESS_THAN_U => Instruction::BranchLessThanU { src1: d.byte()?, src2: d.byte()?, label: d.str()? },
+            op::BRANCH_GREATER_EQUAL_U => Instruction::BranchGreaterEqualU { src1: d.byte()?, src2: d.byte()?, label: d.str()? },
+            
+            op::CHECKPOINT => Instruction::Checkpoint { label: d.str()? },
+            op::REWIND => Instruction::Rewind { label: d.str()? },
+            op::REWIND_N => Instruction::RewindN { steps: d.byte()? },
+            
+            op::COMPARE => Instruction::Compare { dst: d.byte()?, src1: d.byte()?, src2: d.byte()? },
+            op::EQUAL => Instruction::Equal { dst: d.byte()?, src1: d.byte()?, src2: d.byte()? },
+            op::LESS_THAN => Instruction::LessThan { dst: d.byte()?, src1: d.byte()?, src2: d.byte()? },
+            op::COMPARE_U => Instruction::CompareU { dst: d.byte()?, src1: d.byte()?, src2: d.byte()? },
+            op::LESS_THAN_U => Instruction::LessThanU { dst: d.byte()?, src1: d.byte()?, src2: d.byte()? },
+            
+            op::LOAD_IMM => Instruction::LoadImm { reg: d.byte()?, value: d.imm()? },
+            
+            op::HALT => Instruction::Halt,
+            op::NOP => Instruction::Nop,
+            op::DEBUG => Instruction::Debug { message: d.str()? },
+            op::TRAP => Instruction::Trap { code: d.byte()? },
+            
+            other => return Err(format!("Unknown opcode 0x{:02X}", other)),
+        };
+        
+        Ok((inst, d.pos))
+    }
+}
+
+/// Encode a whole program, returning the bytes and each instruction's offset
+///
+/// The offsets have one extra trailing entry holding the total length.
+pub fn encode_program(instructions: &[Instruction]) -> Result<(Vec<u8>, Vec<usize>), String> {
+    let mut bytes = Vec::new();
+    let mut offsets = Vec::with_capacity(instructions.len() + 1);
+    
+    for (i, inst) in instructions.iter().enumerate() {
+        offsets.push(bytes.len());
+        let encoded = inst.encode().map_err(|e| format!("Instruction {}: {}", i, e))?;
+        bytes.extend_from_slice(&encoded);
+    }
+    offsets.push(bytes.len());
+    
+    Ok((bytes, offsets))
+}
+
+fn merge_strategy_byte(strategy: &MergeStrategy) -> u8 {
+    match strategy {
+        MergeStrategy::Latest => 0,
+        MergeStrategy::Earliest => 1,
+        MergeStrategy::Combine => 2,
+        MergeStrategy::Manual => 3,
+    }
+}
+
+fn merge_strategy_from_byte(byte: u8) -> Result<MergeStrategy, String> {
+    match byte {
+        0 => Ok(MergeStrategy::Latest),
+        1 => Ok(MergeStrategy::Earliest),
+        2 => Ok(MergeStrategy::Combine),
+        3 => Ok(MergeStrategy::Manual),
+        other => Err(format!("Unknown merge strategy {}", other)),
+    }
+}
+
+struct Encoder {
+    bytes: Vec<u8>,
+    /// The first operand that could not be encoded, reported once encoding ends
+    error: Option<String>,
+}
+
+impl Encoder {
+    fn op(&mut self, opcode: u8) -> &mut Self {
+        self.bytes.push(opcode);
+        self
+    }
+    
+    fn regs(&mut self, regs: &[Register]) -> &mut Self {
+        self.bytes.extend_from_slice(regs);
+        self
+    }
+    
+    fn imm(&mut self, value: i64) -> &mut Self {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+    
+    fn str(&mut self, s: &str) -> &mut Self {
+        match u16::try_from(s.len()) {
+            Ok(len) => {
+                self.bytes.extend_from_slice(&len.to_le_bytes());
+                self.bytes.extend_from_slice(s.as_bytes());
+            }
+            Err(_) => {
+                self.error.get_or_insert_with(|| format!(
+                    "String operand of {} bytes exceeds the {} byte limit", s.len(), u16::MAX
+                ));
+            }
+        }
+        self
+    }
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Decoder<'_> {
+    fn take(&mut self, n: usize) -> Result<&[u8], String> {
+        let end = self.pos + n;
+        let slice = self.bytes.get(self.pos..end)
+            .ok_or_else(|| format!("Truncated instruction: need {} bytes, have {}", end, self.bytes.len()))?;
+        self.pos = end;
+        Ok(slice)
+    }
+    
+    fn byte(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+    
+    fn imm(&mut self) -> Result<i64, String> {
+        let bytes = self.take(8)?;
+        Ok(i64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+    
+    fn str(&mut self) -> Result<String, String> {
+        let len_bytes = self.take(2)?;
+        let len = u16::from_le_bytes([len_bytes[0], len_bytes[1]]) as usize;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|e| format!("Invalid string operand: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_decode_roundtrip() {
+        let program = vec![
+            Instruction::LoadImm { reg: 1, value: -42 },
+            Instruction::RAdd { src1: 0, src2: 1, dst: 2 },
+            Instruction::BranchZero { reg: 2, label: "done".to_string() },
+            Instruction::Merge { strategy: MergeStrategy::Combine },
+            Instruction::Halt,
+        ];
+        
+        let (bytes, offsets) = encode_program(&program).unwrap();
+        assert_eq!(offsets.len(), program.len() + 1);
+        assert_eq!(*offsets.last().unwrap(), bytes.len());
+        
+        for (i, expected) in program.iter().enumerate() {
+            let (inst, used) = Instruction::decode(&bytes[offsets[i]..]).unwrap();
+            assert_eq!(&inst, expected);
+            assert_eq!(used, offsets[i + 1] - offsets[i]);
+        }
+    }
+
+    #[test]
+    fn test_every_variant_roundtrips() {
+        for inst in crate::instruction::every_instruction() {
+            let bytes = inst.encode().unwrap();
+            assert_eq!(bytes.len(), inst.size(), "{:?}", inst);
+            
+            let (decoded, used) = Instruction::decode(&bytes).unwrap();
+            assert_eq!(decoded, inst);
+            assert_eq!(used, bytes.len());
+        }
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert!(Instruction::decode(&[]).is_err());
+        assert!(Instruction::decode(&[0xEE]).unwrap_err().contains("Unknown opcode"));
+        
+        let bytes = Instruction::LoadImm { reg: 0, value: 7 }.encode().unwrap();
+        assert!(Instruction::decode(&bytes[..bytes.len() - 1]).unwrap_err().contains("Truncated"));
+    }
+
+    #[test]
+    fn test_encode_rejects_long_strings() {
+        let label = "x".repeat(u16::MAX as usize);
+        assert!(Instruction::Jump { label: label.clone() }.encode().is_ok());
+        
+        let long = Instruction::Debug { message: label + "x" };
+        assert_eq!(long.encode().unwrap_err(), "String operand of 65536 bytes exceeds the 65535 byte limit");
+        let err = encode_program(&[Instruction::Nop, long]).unwrap_err();
+        assert!(err.starts_with("Instruction 1: "), "{}", err);
+    }
+}
@@ -3,19 +3,64 @@
 //! All instructions are designed to be reversible, with each having
 //! a clear inverse operation for supporting time-travel debugging.
 
-use crate::vm::Register;
+use crate::tape::TrailOp;
+use crate::vm::{HistoryFrame, Register, SavedRegisters};
 
-#[derive(Debug, Clone)]
+mod encoding;
+
+pub use encoding::encode_program;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Instruction {
     // Reversible arithmetic operations (RISA)
     RAdd { src1: Register, src2: Register, dst: Register },
     RSub { src1: Register, src2: Register, dst: Register },
     RXor { src: Register, dst: Register },
+    /// dst = src mod modulus by repeated subtraction, counting subtractions into quotient;
+    /// modulus must be positive and quotient must differ from dst
+    ModRepeat { dst: Register, src: Register, modulus: Register, quotient: Register },
+    /// dst = src with its low `width` bits reversed; higher bits are preserved
+    BitReverse { dst: Register, src: Register, width: u8 },
+    /// dst = !src; only the in-place form (dst == src) has an `inverse()`
+    INot { dst: Register, src: Register },
+    
+    // Non-reversible convenience arithmetic (undone from history frames)
+    IMul { src1: Register, src2: Register, dst: Register },
+    /// dst = src1 / src2, truncating; the discarded remainder means there is no `inverse()`
+    IDiv { src1: Register, src2: Register, dst: Register },
+    /// dst = src1 % src2 (sign follows src1); the discarded quotient means there is no `inverse()`
+    IMod { src1: Register, src2: Register, dst: Register },
+    /// dst = src << (amount & 63); bits shifted out are lost, so there is no `inverse()`
+    Shl { dst: Register, src: Register, amount: Register },
+    /// dst = src >> (amount & 63), filling with zeros
+    Shr { dst: Register, src: Register, amount: Register },
+    /// dst = src >> (amount & 63), filling with the sign bit
+    Sar { dst: Register, src: Register, amount: Register },
+    /// dst = src1 & src2
+    IAnd { src1: Register, src2: Register, dst: Register },
+    /// dst = src1 | src2
+    IOr { src1: Register, src2: Register, dst: Register },
     
     // Reversible memory operations (RISA)
     RLoad { dst: Register, addr: Register, old: Register },
     RStore { addr: Register, src: Register, old: Register },
     MSwap { addr: Register, reg: Register },
+    /// dst = CRC32 of `registers[len]` tape bytes starting at `registers[addr]`
+    Crc32 { dst: Register, addr: Register, len: Register },
+    /// Stable sort of `registers[count]` signed little-endian `elem_size`-byte
+    /// integers starting at `registers[addr]`
+    SortRange { addr: Register, count: Register, elem_size: u8 },
+    /// Write `registers[count]` byte pairs a0,b0,a1,b1,... from the regions at
+    /// `registers[a]` and `registers[b]` to `registers[dst]`
+    Interleave { a: Register, b: Register, dst: Register, count: Register },
+    /// Split `2 * registers[count]` interleaved bytes at `registers[src]` back
+    /// into the regions at `registers[a]` and `registers[b]`
+    Deinterleave { src: Register, a: Register, b: Register, count: Register },
+    /// Fold `registers[len]` tape bytes at `registers[addr]` into the rolling hash in `state`
+    HashUpdate { state: Register, addr: Register, len: Register },
+    /// Increment the word at `registers[addr]`, saturating at `registers[max]`,
+    /// and leave the head on it
+    IncSat { addr: Register, max: Register },
     
     // Register operations
     Swap { reg1: Register, reg2: Register },
@@ -23,6 +68,14 @@ pub enum Instruction {
     // Stack operations (still needed for function calls)
     Push { reg: Register },
     Pop { reg: Register },
+    /// Push a copy of the top stack word
+    Dup,
+    /// Discard the top stack word
+    Drop,
+    /// Push a copy of the second stack word
+    Over,
+    /// Exchange the top two stack words
+    SwapTop,
     
     // Tape primitive operations
     TapeRead { reg: Register, len: u8 },
@@ -44,6 +97,8 @@ pub enum Instruction {
     Compact { start: i64, end: i64 },
     Fork { label: String },
     Merge { strategy: MergeStrategy },
+    /// Make the named timeline the active one
+    TimelineSwitch { label: String },
     
     // Control flow
     Call { label: String },
@@ -52,6 +107,10 @@ pub enum Instruction {
     Branch { condition: Register, label: String },
     BranchZero { reg: Register, label: String },
     BranchNotZero { reg: Register, label: String },
+    /// Branch if src1 < src2 with both operands treated as `u64`
+    BranchLessThanU { src1: Register, src2: Register, label: String },
+    /// Branch if src1 >= src2 with both operands treated as `u64`
+    BranchGreaterEqualU { src1: Register, src2: Register, label: String },
     
     // Time operations
     Checkpoint { label: String },
@@ -62,6 +121,10 @@ pub enum Instruction {
     Compare { dst: Register, src1: Register, src2: Register },
     Equal { dst: Register, src1: Register, src2: Register },
     LessThan { dst: Register, src1: Register, src2: Register },
+    /// `Compare` treating both operands as `u64`
+    CompareU { dst: Register, src1: Register, src2: Register },
+    /// `LessThan` treating both operands as `u64`
+    LessThanU { dst: Register, src1: Register, src2: Register },
     
     // Constants
     LoadImm { reg: Register, value: i64 },
@@ -70,9 +133,11 @@ pub enum Instruction {
     Halt,
     Nop,
     Debug { message: String },
+    /// Call out to the host's trap handler
+    Trap { code: u8 },
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum MergeStrategy {
     Latest,
     Earliest,
@@ -90,6 +155,10 @@ impl Instruction {
             Instruction::RSub { src1, src2, dst } => 
                 Some(Instruction::RAdd { src1: *src1, src2: *src2, dst: *dst }),
             Instruction::RXor { .. } => Some(self.clone()), // Self-inverse
+            Instruction::BitReverse { dst, src, .. } if dst == src =>
+                Some(self.clone()), // Self-inverse in place
+            Instruction::INot { dst, src } if dst == src =>
+                Some(self.clone()), // Self-inverse in place
             
             // RISA memory operations
             Instruction::RLoad { dst, addr, old } => 
@@ -97,6 +166,10 @@ impl Instruction {
             Instruction::RStore { addr, src, old } => 
                 Some(Instruction::RStore { addr: *addr, src: *old, old: *src }), // Swap src and old
             Instruction::MSwap { .. } => Some(self.clone()), // Self-inverse
+            Instruction::Interleave { a, b, dst, count } =>
+                Some(Instruction::Deinterleave { src: *dst, a: *a, b: *b, count: *count }),
+            Instruction::Deinterleave { src, a, b, count } =>
+                Some(Instruction::Interleave { a: *a, b: *b, dst: *src, count: *count }),
             
             // Register operations
             Instruction::Swap { .. } => Some(self.clone()),  // Self-inverse
@@ -104,8 +177,11 @@ impl Instruction {
             // Stack operations
             Instruction::Push { reg } => Some(Instruction::Pop { reg: *reg }),
             Instruction::Pop { reg } => Some(Instruction::Push { reg: *reg }),
+            Instruction::Dup | Instruction::Over => Some(Instruction::Drop),
+            Instruction::SwapTop => Some(self.clone()), // Self-inverse
             
-            // Tape operations
+            // Tape operations; absolute seeks (`TapeSeek`, `TapeSeekReg`) lose
+            // the old position and are undone from the trail's `Seek` op
             Instruction::TapeAdvance { delta } => 
                 Some(Instruction::TapeAdvance { delta: -delta }),
                 
@@ -113,15 +189,87 @@ impl Instruction {
         }
     }
     
+    /// A concrete inverse using the history frame and tape trail ops it produced
+    ///
+    /// `trail` holds the operations recorded while this instruction ran, from
+    /// `frame.tape_trail_len` on. Absolute seeks invert to a seek back to the
+    /// old head, and `TapeWrite` to loading the overwritten bytes into its
+    /// register, writing them back and restoring the register. Instructions
+    /// with a context-free `inverse` return that.
+    pub fn inverse_with(&self, frame: &HistoryFrame, trail: &[TrailOp]) -> Option<Vec<Instruction>> {
+        if let Some(inverse) = self.inverse() {
+            return Some(vec![inverse]);
+        }
+        
+        match self {
+            Instruction::TapeSeek { .. } |
+            Instruction::TapeSeekReg { .. } |
+            Instruction::TapeSeekMark { .. } => trail.iter().find_map(|op| match op {
+                TrailOp::Seek { old_pos, .. } => Some(vec![Instruction::TapeSeek { position: *old_pos }]),
+                _ => None,
+            }),
+            Instruction::TapeWrite { reg, len } => {
+                let SavedRegisters::All(registers) = &frame.registers_before else {
+                    return None;
+                };
+                let old = trail.iter().find_map(|op| match op {
+                    TrailOp::Write { old, .. } => Some(old),
+                    _ => None,
+                })?;
+                let mut bytes = [0u8; 8];
+                bytes[..old.len().min(8)].copy_from_slice(&old[..old.len().min(8)]);
+                Some(vec![
+                    Instruction::LoadImm { reg: *reg, value: i64::from_le_bytes(bytes) },
+                    Instruction::TapeWrite { reg: *reg, len: *len },
+                    Instruction::LoadImm { reg: *reg, value: registers.read(*reg).ok()? },
+                ])
+            }
+            _ => None,
+        }
+    }
+    
+    /// Whether the instruction's result preserves the information in its operands
+    ///
+    /// Only the convenience arithmetic and bitwise ops outside RISA count as
+    /// lossy: their result cannot be mapped back to their inputs. Instructions
+    /// that overwrite a destination wholesale (`LoadImm`, `Pop`, `TapeRead`,
+    /// comparisons, checksums, ...) also discard its old value but are not
+    /// counted here; like everything else they are undone from a history frame.
+    pub fn is_reversible(&self) -> bool {
+        !matches!(self,
+            Instruction::IMul { .. } |
+            Instruction::IDiv { .. } |
+            Instruction::IMod { .. } |
+            Instruction::Shl { .. } |
+            Instruction::Shr { .. } |
+            Instruction::Sar { .. } |
+            Instruction::IAnd { .. } |
+            Instruction::IOr { .. }
+        )
+    }
+    
     /// Check if instruction modifies state
     pub fn is_stateful(&self) -> bool {
-        match self {
-            Instruction::Nop | 
+        !matches!(self,
+            Instruction::Nop |
             Instruction::Debug { .. } |
             Instruction::Compare { .. } |
             Instruction::Equal { .. } |
-            Instruction::LessThan { .. } => false,
-            _ => true,
+            Instruction::LessThan { .. } |
+            Instruction::CompareU { .. } |
+            Instruction::LessThanU { .. }
+        )
+    }
+    
+    /// Register loaded by an instruction that otherwise only reads state
+    ///
+    /// Reversing such an instruction only needs that register's old value.
+    pub fn read_destination(&self) -> Option<Register> {
+        match self {
+            Instruction::TapeRead { reg, .. } => Some(*reg),
+            Instruction::Crc32 { dst, .. } => Some(*dst),
+            Instruction::SegmentRead { dst, .. } => Some(*dst),
+            _ => None,
         }
     }
     
@@ -132,30 +280,295 @@ impl Instruction {
             Instruction::Branch { .. } |
             Instruction::BranchZero { .. } |
             Instruction::BranchNotZero { .. } |
+            Instruction::BranchLessThanU { .. } |
+            Instruction::BranchGreaterEqualU { .. } |
             Instruction::Call { .. } |
             Instruction::Return
         )
     }
     
-    /// Get the size of this instruction in bytes (for future binary encoding)
-    pub fn size(&self) -> usize {
+    /// The variant name, e.g. `"RAdd"`, for tracing and reports
+    pub fn name(&self) -> &'static str {
         match self {
-            Instruction::Nop => 1,
-            Instruction::Halt => 1,
-            Instruction::Return => 1,
-            Instruction::LoadImm { .. } => 10, // 1 byte opcode + 1 byte reg + 8 bytes value
-            Instruction::RAdd { .. } | 
-            Instruction::RSub { .. } => 4, // 1 byte opcode + 3 bytes for registers
-            Instruction::RXor { .. } => 3, // 1 byte opcode + 2 bytes for registers
-            Instruction::RLoad { .. } |
-            Instruction::RStore { .. } => 4, // 1 byte opcode + 3 bytes for registers
+            Instruction::RAdd { .. } => "RAdd",
+            Instruction::RSub { .. } => "RSub",
+            Instruction::RXor { .. } => "RXor",
+            Instruction::ModRepeat { .. } => "ModRepeat",
+            Instruction::BitReverse { .. } => "BitReverse",
+            Instruction::INot { .. } => "INot",
+            Instruction::IMul { .. } => "IMul",
+            Instruction::IDiv { .. } => "IDiv",
+            Instruction::IMod { .. } => "IMod",
+            Instruction::Shl { .. } => "Shl",
+            Instruction::Shr { .. } => "Shr",
+            Instruction::Sar { .. } => "Sar",
+            Instruction::IAnd { .. } => "IAnd",
+            Instruction::IOr { .. } => "IOr",
+            Instruction::RLoad { .. } => "RLoad",
+            Instruction::RStore { .. } => "RStore",
+            Instruction::MSwap { .. } => "MSwap",
+            Instruction::Crc32 { .. } => "Crc32",
+            Instruction::SortRange { .. } => "SortRange",
+            Instruction::Interleave { .. } => "Interleave",
+            Instruction::Deinterleave { .. } => "Deinterleave",
+            Instruction::HashUpdate { .. } => "HashUpdate",
+            Instruction::IncSat { .. } => "IncSat",
+            Instruction::Swap { .. } => "Swap",
+            Instruction::Push { .. } => "Push",
+            Instruction::Pop { .. } => "Pop",
+            Instruction::Dup => "Dup",
+            Instruction::Drop => "Drop",
+            Instruction::Over => "Over",
+            Instruction::SwapTop => "SwapTop",
+            Instruction::TapeRead { .. } => "TapeRead",
+            Instruction::TapeWrite { .. } => "TapeWrite",
+            Instruction::TapeSeek { .. } => "TapeSeek",
+            Instruction::TapeSeekReg { .. } => "TapeSeekReg",
+            Instruction::TapeAdvance { .. } => "TapeAdvance",
+            Instruction::TapeMark { .. } => "TapeMark",
+            Instruction::TapeSeekMark { .. } => "TapeSeekMark",
+            Instruction::SegmentCreate { .. } => "SegmentCreate",
+            Instruction::SegmentSeek { .. } => "SegmentSeek",
+            Instruction::SegmentRead { .. } => "SegmentRead",
+            Instruction::SegmentWrite { .. } => "SegmentWrite",
+            Instruction::Splice { .. } => "Splice",
+            Instruction::Compact { .. } => "Compact",
+            Instruction::Fork { .. } => "Fork",
+            Instruction::Merge { .. } => "Merge",
+            Instruction::TimelineSwitch { .. } => "TimelineSwitch",
+            Instruction::Call { .. } => "Call",
+            Instruction::Return => "Return",
+            Instruction::Jump { .. } => "Jump",
+            Instruction::Branch { .. } => "Branch",
+            Instruction::BranchZero { .. } => "BranchZero",
+            Instruction::BranchNotZero { .. } => "BranchNotZero",
+            Instruction::BranchLessThanU { .. } => "BranchLessThanU",
+            Instruction::BranchGreaterEqualU { .. } => "BranchGreaterEqualU",
+            Instruction::Checkpoint { .. } => "Checkpoint",
+            Instruction::Rewind { .. } => "Rewind",
+            Instruction::RewindN { .. } => "RewindN",
+            Instruction::Compare { .. } => "Compare",
+            Instruction::Equal { .. } => "Equal",
+            Instruction::LessThan { .. } => "LessThan",
+            Instruction::CompareU { .. } => "CompareU",
+            Instruction::LessThanU { .. } => "LessThanU",
+            Instruction::LoadImm { .. } => "LoadImm",
+            Instruction::Halt => "Halt",
+            Instruction::Nop => "Nop",
+            Instruction::Debug { .. } => "Debug",
+            Instruction::Trap { .. } => "Trap",
+        }
+    }
+    
+    /// Nominal execution cost in cycles, used by the default cost model
+    pub fn cycle_cost(&self) -> u64 {
+        match self {
+            Instruction::Nop |
+            Instruction::Halt |
+            Instruction::LoadImm { .. } |
+            Instruction::Swap { .. } |
+            Instruction::RAdd { .. } |
+            Instruction::RSub { .. } |
+            Instruction::RXor { .. } |
+            Instruction::BitReverse { .. } |
+            Instruction::Shl { .. } |
+            Instruction::Shr { .. } |
+            Instruction::Sar { .. } |
+            Instruction::IAnd { .. } |
+            Instruction::IOr { .. } |
+            Instruction::INot { .. } |
+            Instruction::Compare { .. } |
+            Instruction::Equal { .. } |
+            Instruction::LessThan { .. } |
+            Instruction::CompareU { .. } |
+            Instruction::LessThanU { .. } => 1,
+            Instruction::IMul { .. } |
+            Instruction::Jump { .. } |
+            Instruction::Branch { .. } |
+            Instruction::BranchZero { .. } |
+            Instruction::BranchNotZero { .. } |
+            Instruction::BranchLessThanU { .. } |
+            Instruction::BranchGreaterEqualU { .. } => 3,
+            Instruction::IDiv { .. } |
+            Instruction::IMod { .. } |
+            Instruction::ModRepeat { .. } |
+            Instruction::Crc32 { .. } |
+            Instruction::HashUpdate { .. } |
+            Instruction::SortRange { .. } => 20,
+            Instruction::Fork { .. } |
+            Instruction::TimelineSwitch { .. } |
+            Instruction::Merge { .. } |
+            Instruction::Compact { .. } |
+            Instruction::Splice { .. } |
+            Instruction::Rewind { .. } |
+            Instruction::RewindN { .. } |
+            Instruction::Trap { .. } => 50,
+            _ => 4, // Memory, stack, tape and segment access
+        }
+    }
+    
+    /// Size of this instruction's binary encoding in bytes
+    ///
+    /// Equal to the length of `self.encode()` when it succeeds: a 1-byte opcode plus operands,
+    /// with strings costing a 2-byte length prefix plus their UTF-8 bytes.
+    pub fn size(&self) -> usize {
+        let string = |s: &String| 2 + s.len();
+        
+        1 + match self {
+            Instruction::Nop |
+            Instruction::Halt |
+            Instruction::Return |
+            Instruction::Dup |
+            Instruction::Drop |
+            Instruction::Over |
+            Instruction::SwapTop => 0,
+            
+            Instruction::Push { .. } |
+            Instruction::Pop { .. } |
+            Instruction::TapeSeekReg { .. } |
+            Instruction::Merge { .. } |
+            Instruction::RewindN { .. } |
+            Instruction::Trap { .. } => 1,
+            
+            Instruction::RXor { .. } |
+            Instruction::INot { .. } |
             Instruction::MSwap { .. } |
-            Instruction::Swap { .. } => 3, // 1 byte opcode + 2 bytes for registers
-            _ => 8, // Default size for now
+            Instruction::IncSat { .. } |
+            Instruction::Swap { .. } |
+            Instruction::TapeRead { .. } |
+            Instruction::TapeWrite { .. } => 2,
+            
+            Instruction::RAdd { .. } |
+            Instruction::RSub { .. } |
+            Instruction::BitReverse { .. } |
+            Instruction::IMul { .. } |
+            Instruction::IDiv { .. } |
+            Instruction::IMod { .. } |
+            Instruction::IAnd { .. } |
+            Instruction::IOr { .. } |
+            Instruction::Shl { .. } |
+            Instruction::Shr { .. } |
+            Instruction::Sar { .. } |
+            Instruction::RLoad { .. } |
+            Instruction::RStore { .. } |
+            Instruction::Crc32 { .. } |
+            Instruction::SortRange { .. } |
+            Instruction::HashUpdate { .. } |
+            Instruction::Compare { .. } |
+            Instruction::Equal { .. } |
+            Instruction::LessThan { .. } |
+            Instruction::CompareU { .. } |
+            Instruction::LessThanU { .. } => 3,
+            
+            Instruction::ModRepeat { .. } |
+            Instruction::Interleave { .. } |
+            Instruction::Deinterleave { .. } => 4,
+            
+            Instruction::TapeSeek { .. } |
+            Instruction::TapeAdvance { .. } => 8,
+            Instruction::LoadImm { .. } => 9,
+            Instruction::Compact { .. } => 16,
+            Instruction::Splice { .. } => 17,
+            
+            Instruction::TapeMark { label } |
+            Instruction::TapeSeekMark { label } |
+            Instruction::Fork { label } |
+            Instruction::TimelineSwitch { label } |
+            Instruction::Call { label } |
+            Instruction::Jump { label } |
+            Instruction::Checkpoint { label } |
+            Instruction::Rewind { label } => string(label),
+            Instruction::Debug { message } => string(message),
+            
+            Instruction::Branch { label, .. } |
+            Instruction::BranchZero { label, .. } |
+            Instruction::BranchNotZero { label, .. } => 1 + string(label),
+            Instruction::BranchLessThanU { label, .. } |
+            Instruction::BranchGreaterEqualU { label, .. } => 2 + string(label),
+            
+            Instruction::SegmentCreate { name, .. } |
+            Instruction::SegmentSeek { name, .. } => string(name) + 1,
+            Instruction::SegmentRead { name, .. } |
+            Instruction::SegmentWrite { name, .. } => string(name) + 3,
         }
     }
 }
 
+/// One instance of every instruction variant
+#[cfg(test)]
+pub(crate) fn every_instruction() -> Vec<Instruction> {
+    let label = || "target".to_string();
+    vec![
+        Instruction::RAdd { src1: 0, src2: 1, dst: 2 },
+        Instruction::RSub { src1: 0, src2: 1, dst: 2 },
+        Instruction::RXor { src: 0, dst: 2 },
+        Instruction::ModRepeat { dst: 2, src: 0, modulus: 1, quotient: 3 },
+        Instruction::BitReverse { dst: 2, src: 0, width: 8 },
+        Instruction::IMul { src1: 0, src2: 1, dst: 2 },
+        Instruction::IDiv { src1: 0, src2: 1, dst: 2 },
+        Instruction::IMod { src1: 0, src2: 1, dst: 2 },
+        Instruction::IAnd { src1: 0, src2: 1, dst: 2 },
+        Instruction::IOr { src1: 0, src2: 1, dst: 2 },
+        Instruction::INot { dst: 2, src: 0 },
+        Instruction::Shl { dst: 2, src: 0, amount: 1 },
+        Instruction::Shr { dst: 2, src: 0, amount: 1 },
+        Instruction::Sar { dst: 2, src: 0, amount: 1 },
+        Instruction::RLoad { dst: 2, addr: 4, old: 3 },
+        Instruction::RStore { addr: 4, src: 0, old: 3 },
+        Instruction::MSwap { addr: 4, reg: 0 },
+        Instruction::Crc32 { dst: 2, addr: 4, len: 1 },
+        Instruction::SortRange { addr: 4, count: 1, elem_size: 1 },
+        Instruction::Interleave { a: 4, b: 4, dst: 4, count: 1 },
+        Instruction::Deinterleave { src: 4, a: 4, b: 4, count: 1 },
+        Instruction::HashUpdate { state: 2, addr: 4, len: 1 },
+        Instruction::IncSat { addr: 4, max: 1 },
+        Instruction::Swap { reg1: 0, reg2: 1 },
+        Instruction::Push { reg: 0 },
+        Instruction::Pop { reg: 2 },
+        Instruction::Dup,
+        Instruction::Drop,
+        Instruction::Over,
+        Instruction::SwapTop,
+        Instruction::TapeRead { reg: 2, len: 8 },
+        Instruction::TapeWrite { reg: 0, len: 8 },
+        Instruction::TapeSeek { position: 64 },
+        Instruction::TapeSeekReg { reg: 4 },
+        Instruction::TapeAdvance { delta: 8 },
+        Instruction::TapeMark { label: label() },
+        Instruction::TapeSeekMark { label: label() },
+        Instruction::SegmentCreate { name: "extra".to_string(), size: 1 },
+        Instruction::SegmentSeek { name: "heap".to_string(), offset: 0 },
+        Instruction::SegmentRead { name: "heap".to_string(), offset: 0, len: 1, dst: 2 },
+        Instruction::SegmentWrite { name: "heap".to_string(), offset: 0, len: 1, src: 0 },
+        Instruction::Splice { dst: 128, src: 64, len: 1 },
+        Instruction::Compact { start: 0, end: 64 },
+        Instruction::Fork { label: label() },
+        Instruction::Merge { strategy: MergeStrategy::Latest },
+        Instruction::TimelineSwitch { label: label() },
+        Instruction::Call { label: label() },
+        Instruction::Return,
+        Instruction::Jump { label: label() },
+        Instruction::Branch { condition: 0, label: label() },
+        Instruction::BranchZero { reg: 0, label: label() },
+        Instruction::BranchNotZero { reg: 0, label: label() },
+        Instruction::BranchLessThanU { src1: 0, src2: 1, label: label() },
+        Instruction::BranchGreaterEqualU { src1: 0, src2: 1, label: label() },
+        Instruction::Checkpoint { label: label() },
+        Instruction::Rewind { label: label() },
+        Instruction::RewindN { steps: 5 },
+        Instruction::Compare { dst: 2, src1: 0, src2: 1 },
+        Instruction::Equal { dst: 2, src1: 0, src2: 1 },
+        Instruction::LessThan { dst: 2, src1: 0, src2: 1 },
+        Instruction::CompareU { dst: 2, src1: 0, src2: 1 },
+        Instruction::LessThanU { dst: 2, src1: 0, src2: 1 },
+        Instruction::LoadImm { reg: 0, value: 1 },
+        Instruction::Halt,
+        Instruction::Nop,
+        Instruction::Debug { message: "reachable".to_string() },
+        Instruction::Trap { code: 0 },
+    ]
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -11,7 +11,7 @@ pub mod compiler;
 pub use tape::{Tape, Segment, SegmentType};
 pub use vm::{VM, Register};
 pub use instruction::Instruction;
-pub use compiler::Parser;
+pub use compiler::{Parser, Program};
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,364 @@
+//! B-tree index stored in a tape segment
+//!
+//! The tree lives at the index's `root_position` within its segment: a 16-byte
+//! header holding the root node and the number of allocated nodes, followed by
+//! fixed-size nodes numbered from zero. A freshly zeroed region is an empty
+//! tree. Nodes are never freed, and every update goes through `write_segment`,
+//! so the tree is persisted with the tape and stays reversible.
+
+use super::segment::{IndexType, SegmentedTape};
+
+const HEADER_SIZE: usize = 16;
+/// Minimum degree: every node but the root holds between `T - 1` and `2T - 1` keys
+const T: usize = 4;
+const MAX_KEYS: usize = 2 * T - 1;
+/// Leaf flag and key count, then keys, values and child node numbers
+const NODE_SIZE: usize = 8 + MAX_KEYS * 16 + (MAX_KEYS + 1) * 8;
+
+/// A decoded node
+struct Node {
+    leaf: bool,
+    keys: Vec<i64>,
+    values: Vec<i64>,
+    children: Vec<u64>,
+}
+
+impl Node {
+    fn leaf() -> Self {
+        Node { leaf: true, keys: Vec::new(), values: Vec::new(), children: Vec::new() }
+    }
+    
+    fn is_full(&self) -> bool {
+        self.keys.len() == MAX_KEYS
+    }
+    
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = vec![0u8; NODE_SIZE];
+        bytes[0] = self.leaf as u8;
+        bytes[1] = self.keys.len() as u8;
+        for (i, (key, value)) in self.keys.iter().zip(&self.values).enumerate() {
+            let at = 8 + i * 16;
+            bytes[at..at + 8].copy_from_slice(&key.to_le_bytes());
+            bytes[at + 8..at + 16].copy_from_slice(&value.to_le_bytes());
+        }
+        for (i, child) in self.children.iter().enumerate() {
+            let at = 8 + MAX_KEYS * 16 + i * 8;
+            bytes[at..at + 8].copy_from_slice(&child.to_le_bytes());
+        }
+        bytes
+    }
+    
+    fn decode(bytes: &[u8]) -> Result<Self, String> {
+        let word = |at: usize| i64::from_le_bytes(bytes[at..at + 8].try_into().unwrap());
+        let count = bytes[1] as usize;
+        if count > MAX_KEYS {
+            return Err(format!("Corrupt B-tree node with {} keys", count));
+        }
+        
+        let leaf = bytes[0] != 0;
+        let children = if leaf { 0 } else { count + 1 };
+        Ok(Node {
+            leaf,
+            keys: (0..count).map(|i| word(8 + i * 16)).collect(),
+            values: (0..count).map(|i| word(8 + i * 16 + 8)).collect(),
+            children: (0..children).map(|i| word(8 + MAX_KEYS * 16 + i * 8) as u64).collect(),
+        })
+    }
+}
+
+/// Borrowing view of an `i64 -> i64` B-tree in a segment
+pub struct BTreeIndex<'a> {
+    tape: &'a mut SegmentedTape,
+    segment: String,
+    /// Offset of the tree's header within the segment
+    base: i64,
+    /// Nodes that fit between the header and the end of the tree's region
+    capacity: usize,
+}
+
+impl<'a> BTreeIndex<'a> {
+    /// Open the tree whose header is at `root_position` within `segment`
+    ///
+    /// The tree may grow up to the next index root in the segment.
+    pub fn new(tape: &'a mut SegmentedTape, segment: &str, root_position: i64) -> Result<Self, String> {
+        let end = tape.get_segment(segment)
+            .ok_or_else(|| format!("Unknown segment: {}", segment))?
+            .region_end(root_position);
+        if root_position < 0 || root_position + HEADER_SIZE as i64 > end {
+            return Err(format!("B-tree header at {} lies outside segment '{}'", root_position, segment));
+        }
+        
+        Ok(BTreeIndex {
+            tape,
+            segment: segment.to_string(),
+            base: root_position,
+            capacity: ((end - root_position) as usize - HEADER_SIZE) / NODE_SIZE,
+        })
+    }
+    
+    /// Open the segment's B-tree index called `name`
+    pub fn open(tape: &'a mut SegmentedTape, segment: &str, name: &str) -> Result<Self, String> {
+        let index = tape.get_segment(segment)
+            .ok_or_else(|| format!("Unknown segment: {}", segment))?
+            .indices.iter()
+            .find(|index| index.name == name)
+            .ok_or_else(|| format!("Segment '{}' has no index '{}'", segment, name))?;
+        if !matches!(index.index_type, IndexType::BTree) {
+            return Err(format!("Index '{}' is not a B-tree", name));
+        }
+        
+        let root_position = index.root_position;
+        Self::new(tape, segment, root_position)
+    }
+    
+    /// Insert `key`, replacing the value if it is already present
+    pub fn insert(&mut self, key: i64, value: i64) -> Result<(), String> {
+        let (root, nodes) = self.header()?;
+        if nodes == 0 {
+            let mut node = Node::leaf();
+            node.keys.push(key);
+            node.values.push(value);
+            let root = self.allocate(&node)?;
+            return self.set_root(root);
+        }
+        
+        // Split a full root first so the descent never has to back up
+        let mut current = root;
+        if self.read_node(root)?.is_full() {
+            let new_root = Node { leaf: false, keys: Vec::new(), values: Vec::new(), children: vec![root] };
+            current = self.allocate(&new_root)?;
+            self.split_child(current, 0)?;
+            self.set_root(current)?;
+        }
+        
+        loop {
+            let mut node = self.read_node(current)?;
+            let mut i = node.keys.partition_point(|&k| k < key);
+            if node.keys.get(i) == Some(&key) {
+                node.values[i] = value;
+                return self.write_node(current, &node);
+            }
+            if node.leaf {
+                node.keys.insert(i, key);
+                node.values.insert(i, value);
+                return self.write_node(current, &node);
+            }
+            
+            if self.read_node(node.children[i])?.is_full() {
+                self.split_child(current, i)?;
+                node = self.read_node(current)?;
+                if node.keys[i] == key {
+                    node.values[i] = value;
+                    return self.write_node(current, &node);
+                }
+                if key > node.keys[i] {
+                    i += 1;
+                }
+            }
+            current = node.children[i];
+        }
+    }
+    
+    /// The value stored for `key`
+    pub fn lookup(&self, key: i64) -> Option<i64> {
+        let (mut current, nodes) = self.header().ok()?;
+        if nodes == 0 {
+            return None;
+        }
+        
+        loop {
+            let node = self.read_node(current).ok()?;
+            let i = node.keys.partition_point(|&k| k < key);
+            if node.keys.get(i) == Some(&key) {
+                return Some(node.values[i]);
+            }
+            if node.leaf {
+                return None;
+            }
+            current = node.children[i];
+        }
+    }
+    
+    /// Every entry in ascending key order
+    pub fn entries(&self) -> Result<Vec<(i64, i64)>, String> {
+        let (root, nodes) = self.header()?;
+        let mut entries = Vec::new();
+        if nodes > 0 {
+            self.collect(root, &mut entries)?;
+        }
+        Ok(entries)
+    }
+    
+    fn collect(&self, index: u64, entries: &mut Vec<(i64, i64)>) -> Result<(), String> {
+        let node = self.read_node(index)?;
+        for i in 0..node.keys.len() {
+            if !node.leaf {
+                self.collect(node.children[i], entries)?;
+            }
+            entries.push((node.keys[i], node.values[i]));
+        }
+        if let Some(&last) = node.children.last() {
+            self.collect(last, entries)?;
+        }
+        Ok(())
+    }
+    
+    /// Move the upper half of the full child `i` of `parent` into a new sibling
+    fn split_child(&mut self, parent: u64, i: usize) -> Result<(), String> {
+        let mut node = self.read_node(parent)?;
+        let mut child = self.read_node(node.children[i])?;
+        
+        let sibling = Node {
+            leaf: child.leaf,
+            keys: child.keys.split_off(T),
+            values: child.values.split_off(T),
+            children: if child.leaf { Vec::new() } else { child.children.split_off(T) },
+        };
+        let median_key = child.keys.pop().unwrap();
+        let median_value = child.values.pop().unwrap();
+        
+        let sibling = self.allocate(&sibling)?;
+        self.write_node(node.children[i], &child)?;
+        node.keys.insert(i, median_key);
+        node.values.insert(i, median_value);
+        node.children.insert(i + 1, sibling);
+        self.write_node(parent, &node)
+    }
+    
+    /// (root node, allocated node count)
+    fn header(&self) -> Result<(u64, u64), String> {
+        let header = self.tape.read_segment(&self.segment, self.base, HEADER_SIZE)?;
+        let root = u64::from_le_bytes(header[0..8].try_into().unwrap());
+        let nodes = u64::from_le_bytes(header[8..16].try_into().unwrap());
+        Ok((root, nodes))
+    }
+    
+    fn set_header(&mut self, root: u64, nodes: u64) -> Result<(), String> {
+        let mut header = [0u8; HEADER_SIZE];
+        header[0..8].copy_from_slice(&root.to_le_bytes());
+        header[8..16].copy_from_slice(&nodes.to_le_bytes());
+        self.tape.write_segment(&self.segment, self.base, &header)
+    }
+    
+    fn set_root(&mut self, root: u64) -> Result<(), String> {
+        let (_, nodes) = self.header()?;
+        self.set_header(root, nodes)
+    }
+    
+    /// Store `node` in the next free slot and return its number
+    fn allocate(&mut self, node: &Node) -> Result<u64, String> {
+        let (root, nodes) = self.header()?;
+        if nodes as usize >= self.capacity {
+            return Err(format!("B-tree in segment '{}' is full ({} nodes)", self.segment, self.capacity));
+        }
+        
+        self.write_node(nodes, node)?;
+        self.set_header(root, nodes + 1)?;
+        Ok(nodes)
+    }
+    
+    fn node_offset(&self, index: u64) -> i64 {
+        self.base + (HEADER_SIZE + index as usize * NODE_SIZE) as i64
+    }
+    
+    fn read_node(&self, index: u64) -> Result<Node, String> {
+        let bytes = self.tape.read_segment(&self.segment, self.node_offset(index), NODE_SIZE)?;
+        Node::decode(&bytes)
+    }
+    
+    fn write_node(&mut self, index: u64, node: &Node) -> Result<(), String> {
+        self.tape.write_segment(&self.segment, self.node_offset(index), &node.encode())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tape::{Index, SegmentType, StateReader, StateWriter};
+    
+    /// A tape with a segment room enough for `nodes` B-tree nodes after the header
+    fn index_tape(nodes: usize) -> SegmentedTape {
+        let mut tape = SegmentedTape::new();
+        tape.create_segment("idx".to_string(), HEADER_SIZE + nodes * NODE_SIZE, SegmentType::Index).unwrap();
+        tape
+    }
+    
+    /// 0..n in a scrambled but deterministic order
+    fn shuffled(n: i64) -> Vec<i64> {
+        let mut keys: Vec<i64> = (0..n).collect();
+        let mut state = 0x2545F4914F6CDD1Du64;
+        for i in (1..keys.len()).rev() {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            keys.swap(i, (state % (i as u64 + 1)) as usize);
+        }
+        keys
+    }
+
+    #[test]
+    fn test_random_inserts_lookup_in_order() {
+        let mut tape = index_tape(512);
+        let mut tree = BTreeIndex::new(&mut tape, "idx", 0).unwrap();
+        assert_eq!(tree.lookup(5), None);
+        
+        for key in shuffled(1000) {
+            tree.insert(key * 2, -key).unwrap();
+        }
+        
+        for key in 0..1000 {
+            assert_eq!(tree.lookup(key * 2), Some(-key));
+            assert_eq!(tree.lookup(key * 2 + 1), None);
+        }
+        let entries = tree.entries().unwrap();
+        assert_eq!(entries.len(), 1000);
+        assert!(entries.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    }
+
+    #[test]
+    fn test_insert_replaces_existing_value() {
+        let mut tape = index_tape(64);
+        let mut tree = BTreeIndex::new(&mut tape, "idx", 0).unwrap();
+        for key in shuffled(100) {
+            tree.insert(key, key).unwrap();
+        }
+        for key in shuffled(100) {
+            tree.insert(key, key + 1000).unwrap();
+        }
+        
+        assert_eq!(tree.entries().unwrap().len(), 100);
+        assert_eq!(tree.lookup(42), Some(1042));
+    }
+
+    #[test]
+    fn test_full_segment_is_an_error() {
+        let mut tape = index_tape(2);
+        let mut tree = BTreeIndex::new(&mut tape, "idx", 0).unwrap();
+        let result = (0..100).try_for_each(|key| tree.insert(key, key));
+        assert!(result.unwrap_err().contains("is full"));
+    }
+
+    #[test]
+    fn test_index_survives_serialization() {
+        let mut tape = index_tape(64);
+        tape.segments.get_mut("idx").unwrap().indices.push(Index {
+            name: "by_id".to_string(),
+            index_type: IndexType::BTree,
+            fields: vec!["id".to_string()],
+            root_position: 0,
+        });
+        let mut tree = BTreeIndex::open(&mut tape, "idx", "by_id").unwrap();
+        for key in shuffled(200) {
+            tree.insert(key, key * key).unwrap();
+        }
+        
+        let mut w = StateWriter::new();
+        tape.write_state(&mut w);
+        let bytes = w.into_bytes();
+        let mut restored = SegmentedTape::read_state(&mut StateReader::new(&bytes)).unwrap();
+        
+        let tree = BTreeIndex::open(&mut restored, "idx", "by_id").unwrap();
+        assert_eq!(tree.lookup(150), Some(22500));
+        assert_eq!(tree.entries().unwrap().len(), 200);
+    }
+}
@@ -1,26 +1,53 @@
 //! Core tape implementation with reversibility support
 
+use super::segment::Segment;
+use std::cell::Cell;
 use std::collections::{HashMap, BTreeMap};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Orders writes across every tape in the process, including forked copies
+static WRITE_CLOCK: AtomicU64 = AtomicU64::new(1);
 
 /// The fundamental infinite tape abstraction
 #[derive(Clone)]
 pub struct Tape {
     /// Tape data organized in pages for efficiency
-    pages: BTreeMap<i64, Page>,
+    pub(super) pages: BTreeMap<i64, Page>,
     /// Current head position
-    head: i64,
+    pub(super) head: i64,
     /// Named marks for quick seeking
-    marks: HashMap<String, i64>,
+    pub(super) marks: HashMap<String, i64>,
     /// History trail for reversibility
-    trail: Trail,
+    pub(super) trail: Trail,
+    /// One past the highest position ever written
+    pub(super) high_water: i64,
+    /// Largest combined write, if small sequential writes are combined
+    pub(super) write_combining: Option<usize>,
+    /// Most trail operations kept for reversal, if bounded
+    pub(super) history_limit: Option<usize>,
+    /// Trail ops below this index have been observed and must not be extended
+    pub(super) sealed: Cell<usize>,
 }
 
 /// A 4KB page of tape data
+///
+/// Clones share the page data; the first write to a shared page copies it.
 #[derive(Clone)]
 pub struct Page {
-    pub data: Box<[u8; 4096]>,
-    /// Copy-on-write reference count
-    pub cow_refs: usize,
+    pub data: Arc<[u8; 4096]>,
+}
+
+/// Tape contents captured by `Tape::snapshot`
+///
+/// Pages are shared with the tape until either side writes to them.
+#[derive(Clone)]
+pub struct TapeSnapshot {
+    pages: BTreeMap<i64, Page>,
+    head: i64,
+    marks: HashMap<String, i64>,
+    high_water: i64,
+    trail_len: usize,
 }
 
 /// History trail for reversibility
@@ -30,6 +57,11 @@ pub struct Trail {
     pub operations: Vec<TrailOp>,
     /// Checkpoints for quick rewind
     pub checkpoints: HashMap<String, usize>,
+    /// Operations dropped from the front by the history limit
+    ///
+    /// Trail positions (lengths and checkpoints) still count them, so they
+    /// stay stable as old operations are evicted.
+    pub evicted: usize,
 }
 
 #[derive(Clone, Debug)]
@@ -37,7 +69,11 @@ pub enum TrailOp {
     Write { 
         pos: i64, 
         old: Vec<u8>, 
-        new: Vec<u8> 
+        new: Vec<u8>,
+        /// When the write happened; comparable between tapes forked from each other
+        stamp: u64,
+        /// High-water mark before the write, restored when it is undone
+        high_water: i64,
     },
     Seek { 
         old_pos: i64, 
@@ -58,6 +94,22 @@ pub enum TrailOp {
         old_data: Vec<u8>,
         new_data: Vec<u8>,
     },
+    /// A segment grown in place or moved by `grow_segment`
+    SegmentResize {
+        name: String,
+        old_start: i64,
+        old_size: usize,
+        new_start: i64,
+        new_size: usize,
+    },
+    /// A segment removed by `delete_segment`, kept whole so it can be restored
+    SegmentDelete {
+        segment: Segment,
+    },
+    /// All-zero pages dropped by `compact`
+    Compact {
+        pages: Vec<i64>,
+    },
 }
 
 impl Tape {
@@ -67,17 +119,50 @@ impl Tape {
             head: 0,
             marks: HashMap::new(),
             trail: Trail::new(),
+            high_water: 0,
+            write_combining: None,
+            history_limit: None,
+            sealed: Cell::new(0),
+        }
+    }
+
+    /// Combine contiguous writes into single trail ops of up to `capacity` bytes
+    ///
+    /// A write that continues the last one (and a seek to where it ended) is
+    /// folded into its trail op, so the whole run undoes as one unit. A run
+    /// never crosses a page boundary, and anything that observes or rewinds
+    /// the trail closes it, so instruction-level reversal is unaffected.
+    pub fn set_write_combining(&mut self, capacity: Option<usize>) {
+        self.write_combining = capacity;
+        self.seal();
+    }
+
+    /// Keep only the most recent `max_ops` trail operations reversible
+    ///
+    /// Older operations are evicted in batches, so the trail holds at most a
+    /// quarter more than `max_ops`. Checkpoints that fall outside the window
+    /// can no longer be rewound to. `None` keeps the whole trail, and `Some(0)`
+    /// records nothing at all.
+    pub fn set_history_limit(&mut self, max_ops: Option<usize>) {
+        self.history_limit = max_ops;
+        if let Some(max_ops) = max_ops {
+            self.evict_to(max_ops);
         }
     }
 
     /// Read bytes at current position
     pub fn read(&self, len: usize) -> Vec<u8> {
+        self.read_at(self.head, len)
+    }
+
+    /// Read bytes at an arbitrary position without touching the head or trail
+    pub fn read_at(&self, start: i64, len: usize) -> Vec<u8> {
         let mut result = Vec::with_capacity(len);
-        let mut pos = self.head;
+        let mut pos = start;
         
         while result.len() < len {
-            let page_idx = pos / 4096;
-            let page_offset = (pos % 4096) as usize;
+            let page_idx = pos.div_euclid(4096);
+            let page_offset = pos.rem_euclid(4096) as usize;
             
             if let Some(page) = self.pages.get(&page_idx) {
                 let available = (4096 - page_offset).min(len - result.len());
@@ -87,7 +172,7 @@ impl Tape {
                 pos += available as i64;
             } else {
                 // Uninitialized tape reads as zeros
-                let zeros_needed = (len - result.len()).min(4096);
+                let zeros_needed = (4096 - page_offset).min(len - result.len());
                 result.resize(result.len() + zeros_needed, 0);
                 pos += zeros_needed as i64;
             }
@@ -98,53 +183,119 @@ impl Tape {
 
     /// Write bytes at current position with COW
     pub fn write(&mut self, data: &[u8]) {
-        let old_data = self.read(data.len());
-        
-        // Record for reversibility
-        self.trail.operations.push(TrailOp::Write {
-            pos: self.head,
-            old: old_data,
-            new: data.to_vec(),
-        });
+        if self.history_limit == Some(0) {
+            // Nothing is kept to undo, so skip capturing the old bytes
+            self.trail.evicted += 1;
+        } else {
+            let old_data = self.read(data.len());
+            
+            // Record for reversibility
+            if !self.combine_write(&old_data, data) {
+                self.record(TrailOp::Write {
+                    pos: self.head,
+                    old: old_data,
+                    new: data.to_vec(),
+                    stamp: next_write_stamp(),
+                    high_water: self.high_water,
+                });
+            }
+        }
+        self.high_water = self.high_water.max(self.head + data.len() as i64);
         
         let mut pos = self.head;
         let mut written = 0;
         
         while written < data.len() {
-            let page_idx = pos / 4096;
-            let page_offset = (pos % 4096) as usize;
+            let page_idx = pos.div_euclid(4096);
+            let page_offset = pos.rem_euclid(4096) as usize;
             let to_write = (data.len() - written).min(4096 - page_offset);
             
-            // Copy-on-write logic
-            let page = self.pages.entry(page_idx).or_insert_with(|| {
-                Page {
-                    data: Box::new([0; 4096]),
-                    cow_refs: 0,
-                }
-            });
-            
-            if page.cow_refs > 0 {
-                // Need to copy before writing
-                let mut new_data = page.data.clone();
-                new_data[page_offset..page_offset + to_write]
-                    .copy_from_slice(&data[written..written + to_write]);
-                *page = Page {
-                    data: new_data,
-                    cow_refs: 0,
-                };
-            } else {
-                page.data[page_offset..page_offset + to_write]
-                    .copy_from_slice(&data[written..written + to_write]);
-            }
+            // Copy-on-write: a page shared with a snapshot or clone is copied first
+            let page = self.pages.entry(page_idx).or_insert_with(Page::zeroed);
+            Arc::make_mut(&mut page.data)[page_offset..page_offset + to_write]
+                .copy_from_slice(&data[written..written + to_write]);
             
             written += to_write;
             pos += to_write as i64;
         }
     }
 
+    /// Extend the open combined write with `new` at the head, if it continues it
+    fn combine_write(&mut self, old: &[u8], new: &[u8]) -> bool {
+        let Some(capacity) = self.write_combining else {
+            return false;
+        };
+        if self.trail.operations.len() <= self.sealed.get() {
+            return false;
+        }
+        
+        let head = self.head;
+        match self.trail.operations.last_mut() {
+            Some(TrailOp::Write { pos, old: run_old, new: run_new, stamp, .. })
+                if *pos + run_new.len() as i64 == head
+                    && run_new.len() + new.len() <= capacity
+                    && pos.div_euclid(4096) == (head + new.len() as i64 - 1).div_euclid(4096) =>
+            {
+                run_old.extend_from_slice(old);
+                run_new.extend_from_slice(new);
+                // A combined run counts as written when it was last extended
+                *stamp = next_write_stamp();
+                true
+            }
+            _ => false,
+        }
+    }
+    
+    /// Whether `pos` is where the open combined write ends
+    fn continues_run(&self, pos: i64) -> bool {
+        self.write_combining.is_some()
+            && self.trail.operations.len() > self.sealed.get()
+            && matches!(self.trail.operations.last(),
+                Some(TrailOp::Write { pos: start, new, .. }) if start + new.len() as i64 == pos)
+    }
+    
+    /// Close the open combined write so later writes start a new trail op
+    fn seal(&self) {
+        self.sealed.set(self.trail.operations.len());
+    }
+
+    /// Append an operation to the trail, evicting old ones past the history limit
+    fn record(&mut self, op: TrailOp) {
+        if self.history_limit == Some(0) {
+            self.trail.evicted += 1;
+            return;
+        }
+        self.trail.operations.push(op);
+        if let Some(max_ops) = self.history_limit
+            && self.trail.operations.len() > max_ops + max_ops / 4
+        {
+            self.evict_to(max_ops);
+        }
+    }
+
+    /// Drop the oldest operations until at most `max_ops` remain
+    fn evict_to(&mut self, max_ops: usize) {
+        let excess = self.trail.operations.len().saturating_sub(max_ops);
+        if excess > 0 {
+            self.trail.operations.drain(..excess);
+            self.trail.evicted += excess;
+            self.sealed.set(self.sealed.get().saturating_sub(excess));
+        }
+    }
+
+    /// Trail position of the oldest operation that can still be undone
+    pub fn trail_start(&self) -> usize {
+        self.trail.evicted
+    }
+
     /// Seek to position
     pub fn seek(&mut self, pos: i64) {
-        self.trail.operations.push(TrailOp::Seek {
+        // Undoing the combined write already returns the head to its start
+        if self.continues_run(pos) {
+            self.head = pos;
+            return;
+        }
+        self.record(TrailOp::Seek {
             old_pos: self.head,
             new_pos: pos,
         });
@@ -158,7 +309,7 @@ impl Tape {
 
     /// Mark current position with a label
     pub fn mark(&mut self, label: String) {
-        self.trail.operations.push(TrailOp::Mark {
+        self.record(TrailOp::Mark {
             label: label.clone(),
             pos: self.head,
         });
@@ -174,41 +325,186 @@ impl Tape {
         Ok(())
     }
 
+    /// Drop all-zero pages lying entirely within `[start, end)`
+    ///
+    /// Unallocated pages already read as zeros, so this only saves memory.
+    /// Returns how many pages were reclaimed.
+    pub fn compact(&mut self, start: i64, end: i64) -> usize {
+        let first = start.div_euclid(4096) + (start.rem_euclid(4096) != 0) as i64;
+        let last = end.div_euclid(4096);
+        if first >= last {
+            return 0;
+        }
+        
+        let pages: Vec<i64> = self.pages.range(first..last)
+            .filter(|(_, page)| page.data.iter().all(|&b| b == 0))
+            .map(|(&idx, _)| idx)
+            .collect();
+        for idx in &pages {
+            self.pages.remove(idx);
+        }
+        
+        let reclaimed = pages.len();
+        self.record(TrailOp::Compact { pages });
+        reclaimed
+    }
+
+    /// Number of allocated pages
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Byte ranges `[start, end)` covered by allocated pages, in ascending
+    /// order, with adjacent pages merged into one range
+    ///
+    /// A page counts once it is allocated, even if it holds only zeros; use
+    /// `compact` first to leave those out.
+    pub fn written_ranges(&self) -> impl Iterator<Item = (i64, i64)> + '_ {
+        let mut pages = self.pages.keys().peekable();
+        std::iter::from_fn(move || {
+            let first = *pages.next()?;
+            let mut last = first;
+            while pages.next_if(|&&idx| idx == last + 1).is_some() {
+                last += 1;
+            }
+            Some((first * 4096, (last + 1) * 4096))
+        })
+    }
+
+    /// Byte ranges `[start, end)` where this tape and `other` differ, in order
+    ///
+    /// Only pages allocated on either side are visited, and pages the two
+    /// still share since a fork are skipped without comparing. A page missing
+    /// on one side compares as zeros.
+    pub fn diff_ranges(&self, other: &Tape) -> Vec<(i64, i64)> {
+        let zero = Page::zeroed();
+        let mut indices: Vec<i64> = self.pages.keys().chain(other.pages.keys()).copied().collect();
+        indices.sort_unstable();
+        indices.dedup();
+        
+        let mut ranges: Vec<(i64, i64)> = Vec::new();
+        for idx in indices {
+            let ours = self.pages.get(&idx).unwrap_or(&zero);
+            let theirs = other.pages.get(&idx).unwrap_or(&zero);
+            if Arc::ptr_eq(&ours.data, &theirs.data) {
+                continue;
+            }
+            
+            for (offset, (a, b)) in ours.data.iter().zip(theirs.data.iter()).enumerate() {
+                if a == b {
+                    continue;
+                }
+                let pos = idx * 4096 + offset as i64;
+                match ranges.last_mut() {
+                    Some(range) if range.1 == pos => range.1 += 1,
+                    _ => ranges.push((pos, pos + 1)),
+                }
+            }
+        }
+        ranges
+    }
+
+    /// An independent copy of this tape that shares page memory with it
+    ///
+    /// Cloning is equivalent; pages are only duplicated when either tape
+    /// first writes to them, so forking a large tape is cheap.
+    pub fn fork(&self) -> Tape {
+        self.clone()
+    }
+
+    /// Capture the contents, head and marks without copying any page data
+    pub fn snapshot(&self) -> TapeSnapshot {
+        TapeSnapshot {
+            pages: self.pages.clone(),
+            head: self.head,
+            marks: self.marks.clone(),
+            high_water: self.high_water,
+            trail_len: self.trail_len(),
+        }
+    }
+
+    /// Return to a snapshot in one step instead of replaying the trail
+    ///
+    /// Trail operations and checkpoints recorded since the snapshot are dropped,
+    /// since restoring has undone them; earlier checkpoints remain valid.
+    pub fn restore(&mut self, snapshot: TapeSnapshot) {
+        self.pages = snapshot.pages;
+        self.head = snapshot.head;
+        self.marks = snapshot.marks;
+        self.high_water = snapshot.high_water;
+        self.trail.operations.truncate(snapshot.trail_len.saturating_sub(self.trail.evicted));
+        self.trail.checkpoints.retain(|_, len| *len <= snapshot.trail_len);
+        self.seal();
+    }
+
     /// Create a checkpoint
     pub fn checkpoint(&mut self, name: String) {
-        self.trail.checkpoints.insert(name, self.trail.operations.len());
+        self.seal();
+        self.trail.checkpoints.insert(name, self.trail_len());
     }
 
     /// Rewind to checkpoint
     pub fn rewind(&mut self, name: &str) -> Result<(), String> {
+        self.rewind_with(name, |_, _| {})
+    }
+    
+    /// Trail length at a checkpoint that can still be rewound to
+    ///
+    /// Fails if the checkpoint is unknown or was evicted by the history limit.
+    pub fn checkpoint_position(&self, name: &str) -> Result<usize, String> {
         let checkpoint_pos = *self.trail.checkpoints.get(name)
             .ok_or_else(|| format!("Unknown checkpoint: {}", name))?;
+        if checkpoint_pos < self.trail.evicted {
+            return Err(format!(
+                "Checkpoint '{}' was evicted by the history limit and can no longer be rewound",
+                name
+            ));
+        }
+        Ok(checkpoint_pos)
+    }
+    
+    /// Rewind to checkpoint, passing each operation to `on_undo` before undoing it
+    ///
+    /// Segment operations are only bookkeeping to the tape itself; this is how
+    /// `SegmentedTape` reverses them in trail order, interleaved with the writes.
+    pub fn rewind_with(&mut self, name: &str, mut on_undo: impl FnMut(&mut Tape, &TrailOp)) -> Result<(), String> {
+        let checkpoint_pos = self.checkpoint_position(name)?;
         
         // Undo operations back to checkpoint
-        while self.trail.operations.len() > checkpoint_pos {
+        while self.trail.evicted + self.trail.operations.len() > checkpoint_pos {
             if let Some(op) = self.trail.operations.pop() {
+                on_undo(self, &op);
                 self.undo_operation(op);
             }
         }
+        self.seal();
         
         Ok(())
     }
 
     /// Rewind last n operations
     pub fn rewind_n(&mut self, n: usize) {
+        self.rewind_n_with(n, |_, _| {});
+    }
+    
+    /// Rewind last n operations, passing each to `on_undo` before undoing it
+    pub fn rewind_n_with(&mut self, n: usize, mut on_undo: impl FnMut(&mut Tape, &TrailOp)) {
         for _ in 0..n {
             if let Some(op) = self.trail.operations.pop() {
+                on_undo(self, &op);
                 self.undo_operation(op);
             }
         }
+        self.seal();
     }
 
     fn undo_operation(&mut self, op: TrailOp) {
         match op {
-            TrailOp::Write { pos, old, .. } => {
+            TrailOp::Write { pos, old, high_water, .. } => {
                 self.head = pos;
                 // Write old data without recording to trail
                 self.write_raw(&old);
+                self.high_water = high_water;
             }
             TrailOp::Seek { old_pos, .. } => {
                 self.head = old_pos;
@@ -216,33 +512,40 @@ impl Tape {
             TrailOp::Mark { label, .. } => {
                 self.marks.remove(&label);
             }
-            TrailOp::SegmentCreate { .. } => {
-                // Segment removal handled by SegmentedTape
+            TrailOp::SegmentCreate { .. } |
+            TrailOp::SegmentModify { .. } |
+            TrailOp::SegmentResize { .. } |
+            TrailOp::SegmentDelete { .. } => {
+                // Reversed by SegmentedTape through `rewind_with`
             }
-            TrailOp::SegmentModify { .. } => {
-                // Segment modification handled by SegmentedTape
+            TrailOp::Compact { pages } => {
+                for idx in pages {
+                    self.pages.insert(idx, Page::zeroed());
+                }
             }
         }
     }
 
+    /// Write `data` at `pos` without recording it or moving the head
+    pub(super) fn write_untracked(&mut self, pos: i64, data: &[u8]) {
+        let head = self.head;
+        self.head = pos;
+        self.write_raw(data);
+        self.head = head;
+    }
+    
     fn write_raw(&mut self, data: &[u8]) {
         // Write without recording to trail (for undo operations)
         let mut pos = self.head;
         let mut written = 0;
         
         while written < data.len() {
-            let page_idx = pos / 4096;
-            let page_offset = (pos % 4096) as usize;
+            let page_idx = pos.div_euclid(4096);
+            let page_offset = pos.rem_euclid(4096) as usize;
             let to_write = (data.len() - written).min(4096 - page_offset);
             
-            let page = self.pages.entry(page_idx).or_insert_with(|| {
-                Page {
-                    data: Box::new([0; 4096]),
-                    cow_refs: 0,
-                }
-            });
-            
-            page.data[page_offset..page_offset + to_write]
+            let page = self.pages.entry(page_idx).or_insert_with(Page::zeroed);
+            Arc::make_mut(&mut page.data)[page_offset..page_offset + to_write]
                 .copy_from_slice(&data[written..written + to_write]);
             
             written += to_write;
@@ -255,14 +558,35 @@ impl Tape {
         self.head
     }
 
+    /// One past the highest populated position (the tape's logical end)
+    pub fn high_water(&self) -> i64 {
+        self.high_water
+    }
+
     /// Get trail length (for debugging/testing)
     pub fn trail_len(&self) -> usize {
-        self.trail.operations.len()
+        self.seal();
+        self.trail.evicted + self.trail.operations.len()
+    }
+    
+    /// Trail operations recorded since the trail had length `len`
+    ///
+    /// Fails if some of them were evicted by the history limit.
+    pub fn trail_since(&self, len: usize) -> Result<&[TrailOp], String> {
+        self.seal();
+        if len < self.trail.evicted {
+            return Err(format!(
+                "Trail operations since position {} were evicted by the history limit (oldest kept is {})",
+                len, self.trail.evicted
+            ));
+        }
+        let start = len - self.trail.evicted;
+        Ok(&self.trail.operations[start.min(self.trail.operations.len())..])
     }
     
     /// Add operation to trail (for segment operations)
     pub fn add_trail_op(&mut self, op: TrailOp) {
-        self.trail.operations.push(op);
+        self.record(op);
     }
     
     /// Get a mark position by label
@@ -271,15 +595,38 @@ impl Tape {
     }
 }
 
+impl Page {
+    fn zeroed() -> Self {
+        Page { data: Arc::new([0; 4096]) }
+    }
+}
+
 impl Trail {
     pub fn new() -> Self {
         Trail {
             operations: Vec::new(),
             checkpoints: HashMap::new(),
+            evicted: 0,
         }
     }
 }
 
+impl Default for Trail {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A fresh stamp for `TrailOp::Write`, later than every stamp handed out so far
+fn next_write_stamp() -> u64 {
+    WRITE_CLOCK.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Keep the write clock ahead of a stamp read back from a saved state
+pub(super) fn observe_write_stamp(stamp: u64) {
+    WRITE_CLOCK.fetch_max(stamp + 1, Ordering::Relaxed);
+}
+
 impl Default for Tape {
     fn default() -> Self {
         Self::new()
@@ -348,6 +695,224 @@ mod tests {
         assert_eq!(tape.read(3), vec![1, 2, 3]);
     }
 
+    #[test]
+    fn test_history_limit() {
+        let mut tape = Tape::new();
+        tape.set_history_limit(Some(100));
+        tape.checkpoint("old".to_string());
+        
+        for i in 0..1000i64 {
+            tape.seek(i * 8);
+            tape.write(&i.to_le_bytes());
+            assert!(tape.trail.operations.len() <= 125);
+        }
+        assert_eq!(tape.trail_len(), 2000);
+        
+        let err = tape.rewind("old").unwrap_err();
+        assert!(err.contains("evicted by the history limit"), "{}", err);
+        
+        // The most recent operations remain reversible
+        tape.checkpoint("recent".to_string());
+        tape.seek(0);
+        tape.write(&[0xFF; 8]);
+        tape.rewind("recent").unwrap();
+        assert_eq!(tape.read_at(0, 8), 0i64.to_le_bytes());
+        tape.rewind_n(100);
+        assert_eq!(tape.read_at(950 * 8, 8), vec![0; 8]);
+        
+        assert!(tape.trail_since(0).is_err());
+        assert_eq!(tape.trail_since(tape.trail_start()).unwrap().len(), tape.trail.operations.len());
+        assert_eq!(tape.read_at(949 * 8, 8), 949i64.to_le_bytes());
+    }
+
+    #[test]
+    fn test_write_combining() {
+        let mut tape = Tape::new();
+        tape.set_write_combining(Some(64));
+        let initial = tape.trail_len();
+        
+        for i in 0..40u8 {
+            tape.seek(100 + i as i64);
+            tape.write(&[i + 1]);
+            if i == 20 {
+                // Reads see bytes written so far in the open run
+                assert_eq!(tape.read_at(100, 3), vec![1, 2, 3]);
+            }
+        }
+        
+        // One seek into the run plus one combined write
+        assert_eq!(tape.trail_len() - initial, 2);
+        assert_eq!(tape.read_at(139, 1), vec![40]);
+        
+        // A run never crosses into the next page
+        for i in 0..8 {
+            tape.seek(4090 + i);
+            tape.write(&[7]);
+        }
+        assert_eq!(tape.trail_len() - initial, 5);
+        
+        tape.rewind_n(tape.trail_len() - initial);
+        assert_eq!(tape.position(), 0);
+        assert_eq!(tape.read_at(100, 40), vec![0; 40]);
+        assert_eq!(tape.read_at(4090, 8), vec![0; 8]);
+    }
+
+    #[test]
+    fn test_compact_zero_pages() {
+        let mut tape = Tape::new();
+        tape.seek(4096);
+        tape.write(&vec![0xFF; 4 * 4096]);
+        tape.write(&vec![0; 4 * 4096]);
+        tape.seek(5 * 4096 + 10);
+        tape.write(&[1]);
+        assert_eq!(tape.pages.len(), 5);
+        
+        // Page 4 is zero but only partly inside the range, so it stays
+        let reclaimed = tape.compact(4096, 4 * 4096 + 100);
+        assert_eq!(reclaimed, 3);
+        assert_eq!(tape.pages.len(), 2);
+        assert_eq!(tape.read_at(4096, 4 * 4096), vec![0; 4 * 4096]);
+        assert_eq!(tape.read_at(5 * 4096 + 10, 1), vec![1]);
+        
+        tape.rewind_n(1);
+        assert_eq!(tape.page_count(), 5);
+        assert_eq!(tape.read_at(4096, 4 * 4096), vec![0; 4 * 4096]);
+    }
+
+    #[test]
+    fn test_undo_restores_high_water() {
+        let mut tape = Tape::new();
+        tape.set_write_combining(Some(64));
+        tape.write(b"ab");
+        tape.advance(2);
+        tape.write(b"cd");
+        tape.advance(2);
+        assert_eq!(tape.high_water(), 4);
+        
+        tape.seek(4096);
+        tape.write(b"far");
+        assert_eq!(tape.high_water(), 4099);
+        
+        tape.rewind_n(2);
+        assert_eq!(tape.high_water(), 4);
+        
+        // The combined run is undone as one write
+        tape.rewind_n(tape.trail_len());
+        assert_eq!(tape.high_water(), 0);
+    }
+
+    #[test]
+    fn test_snapshot_restore_shares_pages() {
+        let mut tape = Tape::new();
+        let original: Vec<u8> = (0..8 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+        tape.write(&original);
+        tape.checkpoint("before".to_string());
+        
+        let snapshot = tape.snapshot();
+        assert!(Arc::ptr_eq(&tape.pages[&0].data, &snapshot.pages[&0].data));
+        
+        // Many small writes after the snapshot copy only the pages they touch
+        for i in 0..10_000i64 {
+            tape.seek(i * 64);
+            tape.write(&[0xFF; 8]);
+        }
+        tape.checkpoint("after".to_string());
+        assert!(!Arc::ptr_eq(&tape.pages[&0].data, &snapshot.pages[&0].data));
+        assert!(Arc::ptr_eq(&tape.pages[&1000].data, &snapshot.pages[&1000].data));
+        
+        tape.restore(snapshot);
+        assert_eq!(tape.read_at(0, original.len()), original);
+        assert_eq!(tape.position(), 0);
+        
+        // The trail is back where it was, so older checkpoints still rewind
+        assert!(tape.rewind("after").is_err());
+        tape.rewind("before").unwrap();
+        assert_eq!(tape.read_at(0, original.len()), original);
+    }
+
+    #[test]
+    fn test_fork_duplicates_only_written_pages() {
+        let mut tape = Tape::new();
+        tape.write(&vec![7u8; 256 * 4096]);
+        
+        let mut fork = tape.fork();
+        fork.seek(10 * 4096 + 5);
+        fork.write(&[1]);
+        
+        let duplicated: Vec<i64> = tape.pages.iter()
+            .filter(|(idx, page)| !Arc::ptr_eq(&page.data, &fork.pages[idx].data))
+            .map(|(&idx, _)| idx)
+            .collect();
+        assert_eq!(duplicated, vec![10]);
+        assert_eq!(tape.read_at(10 * 4096 + 5, 1), vec![7]);
+        assert_eq!(fork.read_at(10 * 4096 + 5, 1), vec![1]);
+    }
+
+    #[test]
+    fn test_read_at_leaves_head_and_trail_alone() {
+        let mut tape = Tape::new();
+        tape.seek(4090);
+        tape.write(b"spans two pages");
+        tape.seek(7);
+        let trail_len = tape.trail_len();
+        
+        let mut seeked = tape.clone();
+        seeked.seek(4088);
+        assert_eq!(tape.read_at(4088, 20), seeked.read(20));
+        assert_eq!(tape.read_at(4090, 15), b"spans two pages");
+        assert_eq!(tape.position(), 7);
+        assert_eq!(tape.trail_len(), trail_len);
+    }
+
+    #[test]
+    fn test_negative_positions() {
+        let mut tape = Tape::new();
+        tape.seek(-5);
+        tape.write(b"across zero");
+        
+        assert_eq!(tape.read_at(-5, 11), b"across zero");
+        assert_eq!(tape.read_at(-4097, 3), vec![0, 0, 0]);
+        tape.rewind_n(1);
+        assert_eq!(tape.read_at(-5, 11), vec![0; 11]);
+    }
+
+    #[test]
+    fn test_written_ranges_coalesce_adjacent_pages() {
+        let mut tape = Tape::new();
+        assert_eq!(tape.written_ranges().count(), 0);
+        
+        tape.write(b"page zero");
+        tape.seek(4096 + 10);
+        tape.write(b"page one");
+        tape.seek(100 * 4096);
+        tape.write(b"page one hundred");
+        
+        let ranges: Vec<(i64, i64)> = tape.written_ranges().collect();
+        assert_eq!(ranges, vec![(0, 2 * 4096), (100 * 4096, 101 * 4096)]);
+    }
+
+    #[test]
+    fn test_diff_ranges() {
+        let mut tape = Tape::new();
+        tape.write(b"shared");
+        tape.seek(4096 * 3);
+        tape.write(b"same");
+        
+        let mut fork = tape.fork();
+        assert!(tape.diff_ranges(&fork).is_empty());
+        
+        // Rewriting identical bytes unshares the page but is no difference
+        fork.seek(4096 * 3);
+        fork.write(b"same");
+        fork.seek(4094);
+        fork.write(b"span");
+        fork.seek(9000);
+        fork.write(&[0, 1, 0, 1]);
+        
+        assert_eq!(tape.diff_ranges(&fork), vec![(4094, 4098), (9001, 9002), (9003, 9004)]);
+        assert_eq!(fork.diff_ranges(&tape), tape.diff_ranges(&fork));
+    }
+
     #[test]
     fn test_large_write_spanning_pages() {
         let mut tape = Tape::new();
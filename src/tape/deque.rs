@@ -0,0 +1,220 @@
+//! Double-ended queue stored in a tape segment
+//!
+//! The segment begins with a 16-byte header holding the head slot and the
+//! element count, followed by a circular array of fixed-size slots. Keeping
+//! the count (rather than a tail index) is what tells a full deque from an
+//! empty one when head and tail meet. All updates go through
+//! `write_segment`, so they are recorded on the trail and stay reversible.
+
+use super::segment::SegmentedTape;
+
+const HEADER_SIZE: usize = 16;
+
+/// Borrowing deque view over a segment of fixed-size elements
+pub struct TapeDeque<'a> {
+    tape: &'a mut SegmentedTape,
+    segment: String,
+    elem_size: usize,
+    capacity: usize,
+}
+
+impl<'a> TapeDeque<'a> {
+    /// Open the deque stored in `segment`; a freshly created segment is an empty deque
+    pub fn new(tape: &'a mut SegmentedTape, segment: &str, elem_size: usize) -> Result<Self, String> {
+        let size = tape.get_segment(segment)
+            .ok_or_else(|| format!("Unknown segment: {}", segment))?
+            .size;
+        if elem_size == 0 {
+            return Err("Deque elements must be at least one byte".to_string());
+        }
+        
+        let capacity = size.saturating_sub(HEADER_SIZE) / elem_size;
+        if capacity == 0 {
+            return Err(format!("Segment '{}' is too small for a deque", segment));
+        }
+        
+        Ok(TapeDeque {
+            tape,
+            segment: segment.to_string(),
+            elem_size,
+            capacity,
+        })
+    }
+    
+    /// Maximum number of elements
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    
+    pub fn len(&self) -> usize {
+        self.header().1
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    
+    pub fn is_full(&self) -> bool {
+        self.len() == self.capacity
+    }
+    
+    pub fn push_back(&mut self, elem: &[u8]) -> Result<(), String> {
+        let (head, len) = self.header();
+        self.check_push(elem, len)?;
+        
+        self.write_slot((head + len) % self.capacity, elem)?;
+        self.set_header(head, len + 1)
+    }
+    
+    pub fn push_front(&mut self, elem: &[u8]) -> Result<(), String> {
+        let (head, len) = self.header();
+        self.check_push(elem, len)?;
+        
+        let head = (head + self.capacity - 1) % self.capacity;
+        self.write_slot(head, elem)?;
+        self.set_header(head, len + 1)
+    }
+    
+    pub fn pop_front(&mut self) -> Result<Option<Vec<u8>>, String> {
+        let (head, len) = self.header();
+        if len == 0 {
+            return Ok(None);
+        }
+        
+        let elem = self.read_slot(head)?;
+        self.set_header((head + 1) % self.capacity, len - 1)?;
+        Ok(Some(elem))
+    }
+    
+    pub fn pop_back(&mut self) -> Result<Option<Vec<u8>>, String> {
+        let (head, len) = self.header();
+        if len == 0 {
+            return Ok(None);
+        }
+        
+        let elem = self.read_slot((head + len - 1) % self.capacity)?;
+        self.set_header(head, len - 1)?;
+        Ok(Some(elem))
+    }
+    
+    fn check_push(&self, elem: &[u8], len: usize) -> Result<(), String> {
+        if elem.len() != self.elem_size {
+            return Err(format!("Expected {}-byte element, got {}", self.elem_size, elem.len()));
+        }
+        if len == self.capacity {
+            return Err(format!("Deque full ({} elements)", self.capacity));
+        }
+        Ok(())
+    }
+    
+    /// (head slot, element count)
+    fn header(&self) -> (usize, usize) {
+        let header = self.tape.read_segment(&self.segment, 0, HEADER_SIZE)
+            .expect("deque segment exists");
+        let head = u64::from_le_bytes(header[0..8].try_into().unwrap()) as usize;
+        let len = u64::from_le_bytes(header[8..16].try_into().unwrap()) as usize;
+        (head % self.capacity, len.min(self.capacity))
+    }
+    
+    fn set_header(&mut self, head: usize, len: usize) -> Result<(), String> {
+        let mut header = [0u8; HEADER_SIZE];
+        header[0..8].copy_from_slice(&(head as u64).to_le_bytes());
+        header[8..16].copy_from_slice(&(len as u64).to_le_bytes());
+        self.tape.write_segment(&self.segment, 0, &header)
+    }
+    
+    fn slot_offset(&self, slot: usize) -> i64 {
+        (HEADER_SIZE + slot * self.elem_size) as i64
+    }
+    
+    fn read_slot(&self, slot: usize) -> Result<Vec<u8>, String> {
+        self.tape.read_segment(&self.segment, self.slot_offset(slot), self.elem_size)
+    }
+    
+    fn write_slot(&mut self, slot: usize, elem: &[u8]) -> Result<(), String> {
+        self.tape.write_segment(&self.segment, self.slot_offset(slot), elem)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tape::SegmentType;
+
+    /// A tape with a deque segment holding `capacity` one-byte elements
+    fn deque_tape(capacity: usize) -> SegmentedTape {
+        let mut tape = SegmentedTape::new();
+        tape.create_segment("dq".to_string(), HEADER_SIZE + capacity, SegmentType::Data).unwrap();
+        tape
+    }
+
+    #[test]
+    fn test_interleaved_push_pop() {
+        let mut tape = deque_tape(8);
+        let mut dq = TapeDeque::new(&mut tape, "dq", 1).unwrap();
+        
+        dq.push_back(&[1]).unwrap();
+        dq.push_front(&[0]).unwrap();
+        dq.push_back(&[2]).unwrap();
+        assert_eq!(dq.len(), 3);
+        
+        assert_eq!(dq.pop_front().unwrap(), Some(vec![0]));
+        assert_eq!(dq.pop_back().unwrap(), Some(vec![2]));
+        dq.push_front(&[9]).unwrap();
+        assert_eq!(dq.pop_back().unwrap(), Some(vec![1]));
+        assert_eq!(dq.pop_back().unwrap(), Some(vec![9]));
+        assert_eq!(dq.pop_front().unwrap(), None);
+    }
+
+    #[test]
+    fn test_wraparound_both_directions() {
+        let mut tape = deque_tape(4);
+        let mut dq = TapeDeque::new(&mut tape, "dq", 1).unwrap();
+        
+        // Pushing at the front from slot 0 wraps to the end of the array
+        dq.push_front(&[1]).unwrap();
+        dq.push_front(&[0]).unwrap();
+        dq.push_back(&[2]).unwrap();
+        dq.push_back(&[3]).unwrap();
+        
+        // Cycle elements forward past the end of the array several times
+        for _ in 0..6 {
+            let elem = dq.pop_front().unwrap().unwrap();
+            dq.push_back(&elem).unwrap();
+        }
+        // And backward past the start
+        for _ in 0..9 {
+            let elem = dq.pop_back().unwrap().unwrap();
+            dq.push_front(&elem).unwrap();
+        }
+        
+        let drained: Vec<u8> = std::iter::from_fn(|| dq.pop_front().unwrap())
+            .map(|elem| elem[0])
+            .collect();
+        assert_eq!(drained, vec![1, 2, 3, 0]);
+    }
+
+    #[test]
+    fn test_full_and_empty() {
+        let mut tape = deque_tape(3);
+        let mut dq = TapeDeque::new(&mut tape, "dq", 1).unwrap();
+        assert!(dq.is_empty());
+        assert!(!dq.is_full());
+        
+        dq.push_back(&[1]).unwrap();
+        dq.push_front(&[2]).unwrap();
+        dq.push_back(&[3]).unwrap();
+        assert!(dq.is_full());
+        assert!(dq.push_back(&[4]).is_err());
+        assert!(dq.push_front(&[4]).is_err());
+        assert_eq!(dq.len(), 3);
+        
+        assert!(dq.push_back(&[1, 2]).is_err());
+        
+        for _ in 0..3 {
+            dq.pop_back().unwrap().unwrap();
+        }
+        assert!(dq.is_empty());
+        assert_eq!(dq.pop_back().unwrap(), None);
+    }
+}
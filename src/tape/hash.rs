@@ -0,0 +1,310 @@
+//! Hash index stored in a tape segment
+//!
+//! An open-addressing table with linear probing, at the index's
+//! `root_position` within its segment. A 16-byte header holds the slot count
+//! and the number of entries; a zero slot count (a freshly zeroed region) is an
+//! empty table. The table doubles once it is three quarters full, rehashing
+//! every entry in place, and can grow up to the next index root in the segment.
+
+use super::segment::{IndexType, SegmentedTape};
+use super::table::Value;
+
+const HEADER_SIZE: usize = 16;
+const INITIAL_SLOTS: usize = 8;
+/// Longest encoded key, including its type tag
+const MAX_KEY_LEN: usize = 32;
+/// Occupied flag and key length, then the value and the key bytes
+const SLOT_SIZE: usize = 8 + 8 + MAX_KEY_LEN;
+
+/// Borrowing view of a hash index in a segment
+pub struct HashIndex<'a> {
+    tape: &'a mut SegmentedTape,
+    segment: String,
+    /// Offset of the table's header within the segment
+    base: i64,
+    /// Most slots that fit in the table's region
+    max_slots: usize,
+}
+
+impl<'a> HashIndex<'a> {
+    /// Open the table whose header is at `root_position` within `segment`
+    pub fn new(tape: &'a mut SegmentedTape, segment: &str, root_position: i64) -> Result<Self, String> {
+        let end = tape.get_segment(segment)
+            .ok_or_else(|| format!("Unknown segment: {}", segment))?
+            .region_end(root_position);
+        if root_position < 0 || root_position + HEADER_SIZE as i64 > end {
+            return Err(format!("Hash index header at {} lies outside segment '{}'", root_position, segment));
+        }
+        
+        Ok(HashIndex {
+            tape,
+            segment: segment.to_string(),
+            base: root_position,
+            max_slots: ((end - root_position) as usize - HEADER_SIZE) / SLOT_SIZE,
+        })
+    }
+    
+    /// Open the segment's hash index called `name`
+    pub fn open(tape: &'a mut SegmentedTape, segment: &str, name: &str) -> Result<Self, String> {
+        let index = tape.get_segment(segment)
+            .ok_or_else(|| format!("Unknown segment: {}", segment))?
+            .indices.iter()
+            .find(|index| index.name == name)
+            .ok_or_else(|| format!("Segment '{}' has no index '{}'", segment, name))?;
+        if !matches!(index.index_type, IndexType::Hash) {
+            return Err(format!("Index '{}' is not a hash index", name));
+        }
+        
+        let root_position = index.root_position;
+        Self::new(tape, segment, root_position)
+    }
+    
+    /// Number of entries
+    pub fn len(&self) -> usize {
+        self.header().map_or(0, |(_, len)| len)
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    
+    /// Current number of slots
+    pub fn slots(&self) -> usize {
+        self.header().map_or(0, |(slots, _)| slots)
+    }
+    
+    /// Insert `key`, replacing the value if it is already present
+    ///
+    /// Keys may be integers, timestamps, strings or bytes of up to 31 bytes.
+    pub fn insert(&mut self, key: &Value, value: i64) -> Result<(), String> {
+        let key = encode_key(key)?;
+        let (mut slots, len) = self.header()?;
+        if slots == 0 {
+            slots = self.resize(INITIAL_SLOTS, Vec::new())?;
+        }
+        
+        let (mut slot, existing) = self.probe(&key, slots)?;
+        if existing.is_none() && (len + 1) * 4 > slots * 3 {
+            let entries = self.entries(slots)?;
+            slots = self.resize(slots * 2, entries)?;
+            slot = self.probe(&key, slots)?.0;
+        }
+        
+        self.write_slot(slot, &key, value)?;
+        if existing.is_none() {
+            self.set_header(slots, len + 1)?;
+        }
+        Ok(())
+    }
+    
+    /// The value stored for `key`
+    pub fn lookup(&self, key: &Value) -> Option<i64> {
+        let key = encode_key(key).ok()?;
+        let (slots, _) = self.header().ok()?;
+        if slots == 0 {
+            return None;
+        }
+        self.probe(&key, slots).ok()?.1
+    }
+    
+    /// The slot holding `key`, with its value, or else the empty slot it would go in
+    fn probe(&self, key: &[u8], slots: usize) -> Result<(usize, Option<i64>), String> {
+        let start = (fnv1a(key) % slots as u64) as usize;
+        for i in 0..slots {
+            let slot = (start + i) % slots;
+            match self.read_slot(slot)? {
+                None => return Ok((slot, None)),
+                Some((k, v)) if k == key => return Ok((slot, Some(v))),
+                Some(_) => {}
+            }
+        }
+        Err(format!("Hash index in segment '{}' has no free slot", self.segment))
+    }
+    
+    /// Every (encoded key, value) pair in a table of `slots` slots
+    fn entries(&self, slots: usize) -> Result<Vec<(Vec<u8>, i64)>, String> {
+        let mut entries = Vec::new();
+        for slot in 0..slots {
+            entries.extend(self.read_slot(slot)?);
+        }
+        Ok(entries)
+    }
+    
+    /// Clear the table to `slots` empty slots and reinsert `entries`
+    fn resize(&mut self, slots: usize, entries: Vec<(Vec<u8>, i64)>) -> Result<usize, String> {
+        if slots > self.max_slots {
+            return Err(format!("Hash index in segment '{}' is full ({} slots)", self.segment, self.max_slots));
+        }
+        
+        self.tape.write_segment(&self.segment, self.slot_offset(0), &vec![0; slots * SLOT_SIZE])?;
+        self.set_header(slots, entries.len())?;
+        for (key, value) in entries {
+            let slot = self.probe(&key, slots)?.0;
+            self.write_slot(slot, &key, value)?;
+        }
+        Ok(slots)
+    }
+    
+    /// (slot count, entry count)
+    fn header(&self) -> Result<(usize, usize), String> {
+        let header = self.tape.read_segment(&self.segment, self.base, HEADER_SIZE)?;
+        let slots = u64::from_le_bytes(header[0..8].try_into().unwrap()) as usize;
+        let len = u64::from_le_bytes(header[8..16].try_into().unwrap()) as usize;
+        Ok((slots.min(self.max_slots), len))
+    }
+    
+    fn set_header(&mut self, slots: usize, len: usize) -> Result<(), String> {
+        let mut header = [0u8; HEADER_SIZE];
+        header[0..8].copy_from_slice(&(slots as u64).to_le_bytes());
+        header[8..16].copy_from_slice(&(len as u64).to_le_bytes());
+        self.tape.write_segment(&self.segment, self.base, &header)
+    }
+    
+    fn slot_offset(&self, slot: usize) -> i64 {
+        self.base + (HEADER_SIZE + slot * SLOT_SIZE) as i64
+    }
+    
+    fn read_slot(&self, slot: usize) -> Result<Option<(Vec<u8>, i64)>, String> {
+        let bytes = self.tape.read_segment(&self.segment, self.slot_offset(slot), SLOT_SIZE)?;
+        if bytes[0] == 0 {
+            return Ok(None);
+        }
+        let key_len = (bytes[1] as usize).min(MAX_KEY_LEN);
+        let value = i64::from_le_bytes(bytes[8..16].try_into().unwrap());
+        Ok(Some((bytes[16..16 + key_len].to_vec(), value)))
+    }
+    
+    fn write_slot(&mut self, slot: usize, key: &[u8], value: i64) -> Result<(), String> {
+        let mut bytes = vec![0u8; SLOT_SIZE];
+        bytes[0] = 1;
+        bytes[1] = key.len() as u8;
+        bytes[8..16].copy_from_slice(&value.to_le_bytes());
+        bytes[16..16 + key.len()].copy_from_slice(key);
+        self.tape.write_segment(&self.segment, self.slot_offset(slot), &bytes)
+    }
+}
+
+/// A type tag followed by the key's bytes, so `Int(1)` and `UInt(1)` stay distinct
+fn encode_key(key: &Value) -> Result<Vec<u8>, String> {
+    let (tag, body) = match key {
+        Value::Int(v) => (0, v.to_le_bytes().to_vec()),
+        Value::UInt(v) => (1, v.to_le_bytes().to_vec()),
+        Value::Timestamp(v) => (2, v.to_le_bytes().to_vec()),
+        Value::String(s) => (3, s.as_bytes().to_vec()),
+        Value::Bytes(b) => (4, b.clone()),
+        Value::Null | Value::Float(_) => return Err(format!("Unsupported hash key: {:?}", key)),
+    };
+    if body.len() >= MAX_KEY_LEN {
+        return Err(format!("Hash key longer than {} bytes", MAX_KEY_LEN - 1));
+    }
+    
+    let mut encoded = vec![tag];
+    encoded.extend(body);
+    Ok(encoded)
+}
+
+/// 64-bit FNV-1a, stable across runs so persisted tables stay valid
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &b| (hash ^ b as u64).wrapping_mul(0x100000001b3))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tape::{BTreeIndex, Index, SegmentType};
+    
+    fn index_tape(size: usize) -> SegmentedTape {
+        let mut tape = SegmentedTape::new();
+        tape.create_segment("idx".to_string(), size, SegmentType::Index).unwrap();
+        tape
+    }
+    
+    fn name(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+
+    #[test]
+    fn test_insert_and_lookup() {
+        let mut tape = index_tape(4096);
+        let mut index = HashIndex::new(&mut tape, "idx", 0).unwrap();
+        assert_eq!(index.lookup(&Value::Int(1)), None);
+        
+        index.insert(&Value::Int(1), 10).unwrap();
+        index.insert(&name("alice"), 20).unwrap();
+        index.insert(&Value::UInt(1), 30).unwrap();
+        index.insert(&name("alice"), 21).unwrap();
+        
+        assert_eq!(index.len(), 3);
+        assert_eq!(index.lookup(&Value::Int(1)), Some(10));
+        assert_eq!(index.lookup(&Value::UInt(1)), Some(30));
+        assert_eq!(index.lookup(&name("alice")), Some(21));
+        assert_eq!(index.lookup(&name("bob")), None);
+        assert_eq!(index.lookup(&Value::Int(2)), None);
+        
+        assert!(index.insert(&Value::Null, 0).unwrap_err().contains("Unsupported"));
+        assert!(index.insert(&name(&"x".repeat(40)), 0).unwrap_err().contains("longer than"));
+    }
+
+    #[test]
+    fn test_load_factor_triggers_resize() {
+        let mut tape = index_tape(1 << 16);
+        let mut index = HashIndex::new(&mut tape, "idx", 0).unwrap();
+        for key in 0..6 {
+            index.insert(&Value::Int(key), key * 100).unwrap();
+        }
+        assert_eq!(index.slots(), INITIAL_SLOTS);
+        
+        // The seventh entry would pass three quarters of eight slots
+        index.insert(&Value::Int(6), 600).unwrap();
+        assert_eq!(index.slots(), INITIAL_SLOTS * 2);
+        
+        for key in 7..500 {
+            index.insert(&Value::Int(key), key * 100).unwrap();
+        }
+        assert_eq!(index.len(), 500);
+        assert!(index.slots() * 3 >= 500 * 4);
+        assert!((0..500).all(|key| index.lookup(&Value::Int(key)) == Some(key * 100)));
+    }
+
+    #[test]
+    fn test_region_too_small_to_grow() {
+        let mut tape = index_tape(HEADER_SIZE + 10 * SLOT_SIZE);
+        let mut index = HashIndex::new(&mut tape, "idx", 0).unwrap();
+        let result = (0..10).try_for_each(|key| index.insert(&Value::Int(key), key));
+        assert!(result.unwrap_err().contains("is full"));
+        assert_eq!(index.len(), 6);
+    }
+
+    #[test]
+    fn test_coexists_with_btree_in_one_segment() {
+        let mut tape = index_tape(1 << 16);
+        let indices = &mut tape.segments.get_mut("idx").unwrap().indices;
+        indices.push(Index {
+            name: "by_id".to_string(),
+            index_type: IndexType::BTree,
+            fields: vec!["id".to_string()],
+            root_position: 0,
+        });
+        indices.push(Index {
+            name: "by_name".to_string(),
+            index_type: IndexType::Hash,
+            fields: vec!["name".to_string()],
+            root_position: 1 << 15,
+        });
+        
+        let mut tree = BTreeIndex::open(&mut tape, "idx", "by_id").unwrap();
+        for key in 0..300 {
+            tree.insert(key, key).unwrap();
+        }
+        let mut hash = HashIndex::open(&mut tape, "idx", "by_name").unwrap();
+        for key in 0..200 {
+            hash.insert(&name(&format!("user{}", key)), key).unwrap();
+        }
+        
+        assert!(HashIndex::open(&mut tape, "idx", "by_id").is_err());
+        let tree = BTreeIndex::open(&mut tape, "idx", "by_id").unwrap();
+        assert_eq!(tree.entries().unwrap().len(), 300);
+        let hash = HashIndex::open(&mut tape, "idx", "by_name").unwrap();
+        assert_eq!(hash.lookup(&name("user150")), Some(150));
+    }
+}
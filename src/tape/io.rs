@@ -0,0 +1,165 @@
+//! `std::io` adapter over the tape
+//!
+//! Lets the tape be used with any reader/writer from the ecosystem. Reads and
+//! writes advance the head, and writes go through `Tape::write` so they are
+//! recorded on the trail and stay reversible.
+
+use super::core::Tape;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+/// Borrowing `Read`/`Write`/`Seek` adapter for a `Tape`
+///
+/// Since the tape is infinite, end-of-file is defined as the tape's high-water
+/// mark: reads stop there and `SeekFrom::End` is relative to it.
+pub struct TapeIo<'a> {
+    tape: &'a mut Tape,
+}
+
+impl<'a> TapeIo<'a> {
+    pub fn new(tape: &'a mut Tape) -> Self {
+        TapeIo { tape }
+    }
+    
+    /// Get the underlying tape
+    pub fn tape(&self) -> &Tape {
+        self.tape
+    }
+}
+
+impl Read for TapeIo<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = (self.tape.high_water() - self.tape.position()).max(0) as usize;
+        let len = buf.len().min(remaining);
+        if len == 0 {
+            return Ok(0);
+        }
+        
+        buf[..len].copy_from_slice(&self.tape.read(len));
+        self.tape.advance(len as i64);
+        Ok(len)
+    }
+}
+
+impl Write for TapeIo<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.tape.write(buf);
+        self.tape.advance(buf.len() as i64);
+        Ok(buf.len())
+    }
+    
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for TapeIo<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => i64::try_from(offset).ok(),
+            SeekFrom::Current(delta) => self.tape.position().checked_add(delta),
+            SeekFrom::End(delta) => self.tape.high_water().checked_add(delta),
+        };
+        let target = target.ok_or_else(|| io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Seek to {:?} overflows the tape position", pos),
+        ))?;
+        
+        if target < 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Seek to negative position {}", target),
+            ));
+        }
+        
+        self.tape.seek(target);
+        Ok(target as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufReader;
+
+    #[test]
+    fn test_json_round_trip() {
+        let mut tape = Tape::new();
+        let (name, reversible) = ("palindrome", true);
+        let document = r#"{"name":"palindrome","reversible":true,"values":[1,2,3]}"#;
+        
+        {
+            let mut io = TapeIo::new(&mut tape);
+            write!(io, "{{\"name\":\"{name}\",").unwrap();
+            write!(io, "\"reversible\":{reversible},").unwrap();
+            io.write_all(b"\"values\":[1,2,3]}").unwrap();
+        }
+        
+        assert_eq!(tape.position(), document.len() as i64);
+        assert_eq!(tape.high_water(), document.len() as i64);
+        
+        let mut io = TapeIo::new(&mut tape);
+        io.seek(SeekFrom::Start(0)).unwrap();
+        let mut text = String::new();
+        BufReader::new(io).read_to_string(&mut text).unwrap();
+        assert_eq!(text, document);
+    }
+
+    #[test]
+    fn test_writes_are_reversible() {
+        let mut tape = Tape::new();
+        let before = tape.trail_len();
+        
+        TapeIo::new(&mut tape).write_all(b"undo me").unwrap();
+        assert!(tape.trail_len() > before);
+        
+        tape.rewind_n(tape.trail_len() - before);
+        assert_eq!(tape.position(), 0);
+        assert_eq!(tape.read(7), vec![0; 7]);
+    }
+
+    #[test]
+    fn test_seek_end() {
+        let mut tape = Tape::new();
+        tape.seek(100);
+        tape.write(b"tail");
+        tape.seek(0);
+        
+        let mut io = TapeIo::new(&mut tape);
+        
+        // End is the high-water mark, not the unbounded tape
+        assert_eq!(io.seek(SeekFrom::End(0)).unwrap(), 104);
+        let mut buf = [0u8; 8];
+        assert_eq!(io.read(&mut buf).unwrap(), 0);
+        
+        assert_eq!(io.seek(SeekFrom::End(-4)).unwrap(), 100);
+        let mut tail = String::new();
+        io.read_to_string(&mut tail).unwrap();
+        assert_eq!(tail, "tail");
+        
+        assert!(io.seek(SeekFrom::End(-200)).is_err());
+    }
+
+    #[test]
+    fn test_seek_end_after_undo() {
+        let mut tape = Tape::new();
+        tape.write(b"head");
+        tape.checkpoint("short".to_string());
+        tape.seek(100);
+        tape.write(b"tail");
+        
+        tape.rewind("short").unwrap();
+        assert_eq!(tape.high_water(), 4);
+        assert_eq!(TapeIo::new(&mut tape).seek(SeekFrom::End(0)).unwrap(), 4);
+    }
+
+    #[test]
+    fn test_seek_overflow() {
+        let mut tape = Tape::new();
+        tape.seek(10);
+        
+        let mut io = TapeIo::new(&mut tape);
+        assert!(io.seek(SeekFrom::Current(i64::MAX)).is_err());
+        assert!(io.seek(SeekFrom::Start(u64::MAX)).is_err());
+        assert_eq!(io.tape().position(), 10);
+    }
+}
@@ -0,0 +1,137 @@
+//! Append-only records of log segments
+//!
+//! A log segment begins with a 16-byte header holding the next sequence
+//! number and the write cursor, followed by records packed back to back, each
+//! a `u32` length and its bytes. Record `n` is the `n`th one appended, so
+//! sequence numbers start at zero and never skip. Appends go through
+//! `write_segment`, so rewinding the trail un-appends them.
+
+use super::segment::{SegmentType, SegmentedTape};
+
+const HEADER_SIZE: usize = 16;
+
+impl SegmentedTape {
+    /// Append `record` to a log segment, returning its sequence number
+    pub fn log_append(&mut self, segment: &str, record: &[u8]) -> Result<u64, String> {
+        let size = self.log_segment(segment)?;
+        let (seq, cursor) = self.log_header(segment)?;
+        let len = u32::try_from(record.len())
+            .map_err(|_| format!("Log record of {} bytes is too long", record.len()))?;
+        if HEADER_SIZE + cursor + 4 + record.len() > size {
+            return Err(format!("Log segment '{}' is full", segment));
+        }
+        
+        let mut bytes = len.to_le_bytes().to_vec();
+        bytes.extend_from_slice(record);
+        self.write_segment(segment, (HEADER_SIZE + cursor) as i64, &bytes)?;
+        
+        let mut header = [0u8; HEADER_SIZE];
+        header[0..8].copy_from_slice(&(seq + 1).to_le_bytes());
+        header[8..16].copy_from_slice(&((cursor + bytes.len()) as u64).to_le_bytes());
+        self.write_segment(segment, 0, &header)?;
+        Ok(seq)
+    }
+    
+    /// The record appended with sequence number `seq`
+    pub fn log_read(&self, segment: &str, seq: u64) -> Result<Vec<u8>, String> {
+        self.log_segment(segment)?;
+        let (next, _) = self.log_header(segment)?;
+        if seq >= next {
+            return Err(format!("No record {} in log '{}' ({} appended)", seq, segment, next));
+        }
+        
+        let mut offset = HEADER_SIZE as i64;
+        for _ in 0..seq {
+            offset += 4 + self.log_record_len(segment, offset)? as i64;
+        }
+        let len = self.log_record_len(segment, offset)?;
+        self.read_segment(segment, offset + 4, len)
+    }
+    
+    /// Number of records appended to a log segment
+    pub fn log_len(&self, segment: &str) -> Result<u64, String> {
+        self.log_segment(segment)?;
+        Ok(self.log_header(segment)?.0)
+    }
+    
+    /// The size of a log segment
+    fn log_segment(&self, segment: &str) -> Result<usize, String> {
+        let seg = self.get_segment(segment)
+            .ok_or_else(|| format!("Unknown segment: {}", segment))?;
+        match seg.segment_type {
+            SegmentType::Log => Ok(seg.size),
+            _ => Err(format!("Segment '{}' is not a log", segment)),
+        }
+    }
+    
+    /// (next sequence number, write cursor past the header)
+    fn log_header(&self, segment: &str) -> Result<(u64, usize), String> {
+        let header = self.read_segment(segment, 0, HEADER_SIZE)?;
+        let seq = u64::from_le_bytes(header[0..8].try_into().unwrap());
+        let cursor = u64::from_le_bytes(header[8..16].try_into().unwrap()) as usize;
+        Ok((seq, cursor))
+    }
+    
+    fn log_record_len(&self, segment: &str, offset: i64) -> Result<usize, String> {
+        let len = self.read_segment(segment, offset, 4)?;
+        Ok(u32::from_le_bytes(len.try_into().unwrap()) as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log_tape(size: usize) -> SegmentedTape {
+        let mut tape = SegmentedTape::new();
+        tape.create_segment("wal".to_string(), size, SegmentType::Log).unwrap();
+        tape
+    }
+
+    #[test]
+    fn test_append_and_read_out_of_order() {
+        let mut tape = log_tape(256);
+        let records: [&[u8]; 4] = [b"begin", b"", b"set x = 1", b"commit"];
+        for (i, record) in records.iter().enumerate() {
+            assert_eq!(tape.log_append("wal", record).unwrap(), i as u64);
+        }
+        assert_eq!(tape.log_len("wal").unwrap(), 4);
+        
+        for seq in [2, 0, 3, 1] {
+            assert_eq!(tape.log_read("wal", seq).unwrap(), records[seq as usize]);
+        }
+        assert!(tape.log_read("wal", 4).unwrap_err().contains("No record 4"));
+    }
+
+    #[test]
+    fn test_append_past_end_is_an_error() {
+        let mut tape = log_tape(HEADER_SIZE + 20);
+        tape.log_append("wal", &[1; 12]).unwrap();
+        assert!(tape.log_append("wal", &[2; 5]).unwrap_err().contains("is full"));
+        
+        // The failed append consumed no sequence number
+        assert_eq!(tape.log_append("wal", &[]).unwrap(), 1);
+        assert_eq!(tape.log_read("wal", 1).unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn test_rewinding_trail_unappends() {
+        let mut tape = log_tape(64);
+        tape.log_append("wal", b"kept").unwrap();
+        let mark = tape.tape.trail_len();
+        tape.log_append("wal", b"undone").unwrap();
+        
+        tape.tape.rewind_n(tape.tape.trail_len() - mark);
+        assert_eq!(tape.log_len("wal").unwrap(), 1);
+        assert_eq!(tape.log_append("wal", b"redo").unwrap(), 1);
+        assert_eq!(tape.log_read("wal", 1).unwrap(), b"redo");
+    }
+
+    #[test]
+    fn test_requires_log_segment() {
+        let mut tape = log_tape(64);
+        tape.create_segment("heap".to_string(), 64, SegmentType::Heap).unwrap();
+        assert!(tape.log_append("heap", b"x").unwrap_err().contains("not a log"));
+        assert!(tape.log_read("missing", 0).unwrap_err().contains("Unknown segment"));
+    }
+}
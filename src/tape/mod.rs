@@ -3,12 +3,25 @@
 //! Everything in the VM is stored on an infinite bidirectional tape.
 //! The tape supports reversible operations through a history trail.
 
+mod btree;
 mod core;
+mod deque;
+mod hash;
+mod io;
+mod log;
+mod persist;
 mod segment;
+mod table;
 pub mod sdm;
 
-pub use core::{Tape, Page, Trail, TrailOp};
-pub use segment::{Segment, SegmentedTape, SegmentType, Schema, Field, DataType, Index, IndexType};
+pub use btree::BTreeIndex;
+pub use core::{Tape, TapeSnapshot, Page, Trail, TrailOp};
+pub use deque::TapeDeque;
+pub use hash::HashIndex;
+pub use io::TapeIo;
+pub(crate) use persist::{StateReader, StateWriter};
+pub use segment::{Segment, SegmentExt, SegmentedTape, SegmentType, Schema, Field, DataType, Index, IndexType};
+pub use table::{RowScan, Value};
 
 // Re-export SDM components for easy access
 pub use sdm::{SdmTape, SdmConfig};
\ No newline at end of file
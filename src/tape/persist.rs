@@ -0,0 +1,513 @@
+//! Binary persistence of tape state
+//!
+//! `StateWriter` and `StateReader` implement the little-endian format behind
+//! `VM::save_state`: integers are fixed width, strings and byte vectors carry a
+//! `u64` length prefix, and maps are written in key order so equal states
+//! produce equal files. Only pages that exist are stored, and all-zero pages
+//! are stored as a single tag byte, so a sparse tape stays sparse on disk.
+
+use super::core::{observe_write_stamp, Page, Tape, Trail, TrailOp};
+use super::segment::{DataType, Field, Index, IndexType, Schema, Segment, SegmentType, SegmentedTape};
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+const PAGE_SIZE: usize = 4096;
+
+/// Appends values in the state file format
+#[derive(Default)]
+pub(crate) struct StateWriter {
+    bytes: Vec<u8>,
+}
+
+impl StateWriter {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+    
+    pub(crate) fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+    
+    /// Raw bytes with no length prefix
+    pub(crate) fn raw(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+    
+    pub(crate) fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+    
+    pub(crate) fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+    
+    pub(crate) fn u64(&mut self, value: u64) {
+        self.raw(&value.to_le_bytes());
+    }
+    
+    pub(crate) fn i64(&mut self, value: i64) {
+        self.raw(&value.to_le_bytes());
+    }
+    
+    pub(crate) fn usize(&mut self, value: usize) {
+        self.u64(value as u64);
+    }
+    
+    pub(crate) fn opt_usize(&mut self, value: Option<usize>) {
+        match value {
+            Some(value) => {
+                self.bool(true);
+                self.usize(value);
+            }
+            None => self.bool(false),
+        }
+    }
+    
+    pub(crate) fn bytes(&mut self, bytes: &[u8]) {
+        self.usize(bytes.len());
+        self.raw(bytes);
+    }
+    
+    pub(crate) fn str(&mut self, s: &str) {
+        self.bytes(s.as_bytes());
+    }
+    
+    /// A string-keyed map, in key order
+    pub(crate) fn map<V>(&mut self, map: &HashMap<String, V>, mut value: impl FnMut(&mut Self, &V)) {
+        let sorted: BTreeMap<_, _> = map.iter().collect();
+        self.usize(sorted.len());
+        for (key, v) in sorted {
+            self.str(key);
+            value(self, v);
+        }
+    }
+}
+
+/// Reads values in the state file format, failing on truncated or malformed input
+pub(crate) struct StateReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        StateReader { bytes, pos: 0 }
+    }
+    
+    /// Fail unless every byte has been consumed
+    pub(crate) fn finish(&self) -> Result<(), String> {
+        if self.pos != self.bytes.len() {
+            return Err(format!("{} trailing bytes in state", self.bytes.len() - self.pos));
+        }
+        Ok(())
+    }
+    
+    pub(crate) fn raw(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let slice = self.pos.checked_add(len)
+            .and_then(|end| self.bytes.get(self.pos..end))
+            .ok_or_else(|| format!("Truncated state at byte {}", self.pos))?;
+        self.pos += len;
+        Ok(slice)
+    }
+    
+    pub(crate) fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.raw(1)?[0])
+    }
+    
+    pub(crate) fn bool(&mut self) -> Result<bool, String> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            other => Err(format!("Invalid bool {} in state", other)),
+        }
+    }
+    
+    pub(crate) fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.raw(8)?.try_into().unwrap()))
+    }
+    
+    pub(crate) fn i64(&mut self) -> Result<i64, String> {
+        Ok(i64::from_le_bytes(self.raw(8)?.try_into().unwrap()))
+    }
+    
+    pub(crate) fn usize(&mut self) -> Result<usize, String> {
+        let value = self.u64()?;
+        usize::try_from(value).map_err(|_| format!("Value {} out of range in state", value))
+    }
+    
+    /// An element count, checked against the bytes left so corrupt input cannot force a huge allocation
+    pub(crate) fn count(&mut self) -> Result<usize, String> {
+        let count = self.usize()?;
+        if count > self.bytes.len() - self.pos {
+            return Err(format!("Implausible count {} in state", count));
+        }
+        Ok(count)
+    }
+    
+    pub(crate) fn opt_usize(&mut self) -> Result<Option<usize>, String> {
+        Ok(if self.bool()? { Some(self.usize()?) } else { None })
+    }
+    
+    pub(crate) fn bytes(&mut self) -> Result<Vec<u8>, String> {
+        let len = self.count()?;
+        Ok(self.raw(len)?.to_vec())
+    }
+    
+    pub(crate) fn str(&mut self) -> Result<String, String> {
+        String::from_utf8(self.bytes()?).map_err(|e| format!("Invalid string in state: {}", e))
+    }
+    
+    pub(crate) fn map<V>(
+        &mut self,
+        mut value: impl FnMut(&mut Self) -> Result<V, String>,
+    ) -> Result<HashMap<String, V>, String> {
+        let count = self.count()?;
+        let mut map = HashMap::with_capacity(count);
+        for _ in 0..count {
+            let key = self.str()?;
+            map.insert(key, value(self)?);
+        }
+        Ok(map)
+    }
+    
+    pub(crate) fn vec<V>(
+        &mut self,
+        mut value: impl FnMut(&mut Self) -> Result<V, String>,
+    ) -> Result<Vec<V>, String> {
+        let count = self.count()?;
+        (0..count).map(|_| value(self)).collect()
+    }
+}
+
+impl Tape {
+    pub(crate) fn write_state(&self, w: &mut StateWriter) {
+        w.usize(self.pages.len());
+        for (index, page) in &self.pages {
+            w.i64(*index);
+            if page.data.iter().all(|&b| b == 0) {
+                w.bool(false);
+            } else {
+                w.bool(true);
+                w.raw(&page.data[..]);
+            }
+        }
+        
+        w.i64(self.head);
+        w.map(&self.marks, |w, pos| w.i64(*pos));
+        
+        w.usize(self.trail.operations.len());
+        for op in &self.trail.operations {
+            write_trail_op(w, op);
+        }
+        w.map(&self.trail.checkpoints, |w, len| w.usize(*len));
+        w.usize(self.trail.evicted);
+        
+        w.i64(self.high_water);
+        w.opt_usize(self.write_combining);
+        w.opt_usize(self.history_limit);
+        w.usize(self.sealed.get());
+    }
+    
+    pub(crate) fn read_state(r: &mut StateReader) -> Result<Tape, String> {
+        let mut pages = BTreeMap::new();
+        for _ in 0..r.count()? {
+            let index = r.i64()?;
+            let mut data = [0u8; PAGE_SIZE];
+            if r.bool()? {
+                data.copy_from_slice(r.raw(PAGE_SIZE)?);
+            }
+            pages.insert(index, Page { data: Arc::new(data) });
+        }
+        
+        let head = r.i64()?;
+        let marks = r.map(|r| r.i64())?;
+        let operations = r.vec(read_trail_op)?;
+        let checkpoints = r.map(|r| r.usize())?;
+        let evicted = r.usize()?;
+        
+        Ok(Tape {
+            pages,
+            head,
+            marks,
+            trail: Trail { operations, checkpoints, evicted },
+            high_water: r.i64()?,
+            write_combining: r.opt_usize()?,
+            history_limit: r.opt_usize()?,
+            sealed: Cell::new(r.usize()?),
+        })
+    }
+}
+
+impl SegmentedTape {
+    pub(crate) fn write_state(&self, w: &mut StateWriter) {
+        self.tape.write_state(w);
+        w.map(&self.segments, write_segment);
+    }
+    
+    pub(crate) fn read_state(r: &mut StateReader) -> Result<SegmentedTape, String> {
+        let tape = Tape::read_state(r)?;
+        let segments = r.map(read_segment)?;
+        Ok(SegmentedTape { tape, segments })
+    }
+}
+
+fn write_trail_op(w: &mut StateWriter, op: &TrailOp) {
+    match op {
+        TrailOp::Write { pos, old, new, stamp, high_water } => {
+            w.u8(0);
+            w.i64(*pos);
+            w.bytes(old);
+            w.bytes(new);
+            w.u64(*stamp);
+            w.i64(*high_water);
+        }
+        TrailOp::Seek { old_pos, new_pos } => {
+            w.u8(1);
+            w.i64(*old_pos);
+            w.i64(*new_pos);
+        }
+        TrailOp::Mark { label, pos } => {
+            w.u8(2);
+            w.str(label);
+            w.i64(*pos);
+        }
+        TrailOp::SegmentCreate { name, start, size } => {
+            w.u8(3);
+            w.str(name);
+            w.i64(*start);
+            w.usize(*size);
+        }
+        TrailOp::SegmentModify { name, offset, old_data, new_data } => {
+            w.u8(4);
+            w.str(name);
+            w.i64(*offset);
+            w.bytes(old_data);
+            w.bytes(new_data);
+        }
+        TrailOp::SegmentResize { name, old_start, old_size, new_start, new_size } => {
+            w.u8(6);
+            w.str(name);
+            w.i64(*old_start);
+            w.usize(*old_size);
+            w.i64(*new_start);
+            w.usize(*new_size);
+        }
+        TrailOp::SegmentDelete { segment } => {
+            w.u8(7);
+            write_segment(w, segment);
+        }
+        TrailOp::Compact { pages } => {
+            w.u8(5);
+            w.usize(pages.len());
+            for page in pages {
+                w.i64(*page);
+            }
+        }
+    }
+}
+
+fn read_trail_op(r: &mut StateReader) -> Result<TrailOp, String> {
+    Ok(match r.u8()? {
+        0 => {
+            let (pos, old, new, stamp) = (r.i64()?, r.bytes()?, r.bytes()?, r.u64()?);
+            observe_write_stamp(stamp);
+            TrailOp::Write { pos, old, new, stamp, high_water: r.i64()? }
+        }
+        1 => TrailOp::Seek { old_pos: r.i64()?, new_pos: r.i64()? },
+        2 => TrailOp::Mark { label: r.str()?, pos: r.i64()? },
+        3 => TrailOp::SegmentCreate { name: r.str()?, start: r.i64()?, size: r.usize()? },
+        4 => TrailOp::SegmentModify {
+            name: r.str()?,
+            offset: r.i64()?,
+            old_data: r.bytes()?,
+            new_data: r.bytes()?,
+        },
+        5 => TrailOp::Compact { pages: r.vec(|r| r.i64())? },
+        6 => TrailOp::SegmentResize {
+            name: r.str()?,
+            old_start: r.i64()?,
+            old_size: r.usize()?,
+            new_start: r.i64()?,
+            new_size: r.usize()?,
+        },
+        7 => TrailOp::SegmentDelete { segment: read_segment(r)? },
+        other => return Err(format!("Unknown trail op tag {}", other)),
+    })
+}
+
+fn write_segment(w: &mut StateWriter, segment: &Segment) {
+    w.str(&segment.name);
+    w.i64(segment.start);
+    w.usize(segment.size);
+    write_segment_type(w, &segment.segment_type);
+    
+    w.usize(segment.indices.len());
+    for index in &segment.indices {
+        w.str(&index.name);
+        w.u8(match index.index_type {
+            IndexType::BTree => 0,
+            IndexType::Hash => 1,
+            IndexType::Bitmap => 2,
+            IndexType::FullText => 3,
+        });
+        write_strings(w, &index.fields);
+        w.i64(index.root_position);
+    }
+}
+
+fn read_segment(r: &mut StateReader) -> Result<Segment, String> {
+    Ok(Segment {
+        name: r.str()?,
+        start: r.i64()?,
+        size: r.usize()?,
+        segment_type: read_segment_type(r)?,
+        indices: r.vec(|r| Ok(Index {
+            name: r.str()?,
+            index_type: match r.u8()? {
+                0 => IndexType::BTree,
+                1 => IndexType::Hash,
+                2 => IndexType::Bitmap,
+                3 => IndexType::FullText,
+                other => return Err(format!("Unknown index type tag {}", other)),
+            },
+            fields: r.vec(|r| r.str())?,
+            root_position: r.i64()?,
+        }))?,
+    })
+}
+
+fn write_segment_type(w: &mut StateWriter, segment_type: &SegmentType) {
+    match segment_type {
+        SegmentType::Code => w.u8(0),
+        SegmentType::Data => w.u8(1),
+        SegmentType::Stack => w.u8(2),
+        SegmentType::Heap => w.u8(3),
+        SegmentType::Table { schema } => {
+            w.u8(4);
+            w.usize(schema.fields.len());
+            for field in &schema.fields {
+                w.str(&field.name);
+                write_data_type(w, &field.dtype);
+                w.bool(field.nullable);
+            }
+            write_strings(w, &schema.primary_key);
+        }
+        SegmentType::Index => w.u8(5),
+        SegmentType::Log => w.u8(6),
+    }
+}
+
+fn read_segment_type(r: &mut StateReader) -> Result<SegmentType, String> {
+    Ok(match r.u8()? {
+        0 => SegmentType::Code,
+        1 => SegmentType::Data,
+        2 => SegmentType::Stack,
+        3 => SegmentType::Heap,
+        4 => SegmentType::Table {
+            schema: Schema {
+                fields: r.vec(|r| Ok(Field {
+                    name: r.str()?,
+                    dtype: read_data_type(r)?,
+                    nullable: r.bool()?,
+                }))?,
+                primary_key: r.vec(|r| r.str())?,
+            },
+        },
+        5 => SegmentType::Index,
+        6 => SegmentType::Log,
+        other => return Err(format!("Unknown segment type tag {}", other)),
+    })
+}
+
+fn write_data_type(w: &mut StateWriter, dtype: &DataType) {
+    match dtype {
+        DataType::Int8 => w.u8(0),
+        DataType::Int16 => w.u8(1),
+        DataType::Int32 => w.u8(2),
+        DataType::Int64 => w.u8(3),
+        DataType::UInt8 => w.u8(4),
+        DataType::UInt16 => w.u8(5),
+        DataType::UInt32 => w.u8(6),
+        DataType::UInt64 => w.u8(7),
+        DataType::Float32 => w.u8(8),
+        DataType::Float64 => w.u8(9),
+        DataType::String { max_len } => {
+            w.u8(10);
+            w.opt_usize(*max_len);
+        }
+        DataType::Bytes { max_len } => {
+            w.u8(11);
+            w.opt_usize(*max_len);
+        }
+        DataType::Timestamp => w.u8(12),
+    }
+}
+
+fn read_data_type(r: &mut StateReader) -> Result<DataType, String> {
+    Ok(match r.u8()? {
+        0 => DataType::Int8,
+        1 => DataType::Int16,
+        2 => DataType::Int32,
+        3 => DataType::Int64,
+        4 => DataType::UInt8,
+        5 => DataType::UInt16,
+        6 => DataType::UInt32,
+        7 => DataType::UInt64,
+        8 => DataType::Float32,
+        9 => DataType::Float64,
+        10 => DataType::String { max_len: r.opt_usize()? },
+        11 => DataType::Bytes { max_len: r.opt_usize()? },
+        12 => DataType::Timestamp,
+        other => return Err(format!("Unknown data type tag {}", other)),
+    })
+}
+
+fn write_strings(w: &mut StateWriter, strings: &[String]) {
+    w.usize(strings.len());
+    for s in strings {
+        w.str(s);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sparse_tape_roundtrip() {
+        let mut tape = SegmentedTape::new();
+        tape.create_segment("data".to_string(), 64, SegmentType::Data).unwrap();
+        tape.write_segment("data", 8, b"persist").unwrap();
+        tape.tape.seek(1 << 40);
+        tape.tape.write(b"far away");
+        tape.tape.mark("far".to_string());
+        
+        let mut w = StateWriter::new();
+        tape.write_state(&mut w);
+        let bytes = w.into_bytes();
+        // Two touched pages, not the terabyte between them
+        assert!(bytes.len() < 3 * PAGE_SIZE);
+        
+        let mut r = StateReader::new(&bytes);
+        let mut restored = SegmentedTape::read_state(&mut r).unwrap();
+        r.finish().unwrap();
+        assert_eq!(restored.read_segment("data", 8, 7).unwrap(), b"persist");
+        assert_eq!(restored.tape.read(8), b"far away");
+        assert_eq!(restored.tape.trail_len(), tape.tape.trail_len());
+        
+        // The restored trail still undoes the original writes
+        restored.tape.rewind_n(restored.tape.trail_len());
+        assert_eq!(restored.read_segment("data", 8, 7).unwrap(), vec![0; 7]);
+    }
+
+    #[test]
+    fn test_truncated_state_is_an_error() {
+        let mut w = StateWriter::new();
+        SegmentedTape::new().write_state(&mut w);
+        let bytes = w.into_bytes();
+        
+        let mut r = StateReader::new(&bytes[..bytes.len() - 1]);
+        assert!(SegmentedTape::read_state(&mut r).err().unwrap().contains("Truncated"));
+    }
+}
@@ -116,12 +116,14 @@ impl VirtualAddressSpace {
     
     /// Get region information for an address
     pub fn get_region(&self, address: i64) -> Option<&Region> {
-        for (_, region) in self.regions.range(..=address).rev() {
-            if address >= region.start && address < region.start + region.size as i64 {
-                return Some(region);
-            }
-        }
-        None
+        self.regions.range(..=address).rev()
+            .map(|(_, region)| region)
+            .find(|region| address >= region.start && address < region.start + region.size as i64)
+    }
+    
+    /// Look up a region by name
+    pub fn find_region(&self, name: &str) -> Option<&Region> {
+        self.regions.values().find(|region| region.name.as_deref() == Some(name))
     }
     
     /// Mark a position for quick seeking
@@ -138,7 +140,7 @@ impl VirtualAddressSpace {
     fn check_overlap(&self, start: i64, size: usize) -> bool {
         let end = start + size as i64;
         
-        for (_, region) in &self.regions {
+        for region in self.regions.values() {
             let region_end = region.start + region.size as i64;
             
             // Check for overlap
@@ -154,7 +156,7 @@ impl VirtualAddressSpace {
     pub fn get_regions_in_range(&self, start: i64, end: i64) -> Vec<&Region> {
         let mut result = Vec::new();
         
-        for (_, region) in &self.regions {
+        for region in self.regions.values() {
             let region_end = region.start + region.size as i64;
             
             // Check if region intersects with range
@@ -166,6 +168,15 @@ impl VirtualAddressSpace {
         result
     }
     
+    /// Hints of the regions overlapping page `page_num`, in address order
+    pub fn page_hints(&self, page_num: i64) -> Vec<PolicyHint> {
+        let start = page_num * self.page_size as i64;
+        self.get_regions_in_range(start, start + self.page_size as i64)
+            .into_iter()
+            .map(|region| region.hint)
+            .collect()
+    }
+    
     /// Calculate aligned page boundaries for an address range
     pub fn page_range(&self, start: i64, len: usize) -> (i64, i64) {
         let start_page = start / self.page_size as i64;
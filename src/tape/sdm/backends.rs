@@ -6,10 +6,13 @@
 use std::collections::HashMap;
 use std::fs::{File, OpenOptions};
 use std::io::{Read, Write, Seek, SeekFrom};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
 use lru::LruCache;
-use std::num::NonZeroUsize;
+use crate::tape::sdm::compression;
 
 /// Trait for storage backends
 pub trait StorageBackend: Send + Sync {
@@ -59,23 +62,31 @@ pub struct StorageBackends {
     /// Local SSD/disk storage
     pub local: Arc<RwLock<FileBackend>>,
     
-    /// Network storage (future)
+    /// Network storage
     pub network: Option<Arc<RwLock<NetworkBackend>>>,
     
-    /// Cold storage (future)
+    /// Cold storage
     pub cold: Option<Arc<RwLock<S3Backend>>>,
+    
+    /// DRAM keys spilled to local storage, not yet taken by the page table
+    spilled: Vec<(u64, StorageLocation)>,
 }
 
-/// In-memory storage backend using LRU cache
+/// In-memory storage backend using a byte-budgeted LRU cache
 pub struct MemoryBackend {
-    /// LRU cache mapping keys to data
-    cache: LruCache<u64, Vec<u8>>,
+    /// LRU cache mapping keys to data (unbounded; eviction is driven by `used`)
+    ///
+    /// Behind a mutex so shared reads can still refresh recency.
+    cache: Mutex<LruCache<u64, Vec<u8>>>,
     
     /// Total capacity in bytes
     capacity: usize,
     
     /// Current usage in bytes
     used: usize,
+    
+    /// Entries pushed out by the LRU, waiting to be spilled to a lower tier
+    evicted: Vec<(u64, Vec<u8>)>,
 }
 
 /// File-based storage backend
@@ -91,31 +102,83 @@ pub struct FileBackend {
     
     /// File size limit
     file_size_limit: u64,
+    
+    /// Number of read calls issued against the files
+    read_count: u64,
 }
 
-/// Network storage backend (placeholder)
+/// Network storage backend talking to remote block stores over TCP
+///
+/// Each request is an opcode byte (`0` read, `1` write) followed by the
+/// offset and length as little-endian `u64`s, and the data for a write. The
+/// reply is a status byte: `0` followed by the data for a read, or `1`
+/// followed by a `u32` length and an error message.
 pub struct NetworkBackend {
-    // TODO: Implement network storage
+    /// Node used by the `StorageBackend` interface
+    default_node: String,
+    
+    /// Open connections by node address
+    connections: Mutex<HashMap<String, TcpStream>>,
+}
+
+/// In-process block store serving `NetworkBackend` requests
+pub struct NetworkBlockServer {
+    addr: SocketAddr,
+    stopped: Arc<AtomicBool>,
+}
+
+/// Client for an S3-style object store
+pub trait ObjectStore: Send + Sync {
+    /// Upload `data` as the whole object `key` in `bucket`
+    fn put(&self, bucket: &str, key: &str, data: &[u8]) -> Result<(), String>;
+    
+    /// Download the whole object `key` in `bucket`
+    fn get(&self, bucket: &str, key: &str) -> Result<Vec<u8>, String>;
+}
+
+/// Object store kept in memory, standing in for S3 in tests
+#[derive(Default)]
+pub struct MemoryObjectStore {
+    objects: Mutex<HashMap<(String, String), Vec<u8>>>,
 }
 
-/// S3 cold storage backend (placeholder)
+/// S3 cold storage backend
+///
+/// Each page is one object, named by its `StorageLocation::Cold` key under
+/// `prefix`.
 pub struct S3Backend {
-    // TODO: Implement S3 storage
     bucket: String,
     prefix: String,
+    client: Arc<dyn ObjectStore>,
 }
 
 impl StorageBackends {
     /// Create new storage backends with the given DRAM cache size
     pub fn new(dram_cache_size: usize) -> Self {
+        Self::with_local_dir(dram_cache_size, "./palindrome_data")
+    }
+    
+    /// Create new storage backends keeping local files under `local_dir`
+    pub fn with_local_dir<P: AsRef<Path>>(dram_cache_size: usize, local_dir: P) -> Self {
         StorageBackends {
             dram: Arc::new(RwLock::new(MemoryBackend::new(dram_cache_size))),
-            local: Arc::new(RwLock::new(FileBackend::new("./palindrome_data"))),
+            local: Arc::new(RwLock::new(FileBackend::new(local_dir))),
             network: None,
             cold: None,
+            spilled: Vec::new(),
         }
     }
     
+    /// The network backend, if one is configured
+    fn network(&self) -> Result<&Arc<RwLock<NetworkBackend>>, String> {
+        self.network.as_ref().ok_or_else(|| "No network backend configured".to_string())
+    }
+    
+    /// The cold storage backend, if one is configured
+    fn cold(&self) -> Result<&Arc<RwLock<S3Backend>>, String> {
+        self.cold.as_ref().ok_or_else(|| "No cold storage backend configured".to_string())
+    }
+    
     /// Read from a storage location
     pub fn read(&self, location: &StorageLocation, size: usize) -> Result<Vec<u8>, String> {
         match location {
@@ -129,12 +192,16 @@ impl StorageBackends {
                 Ok(buf)
             }
             
-            StorageLocation::Network { .. } => {
-                Err("Network storage not implemented".to_string())
+            StorageLocation::Network { node, offset } => {
+                let mut buf = vec![0u8; size];
+                self.network()?.read().unwrap().read_from_node(node, *offset, &mut buf)?;
+                Ok(buf)
             }
             
-            StorageLocation::Cold { .. } => {
-                Err("Cold storage not implemented".to_string())
+            StorageLocation::Cold { key } => {
+                let mut buf = vec![0u8; size];
+                self.cold()?.read().unwrap().read_object(key, &mut buf)?;
+                Ok(buf)
             }
             
             StorageLocation::Unallocated => {
@@ -144,8 +211,28 @@ impl StorageBackends {
         }
     }
     
-    /// Write to a storage location
+    /// Read a page stored at `location` as `size` bytes, decompressing it if
+    /// it was stored compressed
+    pub fn read_page(&self, location: &StorageLocation, size: usize, compressed: bool) -> Result<Vec<u8>, String> {
+        let stored = self.read(location, size)?;
+        if compressed {
+            compression::decompress(&stored)
+        } else {
+            Ok(stored)
+        }
+    }
+    
+    /// Write to a storage location, then spill whatever the write evicted from DRAM
     pub fn write(&mut self, location: &StorageLocation, data: &[u8]) -> Result<(), String> {
+        self.write_unspilled(location, data)?;
+        self.spill_evicted()
+    }
+    
+    /// Write to a storage location, leaving any entries a DRAM write evicts
+    /// queued (and readable) until `spill_evicted`
+    ///
+    /// An error means the data was not written.
+    pub fn write_unspilled(&mut self, location: &StorageLocation, data: &[u8]) -> Result<(), String> {
         match location {
             StorageLocation::Dram { key } => {
                 self.dram.write().unwrap().write_key(*key, data)
@@ -155,12 +242,12 @@ impl StorageBackends {
                 self.local.write().unwrap().write_to_file(*file_id, *offset, data)
             }
             
-            StorageLocation::Network { .. } => {
-                Err("Network storage not implemented".to_string())
+            StorageLocation::Network { node, offset } => {
+                self.network()?.read().unwrap().write_to_node(node, *offset, data)
             }
             
-            StorageLocation::Cold { .. } => {
-                Err("Cold storage not implemented".to_string())
+            StorageLocation::Cold { key } => {
+                self.cold()?.read().unwrap().write_object(key, data)
             }
             
             StorageLocation::Unallocated => {
@@ -169,6 +256,46 @@ impl StorageBackends {
         }
     }
     
+    /// Take the DRAM keys spilled to local storage since the last call, with
+    /// the location each one's data now lives at
+    pub fn take_spilled(&mut self) -> Vec<(u64, StorageLocation)> {
+        std::mem::take(&mut self.spilled)
+    }
+    
+    /// Demote entries evicted from DRAM to local files instead of losing them
+    ///
+    /// Spilled entries are listed by `take_spilled`, including those spilled
+    /// before a failure. If spilling fails partway, the entries not yet spilled
+    /// go back to the DRAM backend's eviction queue, where they stay readable
+    /// and are retried on the next spill.
+    pub fn spill_evicted(&mut self) -> Result<(), String> {
+        let evicted = self.dram.write().unwrap().take_evicted();
+        let mut pending = evicted.into_iter();
+        let result = {
+            let mut local = self.local.write().unwrap();
+            pending.try_for_each(|(key, data)| {
+                let spill = local.allocate_space(data.len() as u64)
+                    .and_then(|(file_id, offset)| {
+                        local.write_to_file(file_id, offset, &data)?;
+                        Ok(StorageLocation::Local { file_id, offset })
+                    });
+                match spill {
+                    Ok(location) => {
+                        self.spilled.push((key, location));
+                        Ok(())
+                    }
+                    Err(e) => Err((e, (key, data))),
+                }
+            })
+        };
+        
+        result.map_err(|(e, failed)| {
+            let unspilled = std::iter::once(failed).chain(pending).collect();
+            self.dram.write().unwrap().restore_evicted(unspilled);
+            e
+        })
+    }
+    
     /// Get the best backend for a given access pattern
     pub fn suggest_backend(&self, size: usize, access_frequency: f32) -> StorageLocation {
         // Simple policy: frequently accessed data goes to DRAM
@@ -190,49 +317,95 @@ impl StorageBackends {
 
 impl MemoryBackend {
     fn new(capacity: usize) -> Self {
-        // Ensure at least 1 page in cache
-        let num_pages = (capacity / 4096).max(1);
-        let cache_size = NonZeroUsize::new(num_pages).unwrap();
         MemoryBackend {
-            cache: LruCache::new(cache_size),
+            cache: Mutex::new(LruCache::unbounded()),
             capacity,
             used: 0,
+            evicted: Vec::new(),
         }
     }
     
     fn read_key(&self, key: u64, size: usize) -> Result<Vec<u8>, String> {
-        if let Some(data) = self.cache.peek(&key) {
-            if data.len() >= size {
-                Ok(data[..size].to_vec())
-            } else {
-                Err(format!("Cached data too small: {} < {}", data.len(), size))
-            }
+        let mut cache = self.cache.lock().unwrap();
+        // Entries whose spill failed are still readable until it is retried
+        let data = match cache.get(&key) {
+            Some(data) => data,
+            None => self.evicted.iter().rev()
+                .find(|(evicted_key, _)| *evicted_key == key)
+                .map(|(_, data)| data)
+                .ok_or_else(|| "Key not found in DRAM cache".to_string())?,
+        };
+        
+        if data.len() >= size {
+            Ok(data[..size].to_vec())
         } else {
-            Err("Key not found in DRAM cache".to_string())
+            Err(format!("Cached data too small: {} < {}", data.len(), size))
         }
     }
     
     fn write_key(&mut self, key: u64, data: &[u8]) -> Result<(), String> {
-        let data_vec = data.to_vec();
-        let data_size = data_vec.len();
-        
-        // Check if we have space
-        if self.used + data_size > self.capacity {
-            // LRU eviction will happen automatically
-            if let Some((_, evicted)) = self.cache.push(key, data_vec) {
-                self.used -= evicted.len();
+        let data_size = data.len();
+        if data_size > self.capacity {
+            return Err(format!(
+                "Value of {} bytes exceeds DRAM capacity of {} bytes",
+                data_size, self.capacity
+            ));
+        }
+        
+        let cache = self.cache.get_mut().unwrap();
+        
+        // Replacing a key frees its old bytes first, and supersedes any
+        // evicted copy still waiting to be spilled
+        if let Some(old) = cache.pop(&key) {
+            self.used -= old.len();
+        }
+        self.evicted.retain(|(evicted_key, _)| *evicted_key != key);
+        
+        // Evict least recently used entries until the new value fits
+        while self.used + data_size > self.capacity {
+            match cache.pop_lru() {
+                Some((evicted_key, evicted)) => {
+                    self.used -= evicted.len();
+                    self.evicted.push((evicted_key, evicted));
+                }
+                None => break,
             }
-        } else {
-            self.cache.put(key, data_vec);
         }
         
+        cache.put(key, data.to_vec());
         self.used += data_size;
         Ok(())
     }
     
+    /// Take the entries evicted since the last call
+    fn take_evicted(&mut self) -> Vec<(u64, Vec<u8>)> {
+        std::mem::take(&mut self.evicted)
+    }
+    
+    /// Return evicted entries that could not be spilled, to be retried later
+    fn restore_evicted(&mut self, mut unspilled: Vec<(u64, Vec<u8>)>) {
+        unspilled.append(&mut self.evicted);
+        self.evicted = unspilled;
+    }
+    
     fn available_space(&self) -> usize {
         self.capacity.saturating_sub(self.used)
     }
+    
+    /// Current usage in bytes
+    pub fn used(&self) -> usize {
+        self.used
+    }
+    
+    /// Total capacity in bytes
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    
+    /// Check whether a key is resident in the cache
+    pub fn contains(&self, key: u64) -> bool {
+        self.cache.lock().unwrap().contains(&key)
+    }
 }
 
 impl StorageBackend for MemoryBackend {
@@ -244,7 +417,10 @@ impl StorageBackend for MemoryBackend {
     }
     
     fn write(&mut self, offset: u64, data: &[u8]) -> Result<(), String> {
-        self.write_key(offset, data)
+        // Used on its own, the cache has no lower tier to spill to
+        self.write_key(offset, data)?;
+        self.evicted.clear();
+        Ok(())
     }
     
     fn latency_ns(&self) -> u64 {
@@ -276,6 +452,7 @@ impl FileBackend {
             files: HashMap::new(),
             next_file_id: 0,
             file_size_limit: 1024 * 1024 * 1024, // 1GB per file
+            read_count: 0,
         }
     }
     
@@ -286,6 +463,7 @@ impl FileBackend {
                 .read(true)
                 .write(true)
                 .create(true)
+                .truncate(false)
                 .open(&file_path)
                 .map_err(|e| format!("Failed to open file: {}", e))?;
             
@@ -296,6 +474,7 @@ impl FileBackend {
     }
     
     fn read_from_file(&mut self, file_id: u32, offset: u64, buf: &mut [u8]) -> Result<(), String> {
+        self.read_count += 1;
         let file = self.get_or_create_file(file_id)?;
         
         file.seek(SeekFrom::Start(offset))
@@ -322,6 +501,11 @@ impl FileBackend {
         Ok(())
     }
     
+    /// Number of reads issued so far (each may span several pages)
+    pub fn read_count(&self) -> u64 {
+        self.read_count
+    }
+    
     pub fn allocate_space(&mut self, size: u64) -> Result<(u32, u64), String> {
         // Simple allocation: append to current file
         let file_id = self.next_file_id;
@@ -366,6 +550,329 @@ impl StorageBackend for FileBackend {
     }
 }
 
+const NET_READ: u8 = 0;
+const NET_WRITE: u8 = 1;
+const NET_OK: u8 = 0;
+const NET_ERR: u8 = 1;
+
+impl NetworkBackend {
+    /// Create a backend whose `StorageBackend` interface addresses `default_node`
+    pub fn new(default_node: impl Into<String>) -> Self {
+        NetworkBackend {
+            default_node: default_node.into(),
+            connections: Mutex::new(HashMap::new()),
+        }
+    }
+    
+    /// Read `buf.len()` bytes at `offset` from the block store at `node`
+    pub fn read_from_node(&self, node: &str, offset: u64, buf: &mut [u8]) -> Result<(), String> {
+        self.request(node, NET_READ, offset, buf.len(), &[], buf)
+    }
+    
+    /// Write `data` at `offset` to the block store at `node`
+    pub fn write_to_node(&self, node: &str, offset: u64, data: &[u8]) -> Result<(), String> {
+        self.request(node, NET_WRITE, offset, data.len(), data, &mut [])
+    }
+    
+    /// Send one request and read its reply, dropping the connection on failure
+    /// so the next request reconnects
+    fn request(&self, node: &str, op: u8, offset: u64, len: usize, data: &[u8], reply: &mut [u8]) -> Result<(), String> {
+        let mut connections = self.connections.lock().unwrap();
+        if !connections.contains_key(node) {
+            connections.insert(node.to_string(), connect(node)?);
+        }
+        let stream = connections.get_mut(node).unwrap();
+        
+        let result = exchange(stream, op, offset, len, data, reply);
+        if let Err(NetError::Io(_)) = result {
+            connections.remove(node);
+        }
+        result.map_err(|e| match e {
+            NetError::Io(e) => format!("Network I/O with {} failed: {}", node, e),
+            NetError::Remote(message) => format!("Node {} rejected request: {}", node, message),
+        })
+    }
+}
+
+enum NetError {
+    Io(std::io::Error),
+    Remote(String),
+}
+
+impl From<std::io::Error> for NetError {
+    fn from(e: std::io::Error) -> Self {
+        NetError::Io(e)
+    }
+}
+
+fn connect(node: &str) -> Result<TcpStream, String> {
+    let addr = node.to_socket_addrs()
+        .map_err(|e| format!("Invalid node address {}: {}", node, e))?
+        .next()
+        .ok_or_else(|| format!("Node address {} resolved to nothing", node))?;
+    let stream = TcpStream::connect_timeout(&addr, Duration::from_secs(5))
+        .map_err(|e| format!("Failed to connect to {}: {}", node, e))?;
+    stream.set_read_timeout(Some(Duration::from_secs(30))).ok();
+    stream.set_nodelay(true).ok();
+    Ok(stream)
+}
+
+fn exchange(
+    stream: &mut TcpStream,
+    op: u8,
+    offset: u64,
+    len: usize,
+    data: &[u8],
+    reply: &mut [u8],
+) -> Result<(), NetError> {
+    let mut request = Vec::with_capacity(17 + data.len());
+    request.push(op);
+    request.extend_from_slice(&offset.to_le_bytes());
+    request.extend_from_slice(&(len as u64).to_le_bytes());
+    request.extend_from_slice(data);
+    stream.write_all(&request)?;
+    
+    let mut status = [0u8; 1];
+    stream.read_exact(&mut status)?;
+    if status[0] == NET_OK {
+        stream.read_exact(reply)?;
+        return Ok(());
+    }
+    
+    let mut message_len = [0u8; 4];
+    stream.read_exact(&mut message_len)?;
+    let mut message = vec![0u8; u32::from_le_bytes(message_len) as usize];
+    stream.read_exact(&mut message)?;
+    Err(NetError::Remote(String::from_utf8_lossy(&message).into_owned()))
+}
+
+impl StorageBackend for NetworkBackend {
+    fn read(&self, offset: u64, buf: &mut [u8]) -> Result<(), String> {
+        self.read_from_node(&self.default_node, offset, buf)
+    }
+    
+    fn write(&mut self, offset: u64, data: &[u8]) -> Result<(), String> {
+        self.write_to_node(&self.default_node, offset, data)
+    }
+    
+    fn latency_ns(&self) -> u64 {
+        500_000 // 500μs round trip within a datacenter
+    }
+    
+    fn bandwidth_mbps(&self) -> u64 {
+        1_250 // 10 Gbit/s link
+    }
+    
+    fn persistent(&self) -> bool {
+        true // The remote node keeps the data
+    }
+    
+    fn name(&self) -> &str {
+        "Network"
+    }
+}
+
+impl NetworkBlockServer {
+    /// Largest request the server accepts, to bound memory use
+    const MAX_REQUEST: u64 = 64 * 1024 * 1024;
+    
+    /// Largest store a server started with `bind` grows to
+    pub const DEFAULT_MAX_STORE: u64 = 1024 * 1024 * 1024;
+    
+    /// Start serving an empty block store on `addr` (port 0 picks a free port)
+    ///
+    /// Unwritten bytes read as zero. The server stops when dropped.
+    pub fn bind(addr: &str) -> Result<Self, String> {
+        Self::bind_with_capacity(addr, Self::DEFAULT_MAX_STORE)
+    }
+    
+    /// Start serving an empty block store that holds at most `max_store` bytes
+    ///
+    /// Writes reaching past `max_store` get an error reply and store nothing.
+    pub fn bind_with_capacity(addr: &str, max_store: u64) -> Result<Self, String> {
+        let listener = TcpListener::bind(addr)
+            .map_err(|e| format!("Failed to bind {}: {}", addr, e))?;
+        let addr = listener.local_addr()
+            .map_err(|e| format!("Failed to get bound address: {}", e))?;
+        let stopped = Arc::new(AtomicBool::new(false));
+        let store = Arc::new(Mutex::new(Vec::new()));
+        
+        let stop = stopped.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                if stop.load(Ordering::SeqCst) {
+                    break;
+                }
+                if let Ok(stream) = stream {
+                    let store = store.clone();
+                    std::thread::spawn(move || Self::serve(stream, store, max_store));
+                }
+            }
+        });
+        
+        Ok(NetworkBlockServer { addr, stopped })
+    }
+    
+    /// Address clients should use as the node
+    pub fn node(&self) -> String {
+        self.addr.to_string()
+    }
+    
+    fn serve(mut stream: TcpStream, store: Arc<Mutex<Vec<u8>>>, max_store: u64) {
+        let mut header = [0u8; 17];
+        while stream.read_exact(&mut header).is_ok() {
+            let offset = u64::from_le_bytes(header[1..9].try_into().unwrap());
+            let len = u64::from_le_bytes(header[9..17].try_into().unwrap());
+            let end = offset.checked_add(len).filter(|_| len <= Self::MAX_REQUEST);
+            let Some(end) = end else {
+                Self::reject(&mut stream, "request too large").ok();
+                return;
+            };
+            let (offset, end) = (offset as usize, end as usize);
+            
+            let reply = match header[0] {
+                NET_READ => {
+                    let store = store.lock().unwrap();
+                    let mut data = vec![0u8; end - offset];
+                    if offset < store.len() {
+                        let available = end.min(store.len());
+                        data[..available - offset].copy_from_slice(&store[offset..available]);
+                    }
+                    stream.write_all(&[NET_OK]).and_then(|_| stream.write_all(&data))
+                }
+                NET_WRITE => {
+                    let mut data = vec![0u8; end - offset];
+                    if stream.read_exact(&mut data).is_err() {
+                        return;
+                    }
+                    if end as u64 > max_store {
+                        let message = format!("write ends at {}, past the {} byte store limit", end, max_store);
+                        if Self::reject(&mut stream, &message).is_err() {
+                            return;
+                        }
+                        continue;
+                    }
+                    let mut store = store.lock().unwrap();
+                    if store.len() < end {
+                        store.resize(end, 0);
+                    }
+                    store[offset..end].copy_from_slice(&data);
+                    stream.write_all(&[NET_OK])
+                }
+                op => Self::reject(&mut stream, &format!("unknown opcode {}", op)),
+            };
+            if reply.is_err() {
+                return;
+            }
+        }
+    }
+    
+    fn reject(stream: &mut TcpStream, message: &str) -> std::io::Result<()> {
+        stream.write_all(&[NET_ERR])?;
+        stream.write_all(&(message.len() as u32).to_le_bytes())?;
+        stream.write_all(message.as_bytes())
+    }
+}
+
+impl Drop for NetworkBlockServer {
+    fn drop(&mut self) {
+        // Wake the accept loop so it sees the flag
+        self.stopped.store(true, Ordering::SeqCst);
+        TcpStream::connect(self.addr).ok();
+    }
+}
+
+impl MemoryObjectStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Keys of the objects stored in `bucket`, sorted
+    pub fn keys(&self, bucket: &str) -> Vec<String> {
+        let mut keys: Vec<String> = self.objects.lock().unwrap().keys()
+            .filter(|(b, _)| b == bucket)
+            .map(|(_, key)| key.clone())
+            .collect();
+        keys.sort();
+        keys
+    }
+}
+
+impl ObjectStore for MemoryObjectStore {
+    fn put(&self, bucket: &str, key: &str, data: &[u8]) -> Result<(), String> {
+        self.objects.lock().unwrap().insert((bucket.to_string(), key.to_string()), data.to_vec());
+        Ok(())
+    }
+    
+    fn get(&self, bucket: &str, key: &str) -> Result<Vec<u8>, String> {
+        self.objects.lock().unwrap().get(&(bucket.to_string(), key.to_string()))
+            .cloned()
+            .ok_or_else(|| format!("No object {} in bucket {}", key, bucket))
+    }
+}
+
+impl S3Backend {
+    /// Create a backend storing objects in `bucket` under `prefix` through `client`
+    pub fn new(bucket: impl Into<String>, prefix: impl Into<String>, client: Arc<dyn ObjectStore>) -> Self {
+        S3Backend {
+            bucket: bucket.into(),
+            prefix: prefix.into(),
+            client,
+        }
+    }
+    
+    /// Object key holding the page stored under `key`
+    pub fn object_key(&self, key: &str) -> String {
+        let prefix = self.prefix.trim_end_matches('/');
+        if prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}/{}", prefix, key)
+        }
+    }
+    
+    /// Download the object for `key` and copy its first `buf.len()` bytes
+    pub fn read_object(&self, key: &str, buf: &mut [u8]) -> Result<(), String> {
+        let object = self.client.get(&self.bucket, &self.object_key(key))?;
+        let data = object.get(..buf.len())
+            .ok_or_else(|| format!("Object {} too small: {} < {}", key, object.len(), buf.len()))?;
+        buf.copy_from_slice(data);
+        Ok(())
+    }
+    
+    /// Upload `data` as the whole object for `key`
+    pub fn write_object(&self, key: &str, data: &[u8]) -> Result<(), String> {
+        self.client.put(&self.bucket, &self.object_key(key), data)
+    }
+}
+
+impl StorageBackend for S3Backend {
+    fn read(&self, offset: u64, buf: &mut [u8]) -> Result<(), String> {
+        // For the cold tier, offset names the object
+        self.read_object(&offset.to_string(), buf)
+    }
+    
+    fn write(&mut self, offset: u64, data: &[u8]) -> Result<(), String> {
+        self.write_object(&offset.to_string(), data)
+    }
+    
+    fn latency_ns(&self) -> u64 {
+        50_000_000 // 50ms first-byte latency for an object GET
+    }
+    
+    fn bandwidth_mbps(&self) -> u64 {
+        100 // 100 MB/s for a single object stream
+    }
+    
+    fn persistent(&self) -> bool {
+        true // Objects are durable
+    }
+    
+    fn name(&self) -> &str {
+        "S3"
+    }
+}
+
 // Placeholder for random number generation
 mod rand {
     pub fn random() -> u64 {
@@ -393,6 +900,173 @@ mod tests {
         assert_eq!(&data, b"Hello");
     }
     
+    #[test]
+    fn test_memory_backend_byte_budget() {
+        let mut backend = MemoryBackend::new(100);
+        
+        // Variable-size values that together exceed the capacity
+        backend.write_key(1, &[1u8; 40]).unwrap();
+        backend.write_key(2, &[2u8; 30]).unwrap();
+        backend.write_key(3, &[3u8; 20]).unwrap();
+        assert_eq!(backend.used(), 90);
+        
+        // 90 + 25 > 100: evicting key 1 (the LRU entry) is enough to fit
+        backend.write_key(4, &[4u8; 25]).unwrap();
+        assert!(backend.used() <= backend.capacity());
+        assert_eq!(backend.used(), 75);
+        assert!(!backend.contains(1));
+        assert!(backend.contains(2));
+        assert!(backend.contains(3));
+        assert!(backend.contains(4));
+        
+        // A large value evicts as many entries as needed, regardless of count
+        backend.write_key(5, &[5u8; 80]).unwrap();
+        assert!(backend.used() <= backend.capacity());
+        assert_eq!(backend.used(), 80);
+        assert_eq!(backend.read_key(5, 80).unwrap(), vec![5u8; 80]);
+        
+        // Overwriting a key replaces its bytes instead of double counting
+        backend.write_key(5, &[6u8; 10]).unwrap();
+        assert_eq!(backend.used(), 10);
+        
+        // Values larger than the whole budget are rejected
+        assert!(backend.write_key(6, &[0u8; 101]).is_err());
+        assert!(backend.used() <= backend.capacity());
+    }
+    
+    #[test]
+    fn test_network_backend_round_trip() {
+        let server = NetworkBlockServer::bind("127.0.0.1:0").unwrap();
+        let mut backends = StorageBackends::with_local_dir(1024, "./test_data_network");
+        backends.network = Some(Arc::new(RwLock::new(NetworkBackend::new(server.node()))));
+        
+        let location = StorageLocation::Network { node: server.node(), offset: 100 };
+        backends.write(&location, b"remote page").unwrap();
+        assert_eq!(backends.read(&location, 11).unwrap(), b"remote page");
+        
+        // Unwritten remote bytes read as zero
+        let beyond = StorageLocation::Network { node: server.node(), offset: 4096 };
+        assert_eq!(backends.read(&beyond, 4).unwrap(), vec![0u8; 4]);
+        
+        let network = backends.network.as_ref().unwrap().read().unwrap();
+        let mut buf = [0u8; 6];
+        network.read(100, &mut buf).unwrap();
+        assert_eq!(&buf, b"remote");
+        drop(network);
+        
+        std::fs::remove_dir_all("./test_data_network").ok();
+    }
+    
+    #[test]
+    fn test_network_server_caps_store_size() {
+        let server = NetworkBlockServer::bind_with_capacity("127.0.0.1:0", 64).unwrap();
+        let backend = NetworkBackend::new(server.node());
+        
+        let err = backend.write_to_node(&server.node(), 60, &[1u8; 8]).unwrap_err();
+        assert!(err.contains("past the 64 byte store limit"), "{}", err);
+        let err = backend.write_to_node(&server.node(), u64::MAX / 2, &[1u8; 8]).unwrap_err();
+        assert!(err.contains("store limit"), "{}", err);
+        
+        // The connection stays usable for requests within the limit
+        backend.write_to_node(&server.node(), 56, &[2u8; 8]).unwrap();
+        let mut buf = [0u8; 8];
+        backend.read_from_node(&server.node(), 56, &mut buf).unwrap();
+        assert_eq!(buf, [2u8; 8]);
+    }
+    
+    #[test]
+    fn test_network_backend_reports_connection_failures() {
+        let backend = NetworkBackend::new("127.0.0.1:1");
+        let mut buf = [0u8; 4];
+        assert!(backend.read(0, &mut buf).unwrap_err().contains("Failed to connect"));
+        assert!(backend.read_from_node("not an address", 0, &mut buf).unwrap_err().contains("Invalid node address"));
+        
+        let backends = StorageBackends::with_local_dir(1024, "./test_data_no_network");
+        let location = StorageLocation::Network { node: "127.0.0.1:1".to_string(), offset: 0 };
+        assert_eq!(backends.read(&location, 4).unwrap_err(), "No network backend configured");
+        std::fs::remove_dir_all("./test_data_no_network").ok();
+    }
+    
+    #[test]
+    fn test_memory_backend_stays_within_budget() {
+        let mut backend = MemoryBackend::new(4096);
+        
+        // Mixed sizes, as compressed pages produce, with overwrites of live keys
+        for i in 0..200u64 {
+            let size = 1 + (i * 37 % 1500) as usize;
+            backend.write_key(i % 17, &vec![i as u8; size]).unwrap();
+            assert!(backend.used() <= backend.capacity());
+            
+            let cache = backend.cache.lock().unwrap();
+            let resident: usize = (0..17).filter_map(|k| cache.peek(&k))
+                .map(|data| data.len())
+                .sum();
+            assert_eq!(backend.used(), resident);
+        }
+    }
+    
+    #[test]
+    fn test_memory_backend_reads_refresh_recency() {
+        let mut backend = MemoryBackend::new(100);
+        backend.write_key(1, &[1u8; 40]).unwrap();
+        backend.write_key(2, &[2u8; 40]).unwrap();
+        
+        // Reading key 1 makes key 2 the least recently used
+        backend.read_key(1, 40).unwrap();
+        backend.write_key(3, &[3u8; 40]).unwrap();
+        assert!(backend.contains(1));
+        assert!(!backend.contains(2));
+    }
+    
+    #[test]
+    fn test_failed_spill_keeps_evicted_pages() {
+        let dir = std::env::temp_dir().join(format!("pvm-failed-spill-{}", std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::write(&dir, b"not a directory").unwrap();
+        
+        let mut backends = StorageBackends::with_local_dir(100, &dir);
+        let dram = |key| StorageLocation::Dram { key };
+        backends.write(&dram(1), &[1u8; 40]).unwrap();
+        backends.write(&dram(2), &[2u8; 40]).unwrap();
+        
+        // Both evicted pages fail to spill but stay readable
+        assert!(backends.write(&dram(3), &[3u8; 90]).is_err());
+        assert!(backends.take_spilled().is_empty());
+        assert_eq!(backends.read(&dram(1), 40).unwrap(), vec![1u8; 40]);
+        assert_eq!(backends.read(&dram(2), 40).unwrap(), vec![2u8; 40]);
+        
+        // Once local storage is usable, the next spill retries them
+        std::fs::remove_file(&dir).unwrap();
+        std::fs::create_dir_all(&dir).unwrap();
+        backends.write(&dram(4), &[4u8; 90]).unwrap();
+        let spilled = backends.take_spilled();
+        assert_eq!(spilled.iter().map(|(key, _)| *key).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(backends.read(&spilled[1].1, 40).unwrap(), vec![2u8; 40]);
+        
+        std::fs::remove_dir_all(&dir).ok();
+    }
+    
+    #[test]
+    fn test_cold_tier_round_trip() {
+        let store = Arc::new(MemoryObjectStore::new());
+        let mut backends = StorageBackends::with_local_dir(1024, "./test_data_cold");
+        backends.cold = Some(Arc::new(RwLock::new(S3Backend::new("tapes", "run-1/pages/", store.clone()))));
+        
+        let page: Vec<u8> = (0..64).collect();
+        let location = StorageLocation::Cold { key: "page-7".to_string() };
+        backends.write(&location, &page).unwrap();
+        
+        assert_eq!(store.keys("tapes"), vec!["run-1/pages/page-7"]);
+        assert_eq!(backends.read(&location, 64).unwrap(), page);
+        assert_eq!(backends.read(&location, 10).unwrap(), page[..10]);
+        assert!(backends.read(&location, 65).unwrap_err().contains("too small"));
+        
+        let missing = StorageLocation::Cold { key: "page-8".to_string() };
+        assert!(backends.read(&missing, 1).is_err());
+        
+        std::fs::remove_dir_all("./test_data_cold").ok();
+    }
+    
     #[test]
     fn test_file_backend() {
         let mut backend = FileBackend::new("./test_data");
@@ -0,0 +1,63 @@
+//! Page compression for SDM storage
+//!
+//! Pages the policy marks for compression are stored as zstd frames. Only
+//! zstd is linked in; pages under any other algorithm are stored raw.
+
+use crate::tape::sdm::policy::{CompressionAlgorithm, CompressionPolicy};
+
+/// Compress a page, or `None` if it should be stored raw
+///
+/// Raw storage is used when the algorithm is unavailable or compression
+/// would not make the page smaller.
+pub fn compress(policy: &CompressionPolicy, data: &[u8]) -> Option<Vec<u8>> {
+    let compressed = match policy.algorithm {
+        CompressionAlgorithm::Zstd => zstd::bulk::compress(data, policy.level as i32).ok()?,
+        CompressionAlgorithm::None | CompressionAlgorithm::Lz4 | CompressionAlgorithm::Snappy => {
+            return None;
+        }
+    };
+    (compressed.len() < data.len()).then_some(compressed)
+}
+
+/// Restore a page written by `compress`
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, String> {
+    zstd::decode_all(data)
+        .map_err(|e| format!("Failed to decompress page: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn zstd_policy() -> CompressionPolicy {
+        CompressionPolicy {
+            enabled: true,
+            algorithm: CompressionAlgorithm::Zstd,
+            threshold: 0,
+            level: 3,
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let page = [0xABu8; 4096];
+        let compressed = compress(&zstd_policy(), &page).unwrap();
+        assert!(compressed.len() < page.len());
+        assert_eq!(decompress(&compressed).unwrap(), page);
+    }
+
+    #[test]
+    fn test_incompressible_and_unavailable_stay_raw() {
+        let mut state = 0x2545F4914F6CDD1Du64;
+        let noise: Vec<u8> = (0..4096).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        }).collect();
+        assert!(compress(&zstd_policy(), &noise).is_none());
+        
+        let lz4 = CompressionPolicy { algorithm: CompressionAlgorithm::Lz4, ..zstd_policy() };
+        assert!(compress(&lz4, &[0u8; 4096]).is_none());
+    }
+}
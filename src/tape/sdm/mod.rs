@@ -6,18 +6,26 @@
 
 pub mod address_space;
 pub mod backends;
+pub mod compression;
 pub mod page_table;
 pub mod policy;
 pub mod predictor;
 
+use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
 
 pub use address_space::{VirtualAddressSpace, Region, PolicyHint};
-pub use backends::{StorageBackend, StorageBackends, StorageLocation};
+pub use backends::{
+    MemoryObjectStore, NetworkBackend, NetworkBlockServer, ObjectStore, S3Backend, StorageBackend,
+    StorageBackends, StorageLocation,
+};
 pub use page_table::{PageTable, PageEntry};
 pub use policy::{MemoryPolicy, PlacementRule};
 pub use predictor::AccessPredictor;
 
+/// Host callback supplying the contents of a never-written page
+pub type FaultHandler = Box<dyn Fn(i64) -> Option<Vec<u8>> + Send + Sync>;
+
 /// The main SDM tape implementation
 pub struct SdmTape {
     /// Virtual address space (no actual memory allocated)
@@ -35,6 +43,9 @@ pub struct SdmTape {
     /// Access pattern learning and prediction
     predictor: Arc<RwLock<AccessPredictor>>,
     
+    /// Lazily populates unallocated pages on access
+    fault_handler: Arc<RwLock<Option<FaultHandler>>>,
+    
     /// Configuration
     config: SdmConfig,
 }
@@ -56,6 +67,9 @@ pub struct SdmConfig {
     
     /// Compression threshold in bytes
     pub compression_threshold: usize,
+    
+    /// Directory holding the local storage tier's files
+    pub local_storage_dir: PathBuf,
 }
 
 impl Default for SdmConfig {
@@ -66,6 +80,7 @@ impl Default for SdmConfig {
             prefetch_depth: 5,                  // Prefetch 5 pages ahead
             enable_compression: true,
             compression_threshold: 64 * 1024,   // Compress pages > 64KB
+            local_storage_dir: PathBuf::from("./palindrome_data"),
         }
     }
 }
@@ -81,9 +96,13 @@ impl SdmTape {
         SdmTape {
             address_space: Arc::new(RwLock::new(VirtualAddressSpace::new(config.page_size))),
             policy: Arc::new(MemoryPolicy::default()),
-            backends: Arc::new(RwLock::new(StorageBackends::new(config.dram_cache_size))),
+            backends: Arc::new(RwLock::new(StorageBackends::with_local_dir(
+                config.dram_cache_size,
+                &config.local_storage_dir,
+            ))),
             page_table: Arc::new(RwLock::new(PageTable::new())),
             predictor: Arc::new(RwLock::new(AccessPredictor::new())),
+            fault_handler: Arc::new(RwLock::new(None)),
             config,
         }
     }
@@ -101,31 +120,50 @@ impl SdmTape {
         let page_table = self.page_table.read().unwrap();
         let backends = self.backends.read().unwrap();
         
-        // Read each page
-        for page_num in start_page..=end_page {
-            let page_data = self.read_page(&page_table, &backends, page_num)?;
-            
-            // Calculate offsets within the page
-            let page_start = page_num * self.config.page_size as i64;
-            let offset_in_page = if page_num == start_page {
-                (pos - page_start) as usize
+        // Read each run of pages, coalescing pages stored back-to-back on disk
+        let mut page_num = start_page;
+        while page_num <= end_page {
+            let run = self.local_run_length(&page_table, page_num, end_page);
+            let run_data = if run > 1 {
+                let location = &page_table.get_page(page_num).unwrap().location;
+                backends.read(location, run * self.config.page_size)?
             } else {
-                0
+                self.read_page(&page_table, &backends, page_num)?
             };
             
-            let bytes_from_page = if page_num == end_page {
-                let end_offset = ((pos + len as i64) - page_start) as usize;
-                end_offset - offset_in_page
-            } else {
-                self.config.page_size - offset_in_page
-            };
+            for (i, page_data) in run_data.chunks(self.config.page_size).enumerate() {
+                let page_num = page_num + i as i64;
+                
+                // Calculate offsets within the page
+                let page_start = page_num * self.config.page_size as i64;
+                let offset_in_page = if page_num == start_page {
+                    (pos - page_start) as usize
+                } else {
+                    0
+                };
+                
+                let bytes_from_page = if page_num == end_page {
+                    let end_offset = ((pos + len as i64) - page_start) as usize;
+                    end_offset - offset_in_page
+                } else {
+                    self.config.page_size - offset_in_page
+                };
+                
+                result.extend_from_slice(&page_data[offset_in_page..offset_in_page + bytes_from_page]);
+            }
             
-            result.extend_from_slice(&page_data[offset_in_page..offset_in_page + bytes_from_page]);
+            page_num += run as i64;
         }
         
-        // Trigger prefetch if sequential access detected
-        if let Some(prefetch_pages) = self.predictor.read().unwrap().suggest_prefetch(end_page) {
-            self.prefetch_pages(prefetch_pages);
+        drop(backends);
+        drop(page_table);
+        
+        // Trigger prefetch if sequential access detected; prefetching is best effort
+        if self.policy.prefetch.enabled && self.policy.prefetch.sequential {
+            let suggested = self.predictor.read().unwrap().suggest_prefetch(end_page);
+            if let Some(prefetch_pages) = suggested {
+                self.prefetch_pages(prefetch_pages).ok();
+            }
         }
         
         Ok(result)
@@ -201,15 +239,16 @@ impl SdmTape {
     pub fn read_at_ic(&self, pos: i64, len: usize, ic: u64) -> Result<Vec<u8>, String> {
         // Find the version of pages at the given IC
         let page_table = self.page_table.read().unwrap();
+        let backends = self.backends.read().unwrap();
         let start_page = pos / self.config.page_size as i64;
         let end_page = (pos + len as i64 - 1) / self.config.page_size as i64;
         
         let mut result = Vec::with_capacity(len);
         
         for page_num in start_page..=end_page {
-            // Get version at IC
-            let page_data = page_table.read_at_ic(page_num, ic)
-                .ok_or_else(|| format!("No data for page {} at IC {}", page_num, ic))?;
+            // Get version at IC; pages not yet written read as they did before any write
+            let page_data = page_table.read_at_ic(page_num, ic, &backends)?
+                .unwrap_or_else(|| self.fault_page(page_num));
             
             // Calculate offsets (same as regular read)
             let page_start = page_num * self.config.page_size as i64;
@@ -236,6 +275,7 @@ impl SdmTape {
     pub fn read_at_time(&self, pos: i64, len: usize, timestamp: u64) -> Result<Vec<u8>, String> {
         // Find the version of pages at the given timestamp
         let page_table = self.page_table.read().unwrap();
+        let backends = self.backends.read().unwrap();
         let start_page = pos / self.config.page_size as i64;
         let end_page = (pos + len as i64 - 1) / self.config.page_size as i64;
         
@@ -243,7 +283,7 @@ impl SdmTape {
         
         for page_num in start_page..=end_page {
             // Get historical version of the page
-            let page_data = page_table.read_historical(page_num, timestamp)
+            let page_data = page_table.read_historical(page_num, timestamp, &backends)?
                 .ok_or_else(|| format!("No historical data for page {} at time {}", page_num, timestamp))?;
             
             // Calculate offsets (same as regular read)
@@ -267,107 +307,309 @@ impl SdmTape {
         Ok(result)
     }
     
+    /// Install a page-fault handler for pages that were never written
+    ///
+    /// The handler receives the page number and may return the page's bytes
+    /// (padded or truncated to the page size); `None` falls back to zeros.
+    /// Supplied bytes are not cached, but a partial write to a faulted page
+    /// starts from them.
+    pub fn set_fault_handler(&self, handler: impl Fn(i64) -> Option<Vec<u8>> + Send + Sync + 'static) {
+        *self.fault_handler.write().unwrap() = Some(Box::new(handler));
+    }
+    
+    /// Get the memory placement policy
+    pub fn policy(&self) -> &MemoryPolicy {
+        &self.policy
+    }
+    
+    /// Replace the memory placement policy
+    pub fn set_policy(&mut self, policy: MemoryPolicy) {
+        self.policy = Arc::new(policy);
+    }
+    
+    /// Define a region of the virtual address space with a placement hint
+    pub fn define_region(&self, start: i64, size: usize, hint: PolicyHint, name: Option<String>) -> Result<(), String> {
+        self.address_space.write().unwrap().define_region(start, size, hint, name)
+    }
+    
+    /// How many of a named region's pages have been materialized, out of its total
+    pub fn region_materialization(&self, region_name: &str) -> Option<(usize, usize)> {
+        let address_space = self.address_space.read().unwrap();
+        let region = address_space.find_region(region_name)?;
+        if region.size == 0 {
+            return Some((0, 0));
+        }
+        
+        let (start_page, end_page) = address_space.page_range(region.start, region.size);
+        let materialized = self.page_table.read().unwrap().materialized_in(start_page, end_page);
+        Some((materialized, (end_page - start_page + 1) as usize))
+    }
+    
     /// Mark current position for quick seeking
     pub fn mark(&self, label: String, position: i64) -> Result<(), String> {
         self.address_space.write().unwrap().mark(label, position);
         Ok(())
     }
     
-    /// Create a checkpoint of current state
+    /// Create a checkpoint of current state in the page table and predictor
     pub fn checkpoint(&self, name: String) -> Result<(), String> {
-        self.page_table.write().unwrap().create_checkpoint(name);
+        self.page_table.write().unwrap().create_checkpoint(name.clone());
+        self.predictor.write().unwrap().record_checkpoint(name);
         Ok(())
     }
     
-    /// Internal: Read a single page
-    fn read_page(&self, page_table: &PageTable, backends: &StorageBackends, page_num: i64) -> Result<Vec<u8>, String> {
-        if let Some(entry) = page_table.get_page(page_num) {
-            backends.read(&entry.location, self.config.page_size)
-        } else {
-            // Page doesn't exist, return zeros
-            Ok(vec![0u8; self.config.page_size])
+    /// Whether the page table holds a checkpoint with this name
+    pub fn has_checkpoint(&self, name: &str) -> bool {
+        self.page_table.read().unwrap().has_checkpoint(name)
+    }
+    
+    /// Restore all pages to their contents at the named checkpoint
+    pub fn rewind(&self, name: &str) -> Result<(), String> {
+        let changed = self.page_table.write().unwrap().restore_checkpoint(name)?;
+        self.predictor.write().unwrap().record_rewind(changed.clone(), changed);
+        self.prefetch_rewind_targets().ok();
+        Ok(())
+    }
+    
+    /// Bring the pages past rewinds went on to touch into DRAM, if the policy
+    /// prefetches temporal patterns
+    ///
+    /// Returns the number of pages moved.
+    pub fn prefetch_rewind_targets(&self) -> Result<usize, String> {
+        if !self.policy.prefetch.enabled || !self.policy.prefetch.temporal {
+            return Ok(0);
         }
+        let targets = self.predictor.read().unwrap().predict_rewind_targets();
+        self.prefetch_pages(targets)
     }
     
-    /// Internal: Write to a page
-    fn write_page(&self, page_num: i64, offset: usize, data: &[u8]) -> Result<(), String> {
+    /// Number of pages modified since they were last flushed
+    pub fn dirty_count(&self) -> usize {
+        self.page_table.read().unwrap().dirty_pages().len()
+    }
+    
+    /// Persist every dirty page to the local storage tier
+    ///
+    /// Pages held only in DRAM are copied to local files and relocated there;
+    /// pages already on local storage were written through and are just marked
+    /// clean. Flushing adds no page versions. Returns the number of pages flushed.
+    pub fn flush(&self) -> Result<usize, String> {
         let mut page_table = self.page_table.write().unwrap();
         let mut backends = self.backends.write().unwrap();
         
-        // Get or create page entry
-        let entry = page_table.get_or_create_page(page_num);
+        let dirty = page_table.dirty_pages();
+        for &page_num in &dirty {
+            let entry = page_table.get_page(page_num).unwrap();
+            let location = entry.location.clone();
+            if let StorageLocation::Local { .. } = location {
+                page_table.clear_dirty(page_num);
+                continue;
+            }
+            
+            let data = backends.read(&location, entry.size)?;
+            let (file_id, offset) = backends.local.write().unwrap()
+                .allocate_space(data.len() as u64)?;
+            let local = StorageLocation::Local { file_id, offset };
+            backends.write(&local, &data)?;
+            // The contents are unchanged, so this is a move rather than a new version
+            page_table.relocate(&location, &local);
+            page_table.clear_dirty(page_num);
+        }
         
-        // Read existing page data if partial write or page already exists
-        let mut page_data = if entry.location != StorageLocation::Unallocated && (offset > 0 || data.len() < self.config.page_size) {
-            backends.read(&entry.location, self.config.page_size)?
-        } else {
-            vec![0u8; self.config.page_size]
-        };
+        Ok(dirty.len())
+    }
+    
+    /// Apply up to `limit` of the page table's migration suggestions
+    ///
+    /// Each page is copied to fresh storage in the suggested tier (hot pages
+    /// from local files into DRAM, cold pages the other way) and its entry is
+    /// repointed there; the old copy stays in place for any checkpoint or
+    /// historical version still referring to it. Pages the policy pins are
+    /// skipped. Returns the number of pages moved.
+    pub fn run_migration_pass(&self, limit: usize) -> Result<usize, String> {
+        let mut page_table = self.page_table.write().unwrap();
+        let mut backends = self.backends.write().unwrap();
         
-        // Update page data
-        page_data[offset..offset + data.len()].copy_from_slice(data);
+        let mut moved = 0;
+        for (page_num, suggestion) in page_table.suggest_migrations(limit) {
+            let entry = page_table.get_page(page_num).unwrap().clone();
+            if self.policy.should_pin(&entry, &self.address_space.read().unwrap()) {
+                continue;
+            }
+            
+            let target = match suggestion.target {
+                StorageLocation::Dram { .. } => StorageLocation::Dram { key: page_table.allocate_dram_key() },
+                StorageLocation::Local { .. } => {
+                    let (file_id, offset) = backends.local.write().unwrap()
+                        .allocate_space(entry.size as u64)?;
+                    StorageLocation::Local { file_id, offset }
+                }
+                other => return Err(format!("Cannot migrate page {} to {:?}", page_num, other)),
+            };
+            
+            let data = backends.read(&entry.location, entry.size)?;
+            backends.write_unspilled(&target, &data)?;
+            page_table.get_page_mut(page_num).unwrap().location = target;
+            let spilled = backends.spill_evicted();
+            Self::apply_spills(&mut page_table, &mut backends);
+            spilled?;
+            moved += 1;
+        }
         
-        // Allocate storage if needed
-        let location = if entry.location == StorageLocation::Unallocated {
-            // Allocate new storage - for now, always use DRAM with page number as key
-            StorageLocation::Dram { key: page_num as u64 }
-        } else {
-            entry.location.clone()
+        Ok(moved)
+    }
+    
+    /// Internal: Count how many pages starting at `page_num` sit contiguously
+    /// in the same local file, so they can be fetched with one read
+    fn local_run_length(&self, page_table: &PageTable, page_num: i64, end_page: i64) -> usize {
+        let (file_id, mut offset) = match page_table.get_page(page_num) {
+            Some(PageEntry { location: StorageLocation::Local { file_id, offset }, compressed: false, .. }) => {
+                (*file_id, *offset)
+            }
+            _ => return 1,
         };
         
-        // Write to location
-        backends.write(&location, &page_data)?;
-        
-        // Update page table
-        entry.location = location;
-        entry.increment_version();
-        entry.update_access_time();
-        
-        Ok(())
+        let mut run = 1;
+        for next in page_num + 1..=end_page {
+            offset += self.config.page_size as u64;
+            match page_table.get_page(next) {
+                Some(PageEntry { location: StorageLocation::Local { file_id: f, offset: o }, compressed: false, .. })
+                    if *f == file_id && *o == offset =>
+                {
+                    run += 1;
+                }
+                _ => break,
+            }
+        }
+        run
+    }
+    
+    /// Internal: Read a single page
+    fn read_page(&self, page_table: &PageTable, backends: &StorageBackends, page_num: i64) -> Result<Vec<u8>, String> {
+        match page_table.get_page(page_num) {
+            Some(entry) if entry.location != StorageLocation::Unallocated => {
+                backends.read_page(&entry.location, entry.size, entry.compressed)
+            }
+            _ => Ok(self.fault_page(page_num)),
+        }
+    }
+    
+    /// Internal: Contents of a never-written page, from the fault handler or zeros
+    fn fault_page(&self, page_num: i64) -> Vec<u8> {
+        let mut data = self.fault_handler.read().unwrap()
+            .as_ref()
+            .and_then(|handler| handler(page_num))
+            .unwrap_or_default();
+        data.resize(self.config.page_size, 0);
+        data
+    }
+    
+    /// Internal: Write to a page
+    fn write_page(&self, page_num: i64, offset: usize, data: &[u8]) -> Result<(), String> {
+        let mut page_table = self.page_table.write().unwrap();
+        page_table.record_write(page_num);
+        self.store_page(&mut page_table, page_num, offset, data)
     }
     
     /// Internal: Write to a page with instruction counter
     fn write_page_with_ic(&self, page_num: i64, offset: usize, data: &[u8], ic: u64) -> Result<(), String> {
         let mut page_table = self.page_table.write().unwrap();
+        page_table.record_write_with_ic(page_num, ic, &self.backends.read().unwrap());
+        self.store_page(&mut page_table, page_num, offset, data)
+    }
+    
+    /// Internal: Merge `data` into a page's contents and store the result
+    ///
+    /// Pages still referenced by a checkpoint are written to fresh storage so
+    /// the checkpointed contents survive for a rewind. Pages the policy marks
+    /// for compression are stored compressed when that makes them smaller.
+    fn store_page(&self, page_table: &mut PageTable, page_num: i64, offset: usize, data: &[u8]) -> Result<(), String> {
         let mut backends = self.backends.write().unwrap();
         
-        // Record write with IC
-        page_table.record_write_with_ic(page_num, ic);
-        
-        // Get the updated entry
-        let entry = page_table.get_or_create_page(page_num);
+        let existing = page_table.get_or_create_page(page_num).clone();
         
         // Read existing page data if partial write or page already exists
-        let mut page_data = if entry.location != StorageLocation::Unallocated && (offset > 0 || data.len() < self.config.page_size) {
-            backends.read(&entry.location, self.config.page_size)?
-        } else {
+        let partial = offset > 0 || data.len() < self.config.page_size;
+        let mut page_data = if !partial {
             vec![0u8; self.config.page_size]
+        } else if existing.location != StorageLocation::Unallocated {
+            backends.read_page(&existing.location, existing.size, existing.compressed)?
+        } else {
+            self.fault_page(page_num)
         };
         
         // Update page data
         page_data[offset..offset + data.len()].copy_from_slice(data);
         
-        // Allocate storage if needed
-        let location = if entry.location == StorageLocation::Unallocated {
-            // Allocate new storage - for now, always use DRAM with page number as key
-            StorageLocation::Dram { key: page_num as u64 }
+        let candidate = PageEntry { size: page_data.len(), ..existing.clone() };
+        let compressed = if self.policy.should_compress(&candidate, &self.address_space.read().unwrap()) {
+            compression::compress(&self.policy.compression, &page_data)
         } else {
-            entry.location.clone()
+            None
         };
+        let stored = compressed.as_deref().unwrap_or(&page_data);
+        
+        // A local slot can't hold more than was stored there before
+        let outgrows_slot = matches!(existing.location, StorageLocation::Local { .. })
+            && stored.len() > existing.size;
+        let location = if existing.location == StorageLocation::Unallocated
+            || outgrows_slot
+            || page_table.is_pinned(page_num)
+        {
+            StorageLocation::Dram { key: page_table.allocate_dram_key() }
+        } else {
+            existing.location
+        };
+        backends.write_unspilled(&location, stored)?;
         
-        // Write to location
-        backends.write(&location, &page_data)?;
-        
-        // Update location
+        let entry = page_table.get_or_create_page(page_num);
+        entry.content_hash = page_table::content_hash(&page_data);
+        entry.size = stored.len();
+        entry.compressed = compressed.is_some();
         entry.location = location;
         
-        Ok(())
+        // The page is written and the table points at it, even if the spill fails
+        let spilled = backends.spill_evicted();
+        Self::apply_spills(page_table, &mut backends);
+        spilled
+    }
+    
+    /// Internal: Repoint pages whose DRAM copy was evicted to local storage
+    fn apply_spills(page_table: &mut PageTable, backends: &mut StorageBackends) {
+        for (key, local) in backends.take_spilled() {
+            page_table.relocate(&StorageLocation::Dram { key }, &local);
+        }
     }
     
     /// Prefetch pages based on access prediction
-    fn prefetch_pages(&self, _pages: Vec<i64>) {
-        // TODO: Implement async prefetching
-        // This would spawn a background task to load pages into DRAM
+    ///
+    /// Pages stored below DRAM are copied into it and repointed there, leaving
+    /// the old copy for any version still referring to it. Returns the number
+    /// of pages moved.
+    fn prefetch_pages(&self, pages: Vec<i64>) -> Result<usize, String> {
+        let mut page_table = self.page_table.write().unwrap();
+        let mut backends = self.backends.write().unwrap();
+        
+        let mut moved = 0;
+        for page_num in pages {
+            let Some(entry) = page_table.get_page(page_num) else {
+                continue;
+            };
+            if matches!(entry.location, StorageLocation::Dram { .. } | StorageLocation::Unallocated) {
+                continue;
+            }
+            
+            let data = backends.read(&entry.location, entry.size)?;
+            let location = StorageLocation::Dram { key: page_table.allocate_dram_key() };
+            backends.write_unspilled(&location, &data)?;
+            page_table.get_page_mut(page_num).unwrap().location = location;
+            let spilled = backends.spill_evicted();
+            Self::apply_spills(&mut page_table, &mut backends);
+            spilled?;
+            moved += 1;
+        }
+        
+        Ok(moved)
     }
 }
 
@@ -408,4 +650,367 @@ mod tests {
         let data = tape.read(8, 5).unwrap();
         assert_eq!(&data, b"Hello");
     }
+    
+    /// Build a tape with small pages whose local files live in a private directory
+    fn local_test_tape(name: &str) -> (SdmTape, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("pvm_{}_{}", name, std::process::id()));
+        let config = SdmConfig {
+            page_size: 16,
+            local_storage_dir: dir.clone(),
+            ..Default::default()
+        };
+        (SdmTape::with_config(config), dir)
+    }
+    
+    /// Store a page's bytes in the local file tier and point the page table at them
+    fn place_in_local(tape: &SdmTape, page_num: i64, data: &[u8]) {
+        let mut backends = tape.backends.write().unwrap();
+        let (file_id, offset) = backends.local.write().unwrap()
+            .allocate_space(data.len() as u64).unwrap();
+        let location = StorageLocation::Local { file_id, offset };
+        backends.write(&location, data).unwrap();
+        let mut page_table = tape.page_table.write().unwrap();
+        let entry = page_table.get_or_create_page(page_num);
+        entry.location = location;
+        entry.size = data.len();
+    }
+    
+    #[test]
+    fn test_fault_handler_supplies_unwritten_pages() {
+        let config = SdmConfig {
+            page_size: 16,
+            ..Default::default()
+        };
+        let tape = SdmTape::with_config(config);
+        tape.set_fault_handler(|page_num| Some(vec![0xA0 | page_num as u8; 16]));
+        
+        tape.write(16, &[1u8; 16]).unwrap();
+        tape.write(34, &[2u8; 2]).unwrap(); // Partial write over a faulted page
+        
+        assert_eq!(tape.read(0, 16).unwrap(), vec![0xA0; 16]);
+        assert_eq!(tape.read(16, 16).unwrap(), vec![1u8; 16]);
+        assert_eq!(tape.read(32, 6).unwrap(), vec![0xA2, 0xA2, 2, 2, 0xA2, 0xA2]);
+        assert_eq!(tape.read(80, 4).unwrap(), vec![0xA5; 4]);
+    }
+    
+    #[test]
+    fn test_identical_writes_share_history() {
+        let config = SdmConfig {
+            page_size: 16,
+            ..Default::default()
+        };
+        let tape = SdmTape::with_config(config);
+        
+        for ic in 1..=10 {
+            tape.write_with_ic(0, &[7u8; 16], ic).unwrap();
+        }
+        let table = tape.page_table.read().unwrap();
+        assert_eq!(table.history_depth(0), 1);
+        let idempotent_version = table.get_page(0).unwrap().version;
+        drop(table);
+        
+        for ic in 1..=5 {
+            tape.write_with_ic(16, &[ic as u8; 16], ic).unwrap();
+        }
+        let table = tape.page_table.read().unwrap();
+        assert_eq!(table.history_depth(1), 4);
+        
+        // Versions still advance for every write
+        assert!(idempotent_version >= 10);
+    }
+    
+    #[test]
+    fn test_read_at_ic_returns_overwritten_versions() {
+        let config = SdmConfig {
+            page_size: 16,
+            ..Default::default()
+        };
+        let tape = SdmTape::with_config(config);
+        
+        tape.write_with_ic(0, &[0xA; 16], 1).unwrap();
+        tape.write_with_ic(0, &[0xB; 16], 5).unwrap();
+        // A partial write starts from the current contents, not the historical ones
+        tape.write_with_ic(4, &[0xC; 2], 9).unwrap();
+        
+        assert_eq!(tape.read_at_ic(0, 16, 1).unwrap(), vec![0; 16]);
+        assert_eq!(tape.read_at_ic(0, 16, 3).unwrap(), vec![0xA; 16]);
+        assert_eq!(tape.read_at_ic(0, 16, 7).unwrap(), vec![0xB; 16]);
+        
+        let mut latest = vec![0xB; 16];
+        latest[4..6].copy_from_slice(&[0xC; 2]);
+        assert_eq!(tape.read_at_ic(0, 16, 10).unwrap(), latest);
+        assert_eq!(tape.read(0, 16).unwrap(), latest);
+    }
+    
+    #[test]
+    fn test_migration_pass_moves_hot_and_cold_pages() {
+        let (tape, dir) = local_test_tape("migrate");
+        place_in_local(&tape, 0, &[1u8; 16]);
+        tape.write(16, &[2u8; 16]).unwrap();
+        {
+            let mut table = tape.page_table.write().unwrap();
+            table.get_page_mut(0).unwrap().stats.frequency = 50.0;
+            table.get_page_mut(1).unwrap().stats.last_access = 0;
+        }
+        
+        assert_eq!(tape.run_migration_pass(10).unwrap(), 2);
+        let location = |page_num| tape.page_table.read().unwrap().get_page(page_num).unwrap().location.clone();
+        assert!(matches!(location(0), StorageLocation::Dram { .. }));
+        assert!(matches!(location(1), StorageLocation::Local { .. }));
+        assert_eq!(tape.read(0, 32).unwrap(), [[1u8; 16], [2u8; 16]].concat());
+        
+        std::fs::remove_dir_all(dir).ok();
+    }
+    
+    #[test]
+    fn test_migration_skips_pinned_pages() {
+        let (mut tape, dir) = local_test_tape("migrate_pinned");
+        let mut policy = MemoryPolicy::balanced();
+        policy.rules.push(PlacementRule {
+            condition: policy::Condition::Always,
+            action: policy::PlacementAction::Pin,
+            priority: 1000,
+        });
+        tape.set_policy(policy);
+        
+        place_in_local(&tape, 0, &[1u8; 16]);
+        tape.page_table.write().unwrap().get_page_mut(0).unwrap().stats.frequency = 50.0;
+        
+        assert_eq!(tape.run_migration_pass(10).unwrap(), 0);
+        let table = tape.page_table.read().unwrap();
+        assert!(matches!(table.get_page(0).unwrap().location, StorageLocation::Local { .. }));
+        drop(table);
+        
+        std::fs::remove_dir_all(dir).ok();
+    }
+    
+    #[test]
+    fn test_dram_eviction_spills_to_local() {
+        let dir = std::env::temp_dir().join(format!("pvm-sdm-spill-{}", std::process::id()));
+        let config = SdmConfig {
+            page_size: 16,
+            dram_cache_size: 32,
+            local_storage_dir: dir.clone(),
+            ..Default::default()
+        };
+        let tape = SdmTape::with_config(config);
+        
+        for page in 0..6u8 {
+            tape.write(page as i64 * 16, &[page + 1; 16]).unwrap();
+        }
+        
+        let table = tape.page_table.read().unwrap();
+        assert!(matches!(table.get_page(0).unwrap().location, StorageLocation::Local { .. }));
+        assert!(matches!(table.get_page(5).unwrap().location, StorageLocation::Dram { .. }));
+        drop(table);
+        for page in 0..6u8 {
+            assert_eq!(tape.read(page as i64 * 16, 16).unwrap(), vec![page + 1; 16]);
+        }
+        
+        std::fs::remove_dir_all(dir).ok();
+    }
+    
+    #[test]
+    fn test_failed_spill_still_records_the_write() {
+        let dir = std::env::temp_dir().join(format!("pvm-sdm-failed-spill-{}", std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::write(&dir, b"not a directory").unwrap();
+        let config = SdmConfig {
+            page_size: 16,
+            dram_cache_size: 32,
+            local_storage_dir: dir.clone(),
+            ..Default::default()
+        };
+        let tape = SdmTape::with_config(config);
+        
+        tape.write(0, &[1; 16]).unwrap();
+        tape.write(16, &[2; 16]).unwrap();
+        // The write lands in DRAM; only demoting page 0 fails
+        assert!(tape.write(32, &[3; 16]).is_err());
+        assert!(matches!(tape.page_table.read().unwrap().get_page(2).unwrap().location, StorageLocation::Dram { .. }));
+        for page in 0..3u8 {
+            assert_eq!(tape.read(page as i64 * 16, 16).unwrap(), vec![page + 1; 16]);
+        }
+        
+        // Once local storage works, the next write spills the backlog
+        std::fs::remove_file(&dir).unwrap();
+        std::fs::create_dir_all(&dir).unwrap();
+        tape.write(48, &[4; 16]).unwrap();
+        assert!(matches!(tape.page_table.read().unwrap().get_page(0).unwrap().location, StorageLocation::Local { .. }));
+        for page in 0..4u8 {
+            assert_eq!(tape.read(page as i64 * 16, 16).unwrap(), vec![page + 1; 16]);
+        }
+        
+        std::fs::remove_dir_all(dir).ok();
+    }
+    
+    #[test]
+    fn test_compressible_pages_are_stored_compressed() {
+        let mut tape = SdmTape::new();
+        let mut policy = MemoryPolicy::balanced();
+        policy.compression.threshold = 1024;
+        tape.set_policy(policy);
+        
+        tape.write(0, &[7u8; 4096]).unwrap();
+        tape.write(100, &[9u8; 8]).unwrap(); // Partial write over a compressed page
+        
+        let entry = tape.page_table.read().unwrap().get_page(0).unwrap().clone();
+        assert!(entry.compressed);
+        assert!(entry.size < 4096);
+        
+        let mut expected = vec![7u8; 4096];
+        expected[100..108].copy_from_slice(&[9u8; 8]);
+        assert_eq!(tape.read(0, 4096).unwrap(), expected);
+        
+        // A page that doesn't shrink is stored raw
+        let mut state = 0x2545F4914F6CDD1Du64;
+        let noise: Vec<u8> = (0..4096).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        }).collect();
+        tape.write(4096, &noise).unwrap();
+        let entry = tape.page_table.read().unwrap().get_page(1).unwrap().clone();
+        assert!(!entry.compressed);
+        assert_eq!(entry.size, 4096);
+        assert_eq!(tape.read(4096, 4096).unwrap(), noise);
+    }
+    
+    #[test]
+    fn test_rewind_prefetches_predicted_pages() {
+        let (mut tape, dir) = local_test_tape("rewind_prefetch");
+        for page in 0..4 {
+            place_in_local(&tape, page, &[page as u8; 16]);
+        }
+        tape.checkpoint("start".to_string()).unwrap();
+        
+        // Past rewinds went on to touch pages 1 and 3
+        for _ in 0..3 {
+            tape.predictor.write().unwrap().record_rewind(vec![0], vec![1, 3]);
+        }
+        let in_dram = |tape: &SdmTape, page_num| matches!(
+            tape.page_table.read().unwrap().get_page(page_num).unwrap().location,
+            StorageLocation::Dram { .. }
+        );
+        
+        // The default policy doesn't prefetch temporal patterns
+        tape.rewind("start").unwrap();
+        assert!(!in_dram(&tape, 1));
+        
+        tape.set_policy(MemoryPolicy::performance());
+        tape.rewind("start").unwrap();
+        assert!(in_dram(&tape, 1));
+        assert!(in_dram(&tape, 3));
+        assert!(!in_dram(&tape, 0));
+        assert!(!in_dram(&tape, 2));
+        assert_eq!(tape.read(16, 16).unwrap(), vec![1u8; 16]);
+        assert_eq!(tape.read(48, 16).unwrap(), vec![3u8; 16]);
+        
+        std::fs::remove_dir_all(dir).ok();
+    }
+    
+    #[test]
+    fn test_region_materialization() {
+        let config = SdmConfig {
+            page_size: 16,
+            ..Default::default()
+        };
+        let tape = SdmTape::with_config(config);
+        tape.define_region(160, 16 * 1000, PolicyHint::Heap, Some("big".to_string())).unwrap();
+        assert_eq!(tape.region_materialization("big"), Some((0, 1000)));
+        
+        tape.write(160, b"first").unwrap();
+        tape.write(160 + 16 * 500, b"middle").unwrap();
+        tape.write(160 + 16 * 999 + 15, b"x").unwrap();
+        tape.write(0, b"outside").unwrap();
+        
+        assert_eq!(tape.region_materialization("big"), Some((3, 1000)));
+        assert_eq!(tape.region_materialization("missing"), None);
+    }
+    
+    #[test]
+    fn test_dirty_pages_and_flush() {
+        let (tape, dir) = local_test_tape("dirty");
+        assert_eq!(tape.dirty_count(), 0);
+        
+        tape.write(0, &[1u8; 4]).unwrap();
+        tape.write(40, &[2u8; 20]).unwrap(); // Pages 2 and 3
+        assert_eq!(tape.page_table.read().unwrap().dirty_pages(), vec![0, 2, 3]);
+        assert_eq!(tape.dirty_count(), 3);
+        
+        assert_eq!(tape.flush().unwrap(), 3);
+        assert_eq!(tape.dirty_count(), 0);
+        
+        // Flushed pages now live on local storage and still read back
+        let entry_location = tape.page_table.read().unwrap().get_page(2).unwrap().location.clone();
+        assert!(matches!(entry_location, StorageLocation::Local { .. }));
+        assert_eq!(tape.read(40, 20).unwrap(), vec![2u8; 20]);
+        
+        tape.write(1, &[3u8]).unwrap();
+        assert_eq!(tape.page_table.read().unwrap().dirty_pages(), vec![0]);
+        
+        std::fs::remove_dir_all(dir).ok();
+    }
+    
+    #[test]
+    fn test_flush_adds_no_history() {
+        let (tape, dir) = local_test_tape("flush_history");
+        tape.write_with_ic(0, &[1u8; 16], 1).unwrap();
+        tape.write_with_ic(0, &[2u8; 16], 2).unwrap();
+        
+        let before = {
+            let table = tape.page_table.read().unwrap();
+            (table.history_depth(0), table.get_page(0).unwrap().version)
+        };
+        tape.flush().unwrap();
+        
+        let table = tape.page_table.read().unwrap();
+        assert_eq!((table.history_depth(0), table.get_page(0).unwrap().version), before);
+        let backends = tape.backends.read().unwrap();
+        assert_eq!(table.read_at_ic(0, 2, &backends).unwrap(), Some(vec![1u8; 16]));
+        assert_eq!(table.read_at_ic(0, 3, &backends).unwrap(), Some(vec![2u8; 16]));
+        drop((table, backends));
+        
+        std::fs::remove_dir_all(dir).ok();
+    }
+    
+    #[test]
+    fn test_contiguous_local_pages_coalesce_reads() {
+        let (tape, dir) = local_test_tape("coalesce");
+        
+        let pages: Vec<Vec<u8>> = (0..8u8).map(|i| vec![i + 1; 16]).collect();
+        for (i, page) in pages.iter().enumerate() {
+            place_in_local(&tape, i as i64, page);
+        }
+        
+        let data = tape.read(4, 16 * 7).unwrap();
+        assert_eq!(data, pages.concat()[4..4 + 16 * 7]);
+        
+        // Eight contiguous pages are fetched with a single file read
+        let reads = tape.backends.read().unwrap().local.read().unwrap().read_count();
+        assert_eq!(reads, 1);
+        
+        std::fs::remove_dir_all(dir).ok();
+    }
+    
+    #[test]
+    fn test_non_contiguous_local_pages_read_correctly() {
+        let (tape, dir) = local_test_tape("scattered");
+        
+        // Pages 0 and 1 are separated on disk by an unrelated block
+        place_in_local(&tape, 0, &[1u8; 16]);
+        place_in_local(&tape, 10, &[9u8; 16]);
+        place_in_local(&tape, 1, &[2u8; 16]);
+        place_in_local(&tape, 2, &[3u8; 16]);
+        
+        let data = tape.read(0, 48).unwrap();
+        assert_eq!(data, [[1u8; 16], [2u8; 16], [3u8; 16]].concat());
+        
+        // Page 0 stands alone; pages 1 and 2 are adjacent and coalesce
+        let reads = tape.backends.read().unwrap().local.read().unwrap().read_count();
+        assert_eq!(reads, 2);
+        
+        std::fs::remove_dir_all(dir).ok();
+    }
 }
\ No newline at end of file
@@ -4,7 +4,7 @@
 //! historical versions for time-travel functionality.
 
 use std::collections::{BTreeMap, HashMap, VecDeque};
-use crate::tape::sdm::backends::StorageLocation;
+use crate::tape::sdm::backends::{StorageBackends, StorageLocation};
 
 /// Page table tracking all pages in the system
 #[derive(Debug)]
@@ -21,6 +21,9 @@ pub struct PageTable {
     /// Global version counter
     current_version: u64,
     
+    /// Next unused DRAM key for page storage
+    next_dram_key: u64,
+    
     /// Configuration
     max_history_per_page: usize,
 }
@@ -51,6 +54,9 @@ pub struct PageEntry {
     
     /// Size in bytes (may differ if compressed)
     pub size: usize,
+    
+    /// Hash of the page's current contents (see `content_hash`)
+    pub content_hash: u64,
 }
 
 /// Historical version of a page
@@ -73,6 +79,9 @@ pub struct HistoricalPage {
     
     /// Whether this version is compressed
     pub compressed: bool,
+    
+    /// Hash of this version's contents
+    pub content_hash: u64,
 }
 
 /// Page access statistics
@@ -122,6 +131,9 @@ pub struct CheckpointInfo {
     
     /// Pages modified since this checkpoint
     pub modified_pages: Vec<i64>,
+    
+    /// Page entries as they stood at the checkpoint
+    pub entries: BTreeMap<i64, PageEntry>,
 }
 
 impl PageTable {
@@ -132,6 +144,7 @@ impl PageTable {
             history: BTreeMap::new(),
             checkpoints: HashMap::new(),
             current_version: 0,
+            next_dram_key: 0,
             max_history_per_page: 10, // Keep last 10 versions
         }
     }
@@ -160,12 +173,13 @@ impl PageTable {
                 dirty: false,
                 compressed: false,
                 size: 4096, // Default page size
+                content_hash: content_hash(&[]),
             }
         })
     }
     
     /// Update page location and manage history
-    pub fn update_page_location(&mut self, page_num: i64, new_location: StorageLocation) {
+    pub fn update_page_location(&mut self, page_num: i64, new_location: StorageLocation, backends: &StorageBackends) {
         // Clone the entry if it exists and needs to be saved to history
         let needs_history = if let Some(entry) = self.entries.get(&page_num) {
             entry.location != StorageLocation::Unallocated && entry.location != new_location
@@ -175,7 +189,7 @@ impl PageTable {
         
         if needs_history {
             let entry = self.entries.get(&page_num).unwrap().clone();
-            self.add_to_history(entry);
+            self.add_to_history(entry, backends);
         }
         
         // Get next version before modifying entry
@@ -197,6 +211,28 @@ impl PageTable {
         }
     }
     
+    /// Mark a page as clean once its contents are persisted
+    pub fn clear_dirty(&mut self, page_num: i64) {
+        if let Some(entry) = self.entries.get_mut(&page_num) {
+            entry.dirty = false;
+        }
+    }
+    
+    /// Number of pages in `start_page..=end_page` backed by real storage
+    pub fn materialized_in(&self, start_page: i64, end_page: i64) -> usize {
+        self.entries.range(start_page..=end_page)
+            .filter(|(_, entry)| entry.location != StorageLocation::Unallocated)
+            .count()
+    }
+    
+    /// Page numbers with unflushed modifications, in ascending order
+    pub fn dirty_pages(&self) -> Vec<i64> {
+        self.entries.values()
+            .filter(|entry| entry.dirty)
+            .map(|entry| entry.page_num)
+            .collect()
+    }
+    
     /// Update access statistics
     pub fn record_access(&mut self, page_num: i64, is_write: bool) {
         if let Some(entry) = self.entries.get_mut(&page_num) {
@@ -220,19 +256,30 @@ impl PageTable {
         }
     }
     
-    /// Record a write with instruction counter
-    pub fn record_write_with_ic(&mut self, page_num: i64, ic: u64) {
-        // Need to create a new version before modifying
-        let needs_history = if let Some(entry) = self.entries.get(&page_num) {
-            entry.dirty && entry.written_at_ic != ic
-        } else {
-            false
-        };
+    /// Record a write, giving the page the next global version
+    pub fn record_write(&mut self, page_num: i64) {
+        let new_version = self.next_version();
+        let now = current_timestamp();
         
-        if needs_history {
-            if let Some(entry) = self.entries.remove(&page_num) {
-                self.add_to_history(entry);
-            }
+        let entry = self.get_or_create_page(page_num);
+        entry.version = new_version;
+        entry.dirty = true;
+        entry.stats.write_count += 1;
+        entry.stats.last_write = now;
+        entry.stats.last_access = now;
+    }
+    
+    /// Record a write with instruction counter
+    ///
+    /// A stored version written at an earlier IC moves to history; its storage
+    /// stays pinned there, so the new contents go to a fresh location.
+    pub fn record_write_with_ic(&mut self, page_num: i64, ic: u64, backends: &StorageBackends) {
+        if let Some(entry) = self.entries.get(&page_num)
+            && entry.location != StorageLocation::Unallocated
+            && entry.written_at_ic != ic
+        {
+            let entry = entry.clone();
+            self.add_to_history(entry, backends);
         }
         
         let new_version = self.next_version();
@@ -261,11 +308,75 @@ impl PageTable {
             version: self.current_version,
             created_at: current_timestamp(),
             modified_pages: Vec::new(),
+            entries: self.entries.clone(),
         };
         
         self.checkpoints.insert(name, checkpoint);
     }
     
+    /// Whether a checkpoint with this name exists
+    pub fn has_checkpoint(&self, name: &str) -> bool {
+        self.checkpoints.contains_key(name)
+    }
+    
+    /// Restore every page entry to its state at the named checkpoint
+    ///
+    /// Returns the pages whose entry changed. Version numbers keep increasing.
+    pub fn restore_checkpoint(&mut self, name: &str) -> Result<Vec<i64>, String> {
+        let snapshot = self.checkpoints.get(name)
+            .ok_or_else(|| format!("Unknown checkpoint: {}", name))?
+            .entries.clone();
+        
+        let mut changed: Vec<i64> = self.entries.iter()
+            .filter(|(page_num, entry)| {
+                snapshot.get(page_num).is_none_or(|old| old.version != entry.version)
+            })
+            .map(|(page_num, _)| *page_num)
+            .collect();
+        changed.extend(snapshot.keys().filter(|page_num| !self.entries.contains_key(page_num)));
+        changed.sort_unstable();
+        
+        self.entries = snapshot;
+        Ok(changed)
+    }
+    
+    /// Whether a checkpoint or historical version still refers to the page's
+    /// current storage, in which case the page must not be overwritten in place
+    pub fn is_pinned(&self, page_num: i64) -> bool {
+        let Some(entry) = self.entries.get(&page_num) else {
+            return false;
+        };
+        entry.location != StorageLocation::Unallocated
+            && (self.checkpoints.values().any(|cp| {
+                cp.entries.get(&page_num).is_some_and(|old| old.location == entry.location)
+            }) || self.history.get(&page_num).is_some_and(|history| {
+                history.iter().any(|old| old.location == entry.location)
+            }))
+    }
+    
+    /// Repoint every entry, historical version and checkpoint stored at `from`
+    /// to `to`, after the data has been moved there
+    pub fn relocate(&mut self, from: &StorageLocation, to: &StorageLocation) {
+        let entries = self.entries.values_mut()
+            .chain(self.checkpoints.values_mut().flat_map(|cp| cp.entries.values_mut()))
+            .map(|entry| &mut entry.location);
+        let historical = self.history.values_mut()
+            .flat_map(|history| history.iter_mut())
+            .map(|old| &mut old.location);
+        for location in entries.chain(historical) {
+            if location == from {
+                *location = to.clone();
+            }
+        }
+    }
+    
+    /// Hand out a DRAM key no other page version uses
+    pub fn allocate_dram_key(&mut self) -> u64 {
+        let key = self.next_dram_key;
+        self.next_dram_key += 1;
+        key
+    }
+    
     /// Get pages modified since a checkpoint
     pub fn get_modified_since_checkpoint(&self, checkpoint_name: &str) -> Option<Vec<i64>> {
         self.checkpoints.get(checkpoint_name).map(|cp| {
@@ -277,59 +388,71 @@ impl PageTable {
         })
     }
     
-    /// Read historical version of a page
-    pub fn read_historical(&self, page_num: i64, target_version: u64) -> Option<Vec<u8>> {
-        // Check if current version matches
-        if let Some(entry) = self.entries.get(&page_num) {
-            if entry.version <= target_version {
-                // Current version is what we want
-                // In real implementation, would read from storage
-                return Some(vec![0u8; entry.size]);
-            }
+    /// Read the newest version of a page at or before `target_version`
+    ///
+    /// Returns `None` if every version kept is newer.
+    pub fn read_historical(
+        &self,
+        page_num: i64,
+        target_version: u64,
+        backends: &StorageBackends,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if let Some(entry) = self.entries.get(&page_num)
+            && entry.version <= target_version
+        {
+            return backends.read_page(&entry.location, entry.size, entry.compressed).map(Some);
         }
         
-        // Search in history
-        if let Some(history) = self.history.get(&page_num) {
-            for historical in history.iter().rev() {
-                if historical.version <= target_version {
-                    // Found the right historical version
-                    // In real implementation, would read from storage
-                    return Some(vec![0u8; historical.size]);
-                }
-            }
+        // History is ordered newest first
+        let historical = self.history.get(&page_num)
+            .and_then(|history| history.iter().find(|old| old.version <= target_version));
+        match historical {
+            Some(old) => backends.read_page(&old.location, old.size, old.compressed).map(Some),
+            None => Ok(None),
         }
-        
-        None
     }
     
-    /// Read page at a specific instruction counter
-    pub fn read_at_ic(&self, page_num: i64, target_ic: u64) -> Option<Vec<u8>> {
-        // Check if current version is before target IC
-        if let Some(entry) = self.entries.get(&page_num) {
-            if entry.written_at_ic < target_ic {
-                // Current version is what we want
-                // In real implementation, would read from storage
-                return Some(vec![0u8; entry.size]);
-            }
+    /// Read a page as it stood before instruction `target_ic` wrote to it
+    ///
+    /// Returns `None` if the page had not been written by then.
+    pub fn read_at_ic(
+        &self,
+        page_num: i64,
+        target_ic: u64,
+        backends: &StorageBackends,
+    ) -> Result<Option<Vec<u8>>, String> {
+        if let Some(entry) = self.entries.get(&page_num)
+            && entry.written_at_ic < target_ic
+        {
+            return backends.read_page(&entry.location, entry.size, entry.compressed).map(Some);
         }
         
-        // Search in history for the most recent version before target IC
-        if let Some(history) = self.history.get(&page_num) {
-            for historical in history.iter() {
-                if historical.written_at_ic < target_ic {
-                    // Found the right historical version
-                    // In real implementation, would read from storage
-                    return Some(vec![0u8; historical.size]);
-                }
-            }
+        let historical = self.history.get(&page_num)
+            .and_then(|history| history.iter().find(|old| old.written_at_ic < target_ic));
+        match historical {
+            Some(old) => backends.read_page(&old.location, old.size, old.compressed).map(Some),
+            None => Ok(None),
         }
-        
-        // No version exists before this IC - return zeros
-        Some(vec![0u8; 4096])
+    }
+    
+    /// Number of historical versions kept for a page
+    pub fn history_depth(&self, page_num: i64) -> usize {
+        self.history.get(&page_num).map_or(0, |history| history.len())
     }
     
     /// Add a page to history
-    fn add_to_history(&mut self, entry: PageEntry) {
+    ///
+    /// A version whose contents match the most recent historical version is
+    /// not recorded again. Matching hashes are confirmed against the stored
+    /// bytes, so a hash collision never drops a distinct version.
+    fn add_to_history(&mut self, entry: PageEntry, backends: &StorageBackends) {
+        let history = self.history.entry(entry.page_num).or_default();
+        if history.front().is_some_and(|latest| {
+            latest.content_hash == entry.content_hash && same_contents(latest, &entry, backends)
+        }) {
+            return;
+        }
+        
         let historical = HistoricalPage {
             version: entry.version,
             written_at_ic: entry.written_at_ic,
@@ -337,9 +460,8 @@ impl PageTable {
             replaced_at: current_timestamp(),
             size: entry.size,
             compressed: entry.compressed,
+            content_hash: entry.content_hash,
         };
-        
-        let history = self.history.entry(entry.page_num).or_insert_with(VecDeque::new);
         history.push_front(historical);
         
         // Limit history size
@@ -364,9 +486,8 @@ impl PageTable {
                 continue;
             }
             
-            let suggestion = self.analyze_page_for_migration(entry);
-            if suggestion.is_some() {
-                suggestions.push((*page_num, suggestion.unwrap()));
+            if let Some(suggestion) = self.analyze_page_for_migration(entry) {
+                suggestions.push((*page_num, suggestion));
             }
         }
         
@@ -403,6 +524,12 @@ impl PageTable {
     }
 }
 
+impl Default for PageTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Suggestion for page migration
 #[derive(Debug)]
 pub struct MigrationSuggestion {
@@ -437,6 +564,29 @@ impl PageEntry {
     }
 }
 
+/// FNV-1a hash identifying page contents
+pub fn content_hash(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Whether a historical version and an entry store the same bytes
+///
+/// Unreadable storage counts as different, so the version is kept.
+fn same_contents(old: &HistoricalPage, entry: &PageEntry, backends: &StorageBackends) -> bool {
+    if old.location == entry.location && old.size == entry.size && old.compressed == entry.compressed {
+        return true;
+    }
+    match (
+        backends.read_page(&old.location, old.size, old.compressed),
+        backends.read_page(&entry.location, entry.size, entry.compressed),
+    ) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
 /// Get current timestamp in nanoseconds
 fn current_timestamp() -> u64 {
     std::time::SystemTime::now()
@@ -490,4 +640,33 @@ mod tests {
         assert_eq!(modified.len(), 1);
         assert_eq!(modified[0], 0);
     }
+    
+    #[test]
+    fn test_history_survives_hash_collision() {
+        let dir = std::env::temp_dir().join(format!("pvm_page_table_collision_{}", std::process::id()));
+        let mut backends = StorageBackends::with_local_dir(1 << 20, &dir);
+        backends.write(&StorageLocation::Dram { key: 1 }, &[1u8; 16]).unwrap();
+        backends.write(&StorageLocation::Dram { key: 2 }, &[2u8; 16]).unwrap();
+        backends.write(&StorageLocation::Dram { key: 3 }, &[2u8; 16]).unwrap();
+        
+        let mut table = PageTable::new();
+        // Every version claims the same hash, as if they collided
+        let store = |table: &mut PageTable, key: u64, ic: u64| {
+            table.record_write_with_ic(0, ic, &backends);
+            let entry = table.get_page_mut(0).unwrap();
+            entry.location = StorageLocation::Dram { key };
+            entry.size = 16;
+            entry.content_hash = 42;
+        };
+        store(&mut table, 2, 1);
+        store(&mut table, 3, 2);
+        store(&mut table, 1, 3);
+        // Keys 2 and 3 really hold the same bytes, so only one is kept
+        assert_eq!(table.history_depth(0), 1);
+        
+        // Different bytes behind the same hash are kept as a separate version
+        table.record_write_with_ic(0, 4, &backends);
+        assert_eq!(table.history_depth(0), 2);
+        std::fs::remove_dir_all(dir).ok();
+    }
 }
\ No newline at end of file
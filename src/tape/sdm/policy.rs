@@ -5,7 +5,7 @@
 
 use crate::tape::sdm::backends::{StorageLocation, StorageBackends};
 use crate::tape::sdm::page_table::{PageEntry, AccessPattern};
-use crate::tape::sdm::address_space::PolicyHint;
+use crate::tape::sdm::address_space::{PolicyHint, VirtualAddressSpace};
 
 /// Memory placement policy
 #[derive(Debug, Clone)]
@@ -301,18 +301,21 @@ impl MemoryPolicy {
     }
     
     /// Determine the best location for a page
-    pub fn determine_location(&self, entry: &PageEntry, backends: &StorageBackends) -> Result<StorageLocation, String> {
+    pub fn determine_location(
+        &self,
+        entry: &PageEntry,
+        space: &VirtualAddressSpace,
+        backends: &StorageBackends,
+    ) -> Result<StorageLocation, String> {
         // Evaluate rules in priority order
         let mut rules = self.rules.clone();
         rules.sort_by_key(|r| std::cmp::Reverse(r.priority));
         
         for rule in &rules {
-            if self.evaluate_condition(&rule.condition, entry) {
-                match &rule.action {
-                    PlacementAction::PlaceIn(tier) => {
-                        return self.get_location_for_tier(*tier, entry, backends);
-                    }
-                    _ => {} // Other actions don't determine location
+            if self.evaluate_condition(&rule.condition, entry, space) {
+                // Other actions don't determine location
+                if let PlacementAction::PlaceIn(tier) = &rule.action {
+                    return self.get_location_for_tier(*tier, entry, backends);
                 }
             }
         }
@@ -321,15 +324,35 @@ impl MemoryPolicy {
         Ok(StorageLocation::Local { file_id: 0, offset: 0 })
     }
     
+    /// Check if a page is pinned where it is and must not be migrated
+    ///
+    /// The highest-priority matching `Pin` or `Unpin` rule decides; pages are
+    /// unpinned by default.
+    pub fn should_pin(&self, entry: &PageEntry, space: &VirtualAddressSpace) -> bool {
+        let mut rules: Vec<&PlacementRule> = self.rules.iter().collect();
+        rules.sort_by_key(|r| std::cmp::Reverse(r.priority));
+        
+        for rule in rules {
+            if self.evaluate_condition(&rule.condition, entry, space) {
+                match rule.action {
+                    PlacementAction::Pin => return true,
+                    PlacementAction::Unpin => return false,
+                    _ => {}
+                }
+            }
+        }
+        false
+    }
+    
     /// Check if a page should be compressed
-    pub fn should_compress(&self, entry: &PageEntry) -> bool {
+    pub fn should_compress(&self, entry: &PageEntry, space: &VirtualAddressSpace) -> bool {
         if !self.compression.enabled || entry.size < self.compression.threshold {
             return false;
         }
         
         // Check compression rules
         for rule in &self.rules {
-            if self.evaluate_condition(&rule.condition, entry) {
+            if self.evaluate_condition(&rule.condition, entry, space) {
                 match &rule.action {
                     PlacementAction::Compress => return true,
                     PlacementAction::NoCompress => return false,
@@ -339,22 +362,19 @@ impl MemoryPolicy {
         }
         
         // Default based on policy hint
-        if let Some(hint) = self.get_hint_for_page(entry) {
-            hint.should_compress()
-        } else {
-            true
+        match space.page_hints(entry.page_num).first() {
+            Some(hint) => hint.should_compress(),
+            None => true,
         }
     }
     
     /// Evaluate a condition against a page entry
-    fn evaluate_condition(&self, condition: &Condition, entry: &PageEntry) -> bool {
+    fn evaluate_condition(&self, condition: &Condition, entry: &PageEntry, space: &VirtualAddressSpace) -> bool {
         match condition {
             Condition::Always => true,
             
-            Condition::HasHint(_hint) => {
-                // In real implementation, would check virtual address space
-                false // Placeholder
-            }
+            // True if any region overlapping the page carries the hint
+            Condition::HasHint(hint) => space.page_hints(entry.page_num).contains(hint),
             
             Condition::FrequencyAbove(threshold) => entry.stats.frequency > *threshold,
             Condition::FrequencyBelow(threshold) => entry.stats.frequency < *threshold,
@@ -375,14 +395,14 @@ impl MemoryPolicy {
             Condition::Pattern(pattern) => entry.stats.pattern == *pattern,
             
             Condition::And(a, b) => {
-                self.evaluate_condition(a, entry) && self.evaluate_condition(b, entry)
+                self.evaluate_condition(a, entry, space) && self.evaluate_condition(b, entry, space)
             }
             
             Condition::Or(a, b) => {
-                self.evaluate_condition(a, entry) || self.evaluate_condition(b, entry)
+                self.evaluate_condition(a, entry, space) || self.evaluate_condition(b, entry, space)
             }
             
-            Condition::Not(c) => !self.evaluate_condition(c, entry),
+            Condition::Not(c) => !self.evaluate_condition(c, entry, space),
         }
     }
     
@@ -395,11 +415,7 @@ impl MemoryPolicy {
             StorageTier::Cold => Err("Cold storage not implemented".to_string()),
         }
     }
-    
-    /// Get hint for a page (placeholder)
-    fn get_hint_for_page(&self, _entry: &PageEntry) -> Option<PolicyHint> {
-        None // In real implementation, would look up from address space
-    }
+
 }
 
 /// Macro for building policies declaratively
@@ -439,6 +455,7 @@ mod tests {
     #[test]
     fn test_condition_evaluation() {
         let policy = MemoryPolicy::balanced();
+        let space = VirtualAddressSpace::new(4096);
         let mut entry = PageEntry {
             page_num: 0,
             location: StorageLocation::Unallocated,
@@ -448,19 +465,53 @@ mod tests {
             dirty: false,
             compressed: false,
             size: 4096,
+            content_hash: 0,
         };
         
         // Test frequency condition
         entry.stats.frequency = 100.0;
         let condition = Condition::FrequencyAbove(50.0);
-        assert!(policy.evaluate_condition(&condition, &entry));
+        assert!(policy.evaluate_condition(&condition, &entry, &space));
         
         // Test AND condition
         let and_condition = Condition::And(
             Box::new(Condition::FrequencyAbove(50.0)),
             Box::new(Condition::SizeBelow(8192)),
         );
-        assert!(policy.evaluate_condition(&and_condition, &entry));
+        assert!(policy.evaluate_condition(&and_condition, &entry, &space));
+    }
+    
+    #[test]
+    fn test_code_hint_places_pages_in_dram() {
+        let policy = MemoryPolicy::performance();
+        let mut space = VirtualAddressSpace::new(4096);
+        space.define_region(2 * 4096 + 100, 64, PolicyHint::Code, Some("text".to_string())).unwrap();
+        let dir = std::env::temp_dir().join(format!("pvm-policy-hint-{}", std::process::id()));
+        let backends = StorageBackends::with_local_dir(1024, &dir);
+        
+        let mut entry = PageEntry {
+            page_num: 2,
+            location: StorageLocation::Unallocated,
+            version: 1,
+            written_at_ic: 0,
+            stats: Default::default(),
+            dirty: false,
+            compressed: false,
+            size: 8192,
+            content_hash: 0,
+        };
+        entry.stats.last_access = current_timestamp();
+        assert!(policy.evaluate_condition(&Condition::HasHint(PolicyHint::Code), &entry, &space));
+        assert!(matches!(policy.determine_location(&entry, &space, &backends).unwrap(), StorageLocation::Dram { .. }));
+        assert!(!policy.should_compress(&entry, &space));
+        
+        // The same recently used page outside the region lands on local storage
+        entry.page_num = 3;
+        assert!(!policy.evaluate_condition(&Condition::HasHint(PolicyHint::Code), &entry, &space));
+        assert!(matches!(policy.determine_location(&entry, &space, &backends).unwrap(), StorageLocation::Local { .. }));
+        assert!(policy.should_compress(&entry, &space));
+        
+        std::fs::remove_dir_all(dir).ok();
     }
     
     #[test]
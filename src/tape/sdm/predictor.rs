@@ -41,13 +41,22 @@ pub struct AccessRecord {
 }
 
 /// Markov chain for access prediction
+///
+/// Transitions are keyed on the last `order` pages accessed, so higher orders
+/// can tell apart patterns that pass through the same page.
 #[derive(Debug)]
 pub struct MarkovChain {
-    /// Transition matrix: current_page -> (next_page, count)
-    transitions: HashMap<i64, HashMap<i64, u32>>,
+    /// Number of preceding pages forming a context
+    order: usize,
     
-    /// Total transitions from each page
-    totals: HashMap<i64, u32>,
+    /// Most contexts kept before rarely-seen ones are pruned
+    max_contexts: usize,
+    
+    /// Transition matrix: context -> (next_page, count)
+    transitions: HashMap<Vec<i64>, HashMap<i64, u32>>,
+    
+    /// Total transitions from each context
+    totals: HashMap<Vec<i64>, u32>,
 }
 
 /// Sequential access pattern detector
@@ -116,6 +125,9 @@ pub struct PredictorConfig {
     
     /// Markov chain order (1 = first-order)
     pub markov_order: u32,
+    
+    /// Most Markov contexts remembered (at least 1)
+    pub max_markov_contexts: usize,
 }
 
 impl Default for PredictorConfig {
@@ -125,6 +137,7 @@ impl Default for PredictorConfig {
             min_confidence: 0.3,
             sequential_threshold: 3,
             markov_order: 1,
+            max_markov_contexts: 4096,
         }
     }
 }
@@ -139,7 +152,10 @@ impl AccessPredictor {
     pub fn with_config(config: PredictorConfig) -> Self {
         AccessPredictor {
             history: VecDeque::with_capacity(config.max_history),
-            markov_chain: MarkovChain::new(),
+            markov_chain: MarkovChain::new(
+                config.markov_order.max(1) as usize,
+                config.max_markov_contexts.max(1),
+            ),
             sequential_detector: SequentialDetector::new(),
             temporal_detector: TemporalDetector::new(),
             config,
@@ -148,6 +164,11 @@ impl AccessPredictor {
     
     /// Record an access
     pub fn record_access(&mut self, position: i64, length: usize, is_write: bool) {
+        let page = position / 4096; // Assuming 4KB pages
+        let context = self.recent_pages(self.markov_chain.order);
+        self.markov_chain.record_transition(&context, page);
+        self.sequential_detector.record_access(page);
+        
         let record = AccessRecord {
             position,
             length,
@@ -160,11 +181,6 @@ impl AccessPredictor {
         if self.history.len() > self.config.max_history {
             self.history.pop_front();
         }
-        
-        // Update detectors
-        let page = position / 4096; // Assuming 4KB pages
-        self.markov_chain.record_transition(self.last_page(), page);
-        self.sequential_detector.record_access(page);
     }
     
     /// Suggest pages to prefetch
@@ -176,8 +192,13 @@ impl AccessPredictor {
             suggestions.extend(pages);
         }
         
-        // Use Markov chain for additional predictions
-        let markov_predictions = self.markov_chain.predict_next(current_page, self.config.min_confidence);
+        // Use Markov chain for additional predictions, in the context of the
+        // pages leading up to the current one
+        let mut context = self.recent_pages(self.markov_chain.order);
+        if let Some(last) = context.last_mut() {
+            *last = current_page;
+        }
+        let markov_predictions = self.markov_chain.predict_next(&context, self.config.min_confidence);
         for (page, confidence) in markov_predictions {
             if confidence > self.config.min_confidence && !suggestions.contains(&page) {
                 suggestions.push(page);
@@ -245,31 +266,55 @@ impl AccessPredictor {
             .collect()
     }
     
-    /// Get the last accessed page
-    fn last_page(&self) -> i64 {
-        self.history.back()
+    /// The last `count` pages accessed, oldest first
+    fn recent_pages(&self, count: usize) -> Vec<i64> {
+        let skip = self.history.len().saturating_sub(count);
+        self.history.iter()
+            .skip(skip)
             .map(|r| r.position / 4096)
-            .unwrap_or(0)
+            .collect()
+    }
+}
+
+impl Default for AccessPredictor {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl MarkovChain {
-    fn new() -> Self {
+    fn new(order: usize, max_contexts: usize) -> Self {
         MarkovChain {
+            order,
+            max_contexts,
             transitions: HashMap::new(),
             totals: HashMap::new(),
         }
     }
     
-    fn record_transition(&mut self, from: i64, to: i64) {
-        let transitions = self.transitions.entry(from).or_insert_with(HashMap::new);
+    /// Record that `to` followed the pages in `context` (oldest first)
+    ///
+    /// Only the last `order` pages are used; shorter contexts are ignored.
+    fn record_transition(&mut self, context: &[i64], to: i64) {
+        let Some(key) = self.key(context) else {
+            return;
+        };
+        if !self.transitions.contains_key(&key) && self.transitions.len() >= self.max_contexts {
+            self.prune();
+        }
+        
+        let transitions = self.transitions.entry(key.clone()).or_default();
         *transitions.entry(to).or_insert(0) += 1;
-        *self.totals.entry(from).or_insert(0) += 1;
+        *self.totals.entry(key).or_insert(0) += 1;
     }
     
-    fn predict_next(&self, current: i64, min_confidence: f32) -> Vec<(i64, f32)> {
-        if let Some(transitions) = self.transitions.get(&current) {
-            let total = self.totals.get(&current).unwrap_or(&1);
+    /// Pages likely to follow `context`, most likely first
+    fn predict_next(&self, context: &[i64], min_confidence: f32) -> Vec<(i64, f32)> {
+        let Some(key) = self.key(context) else {
+            return Vec::new();
+        };
+        if let Some(transitions) = self.transitions.get(&key) {
+            let total = self.totals.get(&key).unwrap_or(&1);
             
             let mut predictions: Vec<(i64, f32)> = transitions.iter()
                 .map(|(next, count)| (*next, *count as f32 / *total as f32))
@@ -282,6 +327,31 @@ impl MarkovChain {
             Vec::new()
         }
     }
+    
+    fn key(&self, context: &[i64]) -> Option<Vec<i64>> {
+        context.len().checked_sub(self.order).map(|start| context[start..].to_vec())
+    }
+    
+    /// Forget the least-seen quarter of contexts (at least one) to make room
+    /// for new ones
+    ///
+    /// Ties are broken by context, so equally seen contexts are pruned in a
+    /// stable order rather than all at once.
+    fn prune(&mut self) {
+        let mut by_total: Vec<(u32, &Vec<i64>)> = self.totals.iter()
+            .map(|(context, total)| (*total, context))
+            .collect();
+        by_total.sort_unstable();
+        let pruned: Vec<Vec<i64>> = by_total.iter()
+            .take((by_total.len() / 4).max(1))
+            .map(|(_, context)| (*context).clone())
+            .collect();
+        
+        for context in pruned {
+            self.totals.remove(&context);
+            self.transitions.remove(&context);
+        }
+    }
 }
 
 impl SequentialDetector {
@@ -368,19 +438,20 @@ impl SequentialDetector {
     }
     
     fn predict_next(&self, current: i64, count: usize) -> Option<Vec<i64>> {
-        if let Some(ref run) = self.current_run {
-            if run.current == current && run.length >= 3 {
-                // Predict continuation of current run
-                let mut predictions = Vec::with_capacity(count);
-                let mut next = current;
-                
-                for _ in 0..count {
-                    next += run.stride;
-                    predictions.push(next);
-                }
-                
-                return Some(predictions);
+        if let Some(ref run) = self.current_run
+            && run.current == current
+            && run.length >= 3
+        {
+            // Predict continuation of current run
+            let mut predictions = Vec::with_capacity(count);
+            let mut next = current;
+            
+            for _ in 0..count {
+                next += run.stride;
+                predictions.push(next);
             }
+            
+            return Some(predictions);
         }
         
         None
@@ -428,16 +499,87 @@ mod tests {
     
     #[test]
     fn test_markov_prediction() {
-        let mut chain = MarkovChain::new();
+        let mut chain = MarkovChain::new(1, 64);
         
         // Record pattern: 1 -> 2 -> 3 -> 1
-        chain.record_transition(1, 2);
-        chain.record_transition(2, 3);
-        chain.record_transition(3, 1);
-        chain.record_transition(1, 2);
+        chain.record_transition(&[1], 2);
+        chain.record_transition(&[2], 3);
+        chain.record_transition(&[3], 1);
+        chain.record_transition(&[1], 2);
         
-        let predictions = chain.predict_next(1, 0.5);
+        let predictions = chain.predict_next(&[1], 0.5);
         assert!(!predictions.is_empty());
         assert_eq!(predictions[0].0, 2); // Should predict 2 after 1
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_second_order_markov_prediction() {
+        // Page 2 is followed by 3 after 1, but by 5 after 4
+        let pattern = [1, 2, 3, 4, 2, 5];
+        let config = PredictorConfig { markov_order: 2, ..Default::default() };
+        let mut predictor = AccessPredictor::with_config(config);
+        let mut first_order = MarkovChain::new(1, 64);
+        for (i, page) in pattern.iter().cycle().take(30).enumerate() {
+            predictor.record_access(page * 4096 + 8, 8, false);
+            if i > 0 {
+                first_order.record_transition(&[pattern[(i - 1) % 6]], *page);
+            }
+        }
+        
+        let after_2 = first_order.predict_next(&[2], 0.0);
+        assert_eq!(after_2.len(), 2);
+        assert_eq!(after_2[0].1, 0.5);
+        
+        assert_eq!(predictor.markov_chain.predict_next(&[1, 2], 0.9), vec![(3, 1.0)]);
+        assert_eq!(predictor.markov_chain.predict_next(&[4, 2], 0.9), vec![(5, 1.0)]);
+        
+        // The last access was page 2 reached from 1
+        predictor.record_access(4096 + 8, 8, false);
+        predictor.record_access(2 * 4096 + 8, 8, false);
+        let suggestions = predictor.suggest_prefetch(2).unwrap();
+        assert!(suggestions.contains(&3));
+        assert!(!suggestions.contains(&5));
+    }
+    
+    #[test]
+    fn test_markov_contexts_are_bounded() {
+        let mut chain = MarkovChain::new(2, 8);
+        for _ in 0..10 {
+            chain.record_transition(&[0, 1], 2);
+        }
+        for page in 0..100 {
+            chain.record_transition(&[page, page + 1000], page);
+            assert!(chain.transitions.len() <= 8);
+        }
+        
+        // Frequently seen contexts survive pruning
+        assert_eq!(chain.predict_next(&[0, 1], 0.5), vec![(2, 1.0)]);
+    }
+    
+    #[test]
+    fn test_markov_prune_with_tied_counts() {
+        let mut chain = MarkovChain::new(1, 8);
+        for page in 0..8 {
+            chain.record_transition(&[page], page + 1);
+        }
+        
+        // Every context was seen once; only a quarter of them make room
+        chain.record_transition(&[100], 101);
+        assert_eq!(chain.transitions.len(), 7);
+        assert_eq!(chain.totals.len(), 7);
+        assert!(chain.transitions.contains_key(&vec![100]));
+        assert_eq!(chain.predict_next(&[7], 0.5), vec![(8, 1.0)]);
+    }
+    
+    #[test]
+    fn test_zero_markov_contexts_is_clamped() {
+        let mut predictor = AccessPredictor::with_config(PredictorConfig {
+            max_markov_contexts: 0,
+            ..PredictorConfig::default()
+        });
+        for page in 0..8i64 {
+            predictor.record_access(page * 4096, 1, false);
+        }
+        assert!(predictor.markov_chain.transitions.len() <= 1);
+    }
+}
@@ -14,6 +14,19 @@ pub struct Segment {
     pub indices: Vec<Index>,
 }
 
+impl Segment {
+    /// End of the region starting at `offset`: the next index root after it, or the segment end
+    ///
+    /// Indices and table rows sharing a segment each own the bytes up to the
+    /// next structure's start.
+    pub fn region_end(&self, offset: i64) -> i64 {
+        self.indices.iter()
+            .map(|index| index.root_position)
+            .filter(|&root| root > offset)
+            .fold(self.size as i64, i64::min)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum SegmentType {
     Code,
@@ -121,6 +134,27 @@ impl SegmentedTape {
         
         // Find free space (simple first-fit for now)
         let start = self.find_free_space(size)?;
+        self.create_segment_at(name, start, size, segment_type)
+    }
+    
+    /// Create a segment at a fixed `start`, failing if it would overlap another
+    pub fn create_segment_at(
+        &mut self,
+        name: String,
+        start: i64,
+        size: usize,
+        segment_type: SegmentType
+    ) -> Result<i64, String> {
+        if self.segments.contains_key(&name) {
+            return Err(format!("Segment '{}' already exists", name));
+        }
+        let end = start.checked_add(size as i64)
+            .ok_or_else(|| format!("Segment '{}' does not fit on the tape", name))?;
+        if let Some(other) = self.segments.values()
+            .find(|s| s.start < end && start < s.start + s.size as i64)
+        {
+            return Err(format!("Segment '{}' would overlap segment '{}'", name, other.name));
+        }
         
         let segment = Segment {
             name: name.clone(),
@@ -141,6 +175,26 @@ impl SegmentedTape {
         Ok(start)
     }
     
+    /// Create a segment, suffixing `base_name` with the lowest free `_N` if taken
+    ///
+    /// Returns the name actually used along with the segment's start.
+    pub fn create_segment_unique(
+        &mut self,
+        base_name: &str,
+        size: usize,
+        segment_type: SegmentType
+    ) -> Result<(String, i64), String> {
+        let mut name = base_name.to_string();
+        let mut suffix = 1;
+        while self.segments.contains_key(&name) {
+            name = format!("{}_{}", base_name, suffix);
+            suffix += 1;
+        }
+        
+        let start = self.create_segment(name.clone(), size, segment_type)?;
+        Ok((name, start))
+    }
+    
     pub fn read_segment(
         &self, 
         name: &str, 
@@ -150,16 +204,11 @@ impl SegmentedTape {
         let segment = self.segments.get(name)
             .ok_or_else(|| format!("Unknown segment: {}", name))?;
         
-        if offset < 0 || offset + len as i64 > segment.size as i64 {
+        if offset < 0 || offset.checked_add(len as i64).is_none_or(|end| end > segment.size as i64) {
             return Err("Segment bounds violation".to_string());
         }
         
-        let _old_pos = self.tape.position();
-        let mut tape = self.tape.clone();
-        tape.seek(segment.start + offset);
-        let data = tape.read(len);
-        
-        Ok(data)
+        Ok(self.tape.read_at(segment.start + offset, len))
     }
     
     pub fn write_segment(
@@ -172,7 +221,7 @@ impl SegmentedTape {
             .ok_or_else(|| format!("Unknown segment: {}", name))?
             .clone();
         
-        if offset < 0 || offset + data.len() as i64 > segment.size as i64 {
+        if offset < 0 || offset.checked_add(data.len() as i64).is_none_or(|end| end > segment.size as i64) {
             return Err("Segment bounds violation".to_string());
         }
         
@@ -200,6 +249,120 @@ impl SegmentedTape {
         Ok(())
     }
     
+    /// Move `len` bytes from `src` to `dst`, rewriting embedded tape pointers
+    ///
+    /// `pointer_offsets` are region-relative offsets of little-endian `i64`
+    /// pointers; each is replaced by `fixup(old_value)` in the moved copy.
+    /// Vacated source bytes are zeroed, and every write is recorded in the trail.
+    pub fn relocate_region(
+        &mut self,
+        src: i64,
+        dst: i64,
+        len: usize,
+        pointer_offsets: &[usize],
+        fixup: impl Fn(i64) -> i64,
+    ) -> Result<(), String> {
+        let mut data = self.tape.read_at(src, len);
+        
+        for &offset in pointer_offsets {
+            let field = data.get_mut(offset..offset + 8)
+                .ok_or_else(|| format!("Pointer at offset {} exceeds region of {} bytes", offset, len))?;
+            let old = i64::from_le_bytes(field.try_into().unwrap());
+            field.copy_from_slice(&fixup(old).to_le_bytes());
+        }
+        
+        let old_pos = self.tape.position();
+        self.tape.seek(src);
+        self.tape.write(&vec![0; len]);
+        self.tape.seek(dst);
+        self.tape.write(&data);
+        self.tape.seek(old_pos);
+        
+        Ok(())
+    }
+    
+    /// Extend a segment by `additional` bytes, moving it if its neighbor is in the way
+    ///
+    /// A segment with free space after it grows in place. Otherwise its bytes
+    /// are copied to the first gap large enough for the new size and the old
+    /// location is zeroed; the copy is recorded in the trail like any write,
+    /// followed by a `SegmentResize`. Absolute tape positions pointing into a
+    /// moved segment are not rewritten.
+    pub fn grow_segment(&mut self, name: &str, additional: usize) -> Result<(), String> {
+        let segment = self.segments.remove(name)
+            .ok_or_else(|| format!("Unknown segment: {}", name))?;
+        let new_size = segment.size + additional;
+        
+        let next_start = self.segments.values()
+            .map(|s| s.start)
+            .filter(|&start| start >= segment.start)
+            .min();
+        let new_start = match next_start {
+            Some(next) if segment.start + new_size as i64 > next => self.find_free_space(new_size),
+            _ => Ok(segment.start),
+        };
+        let new_start = match new_start {
+            Ok(start) => start,
+            Err(e) => {
+                self.segments.insert(name.to_string(), segment);
+                return Err(e);
+            }
+        };
+        
+        if new_start != segment.start {
+            self.relocate_region(segment.start, new_start, segment.size, &[], |p| p)?;
+        }
+        self.tape.add_trail_op(TrailOp::SegmentResize {
+            name: name.to_string(),
+            old_start: segment.start,
+            old_size: segment.size,
+            new_start,
+            new_size,
+        });
+        
+        self.segments.insert(name.to_string(), Segment { start: new_start, size: new_size, ..segment });
+        Ok(())
+    }
+    
+    /// Remove a segment, leaving its bytes on the tape
+    ///
+    /// The freed range becomes available to later `create_segment` calls. The
+    /// whole segment is recorded in the trail so it can be restored.
+    pub fn delete_segment(&mut self, name: &str) -> Result<(), String> {
+        let segment = self.segments.remove(name)
+            .ok_or_else(|| format!("Unknown segment: {}", name))?;
+        self.tape.add_trail_op(TrailOp::SegmentDelete { segment });
+        Ok(())
+    }
+    
+    /// Remove a segment and zero the bytes it occupied
+    ///
+    /// The zeroing is recorded in the trail like any write, so rewinding it
+    /// brings the contents back along with the segment.
+    pub fn delete_segment_zeroed(&mut self, name: &str) -> Result<(), String> {
+        let segment = self.segments.get(name)
+            .ok_or_else(|| format!("Unknown segment: {}", name))?;
+        let (start, size) = (segment.start, segment.size);
+        
+        let old_pos = self.tape.position();
+        self.tape.seek(start);
+        self.tape.write(&vec![0; size]);
+        self.tape.seek(old_pos);
+        self.delete_segment(name)
+    }
+    
+    /// Rewind the tape to a checkpoint, reversing segment operations as well
+    pub fn rewind(&mut self, name: &str) -> Result<(), String> {
+        let segments = &mut self.segments;
+        self.tape.rewind_with(name, |tape, op| undo_segment_op(segments, tape, op))
+    }
+    
+    /// Rewind the last `n` trail operations, reversing segment operations as well
+    pub fn rewind_n(&mut self, n: usize) {
+        let segments = &mut self.segments;
+        self.tape.rewind_n_with(n, |tape, op| undo_segment_op(segments, tape, op));
+    }
+    
     pub fn get_segment(&self, name: &str) -> Option<&Segment> {
         self.segments.get(name)
     }
@@ -208,6 +371,13 @@ impl SegmentedTape {
         self.segments.values().collect()
     }
     
+    /// The segment containing absolute position `pos`, with the offset into it
+    pub fn segment_at(&self, pos: i64) -> Option<(&str, i64)> {
+        self.segments.values()
+            .find(|s| pos >= s.start && pos < s.start + s.size as i64)
+            .map(|s| (s.name.as_str(), pos - s.start))
+    }
+    
     fn find_free_space(&self, size: usize) -> Result<i64, String> {
         // Simple allocator: find gap between segments
         let mut segments: Vec<_> = self.segments.values()
@@ -234,32 +404,46 @@ impl Default for SegmentedTape {
     }
 }
 
+/// Reverse one segment operation popped from the trail
+///
+/// Byte writes are undone by their own `Write` operations; restoring
+/// `old_data` here as well keeps segment contents right even for callers that
+/// record `SegmentModify` without a matching write.
+fn undo_segment_op(segments: &mut HashMap<String, Segment>, tape: &mut Tape, op: &TrailOp) {
+    match op {
+        TrailOp::SegmentCreate { name, .. } => {
+            segments.remove(name);
+        }
+        TrailOp::SegmentModify { name, offset, old_data, .. } => {
+            if let Some(segment) = segments.get(name) {
+                tape.write_untracked(segment.start + offset, old_data);
+            }
+        }
+        TrailOp::SegmentResize { name, old_start, old_size, .. } => {
+            if let Some(segment) = segments.get_mut(name) {
+                segment.start = *old_start;
+                segment.size = *old_size;
+            }
+        }
+        TrailOp::SegmentDelete { segment } => {
+            segments.insert(segment.name.clone(), segment.clone());
+        }
+        _ => {}
+    }
+}
+
 // Add segment operations to TrailOp
 impl TrailOp {
     pub fn is_segment_op(&self) -> bool {
         matches!(self, 
             TrailOp::SegmentCreate { .. } | 
-            TrailOp::SegmentModify { .. }
+            TrailOp::SegmentModify { .. } |
+            TrailOp::SegmentResize { .. } |
+            TrailOp::SegmentDelete { .. }
         )
     }
 }
 
-// Extend TrailOp enum in core.rs
-#[derive(Clone, Debug)]
-pub enum SegmentTrailOp {
-    SegmentCreate {
-        name: String,
-        start: i64,
-        size: usize,
-    },
-    SegmentModify {
-        name: String,
-        offset: i64,
-        old_data: Vec<u8>,
-        new_data: Vec<u8>,
-    },
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -296,6 +480,25 @@ mod tests {
         assert_eq!(&data, b"Hello, World!");
     }
 
+    #[test]
+    fn test_segment_read_leaves_tape_untouched() {
+        let mut stape = SegmentedTape::new();
+        stape.create_segment("big".to_string(), 1 << 20, SegmentType::Data).unwrap();
+        
+        let blob: Vec<u8> = (0..512 * 1024).map(|i| (i % 251) as u8).collect();
+        stape.write_segment("big", 4096, &blob).unwrap();
+        let pages = stape.tape.page_count();
+        let position = stape.tape.position();
+        let trail_len = stape.tape.trail_len();
+        
+        let slice = stape.read_segment("big", 4096 + 300_000, 64).unwrap();
+        assert_eq!(slice, blob[300_000..300_064]);
+        assert_eq!(stape.read_segment("big", (1 << 20) - 8, 8).unwrap(), vec![0u8; 8]);
+        assert_eq!(stape.tape.page_count(), pages);
+        assert_eq!(stape.tape.position(), position);
+        assert_eq!(stape.tape.trail_len(), trail_len);
+    }
+
     #[test]
     fn test_segment_bounds_checking() {
         let mut stape = SegmentedTape::new();
@@ -311,6 +514,10 @@ mod tests {
         
         // Should succeed - within bounds
         assert!(stape.write_segment("small", 5, b"fits").is_ok());
+        
+        // Offsets whose end overflows are out of bounds too
+        assert!(stape.write_segment("small", i64::MAX - 2, b"fits").is_err());
+        assert!(stape.read_segment("small", i64::MAX, 1).is_err());
     }
 
     #[test]
@@ -342,6 +549,194 @@ mod tests {
         assert_eq!(stape.list_segments().len(), 3);
     }
 
+    #[test]
+    fn test_create_segment_unique() {
+        let mut stape = SegmentedTape::new();
+        
+        let (a, a_start) = stape.create_segment_unique("buf", 64, SegmentType::Data).unwrap();
+        let (b, b_start) = stape.create_segment_unique("buf", 64, SegmentType::Data).unwrap();
+        let (c, c_start) = stape.create_segment_unique("buf", 64, SegmentType::Data).unwrap();
+        
+        assert_eq!((a.as_str(), b.as_str(), c.as_str()), ("buf", "buf_1", "buf_2"));
+        assert!(b_start >= a_start + 64);
+        assert!(c_start >= b_start + 64);
+    }
+
+    #[test]
+    fn test_create_segment_at() {
+        let mut stape = SegmentedTape::new();
+        assert_eq!(stape.create_segment_at("fixed".to_string(), 4096, 64, SegmentType::Data).unwrap(), 4096);
+        
+        let err = stape.create_segment_at("clash".to_string(), 4100, 8, SegmentType::Data).unwrap_err();
+        assert!(err.contains("overlap segment 'fixed'"), "{}", err);
+        assert!(stape.create_segment_at("edge".to_string(), 4096 + 64, 8, SegmentType::Data).is_ok());
+        
+        // First-fit allocation still uses the gap below
+        assert_eq!(stape.create_segment("low".to_string(), 64, SegmentType::Data).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_grow_segment_in_place() {
+        let mut stape = SegmentedTape::new();
+        let start = stape.create_segment("log".to_string(), 64, SegmentType::Log).unwrap();
+        stape.write_segment("log", 0, &[7; 64]).unwrap();
+        assert!(stape.write_segment("log", 64, &[8]).is_err());
+        
+        stape.grow_segment("log", 64).unwrap();
+        let segment = stape.get_segment("log").unwrap();
+        assert_eq!((segment.start, segment.size), (start, 128));
+        stape.write_segment("log", 64, &[8; 64]).unwrap();
+        assert_eq!(stape.read_segment("log", 0, 64).unwrap(), vec![7; 64]);
+        assert_eq!(stape.read_segment("log", 64, 64).unwrap(), vec![8; 64]);
+    }
+
+    #[test]
+    fn test_grow_segment_relocates_past_neighbor() {
+        let mut stape = SegmentedTape::new();
+        let old_start = stape.create_segment("heap".to_string(), 64, SegmentType::Heap).unwrap();
+        stape.create_segment("next".to_string(), 32, SegmentType::Data).unwrap();
+        stape.write_segment("heap", 0, &[5; 64]).unwrap();
+        stape.write_segment("next", 0, &[9; 32]).unwrap();
+        
+        stape.grow_segment("heap", 100).unwrap();
+        let heap = stape.get_segment("heap").unwrap().clone();
+        let next = stape.get_segment("next").unwrap().clone();
+        assert_eq!(heap.size, 164);
+        assert!(heap.start >= next.start + next.size as i64 || heap.start + heap.size as i64 <= next.start);
+        
+        assert_eq!(stape.read_segment("heap", 0, 64).unwrap(), vec![5; 64]);
+        assert_eq!(stape.read_segment("next", 0, 32).unwrap(), vec![9; 32]);
+        assert_eq!(stape.tape.read_at(old_start, 64), vec![0; 64]);
+        stape.write_segment("heap", 163, &[1]).unwrap();
+        
+        let resize = stape.tape.trail_since(0).unwrap().iter()
+            .find(|op| matches!(op, TrailOp::SegmentResize { .. }));
+        assert!(matches!(resize, Some(TrailOp::SegmentResize { old_start: 0, old_size: 64, new_size: 164, .. })));
+        assert!(stape.grow_segment("missing", 1).is_err());
+    }
+
+    #[test]
+    fn test_deleted_segment_gap_is_reused() {
+        let mut stape = SegmentedTape::new();
+        stape.create_segment("a".to_string(), 64, SegmentType::Data).unwrap();
+        let b_start = stape.create_segment("b".to_string(), 64, SegmentType::Data).unwrap();
+        let c_start = stape.create_segment("c".to_string(), 64, SegmentType::Data).unwrap();
+        stape.write_segment("b", 0, b"middle").unwrap();
+        
+        stape.delete_segment("b").unwrap();
+        assert!(stape.get_segment("b").is_none());
+        assert!(stape.delete_segment("b").is_err());
+        assert!(matches!(stape.tape.trail_since(0).unwrap().last(),
+                         Some(TrailOp::SegmentDelete { segment }) if segment.name == "b"));
+        // Without zeroing, the old bytes stay on the tape
+        assert_eq!(stape.tape.read_at(b_start, 6), b"middle");
+        
+        let d_start = stape.create_segment("d".to_string(), 48, SegmentType::Data).unwrap();
+        assert_eq!(d_start, b_start);
+        assert!(d_start + 48 <= c_start);
+    }
+
+    #[test]
+    fn test_delete_segment_zeroed() {
+        let mut stape = SegmentedTape::new();
+        let start = stape.create_segment("secret".to_string(), 16, SegmentType::Data).unwrap();
+        stape.write_segment("secret", 0, &[0xAA; 16]).unwrap();
+        
+        stape.delete_segment_zeroed("secret").unwrap();
+        assert!(stape.get_segment("secret").is_none());
+        assert_eq!(stape.tape.read_at(start, 16), vec![0; 16]);
+    }
+
+    #[test]
+    fn test_rewind_restores_segment_bytes_and_existence() {
+        let mut stape = SegmentedTape::new();
+        stape.create_segment("kept".to_string(), 32, SegmentType::Data).unwrap();
+        stape.write_segment("kept", 0, b"before").unwrap();
+        stape.create_segment("doomed".to_string(), 32, SegmentType::Data).unwrap();
+        stape.tape.checkpoint("cp".to_string());
+        
+        stape.write_segment("kept", 0, b"after!").unwrap();
+        stape.create_segment("scratch".to_string(), 16, SegmentType::Heap).unwrap();
+        stape.write_segment("scratch", 0, b"temp").unwrap();
+        stape.grow_segment("kept", 64).unwrap();
+        stape.delete_segment("doomed").unwrap();
+        
+        stape.rewind("cp").unwrap();
+        assert_eq!(stape.read_segment("kept", 0, 6).unwrap(), b"before");
+        assert_eq!(stape.get_segment("kept").unwrap().size, 32);
+        assert!(stape.get_segment("doomed").is_some());
+        assert!(stape.get_segment("scratch").is_none());
+        assert!(stape.read_segment("kept", 32, 1).is_err());
+    }
+
+    #[test]
+    fn test_rewind_n_undoes_relocation() {
+        let mut stape = SegmentedTape::new();
+        let start = stape.create_segment("heap".to_string(), 16, SegmentType::Heap).unwrap();
+        stape.create_segment("next".to_string(), 16, SegmentType::Data).unwrap();
+        stape.write_segment("heap", 0, &[3; 16]).unwrap();
+        let mark = stape.tape.trail_len();
+        
+        stape.grow_segment("heap", 16).unwrap();
+        stape.write_segment("heap", 16, &[4; 16]).unwrap();
+        let moved = stape.get_segment("heap").unwrap().start;
+        assert_ne!(moved, start);
+        
+        stape.rewind_n(stape.tape.trail_len() - mark);
+        assert_eq!(stape.get_segment("heap").unwrap().start, start);
+        assert_eq!(stape.read_segment("heap", 0, 16).unwrap(), vec![3; 16]);
+        assert_eq!(stape.tape.read_at(moved, 32), vec![0; 32]);
+    }
+
+    #[test]
+    fn test_relocate_region_fixes_pointers() {
+        let mut stape = SegmentedTape::new();
+        
+        // A 16-byte node: a pointer to its own payload at +8, then the payload
+        let (src, dst) = (100i64, 500i64);
+        stape.tape.seek(src);
+        stape.tape.write(&(src + 8).to_le_bytes());
+        stape.tape.seek(src + 8);
+        stape.tape.write(&42i64.to_le_bytes());
+        
+        let delta = dst - src;
+        stape.relocate_region(src, dst, 16, &[0], |ptr| ptr + delta).unwrap();
+        
+        let moved = stape.tape.read_at(dst, 16);
+        let ptr = i64::from_le_bytes(moved[0..8].try_into().unwrap());
+        assert_eq!(ptr, dst + 8);
+        assert_eq!(stape.tape.read_at(ptr, 8), 42i64.to_le_bytes());
+        assert_eq!(stape.tape.read_at(src, 16), vec![0; 16]);
+        
+        assert!(stape.relocate_region(dst, src, 16, &[12], |ptr| ptr).is_err());
+    }
+
+    #[test]
+    fn test_segment_at() {
+        let mut stape = SegmentedTape::new();
+        stape.create_segment("code".to_string(), 100, SegmentType::Code).unwrap();
+        stape.create_segment("stack".to_string(), 50, SegmentType::Stack).unwrap();
+        
+        // Leave a gap by placing a segment past the end manually
+        stape.segments.insert("far".to_string(), Segment {
+            name: "far".to_string(),
+            start: 1000,
+            size: 10,
+            segment_type: SegmentType::Data,
+            indices: Vec::new(),
+        });
+        
+        assert_eq!(stape.segment_at(0), Some(("code", 0)));
+        assert_eq!(stape.segment_at(99), Some(("code", 99)));
+        assert_eq!(stape.segment_at(100), Some(("stack", 0)));
+        assert_eq!(stape.segment_at(149), Some(("stack", 49)));
+        assert_eq!(stape.segment_at(150), None);
+        assert_eq!(stape.segment_at(500), None);
+        assert_eq!(stape.segment_at(1009), Some(("far", 9)));
+        assert_eq!(stape.segment_at(1010), None);
+        assert_eq!(stape.segment_at(-1), None);
+    }
+
     #[test]
     fn test_segment_with_schema() {
         let mut stape = SegmentedTape::new();
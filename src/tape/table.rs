@@ -0,0 +1,272 @@
+//! Rows of schema-typed table segments
+//!
+//! A table segment begins with a 16-byte header holding the row count and the
+//! number of row bytes in use, followed by the rows packed back to back. Each
+//! field is a presence byte when nullable, then its value: numbers in their
+//! natural little-endian width, strings and bytes as a `u32` length followed
+//! by the contents. Rows are appended through `write_segment`, so inserts are
+//! recorded on the trail. Indices on the table live after the rows, at their
+//! `root_position`s.
+
+use super::segment::{DataType, Field, Schema, SegmentType, SegmentedTape};
+
+const HEADER_SIZE: usize = 16;
+
+/// A single field value of a table row
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    String(String),
+    Bytes(Vec<u8>),
+    Timestamp(i64),
+}
+
+impl SegmentedTape {
+    /// Append a row to a table segment, returning its offset within the segment
+    ///
+    /// Values are matched to the schema's fields in order; a value of the wrong
+    /// type, out of the field's range, too long, or null in a non-nullable
+    /// field is an error and leaves the table unchanged.
+    pub fn insert_row(&mut self, segment: &str, values: &[Value]) -> Result<i64, String> {
+        let (schema, size) = self.table_schema(segment)?;
+        if values.len() != schema.fields.len() {
+            return Err(format!("Expected {} values, got {}", schema.fields.len(), values.len()));
+        }
+        
+        let mut row = Vec::new();
+        for (field, value) in schema.fields.iter().zip(values) {
+            encode_field(field, value, &mut row)?;
+        }
+        
+        let (rows, used) = self.table_header(segment)?;
+        let offset = (HEADER_SIZE + used) as i64;
+        if HEADER_SIZE + used + row.len() > size {
+            return Err(format!("Table segment '{}' is full", segment));
+        }
+        
+        self.write_segment(segment, offset, &row)?;
+        let mut header = [0u8; HEADER_SIZE];
+        header[0..8].copy_from_slice(&(rows as u64 + 1).to_le_bytes());
+        header[8..16].copy_from_slice(&((used + row.len()) as u64).to_le_bytes());
+        self.write_segment(segment, 0, &header)?;
+        Ok(offset)
+    }
+    
+    /// Iterate over the rows of a table segment in insertion order
+    pub fn scan_rows(&self, segment: &str) -> Result<RowScan<'_>, String> {
+        let (schema, _) = self.table_schema(segment)?;
+        let (remaining, _) = self.table_header(segment)?;
+        Ok(RowScan {
+            tape: self,
+            segment: segment.to_string(),
+            schema,
+            offset: HEADER_SIZE as i64,
+            remaining,
+        })
+    }
+    
+    /// The schema of a table segment, with the end of its row region
+    ///
+    /// Rows stop at the first index stored in the same segment.
+    fn table_schema(&self, segment: &str) -> Result<(Schema, usize), String> {
+        let seg = self.get_segment(segment)
+            .ok_or_else(|| format!("Unknown segment: {}", segment))?;
+        match &seg.segment_type {
+            SegmentType::Table { schema } => Ok((schema.clone(), seg.region_end(0) as usize)),
+            _ => Err(format!("Segment '{}' is not a table", segment)),
+        }
+    }
+    
+    /// (row count, row bytes in use)
+    fn table_header(&self, segment: &str) -> Result<(usize, usize), String> {
+        let header = self.read_segment(segment, 0, HEADER_SIZE)?;
+        let rows = u64::from_le_bytes(header[0..8].try_into().unwrap()) as usize;
+        let used = u64::from_le_bytes(header[8..16].try_into().unwrap()) as usize;
+        Ok((rows, used))
+    }
+}
+
+/// Iterator over the rows of a table segment, from `SegmentedTape::scan_rows`
+pub struct RowScan<'a> {
+    tape: &'a SegmentedTape,
+    segment: String,
+    schema: Schema,
+    offset: i64,
+    remaining: usize,
+}
+
+impl RowScan<'_> {
+    fn read(&mut self, len: usize) -> Result<Vec<u8>, String> {
+        let bytes = self.tape.read_segment(&self.segment, self.offset, len)?;
+        self.offset += len as i64;
+        Ok(bytes)
+    }
+    
+    fn read_row(&mut self) -> Result<Vec<Value>, String> {
+        let fields = self.schema.fields.clone();
+        fields.iter().map(|field| self.read_field(field)).collect()
+    }
+    
+    fn read_field(&mut self, field: &Field) -> Result<Value, String> {
+        if field.nullable && self.read(1)?[0] == 0 {
+            return Ok(Value::Null);
+        }
+        
+        let value = match &field.dtype {
+            DataType::Int8 => Value::Int(i8::from_le_bytes(self.array()?) as i64),
+            DataType::Int16 => Value::Int(i16::from_le_bytes(self.array()?) as i64),
+            DataType::Int32 => Value::Int(i32::from_le_bytes(self.array()?) as i64),
+            DataType::Int64 => Value::Int(i64::from_le_bytes(self.array()?)),
+            DataType::UInt8 => Value::UInt(u8::from_le_bytes(self.array()?) as u64),
+            DataType::UInt16 => Value::UInt(u16::from_le_bytes(self.array()?) as u64),
+            DataType::UInt32 => Value::UInt(u32::from_le_bytes(self.array()?) as u64),
+            DataType::UInt64 => Value::UInt(u64::from_le_bytes(self.array()?)),
+            DataType::Float32 => Value::Float(f32::from_le_bytes(self.array()?) as f64),
+            DataType::Float64 => Value::Float(f64::from_le_bytes(self.array()?)),
+            DataType::Timestamp => Value::Timestamp(i64::from_le_bytes(self.array()?)),
+            DataType::String { .. } => {
+                let bytes = self.prefixed()?;
+                Value::String(String::from_utf8(bytes)
+                    .map_err(|_| format!("Field '{}' holds invalid UTF-8", field.name))?)
+            }
+            DataType::Bytes { .. } => Value::Bytes(self.prefixed()?),
+        };
+        Ok(value)
+    }
+    
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        Ok(self.read(N)?.try_into().unwrap())
+    }
+    
+    /// A `u32` length followed by that many bytes
+    fn prefixed(&mut self) -> Result<Vec<u8>, String> {
+        let len = u32::from_le_bytes(self.array()?) as usize;
+        self.read(len)
+    }
+}
+
+impl Iterator for RowScan<'_> {
+    type Item = Result<Vec<Value>, String>;
+    
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        
+        let row = self.read_row();
+        if row.is_err() {
+            // A corrupt row leaves no reliable offset for the next one
+            self.remaining = 0;
+        }
+        Some(row)
+    }
+}
+
+/// Append the serialized form of `value` for `field` to `out`
+fn encode_field(field: &Field, value: &Value, out: &mut Vec<u8>) -> Result<(), String> {
+    if let Value::Null = value {
+        if !field.nullable {
+            return Err(format!("Field '{}' is not nullable", field.name));
+        }
+        out.push(0);
+        return Ok(());
+    }
+    if field.nullable {
+        out.push(1);
+    }
+    
+    let mismatch = || format!("Field '{}' expects {:?}, got {:?}", field.name, field.dtype, value);
+    let out_of_range = || format!("Value {:?} out of range for field '{}'", value, field.name);
+    match (&field.dtype, value) {
+        (DataType::Int8, Value::Int(v)) => out.extend(i8::try_from(*v).map_err(|_| out_of_range())?.to_le_bytes()),
+        (DataType::Int16, Value::Int(v)) => out.extend(i16::try_from(*v).map_err(|_| out_of_range())?.to_le_bytes()),
+        (DataType::Int32, Value::Int(v)) => out.extend(i32::try_from(*v).map_err(|_| out_of_range())?.to_le_bytes()),
+        (DataType::Int64, Value::Int(v)) => out.extend(v.to_le_bytes()),
+        (DataType::UInt8, Value::UInt(v)) => out.extend(u8::try_from(*v).map_err(|_| out_of_range())?.to_le_bytes()),
+        (DataType::UInt16, Value::UInt(v)) => out.extend(u16::try_from(*v).map_err(|_| out_of_range())?.to_le_bytes()),
+        (DataType::UInt32, Value::UInt(v)) => out.extend(u32::try_from(*v).map_err(|_| out_of_range())?.to_le_bytes()),
+        (DataType::UInt64, Value::UInt(v)) => out.extend(v.to_le_bytes()),
+        (DataType::Float32, Value::Float(v)) => out.extend((*v as f32).to_le_bytes()),
+        (DataType::Float64, Value::Float(v)) => out.extend(v.to_le_bytes()),
+        (DataType::Timestamp, Value::Timestamp(v)) => out.extend(v.to_le_bytes()),
+        (DataType::String { max_len }, Value::String(s)) => encode_prefixed(field, s.as_bytes(), *max_len, out)?,
+        (DataType::Bytes { max_len }, Value::Bytes(b)) => encode_prefixed(field, b, *max_len, out)?,
+        _ => return Err(mismatch()),
+    }
+    Ok(())
+}
+
+fn encode_prefixed(field: &Field, bytes: &[u8], max_len: Option<usize>, out: &mut Vec<u8>) -> Result<(), String> {
+    let limit = max_len.unwrap_or(u32::MAX as usize).min(u32::MAX as usize);
+    if bytes.len() > limit {
+        return Err(format!("Field '{}' holds at most {} bytes, got {}", field.name, limit, bytes.len()));
+    }
+    out.extend((bytes.len() as u32).to_le_bytes());
+    out.extend_from_slice(bytes);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    /// A table of (id: Int32, name: nullable String of at most 8 bytes)
+    fn people() -> SegmentedTape {
+        let schema = Schema {
+            fields: vec![
+                Field { name: "id".to_string(), dtype: DataType::Int32, nullable: false },
+                Field { name: "name".to_string(), dtype: DataType::String { max_len: Some(8) }, nullable: true },
+            ],
+            primary_key: vec!["id".to_string()],
+        };
+        let mut tape = SegmentedTape::new();
+        tape.create_segment("people".to_string(), 256, SegmentType::Table { schema }).unwrap();
+        tape
+    }
+
+    #[test]
+    fn test_insert_and_scan_rows() {
+        let mut tape = people();
+        let first = tape.insert_row("people", &[Value::Int(1), Value::String("ada".to_string())]).unwrap();
+        let second = tape.insert_row("people", &[Value::Int(-2), Value::Null]).unwrap();
+        tape.insert_row("people", &[Value::Int(3), Value::String("grace".to_string())]).unwrap();
+        assert_eq!(first, HEADER_SIZE as i64);
+        assert!(second > first);
+        
+        let rows: Vec<_> = tape.scan_rows("people").unwrap().collect::<Result<_, _>>().unwrap();
+        assert_eq!(rows, vec![
+            vec![Value::Int(1), Value::String("ada".to_string())],
+            vec![Value::Int(-2), Value::Null],
+            vec![Value::Int(3), Value::String("grace".to_string())],
+        ]);
+    }
+
+    #[test]
+    fn test_rejected_rows_leave_table_unchanged() {
+        let mut tape = people();
+        let err = |tape: &mut SegmentedTape, values: &[Value]| tape.insert_row("people", values).unwrap_err();
+        
+        assert!(err(&mut tape, &[Value::String("1".to_string()), Value::Null]).contains("expects Int32"));
+        assert!(err(&mut tape, &[Value::Null, Value::Null]).contains("not nullable"));
+        assert!(err(&mut tape, &[Value::Int(1 << 40), Value::Null]).contains("out of range"));
+        assert!(err(&mut tape, &[Value::Int(1), Value::String("too long!".to_string())]).contains("at most 8"));
+        assert!(err(&mut tape, &[Value::Int(1)]).contains("Expected 2 values"));
+        
+        assert_eq!(tape.scan_rows("people").unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_full_table_and_non_table_segments() {
+        let mut tape = people();
+        let row = [Value::Int(7), Value::String("12345678".to_string())];
+        let result = (0..100).try_for_each(|_| tape.insert_row("people", &row).map(|_| ()));
+        assert!(result.unwrap_err().contains("is full"));
+        
+        tape.create_segment("raw".to_string(), 64, SegmentType::Data).unwrap();
+        assert!(tape.scan_rows("raw").err().unwrap().contains("not a table"));
+    }
+}
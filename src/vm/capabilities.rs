@@ -0,0 +1,94 @@
+//! Runtime capability gating for sandboxed execution
+//!
+//! Each VM carries a capability set; instructions needing a capability the VM
+//! lacks are rejected before they touch any state.
+
+use crate::instruction::Instruction;
+
+/// Bitset of optional VM capabilities
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VmCapabilities(u32);
+
+impl VmCapabilities {
+    /// Host calls via `Trap`
+    pub const TRAPS: VmCapabilities = VmCapabilities(1 << 0);
+    /// Host output such as `Debug`
+    pub const HOST_IO: VmCapabilities = VmCapabilities(1 << 1);
+    /// Timeline creation, switching and merging (`Fork`, `TimelineSwitch`, `Merge`)
+    pub const FORK: VmCapabilities = VmCapabilities(1 << 2);
+    /// Checkpoints and rewinds
+    pub const TIME_TRAVEL: VmCapabilities = VmCapabilities(1 << 3);
+    /// Runtime segment management
+    pub const SEGMENTS: VmCapabilities = VmCapabilities(1 << 4);
+    
+    /// No optional capabilities
+    pub const fn none() -> Self {
+        VmCapabilities(0)
+    }
+    
+    /// Every capability
+    pub const fn all() -> Self {
+        VmCapabilities(
+            Self::TRAPS.0 | Self::HOST_IO.0 | Self::FORK.0 | Self::TIME_TRAVEL.0 | Self::SEGMENTS.0
+        )
+    }
+    
+    /// Check whether all capabilities in `other` are present
+    pub fn contains(&self, other: VmCapabilities) -> bool {
+        self.0 & other.0 == other.0
+    }
+    
+    /// Add capabilities
+    pub fn insert(&mut self, other: VmCapabilities) {
+        self.0 |= other.0;
+    }
+    
+    /// Remove capabilities
+    pub fn remove(&mut self, other: VmCapabilities) {
+        self.0 &= !other.0;
+    }
+    
+    /// Capability an instruction requires, if any
+    pub fn required_for(inst: &Instruction) -> Option<VmCapabilities> {
+        match inst {
+            Instruction::Trap { .. } => Some(Self::TRAPS),
+            Instruction::Debug { .. } => Some(Self::HOST_IO),
+            Instruction::Fork { .. } |
+            Instruction::TimelineSwitch { .. } |
+            Instruction::Merge { .. } => Some(Self::FORK),
+            Instruction::Checkpoint { .. } |
+            Instruction::Rewind { .. } |
+            Instruction::RewindN { .. } => Some(Self::TIME_TRAVEL),
+            Instruction::SegmentCreate { .. } |
+            Instruction::SegmentSeek { .. } |
+            Instruction::SegmentRead { .. } |
+            Instruction::SegmentWrite { .. } => Some(Self::SEGMENTS),
+            _ => None,
+        }
+    }
+}
+
+impl Default for VmCapabilities {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_remove() {
+        let mut caps = VmCapabilities::all();
+        assert!(caps.contains(VmCapabilities::TRAPS));
+        
+        caps.remove(VmCapabilities::TRAPS);
+        assert!(!caps.contains(VmCapabilities::TRAPS));
+        assert!(caps.contains(VmCapabilities::FORK));
+        
+        caps.insert(VmCapabilities::TRAPS);
+        assert_eq!(caps, VmCapabilities::all());
+        assert!(!VmCapabilities::none().contains(VmCapabilities::HOST_IO));
+    }
+}
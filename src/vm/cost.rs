@@ -0,0 +1,27 @@
+//! Deterministic cost models for cycle accounting
+//!
+//! The VM charges each executed instruction through a `CostModel`, so
+//! experiments can model a custom architecture without touching the executor.
+
+use crate::instruction::Instruction;
+
+/// Assigns a cycle cost to each executed instruction
+pub trait CostModel {
+    fn cost(&self, inst: &Instruction) -> u64;
+}
+
+/// Charges each instruction its nominal `Instruction::cycle_cost`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultCostModel;
+
+impl CostModel for DefaultCostModel {
+    fn cost(&self, inst: &Instruction) -> u64 {
+        inst.cycle_cost()
+    }
+}
+
+impl<F: Fn(&Instruction) -> u64> CostModel for F {
+    fn cost(&self, inst: &Instruction) -> u64 {
+        self(inst)
+    }
+}
@@ -1,14 +1,64 @@
 //! VM executor - the heart of the Palindrome VM
 
-use crate::tape::{SegmentedTape, SegmentType};
-use crate::instruction::Instruction;
-use crate::vm::registers::RegisterFile;
+use crate::tape::{SdmTape, SegmentedTape, SegmentType, TrailOp};
+use crate::instruction::{encode_program, Instruction};
+use crate::vm::capabilities::VmCapabilities;
+use crate::vm::cost::{CostModel, DefaultCostModel};
+use crate::vm::registers::{Register, RegisterFile};
+use crate::vm::timeline::{ForkPoint, Timeline};
 use std::collections::HashMap;
+use std::io::{self, Write};
+
+/// Maximum number of subtractions `ModRepeat` performs before giving up
+const MOD_REPEAT_LIMIT: i64 = 1 << 20;
+
+/// Odd multiplier of the `HashUpdate` rolling hash, so each step is invertible mod 2^64
+const HASH_MULTIPLIER: u64 = 0x0000_0100_0000_01b3;
+
+/// Initial `sp`; the stack grows down from here
+const STACK_BASE: i64 = 1024 * 1024;
+
+/// Tape address of the `data` segment, right after the standard code, stack and heap
+pub const DATA_SEGMENT_BASE: i64 = 3 * 1024 * 1024;
+
+/// Most tape bytes a single range instruction (sort, splice, checksum, ...) may cover
+pub const MAX_RANGE_BYTES: usize = 64 * 1024 * 1024;
+
+/// Host callback servicing `Trap` instructions
+pub type TrapHandler = Box<dyn FnMut(u8, &mut RegisterFile) -> Result<(), String>>;
+
+/// Receives a `LossInfo` for each executed instruction whose arithmetic discarded operand bits
+pub type LossSink = Box<dyn FnMut(LossInfo)>;
+
+/// Where lossy arithmetic destroyed information during a run
+#[derive(Debug, Clone, PartialEq)]
+pub struct LossInfo {
+    pub ip: i64,
+    pub instruction: Instruction,
+}
+
+/// Receives a `WatchHit` for each tape write that touches a watchpoint
+pub type WatchHandler = Box<dyn FnMut(WatchHit)>;
+
+/// A tape write that intersected a watched range
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchHit {
+    /// The instruction that wrote
+    pub ip: i64,
+    /// Tape position of the whole write
+    pub pos: i64,
+    /// Bytes the write replaced
+    pub old: Vec<u8>,
+    /// Bytes it wrote
+    pub new: Vec<u8>,
+}
 
 /// The main VM structure
 pub struct VM {
     /// The global tape (with segments)
     pub tape: SegmentedTape,
+    /// Optional software-defined memory kept in step with checkpoints and rewinds
+    pub sdm: Option<SdmTape>,
     /// Register file
     pub registers: RegisterFile,
     /// Instruction pointer (position on tape)
@@ -17,16 +67,46 @@ pub struct VM {
     pub sp: i64,
     /// Frame pointer
     pub fp: i64,
-    /// Instruction counter (monotonically increasing)
+    /// Instruction counter; reversing and rewinding restore its earlier value
     pub ic: u64,
+    /// Cycles charged by the cost model; reversal does not refund them
+    pub cycles: u64,
     /// Execution history
     pub history: ExecutionHistory,
+    /// Whether to record history frames; forward-only runs can skip them
+    pub history_enabled: bool,
+    /// Require 8-byte alignment for word memory and stack accesses
+    pub alignment_check: bool,
     /// Parallel timelines (for fork/merge)
     pub timelines: HashMap<String, Timeline>,
     /// Current timeline
     pub current_timeline: String,
+    /// Forks in the current timeline's ancestry, oldest first
+    pub forks: Vec<ForkPoint>,
+    /// Most forked timelines that may exist at once; `None` is unbounded
+    pub max_timelines: Option<usize>,
     /// Symbol table for labels
     pub symbols: HashMap<String, i64>,
+    /// Code segment offset of each loaded instruction, plus the end of the program
+    pub code_offsets: Vec<usize>,
+    /// Optional capabilities this VM may use
+    pub capabilities: VmCapabilities,
+    /// Host handler for `Trap` instructions
+    trap_handler: Option<TrapHandler>,
+    /// Notified when a non-reversible instruction executes
+    loss_sink: Option<LossSink>,
+    /// Prices each executed instruction into `cycles`
+    cost_model: Box<dyn CostModel>,
+    /// Taken/not-taken counts per conditional branch site
+    branch_counts: HashMap<i64, (u64, u64)>,
+    /// Watched tape ranges, each `start..end`
+    watchpoints: Vec<(i64, i64)>,
+    /// Notified of writes into a watched range
+    watch_handler: Option<WatchHandler>,
+    /// Executions per instruction name, when tracing is enabled
+    trace: Option<HashMap<&'static str, u64>>,
+    /// Tape pages reclaimed by `Compact` so far, reported through `RunStats`
+    pages_compacted: usize,
 }
 
 /// Execution history for reversibility
@@ -34,30 +114,71 @@ pub struct ExecutionHistory {
     /// Stack of executed instructions with saved state
     pub stack: Vec<HistoryFrame>,
     /// Named checkpoints
-    pub checkpoints: HashMap<String, usize>,
+    pub checkpoints: HashMap<String, CheckpointState>,
+    /// Instructions undone by `reverse_last`, most recently undone last
+    pub redo: Vec<Instruction>,
+}
+
+/// What a single `VM::step` did
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepOutcome {
+    /// An instruction ran and execution can continue
+    Executed,
+    /// A `Halt` instruction stopped the program
+    Halted,
+    /// `ip` is past the end of the program (always the case for an empty one)
+    Finished,
+}
+
+/// Summary of a `VM::run`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RunStats {
+    /// Instructions executed, including a final `Halt`
+    pub executed: usize,
+    /// Whether a `Halt` instruction stopped the run
+    pub halted: bool,
+    /// `ip` when the run stopped
+    pub final_ip: i64,
+    /// Tape pages reclaimed by `Compact` instructions during the run
+    pub pages_compacted: usize,
+}
+
+/// VM state captured by a checkpoint
+#[derive(Clone)]
+pub struct CheckpointState {
+    /// History depth when the checkpoint was taken
+    pub depth: usize,
+    pub registers: RegisterFile,
+    /// Where execution resumes after a rewind
+    pub ip: i64,
+    pub sp: i64,
+    pub fp: i64,
+    pub ic: u64,
 }
 
 /// A single frame in the execution history
 #[derive(Clone)]
 pub struct HistoryFrame {
     pub instruction: Instruction,
-    pub registers_before: RegisterFile,
+    pub registers_before: SavedRegisters,
     pub ip_before: i64,
     pub sp_before: i64,
     pub fp_before: i64,
     pub ic_before: u64,
     pub tape_trail_len: usize,
+    /// The instruction had host side effects that reversal cannot undo
+    pub irreversible: bool,
 }
 
-/// A parallel timeline (for fork operations)
+/// Register state saved by a history frame
 #[derive(Clone)]
-pub struct Timeline {
-    pub tape: SegmentedTape,
-    pub registers: RegisterFile,
-    pub ip: i64,
-    pub sp: i64,
-    pub fp: i64,
-    pub ic: u64,
+pub enum SavedRegisters {
+    /// The whole register file
+    All(RegisterFile),
+    /// Old value of the one register a read instruction loads into
+    One(Register, i64),
+    /// Nothing: reversal computes the instruction's inverse instead
+    Derived,
 }
 
 impl VM {
@@ -74,25 +195,126 @@ impl VM {
         
         VM {
             tape,
+            sdm: None,
             registers: RegisterFile::new(),
             ip: 0,
-            sp: 1024 * 1024, // Stack starts at 1MB
-            fp: 1024 * 1024,
+            sp: STACK_BASE,
+            fp: STACK_BASE,
             ic: 0,
+            cycles: 0,
             history: ExecutionHistory::new(),
+            history_enabled: true,
+            alignment_check: false,
             timelines: HashMap::new(),
             current_timeline: "main".to_string(),
+            forks: Vec::new(),
+            max_timelines: None,
             symbols: HashMap::new(),
+            code_offsets: Vec::new(),
+            capabilities: VmCapabilities::all(),
+            trap_handler: None,
+            loss_sink: None,
+            cost_model: Box::new(DefaultCostModel),
+            branch_counts: HashMap::new(),
+            watchpoints: Vec::new(),
+            watch_handler: None,
+            trace: None,
+            pages_compacted: 0,
+        }
+    }
+    
+    /// Create a forward-only VM that records no history
+    ///
+    /// Execution skips the per-instruction register snapshot and the tape
+    /// keeps no trail, so neither instructions nor checkpoints can be reversed,
+    /// and timelines forked from it cannot be merged back.
+    pub fn without_history() -> Self {
+        let mut vm = VM {
+            history_enabled: false,
+            ..Self::new()
+        };
+        vm.tape.tape.set_history_limit(Some(0));
+        vm
+    }
+    
+    /// Create a VM with `count` general purpose registers instead of 16
+    ///
+    /// Assemble its programs with `Parser::with_register_count` so the extra
+    /// registers are accepted.
+    pub fn with_register_count(count: usize) -> Self {
+        VM {
+            registers: RegisterFile::with_count(count),
+            ..Self::new()
         }
     }
     
+    /// Revoke capabilities; instructions needing them fail with a `Forbidden` error
+    pub fn disallow(&mut self, capabilities: VmCapabilities) {
+        self.capabilities.remove(capabilities);
+    }
+    
+    /// Install the host handler invoked by `Trap`
+    pub fn set_trap_handler(
+        &mut self,
+        handler: impl FnMut(u8, &mut RegisterFile) -> Result<(), String> + 'static,
+    ) {
+        self.trap_handler = Some(Box::new(handler));
+    }
+    
+    /// Report every executed instruction that is not `Instruction::is_reversible`
+    ///
+    /// Overwriting a destination (`LoadImm`, `Pop`, ...) is not reported.
+    pub fn set_loss_sink(&mut self, sink: impl FnMut(LossInfo) + 'static) {
+        self.loss_sink = Some(Box::new(sink));
+    }
+    
+    /// Watch tape positions `start..end` for writes
+    ///
+    /// Any instruction whose writes intersect the range is reported to the
+    /// handler from `set_watch_handler` once it has executed. Writes are found
+    /// in the tape's trail, so those the history limit has already evicted (and
+    /// every write of a VM built with `without_history`) go unreported.
+    pub fn add_watchpoint(&mut self, start: i64, end: i64) {
+        self.watchpoints.push((start, end));
+    }
+    
+    /// Install the handler notified of writes into watched ranges
+    pub fn set_watch_handler(&mut self, handler: impl FnMut(WatchHit) + 'static) {
+        self.watch_handler = Some(Box::new(handler));
+    }
+    
+    /// Replace the cost model used to charge `cycles`
+    pub fn set_cost_model(&mut self, model: impl CostModel + 'static) {
+        self.cost_model = Box::new(model);
+    }
+    
     /// Execute a single instruction
     pub fn execute(&mut self, inst: Instruction) -> Result<(), String> {
+        // Reject disallowed instructions before they leave any trace
+        if let Some(required) = VmCapabilities::required_for(&inst)
+            && !self.capabilities.contains(required)
+        {
+            return Err(format!("Forbidden: {:?}", inst));
+        }
+        
+        // New execution invalidates anything that could be replayed
+        self.history.redo.clear();
+        
         // Save state for reversibility
-        self.save_history_frame(inst.clone());
+        if self.history_enabled {
+            self.save_history_frame(inst.clone());
+        }
         
         // Increment instruction counter
         self.ic += 1;
+        if let Some(trace) = self.trace.as_mut() {
+            *trace.entry(inst.name()).or_default() += 1;
+        }
+        self.cycles += self.cost_model.cost(&inst);
+        
+        let lost = (!inst.is_reversible() && self.loss_sink.is_some()).then(|| inst.clone());
+        let watch_from = (!self.watchpoints.is_empty() && self.watch_handler.is_some())
+            .then(|| self.tape.tape.trail_len());
         
         match inst {
             // Reversible arithmetic operations (RISA)
@@ -100,16 +322,26 @@ impl VM {
                 let val1 = self.registers.read(src1)?;
                 let val2 = self.registers.read(src2)?;
                 let old_dst = self.registers.read(dst)?;
-                self.registers.write(dst, old_dst.wrapping_add(val1).wrapping_add(val2))?;
-                self.registers.update_flags(self.registers.read(dst)?);
+                let result = old_dst.wrapping_add(val1).wrapping_add(val2);
+                self.registers.write(dst, result)?;
+                
+                // Widen so both additions are judged together, not one at a time
+                let unsigned = old_dst as u64 as u128 + val1 as u64 as u128 + val2 as u64 as u128;
+                let signed = old_dst as i128 + val1 as i128 + val2 as i128;
+                self.registers.update_arith_flags(result, unsigned > u64::MAX as u128, signed != result as i128);
             }
             
             Instruction::RSub { src1, src2, dst } => {
                 let val1 = self.registers.read(src1)?;
                 let val2 = self.registers.read(src2)?;
                 let old_dst = self.registers.read(dst)?;
-                self.registers.write(dst, old_dst.wrapping_sub(val1).wrapping_sub(val2))?;
-                self.registers.update_flags(self.registers.read(dst)?);
+                let result = old_dst.wrapping_sub(val1).wrapping_sub(val2);
+                self.registers.write(dst, result)?;
+                
+                // Carry is the unsigned borrow
+                let unsigned = old_dst as u64 as i128 - val1 as u64 as i128 - val2 as u64 as i128;
+                let signed = old_dst as i128 - val1 as i128 - val2 as i128;
+                self.registers.update_arith_flags(result, unsigned < 0, signed != result as i128);
             }
             
             Instruction::RXor { src, dst } => {
@@ -119,16 +351,134 @@ impl VM {
                 self.registers.update_flags(self.registers.read(dst)?);
             }
             
+            Instruction::ModRepeat { dst, src, modulus, quotient } => {
+                let value = self.registers.read(src)?;
+                let m = self.registers.read(modulus)?;
+                
+                if m <= 0 {
+                    return Err(format!("ModRepeat requires a positive modulus, got {}", m));
+                }
+                if quotient == dst {
+                    return Err(format!("ModRepeat quotient and dst must differ, both are R{}", dst));
+                }
+                
+                // Only subtraction is used, so src = quotient * modulus + dst
+                // can always be reconstructed from the outputs
+                let mut remainder = value;
+                let mut count = 0i64;
+                while remainder >= m {
+                    if count >= MOD_REPEAT_LIMIT {
+                        return Err(format!(
+                            "ModRepeat exceeded {} subtractions (modulus {})",
+                            MOD_REPEAT_LIMIT, m
+                        ));
+                    }
+                    remainder -= m;
+                    count += 1;
+                }
+                
+                self.registers.write(quotient, count)?;
+                self.registers.write(dst, remainder)?;
+                self.registers.update_flags(remainder);
+            }
+            
+            Instruction::IMul { src1, src2, dst } => {
+                let product = self.registers.read(src1)?.wrapping_mul(self.registers.read(src2)?);
+                self.registers.write(dst, product)?;
+                self.registers.update_flags(product);
+            }
+            
+            Instruction::IDiv { src1, src2, dst } => {
+                let divisor = self.registers.read(src2)?;
+                if divisor == 0 {
+                    return Err("Division by zero".to_string());
+                }
+                let quotient = self.registers.read(src1)?.wrapping_div(divisor);
+                self.registers.write(dst, quotient)?;
+                self.registers.update_flags(quotient);
+            }
+            
+            Instruction::IMod { src1, src2, dst } => {
+                let divisor = self.registers.read(src2)?;
+                if divisor == 0 {
+                    return Err("Division by zero".to_string());
+                }
+                let remainder = self.registers.read(src1)?.wrapping_rem(divisor);
+                self.registers.write(dst, remainder)?;
+                self.registers.update_flags(remainder);
+            }
+            
+            Instruction::IAnd { src1, src2, dst } => {
+                let result = self.registers.read(src1)? & self.registers.read(src2)?;
+                self.registers.write(dst, result)?;
+                self.registers.update_flags(result);
+            }
+            
+            Instruction::IOr { src1, src2, dst } => {
+                let result = self.registers.read(src1)? | self.registers.read(src2)?;
+                self.registers.write(dst, result)?;
+                self.registers.update_flags(result);
+            }
+            
+            Instruction::INot { dst, src } => {
+                let result = !self.registers.read(src)?;
+                self.registers.write(dst, result)?;
+                self.registers.update_flags(result);
+            }
+            
+            Instruction::Shl { dst, src, amount } |
+            Instruction::Shr { dst, src, amount } |
+            Instruction::Sar { dst, src, amount } => {
+                let value = self.registers.read(src)?;
+                let shift = (self.registers.read(amount)? & 63) as u32;
+                let (result, last_out) = match inst {
+                    Instruction::Shl { .. } => (
+                        value.wrapping_shl(shift),
+                        shift > 0 && (value as u64 >> (64 - shift)) & 1 == 1,
+                    ),
+                    Instruction::Shr { .. } => (
+                        (value as u64).wrapping_shr(shift) as i64,
+                        shift > 0 && (value as u64 >> (shift - 1)) & 1 == 1,
+                    ),
+                    _ => (
+                        value.wrapping_shr(shift),
+                        shift > 0 && (value as u64 >> (shift - 1)) & 1 == 1,
+                    ),
+                };
+                self.registers.write(dst, result)?;
+                self.registers.update_flags(result);
+                // Carry holds the last bit shifted out
+                self.registers.flags.carry = last_out;
+            }
+            
+            Instruction::BitReverse { dst, src, width } => {
+                if width > 64 {
+                    return Err(format!("BitReverse width {} exceeds 64 bits", width));
+                }
+                
+                let value = self.registers.read(src)? as u64;
+                let result = if width == 0 {
+                    value
+                } else {
+                    let mask = u64::MAX >> (64 - width as u32);
+                    let reversed = (value & mask).reverse_bits() >> (64 - width as u32);
+                    (value & !mask) | reversed
+                } as i64;
+                
+                self.registers.write(dst, result)?;
+                self.registers.update_flags(result);
+            }
+            
             // Reversible memory operations (RISA)
             Instruction::RLoad { dst, addr, old } => {
                 let address = self.registers.read(addr)?;
+                self.check_aligned(address)?;
                 let old_dst = self.registers.read(dst)?;
                 
-                // Use SDM if available, otherwise use regular tape
-                // For now, using regular tape
-                self.tape.tape.seek(address);
+                // Read in place: the head stays put, so the inverse undoes the
+                // load without anything on the trail
                 let value = i64::from_le_bytes(
-                    self.tape.tape.read(8).try_into()
+                    self.tape.tape.read_at(address, 8).try_into()
                         .map_err(|_| "Failed to read 8 bytes")?
                 );
                 
@@ -138,6 +488,7 @@ impl VM {
             
             Instruction::RStore { addr, src, old } => {
                 let address = self.registers.read(addr)?;
+                self.check_aligned(address)?;
                 let value = self.registers.read(src)?;
                 
                 // Read old value from memory
@@ -157,6 +508,7 @@ impl VM {
             
             Instruction::MSwap { addr, reg } => {
                 let address = self.registers.read(addr)?;
+                self.check_aligned(address)?;
                 let reg_value = self.registers.read(reg)?;
                 
                 // Read memory value
@@ -172,6 +524,91 @@ impl VM {
                 self.tape.tape.write(&reg_value.to_le_bytes());
             }
             
+            Instruction::IncSat { addr, max } => {
+                let address = self.registers.read(addr)?;
+                self.check_aligned(address)?;
+                let limit = self.registers.read(max)?;
+                let value = i64::from_le_bytes(
+                    self.tape.tape.read_at(address, 8).try_into()
+                        .map_err(|_| "Failed to read 8 bytes")?
+                );
+                
+                // The head lands on the counter either way; a saturated one is
+                // not rewritten, so the trail holds no write to undo
+                self.tape.tape.seek(address);
+                if value < limit {
+                    self.tape.tape.write(&(value + 1).to_le_bytes());
+                }
+            }
+            
+            Instruction::HashUpdate { state, addr, len } => {
+                let data = self.hash_input(addr, len)?;
+                let hash = rolling_hash(self.registers.read(state)? as u64, &data);
+                self.registers.write(state, hash as i64)?;
+            }
+            
+            Instruction::Crc32 { dst, addr, len } => {
+                let address = self.registers.read(addr)?;
+                let length = range_len("CRC32 length", address, self.registers.read(len)?, 1)?;
+                
+                let data = self.tape.tape.read_at(address, length);
+                self.registers.write(dst, crc32(&data) as i64)?;
+            }
+            
+            Instruction::SortRange { addr, count, elem_size } => {
+                let address = self.registers.read(addr)?;
+                if !(1..=8).contains(&elem_size) {
+                    return Err(format!("Invalid sort element size: {}", elem_size));
+                }
+                
+                let size = elem_size as usize;
+                let length = range_len("sort count", address, self.registers.read(count)?, size)?;
+                let data = self.tape.tape.read_at(address, length);
+                let mut elements: Vec<&[u8]> = data.chunks(size).collect();
+                elements.sort_by_key(|elem| sign_extend_le(elem));
+                
+                // One write covering the range keeps the pre-image in the trail
+                let sorted = elements.concat();
+                if sorted != data {
+                    self.tape.tape.seek(address);
+                    self.tape.tape.write(&sorted);
+                }
+            }
+            
+            Instruction::Interleave { a, b, dst, count } => {
+                let n = self.registers.read(count)?;
+                let (a, b, dst) = (self.registers.read(a)?, self.registers.read(b)?, self.registers.read(dst)?);
+                let len = range_len("interleave count", a, n, 1)?;
+                range_len("interleave count", b, n, 1)?;
+                range_len("interleave count", dst, n, 2)?;
+                
+                let lane_a = self.tape.tape.read_at(a, len);
+                let lane_b = self.tape.tape.read_at(b, len);
+                let mixed: Vec<u8> = lane_a.iter().zip(&lane_b)
+                    .flat_map(|(&x, &y)| [x, y])
+                    .collect();
+                
+                self.tape.tape.seek(dst);
+                self.tape.tape.write(&mixed);
+            }
+            
+            Instruction::Deinterleave { src, a, b, count } => {
+                let n = self.registers.read(count)?;
+                let (src, a, b) = (self.registers.read(src)?, self.registers.read(a)?, self.registers.read(b)?);
+                let len = range_len("deinterleave count", src, n, 2)?;
+                range_len("deinterleave count", a, n, 1)?;
+                range_len("deinterleave count", b, n, 1)?;
+                
+                let mixed = self.tape.tape.read_at(src, len);
+                let lane_a: Vec<u8> = mixed.iter().step_by(2).copied().collect();
+                let lane_b: Vec<u8> = mixed.iter().skip(1).step_by(2).copied().collect();
+                
+                self.tape.tape.seek(a);
+                self.tape.tape.write(&lane_a);
+                self.tape.tape.seek(b);
+                self.tape.tape.write(&lane_b);
+            }
+            
             // Register operations
             Instruction::Swap { reg1, reg2 } => {
                 let val1 = self.registers.read(reg1)?;
@@ -181,6 +618,7 @@ impl VM {
             }
             
             Instruction::Push { reg } => {
+                self.check_aligned(self.sp)?;
                 self.sp -= 8;
                 self.tape.tape.seek(self.sp);
                 let value = self.registers.read(reg)?;
@@ -188,6 +626,7 @@ impl VM {
             }
             
             Instruction::Pop { reg } => {
+                self.check_aligned(self.sp)?;
                 self.tape.tape.seek(self.sp);
                 let value = i64::from_le_bytes(
                     self.tape.tape.read(8).try_into()
@@ -197,6 +636,33 @@ impl VM {
                 self.sp += 8;
             }
             
+            Instruction::Dup => {
+                self.check_stack_depth(1)?;
+                let top = self.stack_word(0)?;
+                self.sp -= 8;
+                self.write_stack_word(0, top);
+            }
+            
+            Instruction::Drop => {
+                self.check_stack_depth(1)?;
+                self.sp += 8;
+            }
+            
+            Instruction::Over => {
+                self.check_stack_depth(2)?;
+                let second = self.stack_word(8)?;
+                self.sp -= 8;
+                self.write_stack_word(0, second);
+            }
+            
+            Instruction::SwapTop => {
+                self.check_stack_depth(2)?;
+                let top = self.stack_word(0)?;
+                let second = self.stack_word(8)?;
+                self.write_stack_word(0, second);
+                self.write_stack_word(8, top);
+            }
+            
             // Tape operations
             Instruction::TapeRead { reg, len } => {
                 let data = self.tape.tape.read(len as usize);
@@ -241,14 +707,45 @@ impl VM {
             }
             
             Instruction::BranchZero { reg, label } => {
-                if self.registers.read(reg)? == 0 {
+                let taken = self.registers.read(reg)? == 0;
+                self.record_branch(taken);
+                if taken {
+                    self.ip = self.resolve_label(&label)?;
+                    return Ok(()); // Don't increment IP
+                }
+            }
+            
+            Instruction::Branch { condition, label } => {
+                let taken = self.registers.read(condition)? != 0;
+                self.record_branch(taken);
+                if taken {
                     self.ip = self.resolve_label(&label)?;
                     return Ok(()); // Don't increment IP
                 }
             }
             
             Instruction::BranchNotZero { reg, label } => {
-                if self.registers.read(reg)? != 0 {
+                let taken = self.registers.read(reg)? != 0;
+                self.record_branch(taken);
+                if taken {
+                    self.ip = self.resolve_label(&label)?;
+                    return Ok(()); // Don't increment IP
+                }
+            }
+            
+            Instruction::BranchLessThanU { src1, src2, label } => {
+                let taken = (self.registers.read(src1)? as u64) < self.registers.read(src2)? as u64;
+                self.record_branch(taken);
+                if taken {
+                    self.ip = self.resolve_label(&label)?;
+                    return Ok(()); // Don't increment IP
+                }
+            }
+            
+            Instruction::BranchGreaterEqualU { src1, src2, label } => {
+                let taken = self.registers.read(src1)? as u64 >= self.registers.read(src2)? as u64;
+                self.record_branch(taken);
+                if taken {
                     self.ip = self.resolve_label(&label)?;
                     return Ok(()); // Don't increment IP
                 }
@@ -294,34 +791,100 @@ impl VM {
             
             // Time operations
             Instruction::Checkpoint { label } => {
-                self.tape.tape.checkpoint(label.clone());
-                self.history.checkpoints.insert(label, self.history.stack.len());
+                self.checkpoint(&label)?;
+                // Resume after this instruction when rewound to
+                if let Some(state) = self.history.checkpoints.get_mut(&label) {
+                    state.ip += 1;
+                }
             }
             
-            Instruction::Rewind { label } => {
-                self.tape.tape.rewind(&label)?;
+            Instruction::Splice { dst, src, len } => {
+                let length = self.registers.read(len)?;
+                range_len("splice length", dst, length, 1)?;
+                let length = range_len("splice length", src, length, 1)?;
                 
-                // Restore VM state
-                if let Some(&checkpoint_pos) = self.history.checkpoints.get(&label) {
-                    while self.history.stack.len() > checkpoint_pos {
+                // Buffer the source first so overlapping ranges copy correctly;
+                // the write records the overwritten bytes on the trail
+                let data = self.tape.tape.read_at(src, length);
+                let head = self.tape.tape.position();
+                self.tape.tape.seek(dst);
+                self.tape.tape.write(&data);
+                self.tape.tape.seek(head);
+            }
+            
+            Instruction::Compact { start, end } => {
+                self.pages_compacted += self.tape.tape.compact(start, end);
+            }
+            
+            Instruction::Fork { label } => {
+                if let Err(e) = self.fork(&label) {
+                    // No timeline was created, so there is nothing for reversal to discard
+                    if self.history_enabled {
                         self.history.stack.pop();
                     }
-                    
-                    if let Some(frame) = self.history.stack.last() {
-                        self.registers = frame.registers_before.clone();
-                        self.ip = frame.ip_before;
-                        self.sp = frame.sp_before;
-                        self.fp = frame.fp_before;
+                    return Err(e);
+                }
+                // The forked timeline resumes after this instruction
+                if let Some(timeline) = self.timelines.get_mut(&label) {
+                    timeline.ip += 1;
+                }
+            }
+            
+            Instruction::TimelineSwitch { label } => {
+                // The switch exchanges whole histories, so it is not an entry in either
+                if self.history_enabled {
+                    self.history.stack.pop();
+                }
+                
+                // The timeline being parked resumes after this instruction
+                self.ip += 1;
+                if let Err(e) = self.switch_timeline(&label) {
+                    self.ip -= 1;
+                    return Err(e);
+                }
+                return Ok(()); // IP comes from the loaded timeline
+            }
+            
+            Instruction::Merge { strategy } => {
+                let sources = self.merge_sources();
+                let conflicts = self.merge_all(&sources, strategy)?;
+                if !conflicts.is_empty() {
+                    return Err(format!(
+                        "Merge conflicts at tape positions {:?} (see merge_conflicts)", conflicts
+                    ));
+                }
+            }
+            
+            Instruction::Rewind { label } => {
+                if let Err(e) = self.rewind(&label) {
+                    // Nothing was rewound, so drop this instruction's own frame
+                    if self.history_enabled {
+                        self.history.stack.pop();
                     }
+                    return Err(e);
                 }
                 return Ok(()); // IP already restored
             }
             
             Instruction::RewindN { steps } => {
-                let n = self.registers.read(steps)? as usize;
-                for _ in 0..n {
+                // Our own frame is not one of the steps to undo
+                if self.history_enabled {
+                    self.history.stack.pop();
+                }
+                let depth = self.rewind_depth(self.registers.read(steps)?)?;
+                if depth == 0 {
+                    // Nothing to undo, so reverse_last will not move the IP
+                    self.ip += 1;
+                    return Ok(());
+                }
+                for _ in 0..depth {
                     self.reverse_last()?;
                 }
+                // Steps undone by the program itself are not replayable
+                self.history.redo.clear();
+                if let Some(sdm) = &self.sdm {
+                    sdm.prefetch_rewind_targets().ok();
+                }
                 return Ok(()); // IP handled by reverse_last
             }
             
@@ -355,6 +918,22 @@ impl VM {
                 self.registers.update_flags(result);
             }
             
+            Instruction::CompareU { dst, src1, src2 } => {
+                let val1 = self.registers.read(src1)? as u64;
+                let val2 = self.registers.read(src2)? as u64;
+                let result = val1.cmp(&val2) as i64;
+                self.registers.write(dst, result)?;
+                self.registers.update_flags(result);
+            }
+            
+            Instruction::LessThanU { dst, src1, src2 } => {
+                let val1 = self.registers.read(src1)? as u64;
+                let val2 = self.registers.read(src2)? as u64;
+                let result = if val1 < val2 { 1 } else { 0 };
+                self.registers.write(dst, result)?;
+                self.registers.update_flags(result);
+            }
+            
             // System
             Instruction::Halt => {
                 return Err("HALT".to_string());
@@ -364,23 +943,108 @@ impl VM {
                 // Do nothing
             }
             
+            Instruction::Trap { code } => {
+                let handler = self.trap_handler.as_mut()
+                    .ok_or_else(|| format!("Unhandled trap: {}", code))?;
+                handler(code, &mut self.registers)?;
+                // Only a trap the host serviced has side effects reversal cannot undo
+                if self.history_enabled
+                    && let Some(frame) = self.history.stack.last_mut()
+                {
+                    frame.irreversible = true;
+                }
+            }
+            
             Instruction::Debug { message } => {
                 println!("DEBUG: {}", message);
                 println!("  IP: {}, SP: {}, FP: {}", self.ip, self.sp, self.fp);
-                println!("  Registers: {:?}", &self.registers.general[0..8]);
+                let general = &self.registers.general;
+                println!("  Registers: {:?}", &general[..general.len().min(8)]);
+            }
+            
+            // Segments
+            Instruction::SegmentCreate { name, size } => {
+                let size = self.registers.read(size)?;
+                if size < 0 {
+                    return Err(format!("Negative segment size: {}", size));
+                }
+                self.tape.create_segment(name, size as usize, SegmentType::Data)?;
+            }
+            
+            Instruction::SegmentSeek { name, offset } => {
+                let offset = self.registers.read(offset)?;
+                let segment = self.tape.get_segment(&name)
+                    .ok_or_else(|| format!("Unknown segment: {}", name))?;
+                // One past the end is a valid position, like the end of a slice
+                if offset < 0 || offset > segment.size as i64 {
+                    return Err("Segment bounds violation".to_string());
+                }
+                let position = segment.start + offset;
+                self.tape.tape.seek(position);
+            }
+            
+            Instruction::SegmentRead { name, offset, len, dst } => {
+                let len = register_access_len(self.registers.read(len)?)?;
+                let data = self.tape.read_segment(&name, self.registers.read(offset)?, len)?;
+                let mut bytes = [0u8; 8];
+                bytes[..len].copy_from_slice(&data);
+                self.registers.write(dst, i64::from_le_bytes(bytes))?;
             }
             
-            _ => return Err(format!("Unimplemented instruction: {:?}", inst)),
+            Instruction::SegmentWrite { name, offset, len, src } => {
+                let len = register_access_len(self.registers.read(len)?)?;
+                let bytes = self.registers.read(src)?.to_le_bytes();
+                self.tape.write_segment(&name, self.registers.read(offset)?, &bytes[..len])?;
+            }
+        }
+        
+        if let (Some(instruction), Some(sink)) = (lost, self.loss_sink.as_mut()) {
+            sink(LossInfo { ip: self.ip, instruction });
+        }
+        if let Some(trail_len) = watch_from {
+            self.report_watched_writes(trail_len);
         }
         
         self.ip += 1;
         Ok(())
     }
     
+    /// Pass writes recorded since `trail_len` that hit a watchpoint to the handler
+    ///
+    /// Writes the trail no longer holds cannot be reported and are skipped;
+    /// the instruction has completed regardless.
+    fn report_watched_writes(&mut self, trail_len: usize) {
+        let Some(handler) = self.watch_handler.as_mut() else {
+            return;
+        };
+        let trail = &self.tape.tape;
+        let ops = trail.trail_since(trail_len.max(trail.trail_start())).unwrap_or_default();
+        for op in ops {
+            if let TrailOp::Write { pos, old, new, .. } = op {
+                let end = pos + new.len() as i64;
+                if self.watchpoints.iter().any(|&(start, stop)| *pos < stop && start < end) {
+                    handler(WatchHit { ip: self.ip, pos: *pos, old: old.clone(), new: new.clone() });
+                }
+            }
+        }
+    }
+    
     fn save_history_frame(&mut self, instruction: Instruction) {
+        let registers_before = match (&instruction, instruction.read_destination()) {
+            // The hash step is inverted from its input, unless `state` overwrites an operand
+            (Instruction::HashUpdate { state, addr, len }, _) if state != addr && state != len =>
+                SavedRegisters::Derived,
+            // Reads only change their destination, so that is all we need to keep
+            (_, Some(reg)) => match self.registers.read(reg) {
+                Ok(value) => SavedRegisters::One(reg, value),
+                Err(_) => SavedRegisters::All(self.registers.clone()),
+            },
+            _ => SavedRegisters::All(self.registers.clone()),
+        };
         let frame = HistoryFrame {
+            irreversible: false,
             instruction,
-            registers_before: self.registers.clone(),
+            registers_before,
             ip_before: self.ip,
             sp_before: self.sp,
             fp_before: self.fp,
@@ -390,6 +1054,87 @@ impl VM {
         self.history.stack.push(frame);
     }
     
+    /// Invert an instruction whose history frame saved no registers
+    fn undo_derived(&mut self, instruction: &Instruction) -> Result<(), String> {
+        if let Instruction::HashUpdate { state, addr, len } = *instruction {
+            // Unreadable input means the hash step failed and `state` never changed
+            if let Ok(data) = self.hash_input(addr, len) {
+                let hash = rolling_hash_inverse(self.registers.read(state)? as u64, &data);
+                self.registers.write(state, hash as i64)?;
+            }
+        }
+        Ok(())
+    }
+    
+    /// The tape bytes a `HashUpdate` folds in
+    fn hash_input(&self, addr: Register, len: Register) -> Result<Vec<u8>, String> {
+        let address = self.registers.read(addr)?;
+        let length = range_len("hash length", address, self.registers.read(len)?, 1)?;
+        Ok(self.tape.tape.read_at(address, length))
+    }
+    
+    /// Fail with `Unaligned` if alignment checking is on and `address` is not word-aligned
+    fn check_aligned(&self, address: i64) -> Result<(), String> {
+        if self.alignment_check && address % 8 != 0 {
+            return Err(format!("Unaligned: word access at {} is not 8-byte aligned", address));
+        }
+        Ok(())
+    }
+    
+    /// Fail unless `sp` is aligned and at least `words` words sit above it
+    fn check_stack_depth(&self, words: i64) -> Result<(), String> {
+        self.check_aligned(self.sp)?;
+        let depth = (STACK_BASE - self.sp) / 8;
+        if depth < words {
+            return Err(format!(
+                "Stack underflow: {} words needed, {} on the stack", words, depth
+            ));
+        }
+        Ok(())
+    }
+    
+    /// Read the stack word `offset` bytes above `sp`
+    fn stack_word(&self, offset: i64) -> Result<i64, String> {
+        let bytes = self.tape.tape.read_at(self.sp + offset, 8);
+        Ok(i64::from_le_bytes(bytes.try_into().map_err(|_| "Failed to read stack word")?))
+    }
+    
+    /// Write the stack word `offset` bytes above `sp`, recording it in the trail
+    fn write_stack_word(&mut self, offset: i64, value: i64) {
+        self.tape.tape.seek(self.sp + offset);
+        self.tape.tape.write(&value.to_le_bytes());
+    }
+    
+    fn record_branch(&mut self, taken: bool) {
+        let counts = self.branch_counts.entry(self.ip).or_default();
+        if taken {
+            counts.0 += 1;
+        } else {
+            counts.1 += 1;
+        }
+    }
+    
+    /// Conditional branch outcomes as `(site, taken, not_taken)`, ordered by site
+    pub fn branch_stats(&self) -> Vec<(i64, u64, u64)> {
+        let mut stats: Vec<_> = self.branch_counts.iter()
+            .map(|(&site, &(taken, not_taken))| (site, taken, not_taken))
+            .collect();
+        stats.sort_unstable();
+        stats
+    }
+    
+    /// Start counting executed instructions by name, from zero
+    pub fn enable_trace(&mut self) {
+        self.trace = Some(HashMap::new());
+    }
+    
+    /// Executions per instruction name since `enable_trace`
+    ///
+    /// Reversed instructions stay counted. Empty when tracing is off.
+    pub fn trace_summary(&self) -> HashMap<&'static str, u64> {
+        self.trace.clone().unwrap_or_default()
+    }
+    
     fn resolve_label(&self, label: &str) -> Result<i64, String> {
         self.symbols.get(label)
             .copied()
@@ -398,32 +1143,325 @@ impl VM {
     }
     
     /// Reverse the last executed instruction
+    ///
+    /// Fails with `CannotReverseTrap` if that instruction was a trap, since its
+    /// host side effects cannot be undone; see `reverse_last_force`.
     pub fn reverse_last(&mut self) -> Result<(), String> {
-        if let Some(frame) = self.history.stack.pop() {
-            // Restore registers
-            self.registers = frame.registers_before;
-            self.ip = frame.ip_before;
-            self.sp = frame.sp_before;
-            self.fp = frame.fp_before;
-            self.ic = frame.ic_before;
-            
+        if !self.history_enabled {
+            return Err(history_disabled());
+        }
+        if let Some(frame) = self.history.stack.last()
+            && frame.irreversible
+        {
+            return Err(cannot_reverse_trap(frame));
+        }
+        self.reverse_last_force()
+    }
+    
+    /// A concrete inverse of the last executed instruction, for analysis tools
+    ///
+    /// See `Instruction::inverse_with`; unlike `reverse_last` this changes nothing.
+    pub fn last_inverse(&self) -> Option<Vec<Instruction>> {
+        let frame = self.history.stack.last()?;
+        let trail = self.tape.tape.trail_since(frame.tape_trail_len).ok()?;
+        frame.instruction.inverse_with(frame, trail)
+    }
+    
+    /// Reverse the last executed instruction even if it was an irreversible trap
+    ///
+    /// VM state is restored, but the host is left to reconcile its own side effects.
+    pub fn reverse_last_force(&mut self) -> Result<(), String> {
+        if !self.history_enabled {
+            return Err(history_disabled());
+        }
+        if let Some(frame) = self.history.stack.last()
+            && frame.tape_trail_len < self.tape.tape.trail_start()
+        {
+            return Err(format!(
+                "Cannot reverse {:?}: its tape history was evicted by the history limit",
+                frame.instruction
+            ));
+        }
+        if let Some(frame) = self.history.stack.pop() {
+            // Restore registers
+            match frame.registers_before {
+                SavedRegisters::All(registers) => self.registers = registers,
+                SavedRegisters::One(reg, value) => self.registers.write(reg, value)?,
+                SavedRegisters::Derived => self.undo_derived(&frame.instruction)?,
+            }
+            self.ip = frame.ip_before;
+            self.sp = frame.sp_before;
+            self.fp = frame.fp_before;
+            self.ic = frame.ic_before;
+            
             // Rewind tape operations
             let rewind_count = self.tape.tape.trail_len() - frame.tape_trail_len;
-            self.tape.tape.rewind_n(rewind_count);
+            self.tape.rewind_n(rewind_count);
             
+            if let Instruction::Fork { label } = &frame.instruction
+                && let Some(timeline) = self.timelines.remove(label)
+            {
+                // The fork point is shared with nothing now
+                let fork = timeline.forks.last().map(|f| f.id);
+                if self.forks.last().map(|f| f.id) == fork {
+                    self.forks.pop();
+                }
+            }
+            
+            self.history.redo.push(frame.instruction);
             Ok(())
         } else {
             Err("No operations to reverse".to_string())
         }
     }
     
+    /// Re-execute the instruction most recently undone by `reverse_last`
+    ///
+    /// Reversal restores the exact state the instruction first ran in, so
+    /// replaying it reproduces its original effect. Executing anything else
+    /// clears the instructions available to replay.
+    pub fn replay_next(&mut self) -> Result<(), String> {
+        let inst = self.history.redo.pop()
+            .ok_or("No operations to replay")?;
+        
+        let redo = std::mem::take(&mut self.history.redo);
+        let result = self.execute(inst.clone());
+        self.history.redo = redo;
+        
+        match result {
+            Err(e) if e != "HALT" => {
+                self.history.redo.push(inst);
+                Err(e)
+            }
+            result => result,
+        }
+    }
+    
+    /// Number of history frames to pop so that `steps` stateful instructions are undone
+    ///
+    /// Non-stateful instructions in between are undone too but not counted.
+    /// Fails without changing anything if history runs out first or a trap
+    /// is in the way.
+    fn rewind_depth(&self, steps: i64) -> Result<usize, String> {
+        if !self.history_enabled {
+            return Err(history_disabled());
+        }
+        if steps < 0 {
+            return Err(format!("Invalid rewind count: {}", steps));
+        }
+        
+        let mut remaining = steps as usize;
+        let mut depth = 0;
+        for frame in self.history.stack.iter().rev() {
+            if remaining == 0 {
+                break;
+            }
+            if frame.irreversible {
+                return Err(cannot_reverse_trap(frame));
+            }
+            if frame.instruction.is_stateful() {
+                remaining -= 1;
+            }
+            depth += 1;
+        }
+        
+        if remaining > 0 {
+            return Err(format!(
+                "Cannot rewind {} steps: only {} in history", steps, steps as usize - remaining
+            ));
+        }
+        Ok(depth)
+    }
+    
     /// Load a program into the code segment
+    ///
+    /// Instructions are encoded and written back to back from the start of the
+    /// `code` segment; `ip` indexes them by position in `instructions`. Fails if
+    /// an instruction cannot be encoded.
     pub fn load_program(&mut self, instructions: Vec<Instruction>) -> Result<(), String> {
-        // For now, just store instruction count
-        // In a real implementation, we'd encode instructions to bytes
+        let (bytes, offsets) = encode_program(&instructions)?;
+        self.tape.write_segment("code", 0, &bytes)
+            .map_err(|e| format!("Program of {} bytes does not fit: {}", bytes.len(), e))?;
+        
+        self.code_offsets = offsets;
         self.symbols.insert("__program_size__".to_string(), instructions.len() as i64);
         Ok(())
     }
+    
+    /// Load the initialized bytes of a `.data` section into a new `data` segment
+    ///
+    /// The segment is placed at `DATA_SEGMENT_BASE`, where the parser resolved
+    /// the section's labels, so it fails if another segment overlaps that range.
+    pub fn load_data(&mut self, data: &[u8]) -> Result<(), String> {
+        self.tape.create_segment_at("data".to_string(), DATA_SEGMENT_BASE, data.len(), SegmentType::Data)?;
+        self.tape.write_segment("data", 0, data)
+    }
+    
+    /// Checkpoint the tape, the VM state and any attached SDM under one name
+    ///
+    /// Rewinding to it resumes at the current `ip`.
+    pub fn checkpoint(&mut self, name: &str) -> Result<(), String> {
+        if let Some(sdm) = &self.sdm {
+            sdm.checkpoint(name.to_string())?;
+        }
+        self.tape.tape.checkpoint(name.to_string());
+        self.history.checkpoints.insert(name.to_string(), CheckpointState {
+            depth: self.history.stack.len(),
+            registers: self.registers.clone(),
+            ip: self.ip,
+            sp: self.sp,
+            fp: self.fp,
+            ic: self.ic,
+        });
+        Ok(())
+    }
+    
+    /// Restore the tape, the VM state and any attached SDM to a checkpoint
+    ///
+    /// Fails without changing anything if a trap ran since the checkpoint.
+    pub fn rewind(&mut self, name: &str) -> Result<(), String> {
+        let state = self.history.checkpoints.get(name)
+            .ok_or_else(|| format!("Unknown checkpoint: {}", name))?
+            .clone();
+        
+        // Refuse to rewind across host side effects
+        if let Some(frame) = self.history.stack.iter()
+            .skip(state.depth)
+            .find(|frame| frame.irreversible)
+        {
+            return Err(cannot_reverse_trap(frame));
+        }
+        
+        // Check every subsystem first so a failure leaves all of them untouched
+        self.tape.tape.checkpoint_position(name)?;
+        if let Some(sdm) = &self.sdm
+            && !sdm.has_checkpoint(name)
+        {
+            return Err(format!("Unknown SDM checkpoint: {}", name));
+        }
+        
+        self.tape.rewind(name)?;
+        if let Some(sdm) = &self.sdm {
+            sdm.rewind(name)?;
+        }
+        
+        self.history.stack.truncate(state.depth);
+        self.history.redo.clear();
+        self.registers = state.registers;
+        self.ip = state.ip;
+        self.sp = state.sp;
+        self.fp = state.fp;
+        self.ic = state.ic;
+        Ok(())
+    }
+    
+    /// Write a human-readable summary of registers, flags and pointers to `w`
+    pub fn dump_state(&self, w: &mut impl Write) -> io::Result<()> {
+        writeln!(w, "=== VM State ===")?;
+        writeln!(w, "IP: {}, SP: {}, FP: {}", self.ip, self.sp, self.fp)?;
+        writeln!(w, "\nRegisters:")?;
+        for (i, value) in self.registers.general.iter().enumerate().take(8) {
+            write!(w, "  R{}: {:8} ", i, value)?;
+            if i % 4 == 3 {
+                writeln!(w)?;
+            }
+        }
+        writeln!(w, "\nFlags:")?;
+        let flags = &self.registers.flags;
+        writeln!(w, "  Zero: {}, Negative: {}, Carry: {}, Overflow: {}",
+            flags.zero, flags.negative, flags.carry, flags.overflow)?;
+        writeln!(w, "\nTape position: {}", self.tape.tape.position())?;
+        writeln!(w, "History depth: {}", self.history.stack.len())?;
+        writeln!(w, "================")
+    }
+    
+    /// Number of instructions in the loaded program
+    pub fn program_len(&self) -> usize {
+        self.code_offsets.len().saturating_sub(1)
+    }
+    
+    /// Fetch and decode the instruction at `ip` from the code segment
+    ///
+    /// Returns `None` once `ip` is outside the loaded program.
+    pub fn fetch(&self) -> Result<Option<Instruction>, String> {
+        if self.ip < 0 || self.ip as usize >= self.program_len() {
+            return Ok(None);
+        }
+        
+        let index = self.ip as usize;
+        let start = self.code_offsets[index];
+        let len = self.code_offsets[index + 1] - start;
+        let code = self.tape.get_segment("code")
+            .ok_or("Code segment missing")?;
+        let bytes = self.tape.tape.read_at(code.start + start as i64, len);
+        
+        let (inst, _) = Instruction::decode(&bytes)
+            .map_err(|e| format!("Corrupt code at IP {}: {}", self.ip, e))?;
+        Ok(Some(inst))
+    }
+    
+    /// Fetch and execute the instruction at `ip`
+    ///
+    /// Both `Halt` and running off the end of the program are clean terminal
+    /// outcomes; only genuine execution failures are errors.
+    pub fn step(&mut self) -> Result<StepOutcome, String> {
+        let Some(inst) = self.fetch()? else {
+            return Ok(StepOutcome::Finished);
+        };
+        match self.execute(inst) {
+            Ok(()) => Ok(StepOutcome::Executed),
+            Err(e) if e == "HALT" => Ok(StepOutcome::Halted),
+            Err(e) => Err(e),
+        }
+    }
+    
+    /// Step until `Halt`, the end of the program, or `max_steps` instructions
+    ///
+    /// Stops at the first execution error, leaving `ip` at the failing
+    /// instruction.
+    pub fn run(&mut self, max_steps: Option<usize>) -> Result<RunStats, String> {
+        let mut executed = 0;
+        let mut halted = false;
+        let compacted_before = self.pages_compacted;
+        
+        while max_steps.is_none_or(|max| executed < max) {
+            match self.step()? {
+                StepOutcome::Executed => executed += 1,
+                StepOutcome::Halted => {
+                    executed += 1;
+                    halted = true;
+                    break;
+                }
+                StepOutcome::Finished => break,
+            }
+        }
+        
+        Ok(RunStats {
+            executed,
+            halted,
+            final_ip: self.ip,
+            pages_compacted: self.pages_compacted - compacted_before,
+        })
+    }
+    
+    /// Run like `run`, but fail with `"budget exhausted"` after `budget` instructions
+    ///
+    /// Unlike `Halt`, this is a failure of the program rather than its end.
+    /// Instructions that ran keep their effects and `ip` is left at the first
+    /// one that did not, so the VM can be inspected, reversed or resumed.
+    pub fn run_with_budget(&mut self, budget: usize) -> Result<RunStats, String> {
+        let stats = self.run(Some(budget))?;
+        if stats.executed == budget && !stats.halted && self.fetch()?.is_some() {
+            return Err("budget exhausted".to_string());
+        }
+        Ok(stats)
+    }
+}
+
+impl Default for VM {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ExecutionHistory {
@@ -431,8 +1469,91 @@ impl ExecutionHistory {
         ExecutionHistory {
             stack: Vec::new(),
             checkpoints: HashMap::new(),
+            redo: Vec::new(),
+        }
+    }
+}
+
+fn history_disabled() -> String {
+    "History disabled: VM was created with VM::without_history".to_string()
+}
+
+fn cannot_reverse_trap(frame: &HistoryFrame) -> String {
+    format!(
+        "CannotReverseTrap: {:?} at IP {} had host side effects",
+        frame.instruction, frame.ip_before
+    )
+}
+
+/// Check the byte count of a segment access moving data through a register
+fn register_access_len(len: i64) -> Result<usize, String> {
+    if !(0..=8).contains(&len) {
+        return Err(format!("Segment access of {} bytes does not fit a register", len));
+    }
+    Ok(len as usize)
+}
+
+/// Bytes spanned by `count` elements of `elem_size` bytes starting at `address`
+///
+/// Fails if the address or count is negative, the range exceeds
+/// `MAX_RANGE_BYTES`, or it runs off the end of the tape's address space.
+fn range_len(what: &str, address: i64, count: i64, elem_size: usize) -> Result<usize, String> {
+    let count = usize::try_from(count).map_err(|_| format!("Invalid {}: {}", what, count))?;
+    let len = count.checked_mul(elem_size)
+        .filter(|&len| len <= MAX_RANGE_BYTES)
+        .ok_or_else(|| format!(
+            "Invalid {}: {} elements of {} bytes exceed the {} byte range limit",
+            what, count, elem_size, MAX_RANGE_BYTES
+        ))?;
+    if address < 0 {
+        return Err(format!("Invalid {}: negative address {}", what, address));
+    }
+    if address.checked_add(len as i64).is_none() {
+        return Err(format!("Invalid {}: {} bytes at {} overflow the tape", what, len, address));
+    }
+    Ok(len)
+}
+
+/// Interpret up to 8 little-endian bytes as a sign-extended integer
+fn sign_extend_le(bytes: &[u8]) -> i64 {
+    let mut buf = [0u8; 8];
+    buf[..bytes.len()].copy_from_slice(bytes);
+    let shift = 64 - 8 * bytes.len() as u32;
+    (i64::from_le_bytes(buf) << shift) >> shift
+}
+
+/// CRC-32 (IEEE 802.3, reflected polynomial 0xEDB88320)
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
         }
     }
+    !crc
+}
+
+/// One `HashUpdate` step: `state = state * HASH_MULTIPLIER + byte` for each byte
+fn rolling_hash(state: u64, data: &[u8]) -> u64 {
+    data.iter().fold(state, |h, &byte| h.wrapping_mul(HASH_MULTIPLIER).wrapping_add(byte as u64))
+}
+
+/// Undo `rolling_hash` over the same bytes
+fn rolling_hash_inverse(state: u64, data: &[u8]) -> u64 {
+    // Newton's iteration for the inverse of an odd number mod 2^64
+    let mut inverse = HASH_MULTIPLIER;
+    for _ in 0..5 {
+        inverse = inverse.wrapping_mul(2u64.wrapping_sub(HASH_MULTIPLIER.wrapping_mul(inverse)));
+    }
+    data.iter().rev().fold(state, |h, &byte| h.wrapping_sub(byte as u64).wrapping_mul(inverse))
+}
+
+impl Default for ExecutionHistory {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[cfg(test)]
@@ -462,6 +1583,1436 @@ mod tests {
         assert_eq!(vm.registers.read(2).unwrap(), 30);
     }
 
+    #[test]
+    fn test_mod_repeat() {
+        let mut vm = VM::new();
+        
+        vm.execute(Instruction::LoadImm { reg: 0, value: 17 }).unwrap();
+        vm.execute(Instruction::LoadImm { reg: 1, value: 5 }).unwrap();
+        vm.execute(Instruction::ModRepeat { dst: 2, src: 0, modulus: 1, quotient: 3 }).unwrap();
+        
+        assert_eq!(vm.registers.read(2).unwrap(), 2);
+        assert_eq!(vm.registers.read(3).unwrap(), 3);
+        
+        // quotient * modulus + remainder reconstructs the source
+        let rebuilt = vm.registers.read(3).unwrap() * vm.registers.read(1).unwrap()
+            + vm.registers.read(2).unwrap();
+        assert_eq!(rebuilt, 17);
+        
+        // Reversing restores the outputs and leaves the source intact
+        vm.reverse_last().unwrap();
+        assert_eq!(vm.registers.read(0).unwrap(), 17);
+        assert_eq!(vm.registers.read(2).unwrap(), 0);
+        assert_eq!(vm.registers.read(3).unwrap(), 0);
+    }
+
+    /// Stack words from the top down
+    fn stack_contents(vm: &VM, depth: i64) -> Vec<i64> {
+        (0..depth).map(|i| vm.stack_word(i * 8).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_stack_word_ops() {
+        let mut vm = VM::new();
+        let base = vm.sp;
+        for value in [1, 2] {
+            vm.execute(Instruction::LoadImm { reg: 0, value }).unwrap();
+            vm.execute(Instruction::Push { reg: 0 }).unwrap();
+        }
+        assert_eq!(stack_contents(&vm, 2), vec![2, 1]);
+        
+        vm.execute(Instruction::Dup).unwrap();
+        assert_eq!(stack_contents(&vm, 3), vec![2, 2, 1]);
+        
+        vm.execute(Instruction::Drop).unwrap();
+        assert_eq!(stack_contents(&vm, 2), vec![2, 1]);
+        
+        vm.execute(Instruction::Over).unwrap();
+        assert_eq!(stack_contents(&vm, 3), vec![1, 2, 1]);
+        
+        vm.execute(Instruction::SwapTop).unwrap();
+        assert_eq!(stack_contents(&vm, 3), vec![2, 1, 1]);
+        assert_eq!(vm.sp, base - 24);
+    }
+
+    #[test]
+    fn test_stack_word_ops_reverse() {
+        let mut vm = VM::new();
+        for value in [10, 20] {
+            vm.execute(Instruction::LoadImm { reg: 0, value }).unwrap();
+            vm.execute(Instruction::Push { reg: 0 }).unwrap();
+        }
+        let sp = vm.sp;
+        let before = stack_contents(&vm, 2);
+        
+        let ops = [Instruction::Dup, Instruction::Over, Instruction::SwapTop, Instruction::Drop];
+        for op in &ops {
+            vm.execute(op.clone()).unwrap();
+        }
+        for _ in &ops {
+            vm.reverse_last().unwrap();
+        }
+        
+        assert_eq!(vm.sp, sp);
+        assert_eq!(stack_contents(&vm, 2), before);
+        // The slots Dup and Over wrote below the stack are restored too
+        assert_eq!(vm.tape.tape.read_at(sp - 16, 16), vec![0; 16]);
+    }
+
+    #[test]
+    fn test_stack_word_ops_underflow() {
+        let mut vm = VM::new();
+        for op in [Instruction::Dup, Instruction::Drop] {
+            let err = vm.execute(op).unwrap_err();
+            assert!(err.starts_with("Stack underflow"), "{}", err);
+        }
+        
+        vm.execute(Instruction::Push { reg: 0 }).unwrap();
+        for op in [Instruction::Over, Instruction::SwapTop] {
+            let err = vm.execute(op).unwrap_err();
+            assert!(err.starts_with("Stack underflow"), "{}", err);
+        }
+        assert_eq!(vm.sp, STACK_BASE - 8);
+        
+        vm.execute(Instruction::Drop).unwrap();
+        assert_eq!(vm.sp, STACK_BASE);
+        
+        // They honour alignment checking like Push and Pop
+        vm.alignment_check = true;
+        vm.sp -= 12;
+        let err = vm.execute(Instruction::Dup).unwrap_err();
+        assert!(err.starts_with("Unaligned"), "{}", err);
+    }
+
+    #[test]
+    fn test_bit_reverse() {
+        let mut vm = VM::new();
+        vm.execute(Instruction::LoadImm { reg: 0, value: 0b1100_0001 }).unwrap();
+        
+        vm.execute(Instruction::BitReverse { dst: 1, src: 0, width: 8 }).unwrap();
+        assert_eq!(vm.registers.read(1).unwrap(), 0b1000_0011);
+        
+        // Bits above the width are left alone, so reversing twice is identity
+        vm.execute(Instruction::LoadImm { reg: 2, value: 0x7F00_0000_0000_1234 }).unwrap();
+        vm.execute(Instruction::BitReverse { dst: 2, src: 2, width: 12 }).unwrap();
+        assert_ne!(vm.registers.read(2).unwrap(), 0x7F00_0000_0000_1234);
+        vm.execute(Instruction::BitReverse { dst: 2, src: 2, width: 12 }).unwrap();
+        assert_eq!(vm.registers.read(2).unwrap(), 0x7F00_0000_0000_1234);
+        
+        vm.execute(Instruction::BitReverse { dst: 3, src: 0, width: 0 }).unwrap();
+        assert_eq!(vm.registers.read(3).unwrap(), 0b1100_0001);
+        vm.execute(Instruction::BitReverse { dst: 3, src: 0, width: 64 }).unwrap();
+        assert_eq!(vm.registers.read(3).unwrap() as u64, 0b1000_0011u64 << 56);
+        assert!(vm.execute(Instruction::BitReverse { dst: 3, src: 0, width: 65 }).is_err());
+    }
+
+    #[test]
+    fn test_mod_repeat_zero_modulus() {
+        let mut vm = VM::new();
+        
+        vm.execute(Instruction::LoadImm { reg: 0, value: 17 }).unwrap();
+        vm.execute(Instruction::LoadImm { reg: 1, value: 0 }).unwrap();
+        assert!(vm.execute(Instruction::ModRepeat { dst: 2, src: 0, modulus: 1, quotient: 3 }).is_err());
+        assert_eq!(vm.registers.read(3).unwrap(), 0);
+        
+        vm.execute(Instruction::LoadImm { reg: 0, value: i64::MAX }).unwrap();
+        vm.execute(Instruction::LoadImm { reg: 1, value: -1 }).unwrap();
+        assert!(vm.execute(Instruction::ModRepeat { dst: 2, src: 0, modulus: 1, quotient: 3 }).is_err());
+        assert_eq!(vm.registers.read(3).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_mod_repeat_limits() {
+        let mut vm = VM::new();
+        
+        vm.execute(Instruction::LoadImm { reg: 0, value: MOD_REPEAT_LIMIT + 1 }).unwrap();
+        vm.execute(Instruction::LoadImm { reg: 1, value: 1 }).unwrap();
+        let err = vm.execute(Instruction::ModRepeat { dst: 2, src: 0, modulus: 1, quotient: 3 }).unwrap_err();
+        assert!(err.contains("exceeded"), "{}", err);
+        assert_eq!(vm.registers.read(3).unwrap(), 0);
+        
+        // One register cannot hold both outputs
+        vm.execute(Instruction::LoadImm { reg: 0, value: 17 }).unwrap();
+        vm.execute(Instruction::LoadImm { reg: 1, value: 5 }).unwrap();
+        assert!(vm.execute(Instruction::ModRepeat { dst: 2, src: 0, modulus: 1, quotient: 2 }).is_err());
+        assert_eq!(vm.registers.read(2).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_forbidden_trap_leaves_no_trace() {
+        let mut vm = VM::new();
+        vm.set_trap_handler(|_, regs| regs.write(0, 99));
+        vm.disallow(VmCapabilities::TRAPS);
+        
+        let err = vm.execute(Instruction::Trap { code: 1 }).unwrap_err();
+        assert!(err.starts_with("Forbidden"));
+        assert!(vm.history.stack.is_empty());
+        assert_eq!(vm.ic, 0);
+        assert_eq!(vm.ip, 0);
+        assert_eq!(vm.registers.read(0).unwrap(), 0);
+        
+        // Instructions without a gated capability still run
+        vm.execute(Instruction::LoadImm { reg: 1, value: 5 }).unwrap();
+        assert_eq!(vm.registers.read(1).unwrap(), 5);
+    }
+
+    #[test]
+    fn test_trap_calls_handler() {
+        let mut vm = VM::new();
+        assert!(vm.execute(Instruction::Trap { code: 7 }).is_err());
+        // No handler ran, so nothing blocks reversing the failed trap
+        vm.reverse_last().unwrap();
+        
+        vm.set_trap_handler(|code, regs| regs.write(0, code as i64 * 2));
+        vm.execute(Instruction::Trap { code: 7 }).unwrap();
+        assert_eq!(vm.registers.read(0).unwrap(), 14);
+        assert!(vm.reverse_last().unwrap_err().starts_with("CannotReverseTrap"));
+        
+        // Nor does a trap whose handler refused it
+        let mut vm = VM::new();
+        vm.set_trap_handler(|code, _| Err(format!("unsupported trap {}", code)));
+        assert!(vm.execute(Instruction::Trap { code: 1 }).is_err());
+        vm.reverse_last().unwrap();
+        assert_eq!(vm.ip, 0);
+    }
+
+    #[test]
+    fn test_load_data_section() {
+        let program = crate::compiler::Parser::new().parse_program(r#"
+            .data
+        message:
+            .ascii "hello"
+            .text
+            HALT
+        "#).unwrap();
+        
+        let mut vm = VM::new();
+        vm.load_program(program.instructions).unwrap();
+        vm.load_data(&program.data).unwrap();
+        
+        let address = program.symbols["message"];
+        assert_eq!(address, DATA_SEGMENT_BASE);
+        assert_eq!(vm.tape.tape.read_at(address, 5), b"hello");
+        assert_eq!(vm.tape.read_segment("data", 0, 5).unwrap(), b"hello");
+        
+        // A second data section has nowhere to go
+        assert!(vm.load_data(b"again").is_err());
+        
+        // Segments above the data segment leave it in place
+        let mut vm = VM::new();
+        vm.tape.create_segment_at("above".to_string(), DATA_SEGMENT_BASE + 4096, 64, SegmentType::Data).unwrap();
+        vm.load_data(&program.data).unwrap();
+        assert_eq!(vm.tape.tape.read_at(address, 5), b"hello");
+        
+        // One starting inside it is refused
+        let mut vm = VM::new();
+        vm.tape.create_segment_at("inside".to_string(), DATA_SEGMENT_BASE + 2, 4, SegmentType::Data).unwrap();
+        let err = vm.load_data(&program.data).unwrap_err();
+        assert_eq!(err, "Segment 'data' would overlap segment 'inside'");
+        
+        // A gap left below the data segment does not move it
+        let mut vm = VM::new();
+        vm.tape.delete_segment("heap").unwrap();
+        vm.load_data(&program.data).unwrap();
+        assert_eq!(vm.tape.get_segment("data").unwrap().start, DATA_SEGMENT_BASE);
+        assert_eq!(vm.tape.tape.read_at(address, 5), b"hello");
+    }
+
+    #[test]
+    fn test_load_program_writes_code_segment() {
+        let program = vec![
+            Instruction::LoadImm { reg: 0, value: 5 },
+            Instruction::LoadImm { reg: 1, value: 7 },
+            Instruction::RAdd { src1: 0, src2: 1, dst: 2 },
+            Instruction::Debug { message: "sum".to_string() },
+            Instruction::Halt,
+        ];
+        let (expected, _) = encode_program(&program).unwrap();
+        
+        let mut vm = VM::new();
+        vm.load_program(program.clone()).unwrap();
+        assert_eq!(vm.program_len(), program.len());
+        assert_eq!(vm.tape.read_segment("code", 0, expected.len()).unwrap(), expected);
+        
+        // Executing from the tape matches executing the vector directly
+        let mut from_tape = Vec::new();
+        while let Some(inst) = vm.fetch().unwrap() {
+            from_tape.push(inst.clone());
+            if vm.execute(inst).is_err() {
+                break;
+            }
+        }
+        assert_eq!(from_tape, program);
+        
+        let mut direct = VM::new();
+        for inst in program {
+            if direct.execute(inst).is_err() {
+                break;
+            }
+        }
+        assert_eq!(vm.registers.general, direct.registers.general);
+        assert_eq!(vm.ip, direct.ip);
+        
+        // A string operand too long to encode fails the load instead of panicking
+        let long = Instruction::Debug { message: "x".repeat(70_000) };
+        assert!(VM::new().load_program(vec![long]).unwrap_err().contains("exceeds"));
+    }
+
+    #[test]
+    fn test_run_to_halt_and_step_limit() {
+        let program = vec![
+            Instruction::LoadImm { reg: 0, value: 5 },
+            Instruction::LoadImm { reg: 1, value: 7 },
+            Instruction::RAdd { src1: 0, src2: 1, dst: 2 },
+            Instruction::Halt,
+            Instruction::LoadImm { reg: 2, value: 0 },
+        ];
+        
+        let mut vm = VM::new();
+        vm.load_program(program.clone()).unwrap();
+        let stats = vm.run(None).unwrap();
+        assert_eq!(stats, RunStats { executed: 4, halted: true, final_ip: 3, pages_compacted: 0 });
+        assert_eq!(vm.registers.read(2).unwrap(), 12);
+        
+        let mut vm = VM::new();
+        vm.load_program(program).unwrap();
+        let stats = vm.run(Some(2)).unwrap();
+        assert_eq!(stats, RunStats { executed: 2, halted: false, final_ip: 2, pages_compacted: 0 });
+        assert_eq!(vm.registers.read(2).unwrap(), 0);
+        
+        // Resuming picks up where the limited run stopped
+        assert!(vm.run(None).unwrap().halted);
+        assert_eq!(vm.registers.read(2).unwrap(), 12);
+        
+        // Without a Halt, the run ends at the end of the program
+        let mut vm = VM::new();
+        vm.load_program(vec![Instruction::Nop, Instruction::Nop]).unwrap();
+        assert_eq!(
+            vm.run(None).unwrap(),
+            RunStats { executed: 2, halted: false, final_ip: 2, pages_compacted: 0 }
+        );
+    }
+
+    #[test]
+    fn test_run_reports_compacted_pages() {
+        let scratch = 1 << 26;
+        let mut vm = VM::new();
+        vm.tape.tape.seek(scratch);
+        vm.tape.tape.write(&[0xFF; 3 * 4096]);
+        vm.tape.tape.seek(scratch);
+        vm.tape.tape.write(&[0; 3 * 4096]);
+        vm.load_program(vec![
+            Instruction::Compact { start: scratch, end: scratch + 3 * 4096 },
+            Instruction::Compact { start: scratch, end: scratch + 3 * 4096 },
+        ]).unwrap();
+        let pages = vm.tape.tape.page_count();
+        
+        // The second Compact finds nothing left to reclaim
+        assert_eq!(vm.run(None).unwrap().pages_compacted, 3);
+        assert_eq!(vm.tape.tape.page_count(), pages - 3);
+        assert_eq!(vm.tape.tape.read_at(scratch, 4), vec![0; 4]);
+    }
+
+    #[test]
+    fn test_replay_after_reverse() {
+        let mut vm = VM::new();
+        for value in 1..=3 {
+            vm.execute(Instruction::LoadImm { reg: 0, value }).unwrap();
+        }
+        vm.execute(Instruction::TapeWrite { reg: 0, len: 8 }).unwrap();
+        
+        vm.reverse_last().unwrap();
+        vm.reverse_last().unwrap();
+        assert_eq!(vm.registers.read(0).unwrap(), 2);
+        assert_eq!(vm.ip, 2);
+        
+        vm.replay_next().unwrap();
+        assert_eq!(vm.registers.read(0).unwrap(), 3);
+        assert_eq!(vm.ip, 3);
+        assert_eq!(vm.tape.tape.read(8), vec![0; 8]);
+        
+        vm.replay_next().unwrap();
+        assert_eq!(vm.tape.tape.read(8), 3i64.to_le_bytes());
+        assert_eq!(vm.replay_next().unwrap_err(), "No operations to replay");
+        
+        // Scrubbing back and forth, then executing something new, drops the redo stack
+        vm.reverse_last().unwrap();
+        vm.reverse_last().unwrap();
+        vm.replay_next().unwrap();
+        vm.execute(Instruction::Nop).unwrap();
+        assert!(vm.replay_next().is_err());
+        assert_eq!(vm.tape.tape.read(8), vec![0; 8]);
+    }
+
+    #[test]
+    fn test_load_program_replaces_previous_code() {
+        let mut vm = VM::new();
+        vm.load_program(vec![Instruction::Nop, Instruction::Nop, Instruction::Nop]).unwrap();
+        vm.load_program(vec![Instruction::Halt]).unwrap();
+        assert_eq!(vm.program_len(), 1);
+        assert_eq!(vm.fetch().unwrap(), Some(Instruction::Halt));
+        vm.ip = 1;
+        assert_eq!(vm.fetch().unwrap(), None);
+        
+        // A program larger than the code segment is rejected and the old one kept
+        let message = "x".repeat(60_000);
+        let huge = vec![Instruction::Debug { message }; 20];
+        assert!(vm.load_program(huge).unwrap_err().contains("does not fit"));
+        assert_eq!(vm.program_len(), 1);
+        vm.ip = 0;
+        assert_eq!(vm.fetch().unwrap(), Some(Instruction::Halt));
+    }
+
+    #[test]
+    fn test_rload_inverse_restores_registers() {
+        let mut vm = VM::new();
+        vm.tape.tape.seek(512);
+        vm.tape.tape.write(&77i64.to_le_bytes());
+        vm.tape.tape.seek(0);
+        vm.execute(Instruction::LoadImm { reg: 0, value: 512 }).unwrap();
+        vm.execute(Instruction::LoadImm { reg: 1, value: 5 }).unwrap();
+        
+        let load = Instruction::RLoad { dst: 1, addr: 0, old: 2 };
+        let trail_len = vm.tape.tape.trail_len();
+        vm.execute(load.clone()).unwrap();
+        assert_eq!(vm.registers.read(1).unwrap(), 77);
+        assert_eq!(vm.registers.read(2).unwrap(), 5);
+        assert_eq!(vm.tape.tape.position(), 0);
+        
+        vm.execute(load.inverse().unwrap()).unwrap();
+        assert_eq!(vm.registers.read(1).unwrap(), 5);
+        assert_eq!(vm.tape.tape.trail_len(), trail_len);
+    }
+
+    #[test]
+    fn test_rstore_inverse_restores_tape() {
+        let mut vm = VM::new();
+        vm.tape.tape.seek(512);
+        vm.tape.tape.write(&0x1111i64.to_le_bytes());
+        vm.execute(Instruction::LoadImm { reg: 0, value: 512 }).unwrap();
+        vm.execute(Instruction::LoadImm { reg: 1, value: 0x2222 }).unwrap();
+        
+        // `old` is a clean ancilla that receives the overwritten word
+        let store = Instruction::RStore { addr: 0, src: 1, old: 2 };
+        vm.execute(store.clone()).unwrap();
+        assert_eq!(vm.tape.tape.read_at(512, 8), 0x2222i64.to_le_bytes());
+        assert_eq!(vm.registers.read(2).unwrap(), 0x1111);
+        
+        // The inverse writes the captured word back and recaptures the stored one
+        vm.execute(store.inverse().unwrap()).unwrap();
+        assert_eq!(vm.tape.tape.read_at(512, 8), 0x1111i64.to_le_bytes());
+        assert_eq!(vm.registers.read(1).unwrap(), 0x2222);
+        assert_eq!(vm.registers.read(2).unwrap(), 0x1111);
+        
+        // Running the pair again is idempotent
+        vm.execute(store.clone()).unwrap();
+        vm.execute(store.inverse().unwrap()).unwrap();
+        assert_eq!(vm.tape.tape.read_at(512, 8), 0x1111i64.to_le_bytes());
+        assert_eq!(vm.registers.read(2).unwrap(), 0x1111);
+    }
+
+    #[test]
+    fn test_branch_on_compare_result() {
+        let run = |a: i64, b: i64| {
+            let mut vm = VM::new();
+            vm.symbols.insert("differ".to_string(), 6);
+            vm.load_program(vec![
+                Instruction::LoadImm { reg: 0, value: a },
+                Instruction::LoadImm { reg: 1, value: b },
+                Instruction::Compare { dst: 2, src1: 0, src2: 1 },
+                Instruction::Branch { condition: 2, label: "differ".to_string() },
+                Instruction::LoadImm { reg: 3, value: 100 },
+                Instruction::Halt,
+                Instruction::LoadImm { reg: 3, value: 200 },
+                Instruction::Halt,
+            ]).unwrap();
+            while vm.step().unwrap() == StepOutcome::Executed {}
+            vm.registers.read(3).unwrap()
+        };
+        
+        assert_eq!(run(4, 4), 100);
+        assert_eq!(run(4, 9), 200);
+        assert_eq!(run(9, 4), 200);
+    }
+
+    #[test]
+    fn test_add_sub_carry_and_overflow() {
+        let mut vm = VM::new();
+        let mut flags_after = |inst: Instruction, a: i64, b: i64, c: i64| {
+            vm.registers.write(0, a).unwrap();
+            vm.registers.write(1, b).unwrap();
+            vm.registers.write(2, c).unwrap();
+            vm.execute(inst).unwrap();
+            let flags = vm.registers.flags.clone();
+            (vm.registers.read(2).unwrap(), flags.carry, flags.overflow, flags.zero)
+        };
+        let add = Instruction::RAdd { src1: 0, src2: 1, dst: 2 };
+        let sub = Instruction::RSub { src1: 0, src2: 1, dst: 2 };
+        
+        // Signed overflow without an unsigned carry
+        assert_eq!(flags_after(add.clone(), 1, 0, i64::MAX), (i64::MIN, false, true, false));
+        // Unsigned carry without signed overflow
+        assert_eq!(flags_after(add.clone(), 1, 0, -1), (0, true, false, true));
+        // Judged on the whole sum: MAX + 1 - 1 does not overflow, though MAX + 1 alone would
+        assert_eq!(flags_after(add.clone(), 1, -1, i64::MAX), (i64::MAX, true, false, false));
+        assert_eq!(flags_after(add, 1, 2, 3), (6, false, false, false));
+        
+        // Borrow without signed overflow
+        assert_eq!(flags_after(sub.clone(), 1, 0, 0), (-1, true, false, false));
+        // Signed overflow without a borrow
+        assert_eq!(flags_after(sub.clone(), 1, 0, i64::MIN), (i64::MAX, false, true, false));
+        assert_eq!(flags_after(sub, 2, 3, 5), (0, false, false, true));
+        assert_eq!(vm.registers.flags.condition_code(), 1);
+    }
+
+    #[test]
+    fn test_rewind_n_counts_prior_instructions() {
+        let mut vm = VM::new();
+        vm.execute(Instruction::LoadImm { reg: 5, value: 3 }).unwrap();
+        vm.execute(Instruction::LoadImm { reg: 0, value: 1 }).unwrap();
+        vm.execute(Instruction::LoadImm { reg: 1, value: 2 }).unwrap();
+        
+        vm.execute(Instruction::RAdd { src1: 0, src2: 1, dst: 2 }).unwrap();
+        vm.execute(Instruction::RAdd { src1: 2, src2: 1, dst: 3 }).unwrap();
+        let after_two = vm.registers.general.clone();
+        vm.execute(Instruction::Nop).unwrap();
+        vm.execute(Instruction::RXor { src: 3, dst: 4 }).unwrap();
+        vm.execute(Instruction::RSub { src1: 0, src2: 1, dst: 2 }).unwrap();
+        vm.execute(Instruction::RAdd { src1: 0, src2: 1, dst: 4 }).unwrap();
+        
+        vm.execute(Instruction::RewindN { steps: 5 }).unwrap();
+        assert_eq!(vm.registers.general, after_two);
+        // Execution resumes at the first undone instruction; the Nop before it stays
+        assert_eq!(vm.ip, 6);
+        assert_eq!(vm.history.stack.len(), 6);
+        
+        // Non-stateful frames are undone on the way but not counted
+        vm.execute(Instruction::RewindN { steps: 5 }).unwrap();
+        assert_eq!(vm.ip, 2);
+        assert_eq!(vm.registers.read(1).unwrap(), 0);
+        
+        // Rewinding past the start of history fails and changes nothing
+        vm.execute(Instruction::LoadImm { reg: 5, value: 10 }).unwrap();
+        let err = vm.execute(Instruction::RewindN { steps: 5 }).unwrap_err();
+        assert!(err.contains("only 3 in history"));
+        assert_eq!(vm.registers.read(5).unwrap(), 10);
+        assert_eq!(vm.history.stack.len(), 3);
+    }
+
+    #[test]
+    fn test_debug_with_few_registers() {
+        let mut vm = VM::with_register_count(4);
+        vm.execute(Instruction::Debug { message: "small".to_string() }).unwrap();
+        assert_eq!(vm.ip, 1);
+    }
+
+    #[test]
+    fn test_rewind_n_zero_steps_advances() {
+        let program = crate::compiler::Parser::new().parse(r#"
+            LI R0, 7
+            LI R5, 0
+            REWINDN R5
+            HALT
+        "#).unwrap();
+        let mut vm = VM::new();
+        vm.load_program(program).unwrap();
+        assert!(vm.run_with_budget(100).unwrap().halted);
+        
+        assert_eq!(vm.registers.read(0).unwrap(), 7);
+        assert_eq!(vm.ip, 3);
+        assert_eq!(vm.history.stack.len(), 3);
+    }
+
+    #[test]
+    fn test_tape_seek_reg_reverses_through_trail() {
+        let program = crate::compiler::Parser::new().parse(r#"
+            TAPESEEK 100
+            LI R3, 4096
+            LI R4, 8
+            RADD R3, R4, R5
+            TAPESEEKREG R5
+            LI R6, 77
+            TAPEWRITE R6, 8
+            HALT
+        "#).unwrap();
+        let mut vm = VM::new();
+        vm.load_program(program).unwrap();
+        vm.run(None).unwrap();
+        assert_eq!(vm.tape.tape.read_at(4104, 8), 77i64.to_le_bytes());
+        
+        vm.reverse_last().unwrap(); // HALT
+        vm.reverse_last().unwrap(); // TAPEWRITE
+        vm.reverse_last().unwrap(); // LI
+        assert_eq!(vm.tape.tape.position(), 4104);
+        vm.reverse_last().unwrap(); // TAPESEEKREG
+        assert_eq!(vm.tape.tape.position(), 100);
+        assert_eq!(vm.tape.tape.read_at(4104, 8), [0u8; 8]);
+    }
+
+    #[test]
+    fn test_swap_with_itself_is_noop() {
+        let mut vm = VM::new();
+        vm.execute(Instruction::LoadImm { reg: 3, value: -9 }).unwrap();
+        vm.execute(Instruction::Swap { reg1: 3, reg2: 3 }).unwrap();
+        assert_eq!(vm.registers.read(3).unwrap(), -9);
+        
+        vm.reverse_last().unwrap();
+        assert_eq!(vm.registers.read(3).unwrap(), -9);
+        assert_eq!(Instruction::Swap { reg1: 3, reg2: 3 }.inverse(), Some(Instruction::Swap { reg1: 3, reg2: 3 }));
+    }
+
+    #[test]
+    fn test_mswap_twice_is_identity() {
+        let mut vm = VM::new();
+        vm.tape.tape.seek(256);
+        vm.tape.tape.write(&(-9i64).to_le_bytes());
+        vm.execute(Instruction::LoadImm { reg: 0, value: 256 }).unwrap();
+        vm.execute(Instruction::LoadImm { reg: 1, value: 1234 }).unwrap();
+        
+        let swap = Instruction::MSwap { addr: 0, reg: 1 };
+        assert_eq!(swap.inverse(), Some(swap.clone()));
+        
+        vm.execute(swap.clone()).unwrap();
+        assert_eq!(vm.registers.read(1).unwrap(), -9);
+        assert_eq!(vm.tape.tape.read_at(256, 8), 1234i64.to_le_bytes());
+        
+        vm.execute(swap).unwrap();
+        assert_eq!(vm.registers.read(1).unwrap(), 1234);
+        assert_eq!(vm.tape.tape.read_at(256, 8), (-9i64).to_le_bytes());
+    }
+
+    #[test]
+    fn test_splice_overlapping_and_reverse() {
+        let mut vm = VM::new();
+        vm.tape.tape.seek(100);
+        vm.tape.tape.write(b"abcdefgh");
+        let before = vm.tape.tape.read_at(90, 30);
+        vm.execute(Instruction::LoadImm { reg: 0, value: 6 }).unwrap();
+        
+        // Shift six bytes right by two, overlapping the source
+        vm.execute(Instruction::Splice { dst: 102, src: 100, len: 0 }).unwrap();
+        assert_eq!(vm.tape.tape.read_at(100, 8), b"ababcdef");
+        assert_eq!(vm.tape.tape.position(), 100);
+        
+        vm.reverse_last().unwrap();
+        assert_eq!(vm.tape.tape.read_at(90, 30), before);
+        assert_eq!(vm.tape.tape.position(), 100);
+    }
+
+    #[test]
+    fn test_segment_instructions() {
+        let program = crate::compiler::Parser::new().parse(r#"
+            LI R0, 64
+            SEGCREATE buf, R0
+            LI R1, 8
+            LI R2, 0x1122334455667788
+            SEGWRITE buf, R1, R1, R2
+            LI R3, 4
+            SEGREAD buf, R1, R3, R4
+            SEGSEEK buf, R1
+            TAPEREAD R5, 8
+            HALT
+        "#).unwrap();
+        let mut vm = VM::new();
+        vm.load_program(program).unwrap();
+        vm.run(None).unwrap();
+        
+        let start = vm.tape.get_segment("buf").unwrap().start;
+        assert_eq!(vm.tape.get_segment("buf").unwrap().size, 64);
+        assert_eq!(vm.registers.read(4).unwrap(), 0x55667788);
+        assert_eq!(vm.registers.read(5).unwrap(), 0x1122334455667788);
+        assert_eq!(vm.tape.tape.position(), start + 8);
+        
+        // Bounds violations surface as execution errors
+        vm.registers.write(1, 62).unwrap();
+        let write = Instruction::SegmentWrite { name: "buf".to_string(), offset: 1, len: 3, src: 2 };
+        assert_eq!(vm.execute(write).unwrap_err(), "Segment bounds violation");
+        let seek = Instruction::SegmentSeek { name: "buf".to_string(), offset: 0 };
+        assert!(vm.execute(seek).is_ok());
+        vm.registers.write(1, 9).unwrap();
+        let read = Instruction::SegmentRead { name: "buf".to_string(), offset: 0, len: 1, dst: 2 };
+        assert!(vm.execute(read).unwrap_err().contains("does not fit a register"));
+        let create = Instruction::SegmentCreate { name: "buf".to_string(), size: 0 };
+        assert!(vm.execute(create).unwrap_err().contains("already exists"));
+        
+        // Reversing a segment write restores the old bytes
+        vm.registers.write(1, 0).unwrap();
+        vm.registers.write(3, 8).unwrap();
+        vm.execute(Instruction::SegmentWrite { name: "buf".to_string(), offset: 1, len: 3, src: 0 }).unwrap();
+        assert_eq!(vm.tape.read_segment("buf", 0, 8).unwrap(), 64i64.to_le_bytes());
+        vm.reverse_last().unwrap();
+        assert_eq!(vm.tape.read_segment("buf", 0, 8).unwrap(), [0u8; 8]);
+        
+        // Reversing the creation removes the segment again
+        vm.execute(Instruction::SegmentCreate { name: "tmp".to_string(), size: 3 }).unwrap();
+        assert!(vm.tape.get_segment("tmp").is_some());
+        vm.reverse_last().unwrap();
+        assert!(vm.tape.get_segment("tmp").is_none());
+    }
+
+    #[test]
+    fn test_every_variant_reaches_executor() {
+        for inst in crate::instruction::every_instruction() {
+            let mut vm = VM::new();
+            vm.symbols.insert("target".to_string(), 0);
+            vm.registers.write(0, 3).unwrap();
+            vm.registers.write(1, 5).unwrap();
+            vm.registers.write(4, 4096).unwrap();
+            
+            let name = format!("{:?}", inst);
+            let unimplemented = vm.execute(inst)
+                .is_err_and(|e| e.starts_with("Unimplemented"));
+            assert!(!unimplemented, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_watchpoint_reports_writes() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+        
+        let hits = Rc::new(RefCell::new(Vec::new()));
+        let mut vm = VM::new();
+        let sink = Rc::clone(&hits);
+        vm.set_watch_handler(move |hit| sink.borrow_mut().push(hit));
+        vm.add_watchpoint(4004, 4005);
+        
+        vm.load_program(vec![
+            Instruction::LoadImm { reg: 0, value: 0x0102_0304_0506_0708 },
+            Instruction::TapeSeek { position: 4000 },
+            Instruction::TapeWrite { reg: 0, len: 8 },
+            Instruction::TapeSeek { position: 4005 },
+            Instruction::TapeWrite { reg: 0, len: 8 },
+            Instruction::LoadImm { reg: 1, value: 3998 },
+            Instruction::RStore { addr: 1, src: 1, old: 2 },
+            Instruction::Halt,
+        ]).unwrap();
+        vm.run(None).unwrap();
+        
+        // The write starting at 4005 misses the watched byte
+        let hits = hits.take();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0], WatchHit {
+            ip: 2,
+            pos: 4000,
+            old: vec![0; 8],
+            new: 0x0102_0304_0506_0708i64.to_le_bytes().to_vec(),
+        });
+        assert_eq!((hits[1].ip, hits[1].pos), (6, 3998));
+        assert_eq!(hits[1].new, 3998i64.to_le_bytes());
+    }
+
+    #[test]
+    fn test_watchpoint_writes_evicted_before_reporting() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+        
+        let hits = Rc::new(Cell::new(0));
+        let mut vm = VM::without_history();
+        let sink = Rc::clone(&hits);
+        vm.set_watch_handler(move |_| sink.set(sink.get() + 1));
+        vm.add_watchpoint(0, 8);
+        
+        // The write lands, and with no trail to find it in it goes unreported
+        vm.execute(Instruction::LoadImm { reg: 0, value: 5 }).unwrap();
+        vm.execute(Instruction::TapeWrite { reg: 0, len: 8 }).unwrap();
+        assert_eq!(vm.ip, 2);
+        assert_eq!(hits.get(), 0);
+        assert_eq!(vm.tape.tape.read_at(0, 1), vec![5]);
+    }
+
+    #[test]
+    fn test_loss_events() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+        
+        let run = |program: Vec<Instruction>| {
+            let events = Rc::new(RefCell::new(Vec::new()));
+            let mut vm = VM::new();
+            let sink = Rc::clone(&events);
+            vm.set_loss_sink(move |info| sink.borrow_mut().push(info));
+            vm.load_program(program).unwrap();
+            while vm.step().unwrap() == StepOutcome::Executed {}
+            events.take()
+        };
+        
+        let multiply = Instruction::IMul { src1: 0, src2: 1, dst: 2 };
+        let events = run(vec![
+            Instruction::LoadImm { reg: 0, value: 6 },
+            Instruction::LoadImm { reg: 1, value: 7 },
+            multiply.clone(),
+            Instruction::RAdd { src1: 0, src2: 1, dst: 3 },
+            Instruction::Halt,
+        ]);
+        assert_eq!(events, vec![LossInfo { ip: 2, instruction: multiply }]);
+        
+        let events = run(vec![
+            Instruction::RAdd { src1: 0, src2: 1, dst: 2 },
+            Instruction::RXor { src: 2, dst: 3 },
+            Instruction::Swap { reg1: 0, reg2: 3 },
+            Instruction::Halt,
+        ]);
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn test_step_empty_program_finishes() {
+        let mut vm = VM::new();
+        vm.load_program(vec![]).unwrap();
+        
+        assert_eq!(vm.step().unwrap(), StepOutcome::Finished);
+        assert_eq!(vm.ic, 0);
+        assert_eq!(vm.ip, 0);
+        
+        vm.load_program(vec![Instruction::Nop, Instruction::Halt]).unwrap();
+        assert_eq!(vm.step().unwrap(), StepOutcome::Executed);
+        assert_eq!(vm.step().unwrap(), StepOutcome::Halted);
+    }
+
+    #[test]
+    fn test_branch_stats() {
+        let mut vm = VM::new();
+        vm.symbols.insert("loop".to_string(), 2);
+        vm.load_program(vec![
+            Instruction::LoadImm { reg: 0, value: 5 },
+            Instruction::LoadImm { reg: 1, value: 1 },
+            Instruction::RSub { src1: 1, src2: 2, dst: 0 },
+            Instruction::BranchNotZero { reg: 0, label: "loop".to_string() },
+            Instruction::Halt,
+        ]).unwrap();
+        
+        while let Some(inst) = vm.fetch().unwrap() {
+            if vm.execute(inst).is_err() {
+                break;
+            }
+        }
+        
+        // Five iterations: the back-branch is taken four times, then falls through
+        assert_eq!(vm.branch_stats(), vec![(3, 4, 1)]);
+    }
+
+    #[test]
+    fn test_inverse_with_restores_written_bytes() {
+        let mut vm = VM::new();
+        vm.tape.tape.seek(300);
+        vm.tape.tape.write(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        vm.execute(Instruction::LoadImm { reg: 0, value: 0x7766 }).unwrap();
+        vm.execute(Instruction::TapeSeek { position: 302 }).unwrap();
+        assert_eq!(vm.last_inverse().unwrap(), vec![Instruction::TapeSeek { position: 300 }]);
+        
+        vm.execute(Instruction::TapeWrite { reg: 0, len: 4 }).unwrap();
+        assert_eq!(vm.tape.tape.read_at(300, 8), vec![1, 2, 0x66, 0x77, 0, 0, 7, 8]);
+        
+        // Running the inverse puts back the overwritten bytes and leaves R0 as it was
+        let inverse = vm.last_inverse().unwrap();
+        assert_eq!(inverse[0], Instruction::LoadImm { reg: 0, value: 0x0605_0403 });
+        for inst in inverse {
+            vm.execute(inst).unwrap();
+        }
+        assert_eq!(vm.tape.tape.read_at(300, 8), vec![1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(vm.registers.read(0).unwrap(), 0x7766);
+        
+        // Context-free inverses are used as they are
+        vm.execute(Instruction::LoadImm { reg: 1, value: 304 }).unwrap();
+        vm.execute(Instruction::RStore { addr: 1, src: 0, old: 2 }).unwrap();
+        let inverse = vm.last_inverse().unwrap();
+        assert_eq!(inverse, vec![Instruction::RStore { addr: 1, src: 2, old: 0 }]);
+        vm.execute(inverse[0].clone()).unwrap();
+        assert_eq!(vm.tape.tape.read_at(300, 8), vec![1, 2, 3, 4, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn test_budget_stops_infinite_loop() {
+        let program = crate::compiler::Parser::new().parse_program(r#"
+            LI R0, 1
+        spin:
+            RADD R0, R2, R1
+            BNZ R0, spin
+            HALT
+        "#).unwrap();
+        let mut vm = VM::new();
+        vm.symbols = program.symbols;
+        vm.load_program(program.instructions).unwrap();
+        
+        assert_eq!(vm.run_with_budget(10).unwrap_err(), "budget exhausted");
+        assert_eq!(vm.ic, 10);
+        assert_eq!(vm.registers.read(1).unwrap(), 5);
+        // LI, four full iterations, then the fifth RADD: the BNZ is next
+        assert_eq!(vm.ip, 2);
+        
+        // A budget the program fits in behaves like `run`
+        let mut vm = VM::new();
+        vm.load_program(vec![Instruction::Nop, Instruction::Halt]).unwrap();
+        assert!(vm.run_with_budget(2).unwrap().halted);
+        
+        let mut vm = VM::new();
+        vm.load_program(vec![Instruction::Nop, Instruction::Nop]).unwrap();
+        assert_eq!(vm.run_with_budget(2).unwrap().executed, 2);
+    }
+
+    #[test]
+    fn test_trace_summary() {
+        let program = crate::compiler::Parser::new().parse_program(r#"
+            LI R0, 5
+            LI R1, 1
+        loop:
+            RSUB R1, R2, R0
+            BNZ R0, loop
+            HALT
+        "#).unwrap();
+        let mut vm = VM::new();
+        vm.symbols = program.symbols;
+        vm.load_program(program.instructions).unwrap();
+        
+        vm.run(Some(2)).unwrap();
+        assert!(vm.trace_summary().is_empty());
+        
+        vm.enable_trace();
+        vm.run(None).unwrap();
+        let summary = vm.trace_summary();
+        assert_eq!(summary["RSub"], 5);
+        assert_eq!(summary["BranchNotZero"], 5);
+        assert_eq!(summary["Halt"], 1);
+        assert!(!summary.contains_key("LoadImm"));
+        assert_eq!(summary.values().sum::<u64>(), vm.ic - 2);
+    }
+
+    #[test]
+    fn test_dump_state() {
+        let mut vm = VM::new();
+        vm.execute(Instruction::LoadImm { reg: 0, value: 42 }).unwrap();
+        vm.execute(Instruction::LoadImm { reg: 5, value: -7 }).unwrap();
+        vm.registers.update_flags(-7);
+        
+        let mut buf = Vec::new();
+        vm.dump_state(&mut buf).unwrap();
+        let dump = String::from_utf8(buf).unwrap();
+        
+        assert!(dump.contains("IP: 2, SP: 1048576, FP: 1048576"));
+        assert!(dump.contains("R0:       42"));
+        assert!(dump.contains("R5:       -7"));
+        assert!(dump.contains("Zero: false, Negative: true"));
+        assert!(dump.contains("History depth: 2"));
+    }
+
+    #[test]
+    fn test_alignment_check() {
+        let mut vm = VM::new();
+        vm.execute(Instruction::LoadImm { reg: 0, value: 2051 }).unwrap();
+        vm.execute(Instruction::LoadImm { reg: 1, value: 2048 }).unwrap();
+        
+        // Unaligned access is fine by default
+        vm.execute(Instruction::RLoad { dst: 2, addr: 0, old: 3 }).unwrap();
+        
+        vm.alignment_check = true;
+        let err = vm.execute(Instruction::RLoad { dst: 2, addr: 0, old: 3 }).unwrap_err();
+        assert!(err.starts_with("Unaligned"));
+        assert!(vm.execute(Instruction::RStore { addr: 0, src: 2, old: 3 }).is_err());
+        
+        vm.execute(Instruction::RLoad { dst: 2, addr: 1, old: 3 }).unwrap();
+        vm.execute(Instruction::RStore { addr: 1, src: 2, old: 3 }).unwrap();
+        vm.execute(Instruction::Push { reg: 2 }).unwrap();
+        vm.execute(Instruction::Pop { reg: 2 }).unwrap();
+        
+        vm.sp -= 4;
+        assert!(vm.execute(Instruction::Push { reg: 2 }).is_err());
+    }
+
+    #[test]
+    fn test_unsigned_comparisons() {
+        let mut vm = VM::new();
+        vm.execute(Instruction::LoadImm { reg: 0, value: -1 }).unwrap();
+        vm.execute(Instruction::LoadImm { reg: 1, value: 1 }).unwrap();
+        
+        vm.execute(Instruction::LessThan { dst: 2, src1: 0, src2: 1 }).unwrap();
+        assert_eq!(vm.registers.read(2).unwrap(), 1);
+        
+        // -1 is u64::MAX when unsigned
+        vm.execute(Instruction::LessThanU { dst: 2, src1: 0, src2: 1 }).unwrap();
+        assert_eq!(vm.registers.read(2).unwrap(), 0);
+        
+        vm.execute(Instruction::Compare { dst: 3, src1: 0, src2: 1 }).unwrap();
+        assert_eq!(vm.registers.read(3).unwrap(), -1);
+        vm.execute(Instruction::CompareU { dst: 3, src1: 0, src2: 1 }).unwrap();
+        assert_eq!(vm.registers.read(3).unwrap(), 1);
+        vm.execute(Instruction::CompareU { dst: 3, src1: 1, src2: 1 }).unwrap();
+        assert_eq!(vm.registers.read(3).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_unsigned_branches() {
+        let program = crate::compiler::Parser::new().parse_program(r#"
+            LI R0, -1
+            LI R1, 1
+            BLTU R0, R1, wrong
+            BGEU R0, R1, right
+        wrong:
+            LI R2, 1
+            HALT
+        right:
+            LI R2, 2
+            BLTU R1, R0, done
+            LI R2, 3
+        done:
+            HALT
+        "#).unwrap();
+        let mut vm = VM::new();
+        vm.symbols = program.symbols;
+        vm.load_program(program.instructions).unwrap();
+        vm.run(None).unwrap();
+        
+        // -1 is u64::MAX, so it is not below 1 but 1 is below it
+        assert_eq!(vm.registers.read(2).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_imul() {
+        let mut vm = VM::new();
+        vm.execute(Instruction::LoadImm { reg: 0, value: 6 }).unwrap();
+        vm.execute(Instruction::LoadImm { reg: 1, value: -7 }).unwrap();
+        vm.execute(Instruction::IMul { src1: 0, src2: 1, dst: 2 }).unwrap();
+        assert_eq!(vm.registers.read(2).unwrap(), -42);
+        
+        vm.reverse_last().unwrap();
+        assert_eq!(vm.registers.read(2).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_idiv_imod() {
+        let mut vm = VM::new();
+        vm.execute(Instruction::LoadImm { reg: 0, value: -17 }).unwrap();
+        vm.execute(Instruction::LoadImm { reg: 1, value: 5 }).unwrap();
+        vm.execute(Instruction::IDiv { src1: 0, src2: 1, dst: 2 }).unwrap();
+        vm.execute(Instruction::IMod { src1: 0, src2: 1, dst: 3 }).unwrap();
+        assert_eq!(vm.registers.read(2).unwrap(), -3);
+        assert_eq!(vm.registers.read(3).unwrap(), -2);
+        assert!(vm.registers.flags.negative);
+        
+        // Exact division leaves no remainder
+        vm.execute(Instruction::LoadImm { reg: 0, value: 20 }).unwrap();
+        vm.execute(Instruction::IDiv { src1: 0, src2: 1, dst: 2 }).unwrap();
+        vm.execute(Instruction::IMod { src1: 0, src2: 1, dst: 3 }).unwrap();
+        assert_eq!(vm.registers.read(2).unwrap(), 4);
+        assert_eq!(vm.registers.read(3).unwrap(), 0);
+        assert!(vm.registers.flags.zero);
+        
+        // Dividing by zero is an error, not a panic, and leaves dst alone
+        vm.execute(Instruction::LoadImm { reg: 1, value: 0 }).unwrap();
+        assert_eq!(vm.execute(Instruction::IDiv { src1: 0, src2: 1, dst: 2 }).unwrap_err(), "Division by zero");
+        assert!(vm.execute(Instruction::IMod { src1: 0, src2: 1, dst: 3 }).is_err());
+        assert_eq!(vm.registers.read(2).unwrap(), 4);
+        
+        assert_eq!(Instruction::IDiv { src1: 0, src2: 1, dst: 2 }.inverse(), None);
+    }
+
+    #[test]
+    fn test_bitwise_de_morgan() {
+        let mut vm = VM::new();
+        vm.execute(Instruction::LoadImm { reg: 0, value: 0x5A5A_0F0F }).unwrap();
+        vm.execute(Instruction::LoadImm { reg: 1, value: -0x3C3C }).unwrap();
+        
+        // !(a & b) == !a | !b
+        vm.execute(Instruction::IAnd { src1: 0, src2: 1, dst: 2 }).unwrap();
+        vm.execute(Instruction::INot { dst: 2, src: 2 }).unwrap();
+        vm.execute(Instruction::INot { dst: 3, src: 0 }).unwrap();
+        vm.execute(Instruction::INot { dst: 4, src: 1 }).unwrap();
+        vm.execute(Instruction::IOr { src1: 3, src2: 4, dst: 5 }).unwrap();
+        assert_eq!(vm.registers.read(2).unwrap(), vm.registers.read(5).unwrap());
+        
+        // !(a | b) == !a & !b
+        vm.execute(Instruction::IOr { src1: 0, src2: 1, dst: 2 }).unwrap();
+        vm.execute(Instruction::INot { dst: 2, src: 2 }).unwrap();
+        vm.execute(Instruction::IAnd { src1: 3, src2: 4, dst: 5 }).unwrap();
+        assert_eq!(vm.registers.read(2).unwrap(), vm.registers.read(5).unwrap());
+        
+        // In-place NOT undoes itself; AND/OR have no inverse
+        let not = Instruction::INot { dst: 0, src: 0 };
+        vm.execute(not.clone()).unwrap();
+        vm.execute(not.inverse().unwrap()).unwrap();
+        assert_eq!(vm.registers.read(0).unwrap(), 0x5A5A_0F0F);
+        assert_eq!(Instruction::INot { dst: 1, src: 0 }.inverse(), None);
+        assert_eq!(Instruction::IAnd { src1: 0, src2: 1, dst: 2 }.inverse(), None);
+    }
+
+    #[test]
+    fn test_shifts() {
+        let mut vm = VM::new();
+        let shift = |vm: &mut VM, inst: Instruction| {
+            vm.execute(inst).unwrap();
+            vm.registers.read(2).unwrap()
+        };
+        vm.execute(Instruction::LoadImm { reg: 0, value: -16 }).unwrap();
+        vm.execute(Instruction::LoadImm { reg: 1, value: 2 }).unwrap();
+        
+        // Logical shifts fill with zeros, arithmetic ones with the sign
+        assert_eq!(shift(&mut vm, Instruction::Sar { dst: 2, src: 0, amount: 1 }), -4);
+        assert!(vm.registers.flags.negative);
+        assert_eq!(shift(&mut vm, Instruction::Shr { dst: 2, src: 0, amount: 1 }), (-16i64 as u64 >> 2) as i64);
+        assert!(!vm.registers.flags.negative);
+        
+        assert_eq!(shift(&mut vm, Instruction::Shl { dst: 2, src: 0, amount: 1 }), -64);
+        assert!(vm.registers.flags.carry);
+        
+        // Amounts are masked to 0..=63: 66 shifts by 2, 64 by nothing
+        vm.execute(Instruction::LoadImm { reg: 1, value: 66 }).unwrap();
+        assert_eq!(shift(&mut vm, Instruction::Sar { dst: 2, src: 0, amount: 1 }), -4);
+        vm.execute(Instruction::LoadImm { reg: 1, value: 64 }).unwrap();
+        assert_eq!(shift(&mut vm, Instruction::Shl { dst: 2, src: 0, amount: 1 }), -16);
+        assert!(!vm.registers.flags.carry);
+        
+        // The carry is the last bit shifted out
+        vm.execute(Instruction::LoadImm { reg: 0, value: 0b110 }).unwrap();
+        vm.execute(Instruction::LoadImm { reg: 1, value: 2 }).unwrap();
+        assert_eq!(shift(&mut vm, Instruction::Shr { dst: 2, src: 0, amount: 1 }), 1);
+        assert!(vm.registers.flags.carry);
+        
+        assert_eq!(Instruction::Shl { dst: 2, src: 0, amount: 1 }.inverse(), None);
+    }
+
+    #[test]
+    fn test_cost_model() {
+        let mut vm = VM::new();
+        vm.set_cost_model(|inst: &Instruction| match inst {
+            Instruction::IMul { .. } => 10,
+            _ => 1,
+        });
+        
+        vm.load_program(vec![
+            Instruction::LoadImm { reg: 0, value: 3 },
+            Instruction::LoadImm { reg: 1, value: 4 },
+            Instruction::IMul { src1: 0, src2: 1, dst: 2 },
+            Instruction::IMul { src1: 2, src2: 2, dst: 3 },
+            Instruction::Nop,
+            Instruction::Halt,
+        ]).unwrap();
+        while let Some(inst) = vm.fetch().unwrap() {
+            if vm.execute(inst).is_err() {
+                break;
+            }
+        }
+        
+        // Four cheap instructions plus two multiplies
+        assert_eq!(vm.registers.read(3).unwrap(), 144);
+        assert_eq!(vm.cycles, 4 + 2 * 10);
+    }
+
+    #[test]
+    fn test_default_cost_model() {
+        let mut vm = VM::new();
+        vm.execute(Instruction::LoadImm { reg: 0, value: 3 }).unwrap();
+        vm.execute(Instruction::IMul { src1: 0, src2: 0, dst: 1 }).unwrap();
+        assert_eq!(vm.cycles, 1 + 3);
+    }
+
+    #[test]
+    fn test_checkpoint_rewind_restores_all_state() {
+        let dir = std::env::temp_dir().join(format!("pvm_vm_checkpoint_{}", std::process::id()));
+        let mut vm = VM::new();
+        vm.sdm = Some(SdmTape::with_config(crate::tape::SdmConfig {
+            page_size: 16,
+            local_storage_dir: dir.clone(),
+            ..Default::default()
+        }));
+        let sdm_write = |vm: &VM, pos: i64, data: &[u8]| vm.sdm.as_ref().unwrap().write(pos, data).unwrap();
+        let sdm_read = |vm: &VM, pos: i64, len: usize| vm.sdm.as_ref().unwrap().read(pos, len).unwrap();
+        
+        vm.execute(Instruction::LoadImm { reg: 0, value: 11 }).unwrap();
+        vm.tape.tape.write(b"before");
+        sdm_write(&vm, 0, b"page zero");
+        
+        vm.execute(Instruction::Checkpoint { label: "cp".to_string() }).unwrap();
+        let ip = vm.ip;
+        
+        vm.execute(Instruction::LoadImm { reg: 0, value: 99 }).unwrap();
+        vm.execute(Instruction::TapeWrite { reg: 0, len: 6 }).unwrap();
+        sdm_write(&vm, 0, b"clobbered");
+        sdm_write(&vm, 40, b"new page");
+        
+        vm.execute(Instruction::Rewind { label: "cp".to_string() }).unwrap();
+        
+        assert_eq!(vm.ip, ip);
+        assert_eq!(vm.registers.read(0).unwrap(), 11);
+        assert_eq!(vm.tape.tape.read(6), b"before");
+        assert_eq!(sdm_read(&vm, 0, 9), b"page zero");
+        assert_eq!(sdm_read(&vm, 40, 8), vec![0u8; 8]);
+        
+        // The same checkpoint can be rewound to again after further writes
+        sdm_write(&vm, 0, b"again....");
+        vm.rewind("cp").unwrap();
+        assert_eq!(sdm_read(&vm, 0, 9), b"page zero");
+        
+        assert!(vm.rewind("missing").is_err());
+        
+        // A checkpoint the SDM never saw fails before the tape is touched
+        vm.sdm = None;
+        vm.execute(Instruction::Checkpoint { label: "tape only".to_string() }).unwrap();
+        vm.execute(Instruction::TapeWrite { reg: 0, len: 6 }).unwrap();
+        vm.sdm = Some(SdmTape::with_config(crate::tape::SdmConfig {
+            page_size: 16,
+            local_storage_dir: dir.clone(),
+            ..Default::default()
+        }));
+        let before = (vm.ip, vm.history.stack.len(), vm.tape.tape.position(), vm.tape.tape.trail_len());
+        assert!(vm.rewind("tape only").is_err());
+        assert_eq!((vm.ip, vm.history.stack.len(), vm.tape.tape.position(), vm.tape.tape.trail_len()), before);
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_without_history() {
+        let mut vm = VM::without_history();
+        
+        vm.execute(Instruction::LoadImm { reg: 0, value: 5 }).unwrap();
+        vm.execute(Instruction::LoadImm { reg: 1, value: 7 }).unwrap();
+        vm.execute(Instruction::RAdd { src1: 0, src2: 1, dst: 2 }).unwrap();
+        assert_eq!(vm.registers.read(2).unwrap(), 12);
+        assert_eq!(vm.ic, 3);
+        
+        // No register snapshots were taken
+        assert!(vm.history.stack.is_empty());
+        
+        let err = vm.reverse_last().unwrap_err();
+        assert!(err.starts_with("History disabled"));
+        assert!(vm.reverse_last_force().is_err());
+        assert_eq!(vm.registers.read(2).unwrap(), 12);
+        
+        // Tape writes are not recorded on the trail either
+        vm.execute(Instruction::TapeWrite { reg: 2, len: 8 }).unwrap();
+        assert!(vm.tape.tape.trail_since(vm.tape.tape.trail_start()).unwrap().is_empty());
+        assert_eq!(vm.tape.tape.read(8), 12i64.to_le_bytes());
+    }
+
+    #[test]
+    fn test_reverse_across_trap() {
+        let mut vm = VM::new();
+        vm.set_trap_handler(|_, _| Ok(()));
+        
+        vm.execute(Instruction::LoadImm { reg: 0, value: 1 }).unwrap();
+        vm.execute(Instruction::Trap { code: 3 }).unwrap();
+        
+        let err = vm.reverse_last().unwrap_err();
+        assert!(err.starts_with("CannotReverseTrap"));
+        assert_eq!(vm.history.stack.len(), 2);
+        assert_eq!(vm.ip, 2);
+        
+        // Opting in reverses the trap and then earlier instructions as usual
+        vm.reverse_last_force().unwrap();
+        assert_eq!(vm.ip, 1);
+        vm.reverse_last().unwrap();
+        assert_eq!(vm.ip, 0);
+        assert_eq!(vm.registers.read(0).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_rewind_stops_before_trap() {
+        let mut vm = VM::new();
+        vm.set_trap_handler(|_, _| Ok(()));
+        
+        vm.execute(Instruction::Checkpoint { label: "start".to_string() }).unwrap();
+        vm.execute(Instruction::Trap { code: 1 }).unwrap();
+        let depth = vm.history.stack.len();
+        
+        let err = vm.execute(Instruction::Rewind { label: "start".to_string() }).unwrap_err();
+        assert!(err.starts_with("CannotReverseTrap"));
+        assert_eq!(vm.history.stack.len(), depth);
+    }
+
+    #[test]
+    fn test_sort_range() {
+        let mut vm = VM::new();
+        let values = [5i64, -3, 9, 0, -3, 2];
+        let original: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        vm.tape.tape.seek(2048);
+        vm.tape.tape.write(&original);
+        
+        vm.execute(Instruction::LoadImm { reg: 0, value: 2048 }).unwrap();
+        vm.execute(Instruction::LoadImm { reg: 1, value: values.len() as i64 }).unwrap();
+        vm.execute(Instruction::SortRange { addr: 0, count: 1, elem_size: 8 }).unwrap();
+        
+        let sorted: Vec<i64> = vm.tape.tape.read_at(2048, 48)
+            .chunks(8)
+            .map(|c| i64::from_le_bytes(c.try_into().unwrap()))
+            .collect();
+        assert_eq!(sorted, vec![-3, -3, 0, 2, 5, 9]);
+        
+        vm.reverse_last().unwrap();
+        assert_eq!(vm.tape.tape.read_at(2048, 48), original);
+        
+        assert!(vm.execute(Instruction::SortRange { addr: 0, count: 1, elem_size: 9 }).is_err());
+    }
+
+    #[test]
+    fn test_interleave_roundtrip() {
+        let mut vm = VM::new();
+        vm.tape.tape.seek(1000);
+        vm.tape.tape.write(b"ACEG");
+        vm.tape.tape.seek(1100);
+        vm.tape.tape.write(b"BDFH");
+        
+        for (reg, value) in [(0, 1000), (1, 1100), (2, 1200), (3, 4)] {
+            vm.execute(Instruction::LoadImm { reg, value }).unwrap();
+        }
+        vm.execute(Instruction::Interleave { a: 0, b: 1, dst: 2, count: 3 }).unwrap();
+        assert_eq!(vm.tape.tape.read_at(1200, 8), b"ABCDEFGH");
+        
+        // Deinterleave into fresh lanes reproduces the originals
+        vm.execute(Instruction::LoadImm { reg: 4, value: 1300 }).unwrap();
+        vm.execute(Instruction::LoadImm { reg: 5, value: 1400 }).unwrap();
+        vm.execute(Instruction::Deinterleave { src: 2, a: 4, b: 5, count: 3 }).unwrap();
+        assert_eq!(vm.tape.tape.read_at(1300, 4), b"ACEG");
+        assert_eq!(vm.tape.tape.read_at(1400, 4), b"BDFH");
+        
+        // Both operations reverse through history
+        for _ in 0..4 {
+            vm.reverse_last().unwrap();
+        }
+        assert_eq!(vm.tape.tape.read_at(1200, 8), vec![0u8; 8]);
+        assert_eq!(vm.tape.tape.read_at(1300, 4), vec![0u8; 4]);
+        assert_eq!(vm.tape.tape.read_at(1000, 4), b"ACEG");
+    }
+
+    #[test]
+    fn test_range_lengths_are_checked() {
+        let mut vm = VM::new();
+        for (reg, value) in [(0, 0), (1, 64), (2, i64::MAX), (3, i64::MAX / 2 + 1), (4, -1), (5, i64::MAX - 4)] {
+            vm.execute(Instruction::LoadImm { reg, value }).unwrap();
+        }
+        let trail_len = vm.tape.tape.trail_len();
+        
+        let huge = [
+            Instruction::SortRange { addr: 0, count: 3, elem_size: 8 },
+            Instruction::Interleave { a: 0, b: 1, dst: 0, count: 2 },
+            Instruction::Deinterleave { src: 0, a: 1, b: 0, count: 3 },
+            Instruction::Crc32 { dst: 6, addr: 0, len: 2 },
+            Instruction::HashUpdate { state: 6, addr: 0, len: 2 },
+            Instruction::Splice { dst: 0, src: 64, len: 2 },
+        ];
+        for inst in huge {
+            let err = vm.execute(inst.clone()).unwrap_err();
+            assert!(err.contains("range limit"), "{:?}: {}", inst, err);
+        }
+        
+        // Negative counts and ranges running off the tape are rejected too
+        let err = vm.execute(Instruction::SortRange { addr: 0, count: 4, elem_size: 8 }).unwrap_err();
+        assert_eq!(err, "Invalid sort count: -1");
+        let err = vm.execute(Instruction::Crc32 { dst: 6, addr: 5, len: 1 }).unwrap_err();
+        assert!(err.contains("overflow the tape"), "{}", err);
+        let err = vm.execute(Instruction::Crc32 { dst: 6, addr: 4, len: 1 }).unwrap_err();
+        assert_eq!(err, "Invalid CRC32 length: negative address -1");
+        
+        assert_eq!(vm.tape.tape.trail_len(), trail_len);
+    }
+
+    #[test]
+    fn test_hash_update_chains_and_reverses() {
+        let mut vm = VM::new();
+        vm.tape.tape.seek(3000);
+        vm.tape.tape.write(b"streamed region!");
+        vm.execute(Instruction::LoadImm { reg: 1, value: 3000 }).unwrap();
+        vm.execute(Instruction::LoadImm { reg: 2, value: 16 }).unwrap();
+        vm.execute(Instruction::LoadImm { reg: 0, value: 5 }).unwrap();
+        
+        vm.execute(Instruction::HashUpdate { state: 0, addr: 1, len: 2 }).unwrap();
+        let whole = vm.registers.read(0).unwrap();
+        
+        // The same bytes hashed in two halves with chained state
+        vm.execute(Instruction::LoadImm { reg: 3, value: 5 }).unwrap();
+        vm.execute(Instruction::LoadImm { reg: 2, value: 8 }).unwrap();
+        vm.execute(Instruction::HashUpdate { state: 3, addr: 1, len: 2 }).unwrap();
+        vm.execute(Instruction::LoadImm { reg: 1, value: 3008 }).unwrap();
+        vm.execute(Instruction::HashUpdate { state: 3, addr: 1, len: 2 }).unwrap();
+        assert_eq!(vm.registers.read(3).unwrap(), whole);
+        
+        // Reversal inverts the hash rather than restoring a snapshot
+        let frame = vm.history.stack.last().unwrap();
+        assert!(matches!(frame.registers_before, SavedRegisters::Derived));
+        for _ in 0..5 {
+            vm.reverse_last().unwrap();
+        }
+        assert_eq!(vm.registers.read(0).unwrap(), whole);
+        vm.reverse_last().unwrap();
+        assert_eq!(vm.registers.read(0).unwrap(), 5);
+    }
+
+    #[test]
+    fn test_inc_sat() {
+        let mut vm = VM::new();
+        vm.execute(Instruction::LoadImm { reg: 0, value: 2048 }).unwrap();
+        vm.execute(Instruction::LoadImm { reg: 1, value: 2 }).unwrap();
+        let counter = |vm: &VM| i64::from_le_bytes(vm.tape.tape.read_at(2048, 8).try_into().unwrap());
+        
+        let head = vm.tape.tape.position();
+        
+        // Below the max the counter moves, and reversing restores it
+        vm.execute(Instruction::IncSat { addr: 0, max: 1 }).unwrap();
+        assert_eq!(counter(&vm), 1);
+        assert_eq!(vm.tape.tape.position(), 2048);
+        vm.reverse_last().unwrap();
+        assert_eq!(counter(&vm), 0);
+        assert_eq!(vm.tape.tape.position(), head);
+        
+        // At the max it stays put, and reversing keeps it saturated
+        vm.execute(Instruction::IncSat { addr: 0, max: 1 }).unwrap();
+        vm.execute(Instruction::IncSat { addr: 0, max: 1 }).unwrap();
+        vm.tape.tape.seek(head);
+        vm.execute(Instruction::IncSat { addr: 0, max: 1 }).unwrap();
+        assert_eq!(counter(&vm), 2);
+        
+        // The head still moves to a saturated counter, and back on reversal
+        assert_eq!(vm.tape.tape.position(), 2048);
+        vm.reverse_last().unwrap();
+        assert_eq!(counter(&vm), 2);
+        assert_eq!(vm.tape.tape.position(), head);
+        vm.reverse_last().unwrap();
+        assert_eq!(counter(&vm), 1);
+    }
+
+    #[test]
+    fn test_crc32() {
+        let mut vm = VM::new();
+        let heap = vm.tape.get_segment("heap").unwrap().start;
+        vm.tape.write_segment("heap", 0, b"123456789").unwrap();
+        let trail_len = vm.tape.tape.trail_len();
+        let position = vm.tape.tape.position();
+        
+        vm.execute(Instruction::LoadImm { reg: 1, value: heap }).unwrap();
+        vm.execute(Instruction::LoadImm { reg: 2, value: 9 }).unwrap();
+        vm.execute(Instruction::Crc32 { dst: 0, addr: 1, len: 2 }).unwrap();
+        
+        // Standard CRC-32 check value
+        assert_eq!(vm.registers.read(0).unwrap(), 0xCBF4_3926);
+        
+        // The source region and the tape head are untouched
+        assert_eq!(vm.tape.read_segment("heap", 0, 9).unwrap(), b"123456789");
+        assert_eq!(vm.tape.tape.trail_len(), trail_len);
+        assert_eq!(vm.tape.tape.position(), position);
+    }
+
+    #[test]
+    fn test_reverse_tape_read_restores_only_destination() {
+        let mut vm = VM::new();
+        vm.tape.tape.seek(100);
+        vm.tape.tape.write(&42i64.to_le_bytes());
+        vm.execute(Instruction::LoadImm { reg: 0, value: -7 }).unwrap();
+        vm.execute(Instruction::LoadImm { reg: 1, value: 5 }).unwrap();
+        
+        let trail_len = vm.tape.tape.trail_len();
+        vm.execute(Instruction::TapeRead { reg: 0, len: 8 }).unwrap();
+        assert_eq!(vm.registers.read(0).unwrap(), 42);
+        assert_eq!(vm.tape.tape.trail_len(), trail_len);
+        
+        // Only the destination register is saved
+        let frame = vm.history.stack.last().unwrap();
+        assert!(matches!(frame.registers_before, SavedRegisters::One(0, -7)));
+        
+        vm.reverse_last().unwrap();
+        assert_eq!(vm.registers.read(0).unwrap(), -7);
+        assert_eq!(vm.registers.read(1).unwrap(), 5);
+        assert_eq!(vm.ip, 2);
+        assert_eq!(vm.tape.tape.trail_len(), trail_len);
+        assert_eq!(vm.tape.tape.read_at(100, 8), 42i64.to_le_bytes());
+    }
+
     #[test]
     fn test_stack_operations() {
         let mut vm = VM::new();
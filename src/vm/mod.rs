@@ -2,11 +2,18 @@
 //! 
 //! The VM executes instructions on the global tape with full reversibility support.
 
+mod capabilities;
+mod cost;
 mod executor;
 mod registers;
+mod state;
+mod timeline;
 
-pub use executor::{VM, ExecutionHistory, HistoryFrame, Timeline};
-pub use registers::{RegisterFile, Flags};
+pub use capabilities::VmCapabilities;
+pub use cost::{CostModel, DefaultCostModel};
+pub use executor::{VM, DATA_SEGMENT_BASE, CheckpointState, ExecutionHistory, HistoryFrame, LossInfo, LossSink, RunStats, SavedRegisters, StepOutcome, TrapHandler, WatchHandler, WatchHit};
+pub use timeline::{ForkPoint, Timeline, TimelineDiff};
+pub use registers::{RegisterFile, Flags, DEFAULT_REGISTER_COUNT};
 
 // Re-export register type
 pub type Register = registers::Register;
\ No newline at end of file
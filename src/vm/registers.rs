@@ -3,11 +3,14 @@
 /// Type alias for register indices
 pub type Register = u8;
 
+/// Number of general purpose registers unless configured otherwise
+pub const DEFAULT_REGISTER_COUNT: usize = 16;
+
 /// Register file containing all CPU registers
 #[derive(Clone, Debug)]
 pub struct RegisterFile {
-    /// 16 general purpose registers
-    pub general: [i64; 16],
+    /// General purpose registers, 16 unless made with `with_count`
+    pub general: Vec<i64>,
     /// Flags register
     pub flags: Flags,
 }
@@ -23,41 +26,59 @@ pub struct Flags {
 
 impl RegisterFile {
     pub fn new() -> Self {
+        Self::with_count(DEFAULT_REGISTER_COUNT)
+    }
+    
+    /// Create a register file with `count` general purpose registers
+    ///
+    /// Panics if `count` exceeds what a `Register` index can address.
+    pub fn with_count(count: usize) -> Self {
+        let max = Register::MAX as usize + 1;
+        assert!(count <= max, "Register count {} exceeds {}", count, max);
         RegisterFile {
-            general: [0; 16],
+            general: vec![0; count],
             flags: Flags::default(),
         }
     }
     
+    /// Number of general purpose registers
+    pub fn count(&self) -> usize {
+        self.general.len()
+    }
+    
     /// Read a register value
     pub fn read(&self, reg: Register) -> Result<i64, String> {
-        if reg < 16 {
-            Ok(self.general[reg as usize])
-        } else {
-            Err(format!("Invalid register: R{}", reg))
-        }
+        self.general.get(reg as usize)
+            .copied()
+            .ok_or_else(|| format!("Invalid register: R{}", reg))
     }
     
     /// Write a register value
     pub fn write(&mut self, reg: Register, value: i64) -> Result<(), String> {
-        if reg < 16 {
-            self.general[reg as usize] = value;
-            Ok(())
-        } else {
-            Err(format!("Invalid register: R{}", reg))
-        }
+        let slot = self.general.get_mut(reg as usize)
+            .ok_or_else(|| format!("Invalid register: R{}", reg))?;
+        *slot = value;
+        Ok(())
     }
     
     /// Update flags based on a value
+    ///
+    /// Carry and overflow are left alone; only `update_arith_flags` sets them.
     pub fn update_flags(&mut self, value: i64) {
         self.flags.zero = value == 0;
         self.flags.negative = value < 0;
-        // Carry and overflow would be set by specific operations
+    }
+    
+    /// Update all four flags after an addition or subtraction
+    pub fn update_arith_flags(&mut self, value: i64, carry: bool, overflow: bool) {
+        self.update_flags(value);
+        self.flags.carry = carry;
+        self.flags.overflow = overflow;
     }
     
     /// Reset all registers to zero
     pub fn reset(&mut self) {
-        self.general = [0; 16];
+        self.general.fill(0);
         self.flags = Flags::default();
     }
 }
@@ -103,6 +124,19 @@ mod tests {
         assert!(regs.read(16).is_err());
     }
 
+    #[test]
+    fn test_register_count() {
+        let mut regs = RegisterFile::with_count(32);
+        assert_eq!(regs.count(), 32);
+        
+        regs.write(20, 7).unwrap();
+        assert_eq!(regs.read(20).unwrap(), 7);
+        assert!(regs.read(32).is_err());
+        
+        regs.reset();
+        assert_eq!(regs.general, vec![0; 32]);
+    }
+
     #[test]
     fn test_flags() {
         let mut regs = RegisterFile::new();
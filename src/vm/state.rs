@@ -0,0 +1,317 @@
+//! Saving and restoring complete VM state
+//!
+//! A state file is the magic `PVMS`, a format version byte, and then the
+//! tape, registers, history and timelines in the format of `StateWriter`.
+//! Host configuration (trap handler, loss sink, cost model, capabilities and
+//! any attached SDM) is not part of the state and starts at its defaults.
+
+use crate::instruction::Instruction;
+use crate::tape::{SegmentedTape, StateReader, StateWriter};
+use crate::vm::executor::{CheckpointState, ExecutionHistory, HistoryFrame, SavedRegisters, VM};
+use crate::vm::registers::{Flags, Register, RegisterFile};
+use crate::vm::timeline::{ForkPoint, Timeline};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+const MAGIC: &[u8; 4] = b"PVMS";
+const VERSION: u8 = 6;
+
+impl VM {
+    /// Write the full VM state to `path`
+    ///
+    /// Covers the tape with its trail and segments, registers, `ip`/`sp`/`fp`,
+    /// symbols, the loaded program, reversal history and parked timelines, so
+    /// a VM restored with `load_state` continues exactly where this one is.
+    pub fn save_state(&self, path: &Path) -> Result<(), String> {
+        std::fs::write(path, self.encode_state()?)
+            .map_err(|e| format!("Failed to write state to {}: {}", path.display(), e))
+    }
+    
+    /// Restore a VM written by `save_state`
+    pub fn load_state(path: &Path) -> Result<VM, String> {
+        let bytes = std::fs::read(path)
+            .map_err(|e| format!("Failed to read state from {}: {}", path.display(), e))?;
+        VM::decode_state(&bytes)
+    }
+    
+    fn encode_state(&self) -> Result<Vec<u8>, String> {
+        let mut w = StateWriter::new();
+        w.raw(MAGIC);
+        w.u8(VERSION);
+        
+        self.tape.write_state(&mut w);
+        write_registers(&mut w, &self.registers);
+        w.i64(self.ip);
+        w.i64(self.sp);
+        w.i64(self.fp);
+        w.u64(self.ic);
+        w.u64(self.cycles);
+        w.bool(self.history_enabled);
+        w.bool(self.alignment_check);
+        w.opt_usize(self.max_timelines);
+        w.map(&self.symbols, |w, pos| w.i64(*pos));
+        w.usize(self.code_offsets.len());
+        for offset in &self.code_offsets {
+            w.usize(*offset);
+        }
+        
+        write_frames(&mut w, &self.history.stack)?;
+        w.map(&self.history.checkpoints, |w, state| {
+            w.usize(state.depth);
+            write_registers(w, &state.registers);
+            w.i64(state.ip);
+            w.i64(state.sp);
+            w.i64(state.fp);
+            w.u64(state.ic);
+        });
+        w.usize(self.history.redo.len());
+        for inst in &self.history.redo {
+            w.bytes(&inst.encode()?);
+        }
+        
+        w.str(&self.current_timeline);
+        write_forks(&mut w, &self.forks);
+        // Written like `StateWriter::map`, but encoding a timeline's history can fail
+        let timelines: BTreeMap<_, _> = self.timelines.iter().collect();
+        w.usize(timelines.len());
+        for (name, timeline) in timelines {
+            w.str(name);
+            timeline.tape.write_state(&mut w);
+            write_registers(&mut w, &timeline.registers);
+            w.i64(timeline.ip);
+            w.i64(timeline.sp);
+            w.i64(timeline.fp);
+            w.u64(timeline.ic);
+            write_forks(&mut w, &timeline.forks);
+            write_frames(&mut w, &timeline.history)?;
+        }
+        
+        Ok(w.into_bytes())
+    }
+    
+    fn decode_state(bytes: &[u8]) -> Result<VM, String> {
+        let mut r = StateReader::new(bytes);
+        if r.raw(MAGIC.len()).ok() != Some(&MAGIC[..]) {
+            return Err("Not a VM state file".to_string());
+        }
+        let version = r.u8()?;
+        if version != VERSION {
+            return Err(format!("Unsupported state version {}", version));
+        }
+        
+        let mut vm = VM::new();
+        vm.tape = SegmentedTape::read_state(&mut r)?;
+        vm.registers = read_registers(&mut r)?;
+        vm.ip = r.i64()?;
+        vm.sp = r.i64()?;
+        vm.fp = r.i64()?;
+        vm.ic = r.u64()?;
+        vm.cycles = r.u64()?;
+        vm.history_enabled = r.bool()?;
+        vm.alignment_check = r.bool()?;
+        vm.max_timelines = r.opt_usize()?;
+        vm.symbols = r.map(|r| r.i64())?;
+        vm.code_offsets = r.vec(|r| r.usize())?;
+        
+        vm.history = ExecutionHistory {
+            stack: read_frames(&mut r)?,
+            checkpoints: r.map(|r| Ok(CheckpointState {
+                depth: r.usize()?,
+                registers: read_registers(r)?,
+                ip: r.i64()?,
+                sp: r.i64()?,
+                fp: r.i64()?,
+                ic: r.u64()?,
+            }))?,
+            redo: r.vec(read_instruction)?,
+        };
+        
+        vm.current_timeline = r.str()?;
+        vm.forks = read_forks(&mut r)?;
+        vm.timelines = r.map(|r| Ok(Timeline {
+            tape: SegmentedTape::read_state(r)?,
+            registers: read_registers(r)?,
+            ip: r.i64()?,
+            sp: r.i64()?,
+            fp: r.i64()?,
+            ic: r.u64()?,
+            forks: read_forks(r)?,
+            history: read_frames(r)?,
+        }))?;
+        
+        r.finish()?;
+        Ok(vm)
+    }
+}
+
+fn write_registers(w: &mut StateWriter, registers: &RegisterFile) {
+    w.usize(registers.count());
+    for value in &registers.general {
+        w.i64(*value);
+    }
+    w.u8(registers.flags.condition_code());
+}
+
+fn read_registers(r: &mut StateReader) -> Result<RegisterFile, String> {
+    let count = r.usize()?;
+    if count > Register::MAX as usize + 1 {
+        return Err(format!("Register count {} out of range", count));
+    }
+    let mut registers = RegisterFile::with_count(count);
+    for value in registers.general.iter_mut() {
+        *value = r.i64()?;
+    }
+    let code = r.u8()?;
+    registers.flags = Flags {
+        zero: code & 1 != 0,
+        carry: code & 2 != 0,
+        overflow: code & 4 != 0,
+        negative: code & 8 != 0,
+    };
+    Ok(registers)
+}
+
+fn write_forks(w: &mut StateWriter, forks: &[ForkPoint]) {
+    w.usize(forks.len());
+    for fork in forks {
+        w.u64(fork.id);
+        w.usize(fork.trail_len);
+        write_registers(w, &fork.registers);
+    }
+}
+
+fn read_forks(r: &mut StateReader) -> Result<Vec<ForkPoint>, String> {
+    r.vec(|r| {
+        Ok(ForkPoint {
+            id: r.u64()?,
+            trail_len: r.usize()?,
+            registers: read_registers(r)?,
+        })
+    })
+}
+
+fn write_frames(w: &mut StateWriter, frames: &[HistoryFrame]) -> Result<(), String> {
+    w.usize(frames.len());
+    for frame in frames {
+        w.bytes(&frame.instruction.encode()?);
+        match &frame.registers_before {
+            SavedRegisters::All(registers) => {
+                w.u8(0);
+                write_registers(w, registers);
+            }
+            SavedRegisters::One(reg, value) => {
+                w.u8(1);
+                w.u8(*reg);
+                w.i64(*value);
+            }
+            SavedRegisters::Derived => w.u8(2),
+        }
+        w.i64(frame.ip_before);
+        w.i64(frame.sp_before);
+        w.i64(frame.fp_before);
+        w.u64(frame.ic_before);
+        w.usize(frame.tape_trail_len);
+        w.bool(frame.irreversible);
+    }
+    Ok(())
+}
+
+fn read_instruction(r: &mut StateReader) -> Result<Instruction, String> {
+    let encoded = r.bytes()?;
+    let (instruction, used) = Instruction::decode(&encoded)?;
+    if used != encoded.len() {
+        return Err("Malformed instruction in history".to_string());
+    }
+    Ok(instruction)
+}
+
+fn read_frames(r: &mut StateReader) -> Result<Vec<HistoryFrame>, String> {
+    r.vec(|r| {
+        Ok(HistoryFrame {
+            instruction: read_instruction(r)?,
+            registers_before: match r.u8()? {
+                0 => SavedRegisters::All(read_registers(r)?),
+                1 => SavedRegisters::One(r.u8()?, r.i64()?),
+                2 => SavedRegisters::Derived,
+                other => return Err(format!("Unknown saved registers tag {}", other)),
+            },
+            ip_before: r.i64()?,
+            sp_before: r.i64()?,
+            fp_before: r.i64()?,
+            ic_before: r.u64()?,
+            tape_trail_len: r.usize()?,
+            irreversible: r.bool()?,
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::Parser;
+
+    /// A VM loaded with a loop that writes 1..=10 to consecutive tape words
+    fn counting_vm() -> VM {
+        let program = Parser::new().parse_program(r#"
+            LI R0, 0
+            LI R1, 1
+            TAPESEEK 3000000
+        loop:
+            RADD R0, R1, R2
+            SWAP R0, R2
+            LI R2, 0
+            TAPEWRITE R0, 8
+            TAPEADVANCE 8
+            LI R4, 10
+            LT R6, R0, R4
+            BNZ R6, loop
+            HALT
+        "#).unwrap();
+        
+        let mut vm = VM::new();
+        vm.symbols = program.symbols;
+        vm.load_program(program.instructions).unwrap();
+        vm
+    }
+
+    #[test]
+    fn test_resumed_run_matches_uninterrupted() {
+        let mut reference = counting_vm();
+        reference.run(None).unwrap();
+        
+        let mut vm = counting_vm();
+        vm.run(Some(20)).unwrap();
+        vm.fork("side").unwrap();
+        
+        let path = std::env::temp_dir().join(format!("pvm-state-{}.bin", std::process::id()));
+        vm.save_state(&path).unwrap();
+        let mut restored = VM::load_state(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        
+        assert_eq!(restored.ip, vm.ip);
+        assert_eq!(restored.history.stack.len(), vm.history.stack.len());
+        assert!(restored.timelines.contains_key("side"));
+        
+        let stats = restored.run(None).unwrap();
+        assert!(stats.halted);
+        assert_eq!(restored.registers.general, reference.registers.general);
+        assert_eq!(restored.ip, reference.ip);
+        assert_eq!(restored.ic, reference.ic);
+        assert_eq!(restored.tape.tape.read_at(3_000_000, 80),
+                   reference.tape.tape.read_at(3_000_000, 80));
+        
+        // History survives the round trip, so the restored VM can still reverse
+        restored.reverse_last().unwrap();
+        restored.reverse_last().unwrap();
+        assert_eq!(restored.ip, reference.ip - 1);
+    }
+
+    #[test]
+    fn test_rejects_foreign_files() {
+        assert_eq!(VM::decode_state(b"nope").err().unwrap(), "Not a VM state file");
+        
+        let mut bytes = VM::new().encode_state().unwrap();
+        bytes.push(0);
+        assert!(VM::decode_state(&bytes).err().unwrap().contains("trailing"));
+    }
+}
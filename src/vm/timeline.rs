@@ -0,0 +1,704 @@
+//! Parallel timelines: forking the VM state and merging it back together
+
+use crate::instruction::MergeStrategy;
+use crate::tape::{SegmentedTape, TrailOp};
+use crate::vm::executor::{HistoryFrame, VM};
+use crate::vm::registers::{Register, RegisterFile};
+use std::collections::BTreeMap;
+
+/// A parallel timeline (for fork operations)
+#[derive(Clone)]
+pub struct Timeline {
+    pub tape: SegmentedTape,
+    pub registers: RegisterFile,
+    pub ip: i64,
+    pub sp: i64,
+    pub fp: i64,
+    pub ic: u64,
+    /// Forks in this timeline's ancestry, oldest first
+    pub forks: Vec<ForkPoint>,
+    /// Reversible history of this timeline while it is not the active one
+    pub history: Vec<HistoryFrame>,
+}
+
+/// A fork, recorded in the ancestry of both the forking and the forked timeline
+#[derive(Clone, Debug)]
+pub struct ForkPoint {
+    /// Identifies the fork across timelines
+    pub id: u64,
+    /// Trail length at the moment of the fork; later trail ops belong to one side only
+    pub trail_len: usize,
+    /// Registers at the moment of the fork, which `Combine` merges diff against
+    pub registers: RegisterFile,
+}
+
+/// How one timeline differs from another, from `VM::timeline_diff`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimelineDiff {
+    /// `(register, value in a, value in b)` for each register that differs
+    pub registers: Vec<(Register, i64, i64)>,
+    /// Tape byte ranges `[start, end)` whose contents differ
+    pub tape: Vec<(i64, i64)>,
+    /// `ip` of b minus `ip` of a
+    pub ip_delta: i64,
+    pub sp_delta: i64,
+    pub fp_delta: i64,
+}
+
+/// Borrowed state of one timeline, active or parked
+struct TimelineView<'a> {
+    tape: &'a SegmentedTape,
+    registers: &'a RegisterFile,
+    ip: i64,
+    sp: i64,
+    fp: i64,
+}
+
+/// A byte written since a fork: its value at the fork point, its final value
+/// and the write stamp of that final value
+#[derive(Clone, Copy, Debug)]
+struct ByteDelta {
+    base: u8,
+    value: u8,
+    stamp: u64,
+}
+
+/// Every writer's delta per tape position, oldest write first
+type Provenance = BTreeMap<i64, Vec<ByteDelta>>;
+
+impl VM {
+    /// Snapshot the current state into a new timeline named `name`
+    ///
+    /// The current timeline keeps running; the snapshot can later be merged back.
+    pub fn fork(&mut self, name: &str) -> Result<(), String> {
+        if name == self.current_timeline || self.timelines.contains_key(name) {
+            return Err(format!("Timeline '{}' already exists", name));
+        }
+        if let Some(limit) = self.max_timelines
+            && self.timelines.len() >= limit
+        {
+            return Err(format!("TooManyTimelines: limit of {} reached", limit));
+        }
+        
+        let id = self.forks.iter()
+            .chain(self.timelines.values().flat_map(|t| &t.forks))
+            .map(|f| f.id + 1)
+            .max()
+            .unwrap_or(0);
+        self.forks.push(ForkPoint {
+            id,
+            trail_len: self.tape.tape.trail_len(),
+            registers: self.registers.clone(),
+        });
+        
+        // Tape pages are shared copy-on-write, so the snapshot costs no page copies
+        let timeline = Timeline {
+            tape: self.tape.clone(),
+            registers: self.registers.clone(),
+            ip: self.ip,
+            sp: self.sp,
+            fp: self.fp,
+            ic: self.ic,
+            forks: self.forks.clone(),
+            history: Vec::new(),
+        };
+        self.timelines.insert(name.to_string(), timeline);
+        Ok(())
+    }
+    
+    /// Make the named timeline the active one, parking the current one in its place
+    ///
+    /// Tape, registers, pointers and history are exchanged wholesale, so later
+    /// execution and reversal only affect the newly active timeline. A forked
+    /// timeline's history starts at its fork.
+    pub fn switch_timeline(&mut self, name: &str) -> Result<(), String> {
+        let target = self.timelines.remove(name)
+            .ok_or_else(|| format!("Unknown timeline: {}", name))?;
+        
+        let parked = Timeline {
+            tape: std::mem::replace(&mut self.tape, target.tape),
+            registers: std::mem::replace(&mut self.registers, target.registers),
+            ip: std::mem::replace(&mut self.ip, target.ip),
+            sp: std::mem::replace(&mut self.sp, target.sp),
+            fp: std::mem::replace(&mut self.fp, target.fp),
+            ic: std::mem::replace(&mut self.ic, target.ic),
+            forks: std::mem::replace(&mut self.forks, target.forks),
+            history: std::mem::replace(&mut self.history.stack, target.history),
+        };
+        let previous = std::mem::replace(&mut self.current_timeline, name.to_string());
+        self.timelines.insert(previous, parked);
+        self.history.redo.clear();
+        Ok(())
+    }
+    
+    /// Compare two timelines, either of which may be the current one
+    ///
+    /// Tapes are compared page by page over the pages either side has
+    /// allocated; pages still shared since the fork are skipped.
+    pub fn timeline_diff(&self, a: &str, b: &str) -> Result<TimelineDiff, String> {
+        let a = self.timeline_view(a)?;
+        let b = self.timeline_view(b)?;
+        
+        let count = a.registers.count().max(b.registers.count());
+        let registers = (0..count)
+            .map(|reg| reg as Register)
+            .map(|reg| (reg, a.registers.read(reg).unwrap_or(0), b.registers.read(reg).unwrap_or(0)))
+            .filter(|(_, va, vb)| va != vb)
+            .collect();
+        
+        Ok(TimelineDiff {
+            registers,
+            tape: a.tape.tape.diff_ranges(&b.tape.tape),
+            ip_delta: b.ip - a.ip,
+            sp_delta: b.sp - a.sp,
+            fp_delta: b.fp - a.fp,
+        })
+    }
+    
+    /// The state of a timeline by name, whether active or parked
+    fn timeline_view(&self, name: &str) -> Result<TimelineView<'_>, String> {
+        if name == self.current_timeline {
+            return Ok(TimelineView {
+                tape: &self.tape,
+                registers: &self.registers,
+                ip: self.ip,
+                sp: self.sp,
+                fp: self.fp,
+            });
+        }
+        let timeline = self.timelines.get(name)
+            .ok_or_else(|| format!("Unknown timeline: {}", name))?;
+        Ok(TimelineView {
+            tape: &timeline.tape,
+            registers: &timeline.registers,
+            ip: timeline.ip,
+            sp: timeline.sp,
+            fp: timeline.fp,
+        })
+    }
+    
+    /// Discard a forked timeline, freeing its slot under `max_timelines`
+    pub fn delete_timeline(&mut self, name: &str) -> Result<(), String> {
+        self.timelines.remove(name)
+            .map(|_| ())
+            .ok_or_else(|| format!("Unknown timeline: {}", name))
+    }
+    
+    /// Fold the named timelines into the current one, position by position
+    ///
+    /// Only bytes written since the latest fork each timeline shares with the
+    /// current one take part. Writers of a position are ordered by when they
+    /// last wrote it:
+    /// - `Latest`: the most recent write wins
+    /// - `Earliest`: the fork-point value wins wherever the writers disagree
+    /// - `Combine`: the XOR of every writer's change is applied to the fork-point
+    ///   value; registers changed since the fork are combined the same way
+    /// - `Manual`: applies nothing if the writers disagree anywhere
+    ///
+    /// Returns the positions left for the caller to resolve, the same ones
+    /// `merge_conflicts` lists; only a `Manual` merge leaves any. The merge is
+    /// applied with ordinary tape writes, so it can be reversed.
+    pub fn merge_all(&mut self, timelines: &[String], strategy: MergeStrategy) -> Result<Vec<i64>, String> {
+        let (own, provenance) = self.provenance(timelines)?;
+        
+        if matches!(strategy, MergeStrategy::Manual) {
+            let conflicts = conflicting_positions(&provenance);
+            if !conflicts.is_empty() {
+                return Ok(conflicts);
+            }
+        }
+        
+        let mut resolved: BTreeMap<i64, u8> = BTreeMap::new();
+        for (pos, writers) in &provenance {
+            let value = match strategy {
+                MergeStrategy::Latest | MergeStrategy::Manual => writers[writers.len() - 1].value,
+                MergeStrategy::Earliest if writers.iter().all(|d| d.value == writers[0].value) => {
+                    writers[0].value
+                }
+                MergeStrategy::Earliest => writers[0].base,
+                MergeStrategy::Combine => writers.iter()
+                    .fold(writers[0].base, |acc, d| acc ^ d.base ^ d.value),
+            };
+            
+            // Skip bytes the current timeline already holds
+            let current = own.get(pos).map(|d| d.value).unwrap_or(writers[0].base);
+            if value != current {
+                resolved.insert(*pos, value);
+            }
+        }
+        
+        self.write_runs(&resolved);
+        if matches!(strategy, MergeStrategy::Combine) {
+            self.combine_registers(timelines)?;
+        }
+        Ok(Vec::new())
+    }
+    
+    /// XOR each timeline's register changes since its fork from the current
+    /// timeline into the current registers
+    fn combine_registers(&mut self, timelines: &[String]) -> Result<(), String> {
+        let mut deltas = Vec::new();
+        for name in timelines {
+            let (timeline, fork) = self.parked_with_fork(name)?;
+            for reg in 0..timeline.registers.count() {
+                let reg = reg as Register;
+                let delta = timeline.registers.read(reg)? ^ fork.registers.read(reg)?;
+                if delta != 0 {
+                    deltas.push((reg, delta));
+                }
+            }
+        }
+        for (reg, delta) in deltas {
+            let current = self.registers.read(reg)?;
+            self.registers.write(reg, current ^ delta)?;
+        }
+        Ok(())
+    }
+    
+    /// Tape positions where the current timeline and `timelines` disagree
+    ///
+    /// These are the positions a `Manual` merge refuses, for callers that want
+    /// to resolve them themselves.
+    pub fn merge_conflicts(&self, timelines: &[String]) -> Result<Vec<i64>, String> {
+        let (_, provenance) = self.provenance(timelines)?;
+        Ok(conflicting_positions(&provenance))
+    }
+    
+    /// The timelines a `Merge` instruction folds into the current one
+    ///
+    /// A forked timeline reconciles against `main`; `main` itself takes in
+    /// every parked timeline, in name order.
+    pub(crate) fn merge_sources(&self) -> Vec<String> {
+        if self.current_timeline != "main" {
+            return vec!["main".to_string()];
+        }
+        let mut names: Vec<String> = self.timelines.keys().cloned().collect();
+        names.sort();
+        names
+    }
+    
+    /// A parked timeline and the latest fork it shares with the current one
+    fn parked_with_fork(&self, name: &str) -> Result<(&Timeline, &ForkPoint), String> {
+        let timeline = self.timelines.get(name)
+            .ok_or_else(|| format!("Unknown timeline: {}", name))?;
+        let fork = timeline.forks.iter()
+            .zip(&self.forks)
+            .take_while(|(theirs, ours)| theirs.id == ours.id)
+            .last()
+            .map(|(fork, _)| fork)
+            .ok_or_else(|| format!(
+                "Timeline '{}' shares no fork with '{}'", name, self.current_timeline
+            ))?;
+        Ok((timeline, fork))
+    }
+    
+    /// The current timeline's writes since the earliest fork it shares with
+    /// `timelines`, and every writer's delta per position, oldest write first
+    ///
+    /// Each timeline contributes its writes since the latest fork it shares
+    /// with the current one.
+    fn provenance(
+        &self,
+        timelines: &[String],
+    ) -> Result<(BTreeMap<i64, ByteDelta>, Provenance), String> {
+        let mut forks = Vec::with_capacity(timelines.len());
+        for name in timelines {
+            forks.push(self.parked_with_fork(name)?);
+        }
+        
+        let mut provenance = Provenance::new();
+        let Some(since) = forks.iter().map(|(_, fork)| fork.trail_len).min() else {
+            return Ok((BTreeMap::new(), provenance));
+        };
+        
+        let own = written_since(&self.tape, since)
+            .map_err(|e| format!("Cannot merge into '{}': {}", self.current_timeline, e))?;
+        for (pos, delta) in &own {
+            provenance.entry(*pos).or_default().push(*delta);
+        }
+        for (name, (timeline, fork)) in timelines.iter().zip(&forks) {
+            let written = written_since(&timeline.tape, fork.trail_len)
+                .map_err(|e| format!("Cannot merge timeline '{}': {}", name, e))?;
+            for (pos, delta) in written {
+                provenance.entry(pos).or_default().push(delta);
+            }
+        }
+        for writers in provenance.values_mut() {
+            writers.sort_by_key(|d| d.stamp);
+        }
+        Ok((own, provenance))
+    }
+    
+    /// Write resolved bytes to the tape, one write per contiguous run
+    fn write_runs(&mut self, bytes: &BTreeMap<i64, u8>) {
+        let head = self.tape.tape.position();
+        
+        let mut iter = bytes.iter().peekable();
+        while let Some((&start, &first)) = iter.next() {
+            let mut run = vec![first];
+            while let Some(&(&pos, &value)) = iter.peek() {
+                if pos != start + run.len() as i64 {
+                    break;
+                }
+                run.push(value);
+                iter.next();
+            }
+            
+            self.tape.tape.seek(start);
+            self.tape.tape.write(&run);
+        }
+        
+        if !bytes.is_empty() {
+            self.tape.tape.seek(head);
+        }
+    }
+}
+
+/// Positions whose writers did not all write the same value
+fn conflicting_positions(provenance: &Provenance) -> Vec<i64> {
+    provenance.iter()
+        .filter(|(_, writers)| writers.iter().any(|d| d.value != writers[0].value))
+        .map(|(pos, _)| *pos)
+        .collect()
+}
+
+/// Bytes written to `tape` since its trail had length `since`
+///
+/// Fails if the history limit has evicted some of those writes.
+fn written_since(tape: &SegmentedTape, since: usize) -> Result<BTreeMap<i64, ByteDelta>, String> {
+    let mut deltas: BTreeMap<i64, ByteDelta> = BTreeMap::new();
+    
+    for op in tape.tape.trail_since(since)? {
+        if let TrailOp::Write { pos, old, new, stamp, .. } = op {
+            for (i, (&base, &value)) in old.iter().zip(new.iter()).enumerate() {
+                deltas.entry(pos + i as i64)
+                    .and_modify(|d| {
+                        d.value = value;
+                        d.stamp = *stamp;
+                    })
+                    .or_insert(ByteDelta { base, value, stamp: *stamp });
+            }
+        }
+    }
+    
+    Ok(deltas)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction::Instruction;
+
+    fn write_at(tape: &mut SegmentedTape, pos: i64, data: &[u8]) {
+        tape.tape.seek(pos);
+        tape.tape.write(data);
+    }
+
+    fn read_at(vm: &mut VM, pos: i64, len: usize) -> Vec<u8> {
+        vm.tape.tape.seek(pos);
+        vm.tape.tape.read(len)
+    }
+
+    /// Fork three timelines that each write a private byte and share position 100
+    fn forked_vm() -> (VM, Vec<String>) {
+        let mut vm = VM::new();
+        write_at(&mut vm.tape, 100, &[0x10]);
+        
+        let names: Vec<String> = ["a", "b", "c"].iter().map(|s| s.to_string()).collect();
+        for name in &names {
+            vm.fork(name).unwrap();
+        }
+        
+        for (i, name) in names.iter().enumerate() {
+            let tape = &mut vm.timelines.get_mut(name).unwrap().tape;
+            write_at(tape, 10 * (i as i64 + 1), &[i as u8 + 1]);
+            write_at(tape, 100, &[0x10 ^ (1 << i)]);
+        }
+        
+        (vm, names)
+    }
+
+    #[test]
+    fn test_merge_all_combine() {
+        let (mut vm, names) = forked_vm();
+        vm.merge_all(&names, MergeStrategy::Combine).unwrap();
+        
+        assert_eq!(read_at(&mut vm, 10, 1), vec![1]);
+        assert_eq!(read_at(&mut vm, 20, 1), vec![2]);
+        assert_eq!(read_at(&mut vm, 30, 1), vec![3]);
+        // Each timeline flipped one bit of the shared byte
+        assert_eq!(read_at(&mut vm, 100, 1), vec![0x17]);
+    }
+
+    #[test]
+    fn test_merge_all_combine_registers() {
+        let (mut vm, names) = forked_vm();
+        vm.registers.write(1, 0b100).unwrap();
+        for (i, name) in names.iter().enumerate() {
+            vm.timelines.get_mut(name).unwrap().registers.write(1, 1 << i).unwrap();
+        }
+        vm.timelines.get_mut("c").unwrap().registers.write(2, 5).unwrap();
+        
+        vm.merge_all(&names, MergeStrategy::Combine).unwrap();
+        // Main's own change and every timeline's change are all kept
+        assert_eq!(vm.registers.read(1).unwrap(), 0b100 ^ 0b111);
+        assert_eq!(vm.registers.read(2).unwrap(), 5);
+        
+        // Other strategies only reconcile the tape
+        let (mut vm, names) = forked_vm();
+        vm.timelines.get_mut("a").unwrap().registers.write(1, 9).unwrap();
+        vm.merge_all(&names, MergeStrategy::Latest).unwrap();
+        assert_eq!(vm.registers.read(1).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_merge_all_latest_and_earliest() {
+        let (mut vm, names) = forked_vm();
+        vm.merge_all(&names, MergeStrategy::Latest).unwrap();
+        assert_eq!(read_at(&mut vm, 100, 1), vec![0x14]);
+        assert_eq!(read_at(&mut vm, 20, 1), vec![2]);
+        
+        // Write order decides, not the order the timelines are named in
+        let (mut vm, mut names) = forked_vm();
+        names.reverse();
+        vm.merge_all(&names, MergeStrategy::Latest).unwrap();
+        assert_eq!(read_at(&mut vm, 100, 1), vec![0x14]);
+        
+        // The fork-point value settles a disagreement; lone writes still merge
+        let (mut vm, names) = forked_vm();
+        vm.merge_all(&names, MergeStrategy::Earliest).unwrap();
+        assert_eq!(read_at(&mut vm, 100, 1), vec![0x10]);
+        assert_eq!(read_at(&mut vm, 30, 1), vec![3]);
+    }
+
+    #[test]
+    fn test_merge_all_manual_reports_conflicts() {
+        let (mut vm, names) = forked_vm();
+        assert_eq!(vm.merge_all(&names, MergeStrategy::Manual).unwrap(), vec![100]);
+        assert_eq!(vm.merge_all(&names, MergeStrategy::Latest).unwrap(), Vec::<i64>::new());
+        
+        let (mut vm, names) = forked_vm();
+        vm.merge_all(&names, MergeStrategy::Manual).unwrap();
+        
+        // Nothing was applied
+        assert_eq!(read_at(&mut vm, 10, 1), vec![0]);
+    }
+
+    #[test]
+    fn test_merge_fails_when_fork_writes_were_evicted() {
+        let (mut vm, names) = forked_vm();
+        let tape = &mut vm.timelines.get_mut("b").unwrap().tape.tape;
+        tape.set_history_limit(Some(4));
+        for i in 0..10 {
+            tape.seek(1000 + i);
+            tape.write(&[0xEE]);
+        }
+        
+        let err = vm.merge_all(&names, MergeStrategy::Latest).unwrap_err();
+        assert!(err.starts_with("Cannot merge timeline 'b'"), "{}", err);
+        assert!(err.contains("evicted by the history limit"), "{}", err);
+        assert!(vm.merge_conflicts(&names).is_err());
+        // Nothing was applied
+        assert_eq!(read_at(&mut vm, 10, 1), vec![0]);
+    }
+
+    #[test]
+    fn test_merge_uses_the_fork_each_pair_shares() {
+        let mut vm = VM::new();
+        vm.fork("a").unwrap();
+        write_at(&mut vm.tape, 100, &[7]);
+        vm.fork("b").unwrap();
+        
+        // Parking main and b must not hand either of them the other's fork point
+        vm.switch_timeline("b").unwrap();
+        vm.switch_timeline("a").unwrap();
+        assert_eq!(vm.merge_conflicts(&["main".to_string()]).unwrap(), Vec::<i64>::new());
+        vm.merge_all(&["main".to_string()], MergeStrategy::Latest).unwrap();
+        assert_eq!(read_at(&mut vm, 100, 1), vec![7]);
+        
+        // b forked after main's write, so it has nothing of its own to bring
+        vm.switch_timeline("main").unwrap();
+        write_at(&mut vm.tape, 100, &[8]);
+        vm.merge_all(&["b".to_string()], MergeStrategy::Latest).unwrap();
+        assert_eq!(read_at(&mut vm, 100, 1), vec![8]);
+    }
+
+    #[test]
+    fn test_merge_all_unknown_timeline() {
+        let mut vm = VM::new();
+        assert!(vm.merge_all(&["missing".to_string()], MergeStrategy::Latest).is_err());
+    }
+
+    #[test]
+    fn test_fork_instruction_isolates_branches() {
+        let mut vm = VM::new();
+        vm.execute(Instruction::LoadImm { reg: 0, value: 1 }).unwrap();
+        vm.execute(Instruction::Fork { label: "alt".to_string() }).unwrap();
+        
+        // Mutate the main branch only
+        vm.execute(Instruction::LoadImm { reg: 0, value: 2 }).unwrap();
+        write_at(&mut vm.tape, 50, &[9]);
+        let alt = &vm.timelines["alt"];
+        assert_eq!(alt.registers.read(0).unwrap(), 1);
+        assert_eq!(alt.ip, 2);
+        
+        vm.switch_timeline("alt").unwrap();
+        assert_eq!(vm.current_timeline, "alt");
+        assert_eq!(vm.registers.read(0).unwrap(), 1);
+        assert_eq!(read_at(&mut vm, 50, 1), vec![0]);
+        assert!(vm.reverse_last().is_err());
+        
+        vm.switch_timeline("main").unwrap();
+        assert_eq!(vm.registers.read(0).unwrap(), 2);
+        assert_eq!(read_at(&mut vm, 50, 1), vec![9]);
+    }
+
+    #[test]
+    fn test_timeline_switch_round_trip() {
+        let mut vm = VM::new();
+        let switch = |name: &str| Instruction::TimelineSwitch { label: name.to_string() };
+        
+        vm.execute(Instruction::Fork { label: "alt".to_string() }).unwrap();
+        vm.execute(Instruction::LoadImm { reg: 0, value: 10 }).unwrap();
+        write_at(&mut vm.tape, 40, &[1]);
+        
+        // alt resumes right after the fork, with none of main's changes
+        vm.execute(switch("alt")).unwrap();
+        assert_eq!(vm.current_timeline, "alt");
+        assert_eq!(vm.ip, 1);
+        assert_eq!(vm.registers.read(0).unwrap(), 0);
+        assert_eq!(read_at(&mut vm, 40, 1), vec![0]);
+        
+        vm.execute(Instruction::LoadImm { reg: 0, value: 20 }).unwrap();
+        write_at(&mut vm.tape, 40, &[2]);
+        
+        // main resumes after its switch instruction
+        vm.execute(switch("main")).unwrap();
+        assert_eq!(vm.ip, 3);
+        assert_eq!(vm.registers.read(0).unwrap(), 10);
+        assert_eq!(read_at(&mut vm, 40, 1), vec![1]);
+        
+        vm.execute(switch("alt")).unwrap();
+        assert_eq!(vm.ip, 3);
+        assert_eq!(vm.registers.read(0).unwrap(), 20);
+        assert_eq!(read_at(&mut vm, 40, 1), vec![2]);
+        
+        let err = vm.execute(switch("nowhere")).unwrap_err();
+        assert_eq!(err, "Unknown timeline: nowhere");
+        assert_eq!(vm.current_timeline, "alt");
+        assert_eq!(vm.ip, 3);
+    }
+
+    #[test]
+    fn test_reverse_fork_discards_timeline() {
+        let mut vm = VM::new();
+        vm.execute(Instruction::Fork { label: "alt".to_string() }).unwrap();
+        assert!(vm.timelines.contains_key("alt"));
+        
+        vm.reverse_last().unwrap();
+        assert!(vm.timelines.is_empty());
+        assert_eq!(vm.ip, 0);
+        
+        // A failed fork leaves no frame that could discard the existing timeline
+        vm.fork("alt").unwrap();
+        assert!(vm.execute(Instruction::Fork { label: "alt".to_string() }).is_err());
+        assert!(vm.reverse_last().is_err());
+        assert!(vm.timelines.contains_key("alt"));
+    }
+
+    /// Fork `alt`, then write 0xAA at 200 in main and 0xBB at 200 and 0xCC at 201 in alt
+    fn conflicting_fork() -> VM {
+        let mut vm = VM::new();
+        write_at(&mut vm.tape, 200, &[0x01, 0x02]);
+        vm.execute(Instruction::Fork { label: "alt".to_string() }).unwrap();
+        write_at(&mut vm.tape, 200, &[0xAA]);
+        
+        vm.switch_timeline("alt").unwrap();
+        write_at(&mut vm.tape, 200, &[0xBB]);
+        write_at(&mut vm.tape, 201, &[0xCC]);
+        vm
+    }
+
+    #[test]
+    fn test_merge_instruction_strategies() {
+        let merge = |strategy| {
+            let mut vm = conflicting_fork();
+            vm.execute(Instruction::Merge { strategy }).unwrap();
+            read_at(&mut vm, 200, 2)
+        };
+        
+        // main wrote 0xAA before alt wrote 0xBB
+        assert_eq!(merge(MergeStrategy::Latest), vec![0xBB, 0xCC]);
+        assert_eq!(merge(MergeStrategy::Earliest), vec![0x01, 0xCC]);
+        assert_eq!(merge(MergeStrategy::Combine), vec![0x01 ^ 0xAA ^ 0xBB, 0xCC]);
+    }
+
+    #[test]
+    fn test_merge_instruction_manual_and_reverse() {
+        let mut vm = conflicting_fork();
+        let main = vec!["main".to_string()];
+        assert_eq!(vm.merge_conflicts(&main).unwrap(), vec![200]);
+        
+        let err = vm.execute(Instruction::Merge { strategy: MergeStrategy::Manual }).unwrap_err();
+        assert!(err.contains("[200]"));
+        assert_eq!(read_at(&mut vm, 200, 2), vec![0xBB, 0xCC]);
+        
+        // Resolve by hand, then the manual merge goes through
+        write_at(&mut vm.tape, 200, &[0xAA]);
+        assert!(vm.merge_conflicts(&main).unwrap().is_empty());
+        vm.execute(Instruction::Merge { strategy: MergeStrategy::Manual }).unwrap();
+        
+        // Merges are ordinary tape writes and reverse like any other instruction
+        let mut vm = conflicting_fork();
+        vm.execute(Instruction::Merge { strategy: MergeStrategy::Latest }).unwrap();
+        vm.reverse_last().unwrap();
+        assert_eq!(read_at(&mut vm, 200, 2), vec![0xBB, 0xCC]);
+    }
+
+    #[test]
+    fn test_timeline_diff() {
+        let mut vm = VM::new();
+        write_at(&mut vm.tape, 5000, &[1, 2, 3]);
+        vm.fork("alt").unwrap();
+        vm.switch_timeline("alt").unwrap();
+        
+        vm.execute(Instruction::LoadImm { reg: 3, value: 0x0A0B }).unwrap();
+        vm.execute(Instruction::TapeSeek { position: 5001 }).unwrap();
+        // Writes 0B 0A 00 00 over 02 03 00 00, so only two bytes change
+        vm.execute(Instruction::TapeWrite { reg: 3, len: 4 }).unwrap();
+        
+        let diff = vm.timeline_diff("main", "alt").unwrap();
+        assert_eq!(diff, TimelineDiff {
+            registers: vec![(3, 0, 0x0A0B)],
+            tape: vec![(5001, 5003)],
+            ip_delta: 3,
+            sp_delta: 0,
+            fp_delta: 0,
+        });
+        
+        let reverse = vm.timeline_diff("alt", "main").unwrap();
+        assert_eq!(reverse.registers, vec![(3, 0x0A0B, 0)]);
+        assert_eq!(reverse.ip_delta, -3);
+        
+        assert!(vm.timeline_diff("alt", "alt").unwrap().tape.is_empty());
+        assert_eq!(vm.timeline_diff("main", "nope").unwrap_err(), "Unknown timeline: nope");
+    }
+
+    #[test]
+    fn test_max_timelines() {
+        let mut vm = VM::new();
+        vm.max_timelines = Some(2);
+        
+        vm.fork("a").unwrap();
+        vm.fork("b").unwrap();
+        
+        let err = vm.fork("c").unwrap_err();
+        assert!(err.starts_with("TooManyTimelines"));
+        assert!(!vm.timelines.contains_key("c"));
+        assert_eq!(vm.timelines.len(), 2);
+        
+        vm.delete_timeline("a").unwrap();
+        vm.fork("c").unwrap();
+        assert!(vm.delete_timeline("a").is_err());
+    }
+}
@@ -0,0 +1,55 @@
+//! Allocation counts, measured with a counting global allocator
+//!
+//! Kept in its own test binary so the allocator does not sit under every
+//! other test in the crate.
+
+use palindrome_vm::{Instruction, VM};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+/// Counts allocations per thread, so tests can measure them in isolation
+struct CountingAlloc;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        unsafe { System.alloc(layout) }
+    }
+    
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// Allocations made on this thread while running `f`
+fn allocations_during(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+#[test]
+fn test_without_history_overhead() {
+    let run = |vm: &mut VM| allocations_during(|| {
+        for _ in 0..1000 {
+            vm.execute(Instruction::RAdd { src1: 0, src2: 1, dst: 2 }).unwrap();
+            vm.execute(Instruction::TapeWrite { reg: 2, len: 8 }).unwrap();
+        }
+    });
+    
+    let mut forward_only = VM::without_history();
+    let mut reversible = VM::new();
+    
+    // Register ops and tape writes to resident pages allocate nothing
+    // without history, but snapshot registers and trail bytes with it
+    forward_only.execute(Instruction::TapeWrite { reg: 2, len: 8 }).unwrap();
+    assert_eq!(run(&mut forward_only), 0);
+    assert!(run(&mut reversible) >= 2000);
+}